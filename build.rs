@@ -0,0 +1,120 @@
+//! Fingerprints and pre-compresses the files in `static/`.
+//!
+//! Generates `$OUT_DIR/assets.rs`, which `web::assets` includes as its
+//! registry. Each file is stored as-is plus gzip and brotli variants, and its
+//! public name gets a content hash so it can be cached forever.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use sha2::{Digest as _, Sha256};
+
+const STATIC_DIR: &str = "static";
+
+/// Hex characters of the SHA-256 digest kept in fingerprinted names.
+const HASH_LEN: usize = 16;
+
+fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `pico.min.css` + `0123abcd` -> `pico.min.0123abcd.css`.
+fn hashed_name(name: &str, hash: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{stem}.{hash}.{ext}"),
+        None => format!("{name}.{hash}"),
+    }
+}
+
+fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(bytes).expect("gzip failed");
+    encoder.finish().expect("gzip failed")
+}
+
+fn brotli(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    encoder.write_all(bytes).expect("brotli failed");
+    encoder.into_inner()
+}
+
+/// Formats `value` as a Rust string literal.
+fn literal(value: &str) -> String {
+    format!("\"{}\"", value.escape_default())
+}
+
+fn path_literal(path: &Path) -> String {
+    literal(path.to_str().expect("Asset paths must be UTF-8"))
+}
+
+fn main() {
+    println!("cargo::rerun-if-changed={STATIC_DIR}");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+    let manifest_dir = PathBuf::from(
+        env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set"),
+    );
+
+    let mut names: Vec<String> = fs::read_dir(manifest_dir.join(STATIC_DIR))
+        .expect("Failed to read static dir")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    let mut registry = String::from("&[\n");
+    for name in names {
+        let source = manifest_dir.join(STATIC_DIR).join(&name);
+        let bytes = fs::read(&source).expect("Failed to read asset");
+
+        let digest = Sha256::digest(&bytes);
+        let mut hash = String::new();
+        for byte in digest {
+            write!(hash, "{byte:02x}").expect("write to String");
+        }
+        hash.truncate(HASH_LEN);
+
+        let gzip_path = out_dir.join(format!("{name}.gz"));
+        let brotli_path = out_dir.join(format!("{name}.br"));
+        fs::write(&gzip_path, gzip(&bytes)).expect("Failed to write gzip");
+        fs::write(&brotli_path, brotli(&bytes)).expect("Failed to write br");
+
+        writeln!(
+            registry,
+            "    Asset {{\n        \
+                name: {name},\n        \
+                hashed_name: {hashed},\n        \
+                content_type: {content_type},\n        \
+                etag: {etag},\n        \
+                identity: include_bytes!({source}),\n        \
+                gzip: include_bytes!({gzip}),\n        \
+                brotli: include_bytes!({brotli}),\n    \
+            }},",
+            name = literal(&name),
+            hashed = literal(&hashed_name(&name, &hash)),
+            content_type = literal(content_type(&name)),
+            etag = literal(&format!("\"{hash}\"")),
+            source = path_literal(&source),
+            gzip = path_literal(&gzip_path),
+            brotli = path_literal(&brotli_path),
+        )
+        .expect("write to String");
+    }
+    registry.push(']');
+
+    fs::write(out_dir.join("assets.rs"), registry)
+        .expect("Failed to write asset registry");
+}
@@ -0,0 +1,4 @@
+-- Persistent sessions ("remember me") get a long-lived cookie; others last
+-- until the browser closes. Existing sessions were all long-lived.
+
+ALTER TABLE session ADD COLUMN persistent BOOLEAN NOT NULL DEFAULT TRUE;
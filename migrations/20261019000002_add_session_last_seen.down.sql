@@ -0,0 +1,2 @@
+ALTER TABLE session DROP COLUMN last_ip;
+ALTER TABLE session DROP COLUMN last_seen_at;
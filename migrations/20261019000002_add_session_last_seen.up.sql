@@ -0,0 +1,14 @@
+-- When and from where each session was last used. Existing sessions start
+-- from their creation time and login address, whose port ("ip:port", or
+-- "[ip]:port" for IPv6) is dropped to match the bare addresses stored since.
+
+ALTER TABLE session ADD COLUMN last_seen_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE session ADD COLUMN last_ip TEXT NOT NULL DEFAULT '';
+
+UPDATE session SET last_seen_at = created_at, last_ip = CASE
+    WHEN ip_address LIKE '[%]:%'
+        THEN substr(ip_address, 2, instr(ip_address, ']') - 2)
+    WHEN ip_address LIKE '%:%' AND ip_address NOT LIKE '%:%:%'
+        THEN substr(ip_address, 1, instr(ip_address, ':') - 1)
+    ELSE ip_address
+END;
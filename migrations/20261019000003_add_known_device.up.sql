@@ -0,0 +1,30 @@
+-- IP/user-agent combinations each user has logged in from, so logins from
+-- anywhere new can be flagged. Seeded from current sessions so existing
+-- users aren't alerted about devices they already use. Addresses are stored
+-- without a port, as logins record them.
+
+CREATE TABLE IF NOT EXISTS known_device(
+    user_id         BLOB NOT NULL,
+    ip_address      TEXT NOT NULL,
+    user_agent      TEXT NOT NULL,
+    first_seen_at   INTEGER NOT NULL,
+    PRIMARY KEY (user_id, ip_address, user_agent),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+INSERT OR IGNORE INTO known_device (user_id, ip_address, user_agent, first_seen_at)
+SELECT user_id, CASE
+    WHEN last_ip LIKE '[%]:%' THEN substr(last_ip, 2, instr(last_ip, ']') - 2)
+    WHEN last_ip LIKE '%:%' AND last_ip NOT LIKE '%:%:%'
+        THEN substr(last_ip, 1, instr(last_ip, ':') - 1)
+    ELSE last_ip
+END, user_agent, created_at FROM session;
+
+-- "This wasn't me" links sent with new-login emails. Each token revokes
+-- one session and goes away with it.
+CREATE TABLE IF NOT EXISTS login_alert(
+    token       BLOB NOT NULL PRIMARY KEY,
+    session_id  BLOB NOT NULL,
+    created_at  INTEGER NOT NULL,
+    FOREIGN KEY (session_id) REFERENCES session(id) ON DELETE CASCADE
+);
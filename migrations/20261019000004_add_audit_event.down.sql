@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS audit_event_append_only;
+DROP TABLE IF EXISTS audit_event;
+ALTER TABLE user DROP COLUMN is_admin;
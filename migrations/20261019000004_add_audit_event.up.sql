@@ -0,0 +1,25 @@
+-- Admins can review every user's security activity.
+ALTER TABLE user ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Append-only log of security-relevant account events. `user_id` has no
+-- foreign key so history outlives the account; `username` is recorded as it
+-- was at the time (or as typed, for failed logins of unknown users).
+CREATE TABLE IF NOT EXISTS audit_event(
+    id          INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id     BLOB,
+    username    TEXT NOT NULL,
+    kind        TEXT NOT NULL,
+    ip_address  TEXT NOT NULL,
+    user_agent  TEXT NOT NULL,
+    detail      TEXT NOT NULL,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_event_user_id ON audit_event(user_id, created_at);
+CREATE INDEX IF NOT EXISTS audit_event_created_at ON audit_event(created_at);
+
+CREATE TRIGGER IF NOT EXISTS audit_event_append_only
+BEFORE UPDATE ON audit_event
+BEGIN
+    SELECT RAISE(ABORT, 'audit_event is append-only');
+END;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS user_username_skeleton;
+DROP INDEX IF EXISTS user_username_canonical;
+ALTER TABLE user DROP COLUMN username_skeleton;
+ALTER TABLE user DROP COLUMN username_canonical;
@@ -0,0 +1,20 @@
+-- Usernames are unique case-insensitively (`username_canonical`, NFKC +
+-- lowercase) and by visual skeleton (`username_skeleton`, confusable
+-- characters folded together), so "Admin", "admin" and "аdmin" with a
+-- Cyrillic "а" can't all exist. Both are computed in Rust; the app fills in
+-- `username_skeleton` (and any missing canonical form) at startup.
+ALTER TABLE user ADD COLUMN username_canonical TEXT;
+ALTER TABLE user ADD COLUMN username_skeleton TEXT;
+
+-- SQLite's lower() only folds ASCII, so only pure-ASCII usernames get their
+-- canonical form here; the rest are left for the app. Existing accounts that
+-- differ only by case keep a NULL canonical form and still log in by exact
+-- username until one of them is renamed.
+UPDATE user SET username_canonical = lower(username)
+WHERE username NOT GLOB '*[^ -~]*' AND (
+    SELECT count(*) FROM user AS other
+    WHERE lower(other.username) = lower(user.username)
+) = 1;
+
+CREATE UNIQUE INDEX IF NOT EXISTS user_username_canonical ON user(username_canonical);
+CREATE UNIQUE INDEX IF NOT EXISTS user_username_skeleton ON user(username_skeleton);
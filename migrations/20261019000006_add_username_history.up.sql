@@ -0,0 +1,17 @@
+-- Previous usernames, so old profile URLs can redirect and recently
+-- released names are held back from other users for a while. A name may
+-- appear several times if it changed hands after its hold expired; the
+-- most recent entry wins.
+CREATE TABLE IF NOT EXISTS username_history(
+    id                  INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id             BLOB NOT NULL,
+    username            TEXT NOT NULL,
+    username_canonical  TEXT NOT NULL,
+    username_skeleton   TEXT NOT NULL,
+    changed_at          INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS username_history_user_id ON username_history(user_id, changed_at);
+CREATE INDEX IF NOT EXISTS username_history_canonical ON username_history(username_canonical, changed_at);
+CREATE INDEX IF NOT EXISTS username_history_skeleton ON username_history(username_skeleton, changed_at);
@@ -0,0 +1,23 @@
+-- Public profile fields. A user without a row has an empty profile with
+-- default visibility. `links` holds one URL per line.
+CREATE TABLE IF NOT EXISTS profile(
+    user_id             BLOB NOT NULL PRIMARY KEY,
+    display_name        TEXT NOT NULL DEFAULT '',
+    bio                 TEXT NOT NULL DEFAULT '',
+    links               TEXT NOT NULL DEFAULT '',
+    bio_visibility      TEXT NOT NULL DEFAULT 'everyone',
+    links_visibility    TEXT NOT NULL DEFAULT 'everyone',
+    avatar_visibility   TEXT NOT NULL DEFAULT 'everyone',
+    updated_at          INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+-- Avatars are resized on upload and kept small, so they live in the
+-- database alongside everything else.
+CREATE TABLE IF NOT EXISTS avatar(
+    user_id         BLOB NOT NULL PRIMARY KEY,
+    content_type    TEXT NOT NULL,
+    data            BLOB NOT NULL,
+    updated_at      INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,14 @@
+-- Metadata for files kept in the configured storage backend. The bytes
+-- live under `storage_key`; rows are the source of truth for ownership.
+CREATE TABLE IF NOT EXISTS upload(
+    id              BLOB NOT NULL PRIMARY KEY,
+    user_id         BLOB NOT NULL,
+    filename        TEXT NOT NULL,
+    content_type    TEXT NOT NULL,
+    size            INTEGER NOT NULL,
+    storage_key     TEXT NOT NULL UNIQUE,
+    created_at      INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS upload_user_id ON upload(user_id, created_at);
@@ -0,0 +1,33 @@
+-- Accounts at external OpenID Connect providers, identified by the
+-- provider's `sub` claim. A user links at most one account per provider.
+--
+-- Accounts created through a provider have no password. `user` keeps
+-- `password_hash` NOT NULL and stores an empty string for them instead:
+-- relaxing the constraint means rebuilding the table, and dropping the old
+-- one inside a migration's transaction would cascade to every table that
+-- references it.
+CREATE TABLE IF NOT EXISTS user_identity(
+    provider    TEXT NOT NULL,
+    subject     TEXT NOT NULL,
+    user_id     BLOB NOT NULL,
+    email       TEXT,
+    created_at  INTEGER NOT NULL,
+    PRIMARY KEY (provider, subject),
+    UNIQUE (user_id, provider),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+-- Sign-ins in flight: the state, nonce and PKCE verifier sent to the
+-- provider, checked and deleted when it redirects back. `link_user_id` is
+-- set when a signed-in user is linking a new provider.
+CREATE TABLE IF NOT EXISTS oidc_login(
+    state           TEXT NOT NULL PRIMARY KEY,
+    provider        TEXT NOT NULL,
+    nonce           TEXT NOT NULL,
+    code_verifier   TEXT NOT NULL,
+    link_user_id    BLOB,
+    created_at      INTEGER NOT NULL,
+    FOREIGN KEY (link_user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS oidc_login_created_at ON oidc_login(created_at);
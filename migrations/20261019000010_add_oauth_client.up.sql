@@ -0,0 +1,43 @@
+-- Apps that sign their users in through this site. `id` is the public
+-- client_id; only a hash of the secret is kept.
+CREATE TABLE IF NOT EXISTS oauth_client(
+    id              TEXT NOT NULL PRIMARY KEY,
+    name            TEXT NOT NULL,
+    secret_hash     TEXT NOT NULL,
+    -- One exact-match URI per line.
+    redirect_uris   TEXT NOT NULL,
+    created_by      BLOB,
+    created_at      INTEGER NOT NULL,
+    FOREIGN KEY (created_by) REFERENCES user(id) ON DELETE SET NULL
+);
+
+-- Scopes a user has agreed to share with a client, so the consent page is
+-- only shown again when a client asks for more.
+CREATE TABLE IF NOT EXISTS oauth_consent(
+    user_id     BLOB NOT NULL,
+    client_id   TEXT NOT NULL,
+    -- Space-separated, as in the `scope` parameter.
+    scope       TEXT NOT NULL,
+    created_at  INTEGER NOT NULL,
+    PRIMARY KEY (user_id, client_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (client_id) REFERENCES oauth_client(id) ON DELETE CASCADE
+);
+
+-- Issued authorization codes, exchanged once at the token endpoint.
+CREATE TABLE IF NOT EXISTS oauth_code(
+    code_hash       TEXT NOT NULL PRIMARY KEY,
+    client_id       TEXT NOT NULL,
+    user_id         BLOB NOT NULL,
+    redirect_uri    TEXT NOT NULL,
+    scope           TEXT NOT NULL,
+    nonce           TEXT,
+    code_challenge  TEXT NOT NULL,
+    -- When the user's session signed in, for the ID token's `auth_time`.
+    auth_time       INTEGER NOT NULL,
+    created_at      INTEGER NOT NULL,
+    FOREIGN KEY (client_id) REFERENCES oauth_client(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS oauth_code_created_at ON oauth_code(created_at);
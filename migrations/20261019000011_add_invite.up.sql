@@ -0,0 +1,16 @@
+-- Codes that let someone sign up when registration is invite-only. Only a
+-- hash of the code is kept.
+CREATE TABLE IF NOT EXISTS invite(
+    id          BLOB NOT NULL PRIMARY KEY,
+    code_hash   TEXT NOT NULL UNIQUE,
+    created_by  BLOB NOT NULL,
+    -- Where the invite link was emailed, if anywhere.
+    email       TEXT,
+    max_uses    INTEGER NOT NULL,
+    use_count   INTEGER NOT NULL DEFAULT 0,
+    expires_at  INTEGER NOT NULL,
+    created_at  INTEGER NOT NULL,
+    FOREIGN KEY (created_by) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS invite_created_by ON invite(created_by, created_at);
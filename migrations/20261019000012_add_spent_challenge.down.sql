@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS audit_event_kind_created_at;
+DROP TABLE IF EXISTS spent_challenge;
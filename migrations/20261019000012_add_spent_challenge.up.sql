@@ -0,0 +1,11 @@
+-- Nonces of bot-check challenges already accepted, so a solved challenge
+-- can't be replayed. Rows are pruned once the challenge would have expired.
+CREATE TABLE IF NOT EXISTS spent_challenge(
+    nonce       TEXT NOT NULL PRIMARY KEY,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS spent_challenge_created_at ON spent_challenge(created_at);
+
+-- Failed logins are counted per username and per address.
+CREATE INDEX IF NOT EXISTS audit_event_kind_created_at ON audit_event(kind, created_at);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS organization_invite;
+DROP INDEX IF EXISTS membership_user_id;
+DROP TABLE IF EXISTS membership;
+DROP TABLE IF EXISTS organization;
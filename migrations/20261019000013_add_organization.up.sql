@@ -0,0 +1,37 @@
+-- Teams of users. Everything else is still owned by a single user; routes
+-- under /orgs/{slug} are scoped to an organization and its members.
+CREATE TABLE IF NOT EXISTS organization(
+    id          BLOB NOT NULL PRIMARY KEY,
+    name        TEXT NOT NULL,
+    -- The organization's part of its URLs.
+    slug        TEXT NOT NULL UNIQUE,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS membership(
+    organization_id BLOB NOT NULL,
+    user_id         BLOB NOT NULL,
+    role            TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+    created_at      INTEGER NOT NULL,
+    PRIMARY KEY (organization_id, user_id),
+    FOREIGN KEY (organization_id) REFERENCES organization(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS membership_user_id ON membership(user_id);
+
+-- Pending invitations, emailed as a link. Only a hash of the link's token
+-- is kept; inviting the same address again replaces the invitation.
+CREATE TABLE IF NOT EXISTS organization_invite(
+    id              BLOB NOT NULL PRIMARY KEY,
+    organization_id BLOB NOT NULL,
+    email           TEXT NOT NULL,
+    role            TEXT NOT NULL CHECK (role IN ('owner', 'admin', 'member')),
+    token_hash      TEXT NOT NULL UNIQUE,
+    invited_by      BLOB NOT NULL,
+    expires_at      INTEGER NOT NULL,
+    created_at      INTEGER NOT NULL,
+    UNIQUE (organization_id, email),
+    FOREIGN KEY (organization_id) REFERENCES organization(id) ON DELETE CASCADE,
+    FOREIGN KEY (invited_by) REFERENCES user(id) ON DELETE CASCADE
+);
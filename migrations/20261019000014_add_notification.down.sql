@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS notification_preference;
+DROP INDEX IF EXISTS notification_user_id;
+DROP TABLE IF EXISTS notification;
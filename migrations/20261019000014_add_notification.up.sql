@@ -0,0 +1,26 @@
+-- In-app notifications, shown under the navbar bell until read.
+CREATE TABLE IF NOT EXISTS notification(
+    id          BLOB NOT NULL PRIMARY KEY,
+    user_id     BLOB NOT NULL,
+    kind        TEXT NOT NULL,
+    title       TEXT NOT NULL,
+    body        TEXT NOT NULL,
+    -- A path on this site with more detail, if any.
+    link        TEXT,
+    read_at     INTEGER,
+    created_at  INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS notification_user_id ON notification(user_id, created_at);
+
+-- How each user wants each kind of notification delivered. Kinds without a
+-- row use the defaults.
+CREATE TABLE IF NOT EXISTS notification_preference(
+    user_id     BLOB NOT NULL,
+    kind        TEXT NOT NULL,
+    email       BOOLEAN NOT NULL,
+    in_app      BOOLEAN NOT NULL,
+    PRIMARY KEY (user_id, kind),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
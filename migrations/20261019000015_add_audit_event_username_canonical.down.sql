@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS audit_event_username_canonical;
+ALTER TABLE audit_event DROP COLUMN username_canonical;
@@ -0,0 +1,17 @@
+-- Audit events are filtered by canonical username (NFKC + lowercase,
+-- computed in Rust), so "Alice" finds alice's events. Existing ASCII names
+-- are lowercased here; SQLite can't normalise the rest, which stay NULL.
+ALTER TABLE audit_event ADD COLUMN username_canonical TEXT;
+
+DROP TRIGGER IF EXISTS audit_event_append_only;
+
+UPDATE audit_event SET username_canonical = lower(username)
+WHERE username NOT GLOB '*[^ -~]*';
+
+CREATE TRIGGER IF NOT EXISTS audit_event_append_only
+BEFORE UPDATE ON audit_event
+BEGIN
+    SELECT RAISE(ABORT, 'audit_event is append-only');
+END;
+
+CREATE INDEX IF NOT EXISTS audit_event_username_canonical ON audit_event(username_canonical, created_at);
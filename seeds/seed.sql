@@ -1,7 +1,10 @@
-INSERT INTO user (id, username, password_hash, created_at) 
+INSERT INTO user (id, username, username_canonical, username_skeleton, password_hash, created_at, is_admin) 
 VALUES (
     X'FAAF4E0603194B2A999A3B0F5BBE0577',
     'admin',
+    'admin',
+    'admin',
     '$argon2id$v=19$m=19456,t=2,p=1$mL9KGD0cIvZEHMP0GilUEw$jBnS2AJEOyuLCSSanhmdcrl30pGTaD8qigDgsXuQFek',
-    1758306817248843
+    1758306817248843,
+    TRUE
 );
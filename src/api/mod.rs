@@ -2,9 +2,14 @@
 use axum::{Router, routing::get};
 
 use basic_site::app_state::AppState;
+use basic_site::web::compression;
 
 mod server_time;
+mod userinfo;
 
 pub fn router() -> Router<AppState> {
-    Router::new().route("/time", get(server_time::get))
+    Router::new()
+        .route("/time", get(server_time::get))
+        .route("/userinfo", get(userinfo::get).post(userinfo::get))
+        .layer(compression::layer())
 }
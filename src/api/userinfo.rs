@@ -0,0 +1,40 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse as _, Response};
+use axum_extra::TypedHeader;
+use axum_extra::headers::Authorization;
+use axum_extra::headers::authorization::Bearer;
+
+use basic_site::app_state::AppState;
+use basic_site::error::internal_error;
+use basic_site::issuer;
+use basic_site::util::current_time_micros;
+
+/// OIDC userinfo: claims about the user an access token was issued for,
+/// limited to the scope they granted.
+pub async fn get(
+    State(state): State<AppState>,
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Response {
+    let now_secs = current_time_micros().div_euclid(1_000_000);
+    let Some(access) = bearer.and_then(|TypedHeader(auth)| {
+        issuer::verify_access_token(auth.token(), now_secs)
+    }) else {
+        return invalid_token();
+    };
+    match issuer::userinfo(&state.db, &access).await {
+        Ok(Some(claims)) => Json(claims).into_response(),
+        Ok(None) => invalid_token(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+fn invalid_token() -> Response {
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static(r#"Bearer error="invalid_token""#),
+    );
+    response
+}
@@ -1,10 +1,21 @@
+use std::sync::Arc;
+
 use sqlx::SqlitePool;
-use tokio::sync::mpsc;
 
-use crate::services::Job;
+use crate::oidc::Providers;
+use crate::services::events::Broker;
+use crate::services::job::JobSender;
+use crate::storage::SharedStorage;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
-    pub job_tx: mpsc::UnboundedSender<Job>,
+    pub job_tx: JobSender,
+    /// Where uploaded files are kept.
+    pub storage: SharedStorage,
+    /// Identity providers users can sign in with; empty when none are
+    /// configured.
+    pub oidc: Arc<Providers>,
+    /// Live updates for users' open pages.
+    pub events: Broker,
 }
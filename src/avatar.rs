@@ -0,0 +1,134 @@
+//! Avatar image processing.
+//!
+//! Uploads are decoded with size limits, cropped to a square and resized to
+//! [`SIZE`] pixels, then re-encoded as PNG. Re-encoding drops metadata
+//! (EXIF location included) and anything that isn't pixels.
+
+use std::io::Cursor;
+
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader, Limits};
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Width and height of stored avatars, in pixels.
+pub const SIZE: u32 = 256;
+
+/// Largest upload accepted, in megabytes.
+pub const MAX_UPLOAD_MB: usize = 5;
+
+/// [`MAX_UPLOAD_MB`] in bytes.
+pub const MAX_UPLOAD_BYTES: usize = MAX_UPLOAD_MB * 1024 * 1024;
+
+/// Content types accepted from the browser. The decoder still checks the
+/// bytes, so a mislabelled file is rejected either way.
+pub const ACCEPTED_TYPES: [&str; 4] =
+    ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// Content type of processed avatars.
+pub const CONTENT_TYPE: &str = "image/png";
+
+/// Largest width or height decoded, so a tiny file can't claim to be a
+/// gigapixel image.
+const MAX_DIMENSION: u32 = 4096;
+
+/// Most memory a single decode may allocate: a [`MAX_DIMENSION`] square at
+/// 8-bit RGBA.
+const MAX_DECODE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Decodes allowed at once, so concurrent uploads can't add up to more than
+/// a few times [`MAX_DECODE_BYTES`].
+static DECODES: Semaphore = Semaphore::const_new(2);
+
+/// Decodes, crops and resizes an uploaded image, returning PNG bytes or a
+/// message for the user.
+pub fn process(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let unreadable =
+        || String::from("That file isn't a PNG, JPEG, GIF or WebP image.");
+
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|_err| unreadable())?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    reader.limits(limits);
+
+    let decoded = reader.decode().map_err(|err| match err {
+        image::ImageError::Limits(_) => format!(
+            "Images can be at most {MAX_DIMENSION}\u{d7}{MAX_DIMENSION} pixels."
+        ),
+        image::ImageError::Decoding(_)
+        | image::ImageError::Encoding(_)
+        | image::ImageError::Parameter(_)
+        | image::ImageError::Unsupported(_)
+        | image::ImageError::IoError(_) => unreadable(),
+    })?;
+
+    let resized = decoded.resize_to_fill(SIZE, SIZE, FilterType::Lanczos3);
+    let mut png = Vec::new();
+    resized
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|err| format!("Failed to process image: {err}"))?;
+    Ok(png)
+}
+
+/// [`process`] on the blocking pool, a few at a time; decoding a large
+/// image takes a while and a fair amount of memory.
+pub async fn process_async(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    let permit = DECODES
+        .acquire()
+        .await
+        .map_err(|err| format!("Failed to process image: {err}"))?;
+    // The permit moves into the task, so a cancelled upload keeps it until
+    // the decode actually finishes.
+    task::spawn_blocking(move || {
+        let result = process(&bytes);
+        drop(permit);
+        result
+    })
+    .await
+    .unwrap_or_else(|err| Err(format!("Failed to process image: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use super::*;
+
+    fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .expect("encoding failed");
+        bytes
+    }
+
+    #[test]
+    fn resizes_to_a_square_png() {
+        let wide = DynamicImage::ImageRgb8(RgbImage::new(600, 300));
+        let png = process(&encode(&wide, ImageFormat::Jpeg)).expect("valid");
+
+        let output =
+            image::load_from_memory_with_format(&png, ImageFormat::Png)
+                .expect("output is PNG");
+        assert_eq!((output.width(), output.height()), (SIZE, SIZE));
+    }
+
+    #[test]
+    fn rejects_oversized_dimensions() {
+        let wide = DynamicImage::ImageRgb8(RgbImage::new(MAX_DIMENSION + 1, 1));
+        let message =
+            process(&encode(&wide, ImageFormat::Png)).expect_err("too wide");
+        assert!(message.contains("at most"), "{message}");
+    }
+
+    #[test]
+    fn rejects_non_images() {
+        process(b"<svg xmlns='http://www.w3.org/2000/svg'/>")
+            .expect_err("SVG is not accepted");
+        process(b"").expect_err("empty");
+    }
+}
@@ -0,0 +1,413 @@
+//! Self-hosted defenses against automated sign-ups and password guessing:
+//! a proof-of-work challenge, a honeypot field, and a minimum time between
+//! showing a form and submitting it.
+//!
+//! A challenge is a signed token, `{issued_at}.{nonce}.{signature}`, put in
+//! the form as it's rendered. `proof-of-work.js` looks for a counter whose
+//! SHA-256 of `{token}:{counter}` starts with the configured number of zero
+//! bits. The server checks the signature, the token's age and the work,
+//! then spends the nonce so each solution is accepted once. The signing key
+//! is random per process, so a restart only costs forms left open across it
+//! another solve.
+
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac as _};
+use sha2::{Digest as _, Sha256};
+use sqlx::SqlitePool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::models::audit_event::AuditEvent;
+use crate::models::spent_challenge::SpentChallenge;
+
+/// Leading zero bits required when `BOT_POW_DIFFICULTY` is unset; about
+/// a second of work in a browser.
+pub const DEFAULT_POW_DIFFICULTY: u32 = 16;
+
+/// Most zero bits `BOT_POW_DIFFICULTY` may ask for.
+pub const MAX_POW_DIFFICULTY: u32 = 32;
+
+/// Seconds a form must be open before it's submitted, when
+/// `BOT_MIN_FILL_SECS` is unset.
+pub const DEFAULT_MIN_FILL_SECS: i64 = 3;
+
+/// Seconds a challenge stays valid when `BOT_CHALLENGE_TTL_SECS` is unset.
+pub const DEFAULT_CHALLENGE_TTL_SECS: i64 = 30 * 60;
+
+/// Failed logins (for the username or from the IP address) before logging
+/// in needs a challenge, when `BOT_LOGIN_FAILURES` is unset.
+pub const DEFAULT_LOGIN_FAILURES: i64 = 3;
+
+/// Seconds failed logins are counted over, when
+/// `BOT_LOGIN_FAILURE_WINDOW_SECS` is unset.
+pub const DEFAULT_LOGIN_FAILURE_WINDOW_SECS: i64 = 15 * 60;
+
+/// The field people never see and bots tend to fill in.
+pub const HONEYPOT_FIELD: &str = "website";
+
+static CONFIG: OnceLock<BotCheckConfig> = OnceLock::new();
+
+#[derive(Clone)]
+pub struct BotCheckConfig {
+    enabled: bool,
+    pow_difficulty: u32,
+    min_fill_micros: i64,
+    challenge_ttl_micros: i64,
+    login_failures: i64,
+    login_failure_window_micros: i64,
+    signing_key: Vec<u8>,
+}
+
+impl Default for BotCheckConfig {
+    /// Checks on, with the default thresholds.
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pow_difficulty: DEFAULT_POW_DIFFICULTY,
+            min_fill_micros: secs_to_micros(DEFAULT_MIN_FILL_SECS),
+            challenge_ttl_micros: secs_to_micros(DEFAULT_CHALLENGE_TTL_SECS),
+            login_failures: DEFAULT_LOGIN_FAILURES,
+            login_failure_window_micros: secs_to_micros(
+                DEFAULT_LOGIN_FAILURE_WINDOW_SECS,
+            ),
+            signing_key: [Uuid::new_v4(), Uuid::new_v4()]
+                .iter()
+                .flat_map(|random| random.into_bytes())
+                .collect(),
+        }
+    }
+}
+
+const fn secs_to_micros(secs: i64) -> i64 {
+    secs.saturating_mul(1_000_000)
+}
+
+/// A whole number from `name`, at least `min`.
+fn int_from_env(name: &str, default: i64, min: i64) -> Result<i64, String> {
+    match dotenvy::var(name) {
+        Ok(value) => match value.parse::<i64>() {
+            Ok(number) if number >= min => Ok(number),
+            _ => Err(format!("Invalid {name} {value:?}")),
+        },
+        Err(_) => Ok(default),
+    }
+}
+
+impl BotCheckConfig {
+    /// No checks at all: every form is accepted as sent by a person.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+
+    /// Reads `BOT_CHECK` (`off` disables every check), `BOT_POW_DIFFICULTY`
+    /// (leading zero bits, `0` for no work), `BOT_MIN_FILL_SECS`,
+    /// `BOT_CHALLENGE_TTL_SECS`, `BOT_LOGIN_FAILURES` (`0` to always
+    /// challenge logins) and `BOT_LOGIN_FAILURE_WINDOW_SECS`.
+    pub fn from_env() -> Result<Self, String> {
+        if dotenvy::var("BOT_CHECK").is_ok_and(|value| value == "off") {
+            return Ok(Self::disabled());
+        }
+        let pow_difficulty = match dotenvy::var("BOT_POW_DIFFICULTY") {
+            Ok(value) => match value.parse::<u32>() {
+                Ok(bits) if bits <= MAX_POW_DIFFICULTY => bits,
+                _ => {
+                    return Err(format!(
+                        "Invalid BOT_POW_DIFFICULTY {value:?}"
+                    ));
+                }
+            },
+            Err(_) => DEFAULT_POW_DIFFICULTY,
+        };
+        Ok(Self {
+            pow_difficulty,
+            min_fill_micros: secs_to_micros(int_from_env(
+                "BOT_MIN_FILL_SECS",
+                DEFAULT_MIN_FILL_SECS,
+                0,
+            )?),
+            challenge_ttl_micros: secs_to_micros(int_from_env(
+                "BOT_CHALLENGE_TTL_SECS",
+                DEFAULT_CHALLENGE_TTL_SECS,
+                1,
+            )?),
+            login_failures: int_from_env(
+                "BOT_LOGIN_FAILURES",
+                DEFAULT_LOGIN_FAILURES,
+                0,
+            )?,
+            login_failure_window_micros: secs_to_micros(int_from_env(
+                "BOT_LOGIN_FAILURE_WINDOW_SECS",
+                DEFAULT_LOGIN_FAILURE_WINDOW_SECS,
+                1,
+            )?),
+            ..Self::default()
+        })
+    }
+
+    /// Overrides the thresholds, e.g. for tests: leading zero bits, seconds
+    /// before a form may be submitted, and failed logins before logging in
+    /// is challenged.
+    #[must_use]
+    pub const fn with_thresholds(
+        mut self,
+        pow_difficulty: u32,
+        min_fill_secs: i64,
+        login_failures: i64,
+    ) -> Self {
+        self.pow_difficulty = pow_difficulty;
+        self.min_fill_micros = secs_to_micros(min_fill_secs);
+        self.login_failures = login_failures;
+        self
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub const fn pow_difficulty(&self) -> u32 {
+        self.pow_difficulty
+    }
+
+    fn mac(&self, form: Form, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.signing_key)
+            .expect("HMAC accepts any key length");
+        mac.update(form.as_str().as_bytes());
+        mac.update(b":");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+pub fn init(config: BotCheckConfig) {
+    if CONFIG.set(config).is_err() {
+        warn!("Bot check config already initialised");
+    }
+}
+
+/// The installed configuration, or [`BotCheckConfig::disabled`] if
+/// [`init`] wasn't called.
+pub fn config() -> &'static BotCheckConfig {
+    CONFIG.get_or_init(BotCheckConfig::disabled)
+}
+
+/// Which form a challenge was issued for; a solution for one isn't
+/// accepted by the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    Signup,
+    Login,
+}
+
+impl Form {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Signup => "signup",
+            Self::Login => "login",
+        }
+    }
+}
+
+/// A challenge to embed in a form.
+#[derive(Debug, Clone)]
+pub struct Challenge {
+    pub token: String,
+    /// Leading zero bits the solution's hash needs.
+    pub difficulty: u32,
+}
+
+impl Challenge {
+    /// A fresh challenge for `form`, or `None` if checks are off.
+    pub fn issue(form: Form, now: i64) -> Option<Self> {
+        let config = config();
+        config.is_enabled().then(|| {
+            let payload = format!("{now}.{}", Uuid::new_v4().simple());
+            let signature =
+                hex::encode(config.mac(form, &payload).finalize().into_bytes());
+            Self {
+                token: format!("{payload}.{signature}"),
+                difficulty: config.pow_difficulty(),
+            }
+        })
+    }
+}
+
+/// The bot-check fields of a submitted form.
+#[derive(Debug, Clone, Copy)]
+pub struct Submission<'a> {
+    /// The [`HONEYPOT_FIELD`], which should be empty.
+    pub honeypot: &'a str,
+    pub challenge: &'a str,
+    pub solution: &'a str,
+}
+
+/// Why a submission looks automated. Only logged: people see one generic
+/// message, so bots learn nothing from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Honeypot,
+    /// No challenge, or one that we didn't sign for this form.
+    BadChallenge,
+    TooFast,
+    Expired,
+    Unsolved,
+    Replayed,
+}
+
+impl Rejection {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Honeypot => "honeypot",
+            Self::BadChallenge => "bad_challenge",
+            Self::TooFast => "too_fast",
+            Self::Expired => "expired",
+            Self::Unsolved => "unsolved",
+            Self::Replayed => "replayed",
+        }
+    }
+}
+
+/// Leading zero bits of `digest`.
+pub fn leading_zero_bits(digest: &[u8]) -> u32 {
+    let mut bits: u32 = 0;
+    for &byte in digest {
+        bits = bits.saturating_add(byte.leading_zeros());
+        if byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn work_hash(token: &str, counter: u64) -> [u8; 32] {
+    Sha256::digest(format!("{token}:{counter}").as_bytes()).into()
+}
+
+/// The first counter that solves `token`, as `proof-of-work.js` finds it.
+pub fn solve(token: &str, difficulty: u32) -> u64 {
+    (0..u64::MAX)
+        .find(|&counter| {
+            leading_zero_bits(&work_hash(token, counter)) >= difficulty
+        })
+        .unwrap_or_default()
+}
+
+/// Checks the challenge's signature, age and work. Returns its nonce,
+/// which the caller spends.
+fn verify<'a>(
+    form: Form,
+    token: &'a str,
+    solution: &str,
+    now: i64,
+) -> Result<&'a str, Rejection> {
+    let config = config();
+    let Some((payload, signature)) = token.rsplit_once('.') else {
+        return Err(Rejection::BadChallenge);
+    };
+    let genuine = hex::decode(signature).is_ok_and(|bytes| {
+        config.mac(form, payload).verify_slice(&bytes).is_ok()
+    });
+    let Some((Ok(issued_at), nonce)) = payload
+        .split_once('.')
+        .map(|(issued, nonce)| (issued.parse::<i64>(), nonce))
+        .filter(|_| genuine)
+    else {
+        return Err(Rejection::BadChallenge);
+    };
+
+    let age = now.saturating_sub(issued_at);
+    if age < config.min_fill_micros {
+        return Err(Rejection::TooFast);
+    }
+    if age > config.challenge_ttl_micros {
+        return Err(Rejection::Expired);
+    }
+    let solved = solution.trim().parse::<u64>().is_ok_and(|counter| {
+        leading_zero_bits(&work_hash(token, counter)) >= config.pow_difficulty
+    });
+    if !solved {
+        return Err(Rejection::Unsolved);
+    }
+    Ok(nonce)
+}
+
+/// Whether the honeypot was left empty.
+pub fn passes_honeypot(submission: &Submission<'_>) -> bool {
+    !config().is_enabled() || submission.honeypot.is_empty()
+}
+
+/// Checks a submitted form, honeypot included. `None` means it passed, or
+/// checks are off.
+pub async fn check(
+    db: &SqlitePool,
+    form: Form,
+    submission: &Submission<'_>,
+    now: i64,
+) -> Result<Option<Rejection>, sqlx::Error> {
+    if !config().is_enabled() {
+        return Ok(None);
+    }
+    if !passes_honeypot(submission) {
+        return Ok(Some(Rejection::Honeypot));
+    }
+    let nonce =
+        match verify(form, submission.challenge, submission.solution, now) {
+            Ok(nonce) => nonce,
+            Err(rejection) => return Ok(Some(rejection)),
+        };
+    let ttl = config().challenge_ttl_micros;
+    SpentChallenge::delete_before(db, now.saturating_sub(ttl)).await?;
+    if SpentChallenge::spend(db, nonce, now).await? {
+        Ok(None)
+    } else {
+        Ok(Some(Rejection::Replayed))
+    }
+}
+
+/// Whether logging in as `username` from `ip` needs a challenge, because
+/// of recent failures for either.
+pub async fn login_needs_challenge(
+    db: &SqlitePool,
+    username: &str,
+    ip: &str,
+    now: i64,
+) -> Result<bool, sqlx::Error> {
+    let config = config();
+    if !config.is_enabled() {
+        return Ok(false);
+    }
+    if config.login_failures == 0 {
+        return Ok(true);
+    }
+    let since = now.saturating_sub(config.login_failure_window_micros);
+    let failures =
+        AuditEvent::count_login_failures(db, username, ip, since).await?;
+    Ok(failures >= config.login_failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_bits_are_counted_across_bytes() {
+        assert_eq!(leading_zero_bits(&[0xFF]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10, 0x00]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn solutions_meet_the_difficulty() {
+        let counter = solve("1.abc.def", 8);
+        assert!(leading_zero_bits(&work_hash("1.abc.def", counter)) >= 8);
+        assert!(
+            (0..counter).all(|earlier| {
+                leading_zero_bits(&work_hash("1.abc.def", earlier)) < 8
+            }),
+            "the first solution is found"
+        );
+    }
+}
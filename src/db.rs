@@ -1,5 +1,9 @@
+use sqlx::migrate::Migrator;
 use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
 
+/// Migrations embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub async fn connect_to_database() -> SqlitePool {
     let url = dotenvy::var("DATABASE_URL").expect("DATABASE_URL not set");
     SqlitePoolOptions::new()
@@ -7,3 +11,20 @@ pub async fn connect_to_database() -> SqlitePool {
         .await
         .expect("Failed to connect to database")
 }
+
+/// Returns the versions of embedded migrations not yet applied to `db`.
+pub async fn pending_migrations(
+    db: &SqlitePool,
+) -> Result<Vec<i64>, sqlx::Error> {
+    let applied: Vec<i64> = sqlx::query_scalar(
+        "SELECT version FROM _sqlx_migrations WHERE success = 1",
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
+}
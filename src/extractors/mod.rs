@@ -1 +1,4 @@
+pub mod client;
+pub mod organization;
 pub mod session;
+pub mod upload;
@@ -0,0 +1,97 @@
+//! Organization-scoped routes.
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts, RawPathParams},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{
+    app_state::AppState,
+    error::internal_error,
+    models::{
+        membership::Membership,
+        organization::{Organization, Role},
+        user::User,
+    },
+};
+
+/// Path parameter naming the organization, e.g. `/orgs/{org_slug}`.
+pub const SLUG_PARAM: &str = "org_slug";
+
+/// The signed-in user, the organization named by the route's
+/// [`SLUG_PARAM`], and the user's role in it.
+///
+/// Rejects with a redirect to the login page when signed out, and with
+/// 404 when the organization doesn't exist or the user isn't a member, so
+/// outsiders can't tell which organizations exist.
+#[derive(Debug, Clone)]
+pub struct OrgMember {
+    pub user: User,
+    pub organization: Organization,
+    pub role: Role,
+}
+
+impl OrgMember {
+    /// 403 unless the member's role is at least `role`.
+    pub fn require(&self, role: Role) -> Result<(), StatusCode> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for OrgMember {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let user_opt =
+            <User as OptionalFromRequestParts<AppState>>::from_request_parts(
+                parts, state,
+            )
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Some(user) = user_opt else {
+            return Err(Redirect::to("/login").into_response());
+        };
+
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Some(slug) = params
+            .iter()
+            .find_map(|(name, value)| (name == SLUG_PARAM).then_some(value))
+        else {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        };
+
+        let organization =
+            match Organization::get_by_slug(&state.db, slug).await {
+                Ok(Some(organization)) => organization,
+                Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+                Err(err) => return Err(internal_error(err).into_response()),
+            };
+        let role = match Membership::role_of(
+            &state.db,
+            organization.id,
+            user.id,
+        )
+        .await
+        {
+            Ok(Some(role)) => role,
+            Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+            Err(err) => return Err(internal_error(err).into_response()),
+        };
+
+        Ok(Self {
+            user,
+            organization,
+            role,
+        })
+    }
+}
@@ -1,8 +1,18 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
 use axum::{
-    extract::{FromRequestParts as _, OptionalFromRequestParts},
-    http::{StatusCode, request::Parts},
+    extract::{
+        ConnectInfo, FromRequestParts as _, OptionalFromRequestParts, Request,
+    },
+    http::{HeaderValue, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
 };
-use axum_extra::extract::{CookieJar, cookie::Cookie};
 use sqlx::Error;
 use tracing::{Span, debug, warn};
 use uuid::Uuid;
@@ -10,11 +20,82 @@ use uuid::Uuid;
 use crate::{
     app_state::AppState,
     error::internal_error,
-    models::{session::Session, user::User},
+    models::session::{PERSISTENT_TTL_MICROS, Session},
+    models::user::User,
     util::current_time_micros,
 };
 
-impl OptionalFromRequestParts<AppState> for User {
+pub const SESSION_COOKIE: &str = "session_id";
+
+/// Builds the session cookie. Persistent sessions outlive the browser;
+/// others are dropped when it closes.
+pub fn build_session_cookie(
+    session_id: Uuid,
+    persistent: bool,
+) -> Cookie<'static> {
+    // This is a workaround so when we're testing locally
+    // without https
+    const IS_RELEASE: bool = !cfg!(debug_assertions);
+    let mut cookie = Cookie::build((SESSION_COOKIE, session_id.to_string()))
+        .path("/")
+        .same_site(SameSite::Strict)
+        .secure(IS_RELEASE)
+        .http_only(true)
+        .build();
+    if persistent {
+        cookie.set_max_age(time::Duration::microseconds(PERSISTENT_TTL_MICROS));
+    }
+    cookie.into_owned()
+}
+
+/// Slot the extractor fills when it slides a persistent session forward, so
+/// [`refresh_session_cookie`] can re-issue the cookie with a fresh max-age.
+#[derive(Clone, Default)]
+struct RenewedCookie(Arc<Mutex<Option<Cookie<'static>>>>);
+
+/// Middleware re-issuing the session cookie after a sliding renewal.
+///
+/// Skipped when the handler already set or removed the session cookie
+/// itself (login, logout).
+pub async fn refresh_session_cookie(
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let slot = RenewedCookie::default();
+    request.extensions_mut().insert(slot.clone());
+
+    let mut response = next.run(request).await;
+
+    let renewed = slot.0.lock().expect("session cookie slot poisoned").take();
+    let handler_set_cookie = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .any(|value| {
+            value
+                .to_str()
+                .is_ok_and(|cookie| cookie.starts_with(SESSION_COOKIE))
+        });
+    if let Some(cookie) = renewed
+        && !handler_set_cookie
+        && let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string())
+    {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+    response
+}
+
+/// The authenticated user along with the session they are using.
+///
+/// Extract this instead of [`User`] when a handler needs to tell the
+/// current session apart from the user's others.
+#[derive(Debug, Clone)]
+pub struct CurrentSession {
+    pub user: User,
+    pub session: Session,
+}
+
+impl OptionalFromRequestParts<AppState> for CurrentSession {
     type Rejection = (StatusCode, String);
 
     async fn from_request_parts(
@@ -35,7 +116,27 @@ impl OptionalFromRequestParts<AppState> for User {
             return Ok(None);
         }
 
-        load_user(&state.db, session.user_id).await
+        renew_if_due(parts, &state.db, &session).await;
+        touch_if_due(parts, &state.db, &session).await;
+
+        let maybe_user = load_user(&state.db, session.user_id).await?;
+        Ok(maybe_user.map(|user| Self { user, session }))
+    }
+}
+
+impl OptionalFromRequestParts<AppState> for User {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        let current =
+            <CurrentSession as OptionalFromRequestParts<AppState>>::from_request_parts(
+                parts, state,
+            )
+            .await?;
+        Ok(current.map(|current_session| current_session.user))
     }
 }
 
@@ -45,7 +146,7 @@ async fn extract_session_id(
 ) -> Result<Option<Uuid>, (StatusCode, String)> {
     let Ok(jar) = CookieJar::from_request_parts(parts, state).await;
 
-    let Some(raw) = jar.get("session_id").map(Cookie::value) else {
+    let Some(raw) = jar.get(SESSION_COOKIE).map(Cookie::value) else {
         return Ok(None);
     };
 
@@ -85,6 +186,51 @@ async fn is_session_expired(
     false
 }
 
+/// Slides the session's expiry forward, at most once per renewal interval.
+async fn renew_if_due(parts: &Parts, db: &sqlx::SqlitePool, session: &Session) {
+    let now = current_time_micros();
+    if !session.needs_renewal(now) {
+        return;
+    }
+
+    let expires_at = now.saturating_add(session.ttl_micros());
+    if let Err(err) = Session::renew(db, session.id, expires_at).await {
+        warn!("Failed to renew session {}: {err}", session.id);
+        return;
+    }
+    debug!(session_id = %session.id, "Session renewed");
+
+    if session.persistent
+        && let Some(slot) = parts.extensions.get::<RenewedCookie>()
+    {
+        *slot.0.lock().expect("session cookie slot poisoned") =
+            Some(build_session_cookie(session.id, true));
+    }
+}
+
+/// Records the session's activity time and client IP, throttled by
+/// [`Session::needs_touch`].
+async fn touch_if_due(parts: &Parts, db: &sqlx::SqlitePool, session: &Session) {
+    let ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(
+            || session.last_ip.clone(),
+            |info| info.0.ip().to_string(),
+        );
+    let now = current_time_micros();
+    if !session.needs_touch(now, &ip) {
+        return;
+    }
+
+    if let Err(err) = Session::touch(db, session.id, now, &ip).await {
+        warn!(
+            "Failed to record activity for session {}: {err}",
+            session.id
+        );
+    }
+}
+
 async fn load_user(
     db: &sqlx::SqlitePool,
     user_id: Uuid,
@@ -0,0 +1,179 @@
+//! Multipart file upload extractor.
+
+use axum::body::Bytes;
+use axum::extract::multipart::MultipartError;
+use axum::extract::{FromRequest, Multipart, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+use crate::upload::{self, sanitize_filename};
+
+/// Form field the file is read from.
+pub const FIELD: &str = "file";
+
+/// The file from a `multipart/form-data` request's [`FIELD`] field,
+/// checked against the configured size and content type limits.
+///
+/// The body is read in chunks and abandoned as soon as it passes the size
+/// limit. Routes using this still need a `DefaultBodyLimit` above
+/// axum's 2 MB default (see [`body_limit`]).
+#[derive(Debug)]
+pub struct FileUpload {
+    /// Sanitised with [`sanitize_filename`].
+    pub filename: String,
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadRejection {
+    /// No non-empty file in the form.
+    Missing,
+    /// Larger than this many bytes.
+    TooLarge(usize),
+    /// The declared content type isn't allowed.
+    UnsupportedType(String),
+    /// Not a readable multipart body.
+    Malformed(String),
+}
+
+impl UploadRejection {
+    pub const fn status(&self) -> StatusCode {
+        match *self {
+            Self::Missing | Self::Malformed(_) => StatusCode::BAD_REQUEST,
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::UnsupportedType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+
+    /// Explanation for the user.
+    #[allow(
+        clippy::allow_attributes,
+        clippy::pattern_type_mismatch,
+        reason = "Payloads are borrowed from the rejection"
+    )]
+    pub fn message(&self) -> String {
+        match self {
+            Self::Missing => "Choose a file to upload.".to_owned(),
+            Self::TooLarge(limit) => {
+                format!("Files can be at most {}.", format_size(*limit))
+            }
+            Self::UnsupportedType(content_type) => {
+                format!("Files of type {content_type} aren't accepted.")
+            }
+            Self::Malformed(detail) => format!("Upload failed: {detail}"),
+        }
+    }
+}
+
+impl IntoResponse for UploadRejection {
+    fn into_response(self) -> Response {
+        (self.status(), self.message()).into_response()
+    }
+}
+
+/// Request body limit for upload routes: the largest file plus room for
+/// the multipart framing and other fields.
+pub fn body_limit() -> usize {
+    upload::config().max_bytes().saturating_add(64 * 1024)
+}
+
+/// `1536` -> `1.5 KB`.
+#[allow(
+    clippy::allow_attributes,
+    clippy::integer_division,
+    clippy::integer_division_remainder_used,
+    reason = "Sizes are shown to one decimal place, rounded down"
+)]
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{bytes} bytes");
+    }
+    let mut divisor: usize = 1024;
+    let mut unit = UNITS[0];
+    for next in UNITS.iter().skip(1) {
+        if bytes.checked_div(divisor).unwrap_or_default() < 1024 {
+            break;
+        }
+        divisor = divisor.saturating_mul(1024);
+        unit = next;
+    }
+    let tenths = bytes
+        .saturating_mul(10)
+        .checked_div(divisor)
+        .unwrap_or_default();
+    format!("{}.{} {unit}", tenths / 10, tenths % 10)
+}
+
+impl<S: Send + Sync> FromRequest<S> for FileUpload {
+    type Rejection = UploadRejection;
+
+    async fn from_request(
+        req: Request,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let config = upload::config();
+        let too_large = || UploadRejection::TooLarge(config.max_bytes());
+        let read_failed = |err: MultipartError| {
+            if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                too_large()
+            } else {
+                UploadRejection::Malformed(err.body_text())
+            }
+        };
+
+        let mut multipart =
+            Multipart::from_request(req, state)
+                .await
+                .map_err(|rejection| {
+                    UploadRejection::Malformed(rejection.body_text())
+                })?;
+
+        while let Some(mut field) =
+            multipart.next_field().await.map_err(read_failed)?
+        {
+            if field.name() != Some(FIELD) {
+                continue;
+            }
+            let filename =
+                sanitize_filename(field.file_name().unwrap_or_default());
+            let content_type = field
+                .content_type()
+                .unwrap_or("application/octet-stream")
+                .to_owned();
+            if !config.allows(&content_type) {
+                return Err(UploadRejection::UnsupportedType(content_type));
+            }
+
+            let mut data = Vec::new();
+            while let Some(chunk) = field.chunk().await.map_err(read_failed)? {
+                if data.len().saturating_add(chunk.len()) > config.max_bytes() {
+                    return Err(too_large());
+                }
+                data.extend_from_slice(&chunk);
+            }
+            if data.is_empty() {
+                return Err(UploadRejection::Missing);
+            }
+            return Ok(Self {
+                filename,
+                content_type,
+                data: Bytes::from(data),
+            });
+        }
+        Err(UploadRejection::Missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(10 * 1024 * 1024), "10.0 MB");
+    }
+}
@@ -0,0 +1,251 @@
+//! This site as an OIDC provider for other apps: the signing key, token
+//! lifetimes and the claims handed out about a user.
+//!
+//! Clients are registered by admins and sign users in with the
+//! authorization-code flow and PKCE. ID tokens and access tokens are both
+//! RS256 JWTs; access tokens name the userinfo endpoint as their audience,
+//! so an ID token can't stand in for one.
+
+use std::fs;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use sqlx::SqlitePool;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::jwt::{self, JwkSet, SigningKey};
+use crate::models::profile::Profile;
+use crate::models::user::User;
+use crate::util::base_url;
+
+/// Scopes clients may request; others are ignored.
+pub const SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+/// Lifetime of ID and access tokens when `OIDC_ISSUER_TOKEN_TTL_SECS` is
+/// unset.
+pub const DEFAULT_TOKEN_TTL_SECS: i64 = 60 * 60;
+
+static CONFIG: OnceLock<IssuerConfig> = OnceLock::new();
+
+#[derive(Clone)]
+pub struct IssuerConfig {
+    signing_key: SigningKey,
+    token_ttl_secs: i64,
+}
+
+impl IssuerConfig {
+    pub const fn new(signing_key: SigningKey) -> Self {
+        Self {
+            signing_key,
+            token_ttl_secs: DEFAULT_TOKEN_TTL_SECS,
+        }
+    }
+
+    /// A random signing key, so tokens stop verifying after a restart.
+    fn generated() -> Self {
+        Self::new(SigningKey::generate().expect("RSA key generation failed"))
+    }
+
+    /// Reads `OIDC_ISSUER_KEY_FILE` (a PKCS#8 PEM RSA private key) and
+    /// `OIDC_ISSUER_TOKEN_TTL_SECS`.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = if let Ok(path) = dotenvy::var("OIDC_ISSUER_KEY_FILE")
+        {
+            let pem = fs::read_to_string(&path)
+                .map_err(|err| format!("Can't read {path}: {err}"))?;
+            let key = SigningKey::from_pem(&pem)
+                .map_err(|err| format!("Invalid key in {path}: {err}"))?;
+            Self::new(key)
+        } else {
+            warn!(
+                "OIDC_ISSUER_KEY_FILE is not set; tokens issued to apps won't survive a restart"
+            );
+            Self::generated()
+        };
+        if let Ok(value) = dotenvy::var("OIDC_ISSUER_TOKEN_TTL_SECS") {
+            config.token_ttl_secs = match value.parse::<i64>() {
+                Ok(secs) if secs > 0 => secs,
+                _ => {
+                    return Err(format!(
+                        "Invalid OIDC_ISSUER_TOKEN_TTL_SECS {value:?}"
+                    ));
+                }
+            };
+        }
+        Ok(config)
+    }
+
+    pub const fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    /// How long issued tokens stay valid, in seconds.
+    pub const fn token_ttl_secs(&self) -> i64 {
+        self.token_ttl_secs
+    }
+
+    /// The published keys, as served from the JWKS endpoint.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: vec![self.signing_key.jwk()],
+        }
+    }
+}
+
+pub fn init(config: IssuerConfig) {
+    if CONFIG.set(config).is_err() {
+        warn!("Issuer config already initialised");
+    }
+}
+
+/// The installed configuration, or one with a generated key if [`init`]
+/// wasn't called.
+pub fn config() -> &'static IssuerConfig {
+    CONFIG.get_or_init(IssuerConfig::generated)
+}
+
+/// The `iss` of every token we sign.
+pub fn issuer() -> String {
+    base_url()
+}
+
+/// The `aud` of access tokens: the only place they're accepted.
+pub fn userinfo_url() -> String {
+    format!("{}/api/v1/userinfo", base_url())
+}
+
+/// The supported scopes named in a space-separated `scope` parameter, in
+/// [`SCOPES`] order.
+pub fn supported_scopes(requested: &str) -> Vec<&'static str> {
+    let named: Vec<&str> = requested.split_whitespace().collect();
+    SCOPES
+        .into_iter()
+        .filter(|scope| named.contains(scope))
+        .collect()
+}
+
+/// Whether the space-separated `granted` scopes include all of `wanted`.
+pub fn covers(granted: &str, wanted: &[&str]) -> bool {
+    let granted_scopes: Vec<&str> = granted.split_whitespace().collect();
+    wanted.iter().all(|scope| granted_scopes.contains(scope))
+}
+
+/// Hex SHA-256 of a client secret or authorization code. Both are random
+/// and long, so a fast hash is enough to keep them out of the database.
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+/// What a client learns about a user, depending on the granted scope.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserClaims {
+    /// The user's ID, which unlike the username never changes.
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Addresses aren't verified yet, so this is `false` whenever `email`
+    /// is present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
+
+impl UserClaims {
+    pub fn new(user: &User, profile: &Profile, scope: &str) -> Self {
+        let (preferred_username, name) = if covers(scope, &["profile"]) {
+            (
+                Some(user.username.clone()),
+                Some(profile.display_name.clone())
+                    .filter(|name| !name.is_empty()),
+            )
+        } else {
+            (None, None)
+        };
+        let email = user.email.clone().filter(|_| covers(scope, &["email"]));
+        Self {
+            sub: user.id.to_string(),
+            preferred_username,
+            name,
+            email_verified: email.as_ref().map(|_| false),
+            email,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    /// The client ID.
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub auth_time: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub user: UserClaims,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    /// Always [`userinfo_url`].
+    pub aud: String,
+    pub client_id: String,
+    pub scope: String,
+    pub exp: i64,
+    pub iat: i64,
+}
+
+/// The access token's claims if we signed it for userinfo and it hasn't
+/// expired by `now_secs`.
+pub fn verify_access_token(
+    token: &str,
+    now_secs: i64,
+) -> Option<AccessTokenClaims> {
+    let claims: AccessTokenClaims =
+        jwt::verify(token, &config().jwks()).ok()?;
+    (claims.iss == issuer()
+        && claims.aud == userinfo_url()
+        && claims.exp > now_secs)
+        .then_some(claims)
+}
+
+/// What the userinfo endpoint returns for a verified access token, or
+/// `None` if its user is gone.
+pub async fn userinfo(
+    db: &SqlitePool,
+    access: &AccessTokenClaims,
+) -> Result<Option<UserClaims>, sqlx::Error> {
+    let Ok(user_id) = Uuid::parse_str(&access.sub) else {
+        return Ok(None);
+    };
+    let user = match User::get_by_id(db, user_id).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let profile = Profile::get(db, user.id).await?;
+    Ok(Some(UserClaims::new(&user, &profile, &access.scope)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_supported_scopes_are_kept() {
+        assert_eq!(
+            supported_scopes("email  openid offline_access"),
+            ["openid", "email"]
+        );
+        assert!(covers("openid profile email", &["email", "openid"]));
+        assert!(!covers("openid", &["openid", "profile"]));
+    }
+}
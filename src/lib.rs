@@ -1,11 +1,25 @@
 //! Basic Site - A forkable Rust web application template.
 
 pub mod app_state;
+pub mod avatar;
+pub mod bot_check;
 pub mod db;
 pub mod error;
 pub mod extractors;
+pub mod issuer;
+pub mod jwt;
 pub mod models;
+pub mod oidc;
+pub mod organization;
 pub mod password;
+pub mod registration;
+pub mod security_headers;
 pub mod services;
+pub mod storage;
+pub mod telemetry;
+pub mod upload;
+pub mod user_agent;
+pub mod username;
 pub mod util;
+pub mod validation;
 pub mod web;
@@ -1,64 +1,99 @@
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use axum::Router;
-use axum::body::Body;
-use axum::http::Request;
 use basic_site::app_state::AppState;
+use basic_site::bot_check::{self, BotCheckConfig};
 use basic_site::db::connect_to_database;
+use basic_site::issuer::{self, IssuerConfig};
+use basic_site::models::user::User;
+use basic_site::oidc::Providers;
+use basic_site::password::{self, PasswordConfig};
+use basic_site::registration::{self, RegistrationMode};
+use basic_site::security_headers::{
+    SecurityHeadersConfig, with_security_headers,
+};
 use basic_site::services;
+use basic_site::storage;
+use basic_site::telemetry::{self, with_request_tracing};
+use basic_site::upload::{self, UploadConfig};
 use basic_site::web;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use tokio::net::TcpListener;
-use tokio::sync::mpsc;
-use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
-use tracing::Level;
-use tracing::field;
 use tracing::info;
-use tracing_subscriber::EnvFilter;
+use tracing::warn;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{EnvFilter, fmt};
 
 mod api;
 
-fn configure_logging() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
+fn configure_logging() -> Option<SdkTracerProvider> {
+    let provider = telemetry::tracer_provider();
+    let otel_layer = provider.as_ref().map(|tracer_provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer_provider.tracer(telemetry::SERVICE_NAME))
+    });
+
+    tracing_subscriber::registry()
+        .with(
             EnvFilter::try_from_default_env()
                 .or_else(|_| {
                     EnvFilter::try_new("basic_site=info,tower_http=info")
                 })
                 .unwrap(),
         )
+        .with(fmt::layer())
+        .with(otel_layer)
         .init();
+
+    provider
 }
 
 #[tokio::main]
 async fn main() {
-    configure_logging();
+    let tracer_provider = configure_logging();
+
+    password::init(
+        PasswordConfig::from_env().expect("Invalid password hashing config"),
+    );
+    upload::init(UploadConfig::from_env().expect("Invalid upload config"));
+    registration::init(
+        RegistrationMode::from_env().expect("Invalid registration config"),
+    );
+    bot_check::init(
+        BotCheckConfig::from_env().expect("Invalid bot check config"),
+    );
+    let storage = storage::from_env().expect("Invalid storage config");
+    let oidc = Providers::from_env().expect("Invalid OIDC provider config");
+    issuer::init(IssuerConfig::from_env().expect("Invalid OIDC issuer config"));
 
     let db = connect_to_database().await;
+    match User::backfill_username_keys(&db).await {
+        Ok(0) => {}
+        Ok(updated) => info!(updated, "Backfilled canonical usernames"),
+        Err(err) => warn!("Failed to backfill canonical usernames: {err}"),
+    }
 
-    let (job_tx, job_rx) = mpsc::unbounded_channel();
+    let (job_tx, job_rx) = services::job::channel();
     tokio::spawn(services::job::run(db.clone(), job_rx));
+    tokio::spawn(services::audit::schedule_pruning(job_tx.clone()));
 
-    let state = AppState { db, job_tx };
+    let state = AppState {
+        db,
+        job_tx,
+        storage,
+        oidc: Arc::new(oidc),
+        events: services::events::Broker::default(),
+    };
 
-    let trace_layer = TraceLayer::new_for_http()
-        .make_span_with(|request: &Request<Body>| {
-            let request_id = uuid::Uuid::new_v4();
-            tracing::info_span!(
-                "request",
-                %request_id,
-                method = %request.method(),
-                uri = %request.uri(),
-                session_id = field::Empty,
-                user_id = field::Empty,
-            )
-        })
-        .on_request(DefaultOnRequest::new().level(Level::DEBUG))
-        .on_response(DefaultOnResponse::new().level(Level::INFO));
-
-    let app = Router::new()
+    let routes = Router::new()
         .merge(web::static_router())
-        .merge(web::router().layer(trace_layer.clone()))
-        .nest("/api/v1", api::router().layer(trace_layer))
+        .merge(web::health_router())
+        .merge(with_request_tracing(web::router()))
+        .nest("/api/v1", with_request_tracing(api::router()));
+    let app = with_security_headers(routes, SecurityHeadersConfig::from_env())
         .with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
@@ -72,4 +107,10 @@ async fn main() {
     )
     .await
     .expect("Failed to serve");
+
+    if let Some(provider) = tracer_provider
+        && let Err(err) = provider.shutdown()
+    {
+        warn!("Failed to flush exported spans: {err}");
+    }
 }
@@ -0,0 +1,219 @@
+use serde::Deserialize;
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+use crate::username;
+
+/// What happened in an [`AuditEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditKind {
+    Login,
+    LoginFailed,
+    Logout,
+    PasswordChanged,
+    UsernameChanged,
+    EmailChanged,
+    SessionRevoked,
+    IdentityLinked,
+    IdentityUnlinked,
+    AppAuthorized,
+    ClientRegistered,
+    ClientRemoved,
+}
+
+impl AuditKind {
+    pub const ALL: [Self; 12] = [
+        Self::Login,
+        Self::LoginFailed,
+        Self::Logout,
+        Self::PasswordChanged,
+        Self::UsernameChanged,
+        Self::EmailChanged,
+        Self::SessionRevoked,
+        Self::IdentityLinked,
+        Self::IdentityUnlinked,
+        Self::AppAuthorized,
+        Self::ClientRegistered,
+        Self::ClientRemoved,
+    ];
+
+    /// Value stored in the `kind` column and used in filter query strings.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Login => "login",
+            Self::LoginFailed => "login_failed",
+            Self::Logout => "logout",
+            Self::PasswordChanged => "password_changed",
+            Self::UsernameChanged => "username_changed",
+            Self::EmailChanged => "email_changed",
+            Self::SessionRevoked => "session_revoked",
+            Self::IdentityLinked => "identity_linked",
+            Self::IdentityUnlinked => "identity_unlinked",
+            Self::AppAuthorized => "app_authorized",
+            Self::ClientRegistered => "client_registered",
+            Self::ClientRemoved => "client_removed",
+        }
+    }
+
+    /// Human-readable description for activity pages.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Login => "Signed in",
+            Self::LoginFailed => "Failed sign-in",
+            Self::Logout => "Signed out",
+            Self::PasswordChanged => "Password changed",
+            Self::UsernameChanged => "Username changed",
+            Self::EmailChanged => "Email changed",
+            Self::SessionRevoked => "Session revoked",
+            Self::IdentityLinked => "Sign-in provider connected",
+            Self::IdentityUnlinked => "Sign-in provider disconnected",
+            Self::AppAuthorized => "App authorized",
+            Self::ClientRegistered => "App registered",
+            Self::ClientRemoved => "App removed",
+        }
+    }
+}
+
+/// A security-relevant account event. Rows are never updated; old ones are
+/// pruned by [`AuditEvent::delete_before`].
+#[derive(Debug, Clone, FromRow)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub username: String,
+    pub kind: AuditKind,
+    pub ip_address: String,
+    pub user_agent: String,
+    pub detail: String,
+    pub created_at: i64,
+}
+
+/// Optional filters for the admin audit log view. Empty strings (as sent by
+/// a blank form field) mean "any".
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub username: Option<String>,
+    /// An [`AuditKind::as_str`] value.
+    pub kind: Option<String>,
+}
+
+impl AuditEvent {
+    /// Appends the event, ignoring `event.id`, and returns the new ID.
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        event: &Self,
+    ) -> Result<i64, sqlx::Error> {
+        let username_canonical = username::canonical(&event.username);
+        sqlx::query!(
+            "INSERT INTO audit_event (user_id, username, username_canonical, kind, ip_address, user_agent, detail, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            event.user_id,
+            event.username,
+            username_canonical,
+            event.kind,
+            event.ip_address,
+            event.user_agent,
+            event.detail,
+            event.created_at,
+        )
+        .execute(db)
+        .await
+        .map(|row| row.last_insert_rowid())
+    }
+
+    /// The user's most recent events, newest first.
+    pub async fn get_by_user_id<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            AuditEvent,
+            r#"SELECT
+            id,
+            user_id as "user_id: uuid::Uuid",
+            username,
+            kind as "kind: AuditKind",
+            ip_address,
+            user_agent,
+            detail,
+            created_at
+            FROM audit_event WHERE user_id = ?
+            ORDER BY id DESC LIMIT ?"#,
+            user_id,
+            limit
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// The most recent events matching `filter` across all users, newest
+    /// first.
+    pub async fn search<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        filter: &AuditFilter,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let username = filter
+            .username
+            .as_deref()
+            .filter(|name| !name.is_empty())
+            .map(username::canonical);
+        let kind = filter.kind.as_deref().filter(|value| !value.is_empty());
+        sqlx::query_as!(
+            AuditEvent,
+            r#"SELECT
+            id,
+            user_id as "user_id: uuid::Uuid",
+            username,
+            kind as "kind: AuditKind",
+            ip_address,
+            user_agent,
+            detail,
+            created_at
+            FROM audit_event
+            WHERE (?1 IS NULL OR username_canonical = ?1) AND (?2 IS NULL OR kind = ?2)
+            ORDER BY id DESC LIMIT ?3"#,
+            username,
+            kind,
+            limit
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Failed logins since `since` that named `username` (in any case) or
+    /// came from `ip`.
+    pub async fn count_login_failures<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        username: &str,
+        ip: &str,
+        since: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let kind = AuditKind::LoginFailed.as_str();
+        let username_canonical =
+            username::canonical(username::truncated(username));
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM audit_event
+            WHERE kind = ? AND created_at >= ? AND (username_canonical = ? OR ip_address = ?)"#,
+            kind,
+            since,
+            username_canonical,
+            ip
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Deletes events older than `cutoff`, returning how many were removed.
+    pub async fn delete_before<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        cutoff: i64,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!("DELETE FROM audit_event WHERE created_at < ?", cutoff)
+            .execute(db)
+            .await
+            .map(|row| row.rows_affected())
+    }
+}
@@ -0,0 +1,72 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// A user's resized profile picture.
+#[derive(Debug, Clone, FromRow)]
+pub struct Avatar {
+    pub content_type: String,
+    pub data: Vec<u8>,
+    /// Also used as the cache-busting version in avatar URLs.
+    pub updated_at: i64,
+}
+
+impl Avatar {
+    /// Stores the user's avatar, replacing any previous one.
+    pub async fn upsert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        avatar: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO avatar (user_id, content_type, data, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+            content_type = excluded.content_type,
+            data = excluded.data,
+            updated_at = excluded.updated_at",
+            user_id,
+            avatar.content_type,
+            avatar.data,
+            avatar.updated_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Avatar,
+            "SELECT content_type, data, updated_at FROM avatar WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// When the user's avatar was last changed, without loading the image.
+    pub async fn updated_at<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT updated_at FROM avatar WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    pub async fn delete<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM avatar WHERE user_id = ?", user_id)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+}
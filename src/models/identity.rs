@@ -0,0 +1,94 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// An account at an external identity provider, linked to a user so they
+/// can sign in with it.
+#[derive(Debug, Clone, FromRow)]
+pub struct Identity {
+    /// The provider's configured name, e.g. "google".
+    pub provider: String,
+    /// The provider's `sub` claim: stable and unique per provider.
+    pub subject: String,
+    pub user_id: Uuid,
+    /// Verified address reported by the provider when linked, for display.
+    pub email: Option<String>,
+    pub created_at: i64,
+}
+
+impl Identity {
+    /// Links the identity. Fails with a unique violation if it's already
+    /// linked, or if the user already has an identity at that provider.
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        identity: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO user_identity (provider, subject, user_id, email, created_at) VALUES (?, ?, ?, ?, ?)",
+            identity.provider,
+            identity.subject,
+            identity.user_id,
+            identity.email,
+            identity.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Identity,
+            r#"SELECT
+            provider,
+            subject,
+            user_id as "user_id: uuid::Uuid",
+            email,
+            created_at
+            FROM user_identity WHERE provider = ? AND subject = ?"#,
+            provider,
+            subject
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    pub async fn get_by_user_id<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Identity,
+            r#"SELECT
+            provider,
+            subject,
+            user_id as "user_id: uuid::Uuid",
+            email,
+            created_at
+            FROM user_identity WHERE user_id = ? ORDER BY created_at"#,
+            user_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Unlinks the user's identity at `provider`. Returns whether there was
+    /// one.
+    pub async fn delete<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        provider: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM user_identity WHERE user_id = ? AND provider = ?",
+            user_id,
+            provider
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,114 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// A code that lets up to `max_uses` people sign up before `expires_at`.
+#[derive(Debug, Clone, FromRow)]
+pub struct Invite {
+    pub id: Uuid,
+    /// See [`crate::registration::hash_code`].
+    pub code_hash: String,
+    pub created_by: Uuid,
+    /// Where the invite link was emailed, used as the new account's
+    /// address.
+    pub email: Option<String>,
+    pub max_uses: i64,
+    pub use_count: i64,
+    pub expires_at: i64,
+    pub created_at: i64,
+}
+
+impl Invite {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        invite: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO invite (id, code_hash, created_by, email, max_uses, use_count, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            invite.id,
+            invite.code_hash,
+            invite.created_by,
+            invite.email,
+            invite.max_uses,
+            invite.use_count,
+            invite.expires_at,
+            invite.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's invites, newest first.
+    pub async fn list_by_creator<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Invite,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            code_hash,
+            created_by as "created_by: uuid::Uuid",
+            email,
+            max_uses,
+            use_count,
+            expires_at,
+            created_at
+            FROM invite WHERE created_by = ? ORDER BY created_at DESC"#,
+            user_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Uses up one sign-up on the invite, if it exists, hasn't expired by
+    /// `now` and has uses left. Run it in the sign-up's transaction so a
+    /// failed sign-up gives the use back.
+    pub async fn redeem<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        code_hash: &str,
+        now: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Invite,
+            r#"UPDATE invite SET use_count = use_count + 1
+            WHERE code_hash = ? AND expires_at > ? AND use_count < max_uses
+            RETURNING
+            id as "id: uuid::Uuid",
+            code_hash,
+            created_by as "created_by: uuid::Uuid",
+            email,
+            max_uses,
+            use_count,
+            expires_at,
+            created_at"#,
+            code_hash,
+            now
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Deletes the invite if `user_id` created it. Returns `false`
+    /// otherwise.
+    pub async fn delete_owned<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM invite WHERE id = ? AND created_by = ?",
+            id,
+            user_id
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Whether the invite can still be used at `now`.
+    pub const fn is_usable(&self, now: i64) -> bool {
+        self.expires_at > now && self.use_count < self.max_uses
+    }
+}
@@ -0,0 +1,29 @@
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+/// An IP/user-agent combination a user has logged in from.
+pub struct KnownDevice;
+
+impl KnownDevice {
+    /// Remembers that `user_id` logged in from `ip_address` with
+    /// `user_agent`. Returns `true` if the combination is new.
+    pub async fn record<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        ip_address: &str,
+        user_agent: &str,
+        time: i64,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query!(
+            "INSERT OR IGNORE INTO known_device (user_id, ip_address, user_agent, first_seen_at)
+            VALUES (?, ?, ?, ?)",
+            user_id,
+            ip_address,
+            user_agent,
+            time
+        )
+        .execute(db)
+        .await
+        .map(|row| row.rows_affected() > 0)
+    }
+}
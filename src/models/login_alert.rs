@@ -0,0 +1,59 @@
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+/// A "this wasn't me" token emailed after a login from a new device.
+#[derive(Debug, Clone)]
+pub struct LoginAlert {
+    pub token: Uuid,
+    pub session_id: Uuid,
+    pub created_at: i64,
+}
+
+impl LoginAlert {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        alert: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO login_alert (token, session_id, created_at) VALUES (?, ?, ?)",
+            alert.token,
+            alert.session_id,
+            alert.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Whether `token` can still sign a session out: it is known and the
+    /// session hasn't ended, which would have deleted the alert with it.
+    pub async fn is_pending<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        token: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM login_alert WHERE token = ?) as "pending: bool""#,
+            token
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Deletes the session the alert was sent for and returns its ID and
+    /// its owner's, or `None` if the token is unknown or the session is
+    /// already gone.
+    pub async fn revoke_session<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        token: Uuid,
+    ) -> Result<Option<(Uuid, Uuid)>, sqlx::Error> {
+        let revoked = sqlx::query!(
+            r#"DELETE FROM session
+            WHERE id = (SELECT session_id FROM login_alert WHERE token = ?)
+            RETURNING id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid""#,
+            token
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(revoked.map(|row| (row.id, row.user_id)))
+    }
+}
@@ -0,0 +1,124 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+use super::organization::Role;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: Role,
+    pub created_at: i64,
+}
+
+/// A member as listed on the organization page.
+#[derive(Debug, Clone, FromRow)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub created_at: i64,
+}
+
+impl Membership {
+    /// Adds the member, keeping their current role if they already are
+    /// one. Returns `false` in that case.
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        membership: &Self,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO membership (organization_id, user_id, role, created_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (organization_id, user_id) DO NOTHING",
+            membership.organization_id,
+            membership.user_id,
+            membership.role,
+            membership.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The user's role in the organization, or `None` if they aren't a
+    /// member.
+    pub async fn role_of<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Role>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT role as "role: Role" FROM membership
+            WHERE organization_id = ? AND user_id = ?"#,
+            organization_id,
+            user_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// The organization's members, most trusted first, then by username.
+    pub async fn list_members<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization_id: Uuid,
+    ) -> Result<Vec<Member>, sqlx::Error> {
+        sqlx::query_as!(
+            Member,
+            r#"SELECT
+            membership.user_id as "user_id: uuid::Uuid",
+            user.username,
+            membership.role as "role: Role",
+            membership.created_at
+            FROM membership
+            JOIN user ON user.id = membership.user_id
+            WHERE membership.organization_id = ?
+            ORDER BY CASE membership.role WHEN 'owner' THEN 0 WHEN 'admin' THEN 1 ELSE 2 END,
+            user.username COLLATE NOCASE"#,
+            organization_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Returns `false` if the user isn't a member.
+    pub async fn update_role<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role: Role,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE membership SET role = ? WHERE organization_id = ? AND user_id = ?",
+            role,
+            organization_id,
+            user_id
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Returns `false` if the user isn't a member, or is the organization's
+    /// last owner. Checked in the same statement, so two owners leaving at
+    /// once can't both succeed.
+    pub async fn delete<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM membership WHERE organization_id = ? AND user_id = ?
+            AND (role != 'owner' OR (
+                SELECT COUNT(*) FROM membership
+                WHERE organization_id = ? AND role = 'owner'
+            ) > 1)",
+            organization_id,
+            user_id,
+            organization_id
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,2 +1,21 @@
+pub mod audit_event;
+pub mod avatar;
+pub mod identity;
+pub mod invite;
+pub mod known_device;
+pub mod login_alert;
+pub mod membership;
+pub mod notification;
+pub mod notification_preference;
+pub mod oauth_client;
+pub mod oauth_code;
+pub mod oauth_consent;
+pub mod oidc_login;
+pub mod organization;
+pub mod organization_invite;
+pub mod profile;
 pub mod session;
+pub mod spent_challenge;
+pub mod upload;
 pub mod user;
+pub mod username_history;
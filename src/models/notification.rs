@@ -0,0 +1,172 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// What a [`Notification`] is about; users choose how each kind reaches
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A sign-in from a new device.
+    SignIn,
+    /// A change to the account's password or other credentials.
+    Account,
+    /// Joining, leaving or changing roles in an organization.
+    Organization,
+}
+
+impl NotificationKind {
+    pub const ALL: [Self; 3] =
+        [Self::SignIn, Self::Account, Self::Organization];
+
+    /// Value stored in the `kind` columns and used in preference form
+    /// field names.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::SignIn => "sign_in",
+            Self::Account => "account",
+            Self::Organization => "organization",
+        }
+    }
+
+    /// Human-readable name for the preferences form.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::SignIn => "Sign-ins from new devices",
+            Self::Account => "Password changes",
+            Self::Organization => "Organization membership",
+        }
+    }
+
+    /// Text for the link on an in-app notification of this kind.
+    pub const fn link_label(self) -> &'static str {
+        match self {
+            Self::SignIn => "This wasn't me",
+            Self::Account => "Review activity",
+            Self::Organization => "Open organization",
+        }
+    }
+}
+
+/// A message for a user, listed on the notifications page until deleted
+/// with the account.
+#[derive(Debug, Clone, FromRow)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    /// A path on this site with more detail.
+    pub link: Option<String>,
+    /// `None` while unread.
+    pub read_at: Option<i64>,
+    pub created_at: i64,
+}
+
+impl Notification {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        notification: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO notification (id, user_id, kind, title, body, link, read_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            notification.id,
+            notification.user_id,
+            notification.kind,
+            notification.title,
+            notification.body,
+            notification.link,
+            notification.read_at,
+            notification.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's most recent notifications, newest first.
+    pub async fn list_by_user<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Notification,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            user_id as "user_id: uuid::Uuid",
+            kind as "kind: NotificationKind",
+            title,
+            body,
+            link,
+            read_at,
+            created_at
+            FROM notification WHERE user_id = ?
+            ORDER BY created_at DESC LIMIT ?"#,
+            user_id,
+            limit
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    pub async fn count_unread<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM notification
+            WHERE user_id = ? AND read_at IS NULL"#,
+            user_id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// Marks one of the user's notifications read, returning it, or `None`
+    /// if they have no such notification.
+    pub async fn mark_read<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        user_id: Uuid,
+        now: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Notification,
+            r#"UPDATE notification SET read_at = COALESCE(read_at, ?)
+            WHERE id = ? AND user_id = ?
+            RETURNING
+            id as "id: uuid::Uuid",
+            user_id as "user_id: uuid::Uuid",
+            kind as "kind: NotificationKind",
+            title,
+            body,
+            link,
+            read_at,
+            created_at"#,
+            now,
+            id,
+            user_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Marks all the user's notifications read. Returns how many were
+    /// unread.
+    pub async fn mark_all_read<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        now: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE notification SET read_at = ? WHERE user_id = ? AND read_at IS NULL",
+            now,
+            user_id
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,95 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+use super::notification::NotificationKind;
+
+/// How a user wants one kind of notification delivered. Without a saved
+/// preference, every kind goes both ways.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromRow)]
+pub struct NotificationPreference {
+    pub kind: NotificationKind,
+    /// Emailed, if the user has an address.
+    pub email: bool,
+    /// Listed on the notifications page.
+    pub in_app: bool,
+}
+
+impl NotificationPreference {
+    pub const fn default_for(kind: NotificationKind) -> Self {
+        Self {
+            kind,
+            email: true,
+            in_app: true,
+        }
+    }
+
+    pub async fn get<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        kind: NotificationKind,
+    ) -> Result<Self, sqlx::Error> {
+        let saved = sqlx::query_as!(
+            NotificationPreference,
+            r#"SELECT
+            kind as "kind: NotificationKind",
+            email,
+            in_app
+            FROM notification_preference WHERE user_id = ? AND kind = ?"#,
+            user_id,
+            kind
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(saved.unwrap_or_else(|| Self::default_for(kind)))
+    }
+
+    /// The user's preference for every kind, in [`NotificationKind::ALL`]
+    /// order.
+    pub async fn list_by_user<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let saved = sqlx::query_as!(
+            NotificationPreference,
+            r#"SELECT
+            kind as "kind: NotificationKind",
+            email,
+            in_app
+            FROM notification_preference WHERE user_id = ?"#,
+            user_id
+        )
+        .fetch_all(db)
+        .await?;
+        Ok(NotificationKind::ALL
+            .into_iter()
+            .map(|kind| {
+                saved
+                    .iter()
+                    .find(|preference| preference.kind == kind)
+                    .copied()
+                    .unwrap_or_else(|| Self::default_for(kind))
+            })
+            .collect())
+    }
+
+    pub async fn upsert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        preference: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO notification_preference (user_id, kind, email, in_app)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id, kind) DO UPDATE SET
+            email = excluded.email,
+            in_app = excluded.in_app",
+            user_id,
+            preference.kind,
+            preference.email,
+            preference.in_app
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
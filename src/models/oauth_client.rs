@@ -0,0 +1,103 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// An app registered by an admin to sign users in through this site.
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthClient {
+    /// The public `client_id`.
+    pub id: String,
+    /// Shown to users on the consent page.
+    pub name: String,
+    /// Hash of the client secret, see [`crate::issuer::hash_secret`].
+    pub secret_hash: String,
+    /// One URI per line; authorization requests must name one exactly.
+    pub redirect_uris: String,
+    /// The admin who registered it, if they still exist.
+    pub created_by: Option<Uuid>,
+    pub created_at: i64,
+}
+
+impl OAuthClient {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        client: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO oauth_client (id, name, secret_hash, redirect_uris, created_by, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            client.id,
+            client.name,
+            client.secret_hash,
+            client.redirect_uris,
+            client.created_by,
+            client.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        client_id: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            OAuthClient,
+            r#"SELECT
+            id,
+            name,
+            secret_hash,
+            redirect_uris,
+            created_by as "created_by: uuid::Uuid",
+            created_at
+            FROM oauth_client WHERE id = ?"#,
+            client_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Every client, oldest first.
+    pub async fn list<'e, E: SqliteExecutor<'e>>(
+        db: E,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            OAuthClient,
+            r#"SELECT
+            id,
+            name,
+            secret_hash,
+            redirect_uris,
+            created_by as "created_by: uuid::Uuid",
+            created_at
+            FROM oauth_client ORDER BY created_at"#
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Removes the client along with its consents and unused codes.
+    /// Returns `false` if there was no such client.
+    pub async fn delete<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        client_id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result =
+            sqlx::query!("DELETE FROM oauth_client WHERE id = ?", client_id)
+                .execute(db)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The registered redirect URIs, skipping blank lines.
+    pub fn redirect_uri_list(&self) -> impl Iterator<Item = &str> {
+        self.redirect_uris
+            .lines()
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+    }
+
+    /// Whether `uri` is registered, compared exactly.
+    pub fn allows_redirect(&self, uri: &str) -> bool {
+        self.redirect_uri_list().any(|registered| registered == uri)
+    }
+}
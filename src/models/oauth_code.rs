@@ -0,0 +1,89 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// How long a client has to exchange an authorization code, in
+/// microseconds.
+pub const CODE_TTL_MICROS: i64 = 60 * 1_000_000;
+
+/// An authorization code handed to a client's redirect URI.
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthCode {
+    /// Hash of the code; the code itself only goes to the client.
+    pub code_hash: String,
+    pub client_id: String,
+    pub user_id: Uuid,
+    /// The token request must repeat it.
+    pub redirect_uri: String,
+    /// Space-separated scopes granted.
+    pub scope: String,
+    /// Echoed in the ID token, if the client sent one.
+    pub nonce: Option<String>,
+    /// S256 PKCE challenge the token request's verifier must match.
+    pub code_challenge: String,
+    /// When the user signed in, in microseconds.
+    pub auth_time: i64,
+    pub created_at: i64,
+}
+
+impl OAuthCode {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        code: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO oauth_code (code_hash, client_id, user_id, redirect_uri, scope, nonce, code_challenge, auth_time, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            code.code_hash,
+            code.client_id,
+            code.user_id,
+            code.redirect_uri,
+            code.scope,
+            code.nonce,
+            code.code_challenge,
+            code.auth_time,
+            code.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes and returns the code, if it was issued at or after
+    /// `not_before`. Each code can be exchanged once.
+    pub async fn take<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        code_hash: &str,
+        not_before: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let code = sqlx::query_as!(
+            OAuthCode,
+            r#"DELETE FROM oauth_code WHERE code_hash = ?
+            RETURNING
+            code_hash,
+            client_id,
+            user_id as "user_id: uuid::Uuid",
+            redirect_uri,
+            scope,
+            nonce,
+            code_challenge,
+            auth_time,
+            created_at"#,
+            code_hash
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(code.filter(|issued| issued.created_at >= not_before))
+    }
+
+    /// Removes codes never exchanged before `cutoff`. Returns how many.
+    pub async fn delete_before<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        cutoff: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query!("DELETE FROM oauth_code WHERE created_at < ?", cutoff)
+                .execute(db)
+                .await?;
+        Ok(result.rows_affected())
+    }
+}
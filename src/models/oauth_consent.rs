@@ -0,0 +1,50 @@
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+/// The scopes a user agreed to share with a client.
+#[derive(Debug, Clone)]
+pub struct OAuthConsent {
+    pub user_id: Uuid,
+    pub client_id: String,
+    /// Space-separated scope names.
+    pub scope: String,
+    pub created_at: i64,
+}
+
+impl OAuthConsent {
+    /// The scope the user has granted the client, if any.
+    pub async fn get_scope<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        client_id: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "SELECT scope FROM oauth_consent WHERE user_id = ? AND client_id = ?",
+            user_id,
+            client_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Records the consent, replacing any earlier one for the client.
+    pub async fn upsert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        consent: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO oauth_consent (user_id, client_id, scope, created_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id, client_id) DO UPDATE SET
+            scope = excluded.scope,
+            created_at = excluded.created_at",
+            consent.user_id,
+            consent.client_id,
+            consent.scope,
+            consent.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// How long a user has to finish signing in at the provider, in
+/// microseconds.
+pub const LOGIN_TTL_MICROS: i64 = 10 * 60 * 1_000_000;
+
+/// A sign-in sent to an identity provider and not yet returned from.
+#[derive(Debug, Clone, FromRow)]
+pub struct OidcLogin {
+    /// Sent as the `state` parameter and kept in a cookie, tying the
+    /// callback to the browser that started the sign-in.
+    pub state: String,
+    pub provider: String,
+    /// Must come back in the ID token.
+    pub nonce: String,
+    /// PKCE verifier; only its hash went to the provider.
+    pub code_verifier: String,
+    /// Set when a signed-in user is linking the provider to their account.
+    pub link_user_id: Option<Uuid>,
+    pub created_at: i64,
+}
+
+impl OidcLogin {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        login: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO oidc_login (state, provider, nonce, code_verifier, link_user_id, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            login.state,
+            login.provider,
+            login.nonce,
+            login.code_verifier,
+            login.link_user_id,
+            login.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Deletes and returns the login started with `state`, if it was
+    /// started at or after `not_before`. Each state can be used once.
+    pub async fn take<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        state: &str,
+        not_before: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let login = sqlx::query_as!(
+            OidcLogin,
+            r#"DELETE FROM oidc_login WHERE state = ?
+            RETURNING
+            state,
+            provider,
+            nonce,
+            code_verifier,
+            link_user_id as "link_user_id: uuid::Uuid",
+            created_at"#,
+            state
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(login.filter(|started| started.created_at >= not_before))
+    }
+
+    /// Removes sign-ins abandoned before `cutoff`. Returns how many.
+    pub async fn delete_before<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        cutoff: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query!("DELETE FROM oidc_login WHERE created_at < ?", cutoff)
+                .execute(db)
+                .await?;
+        Ok(result.rows_affected())
+    }
+}
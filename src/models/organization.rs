@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// What a member may do in an organization. Ordered from least to most
+/// trusted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, sqlx::Type,
+)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Member,
+    /// Invites and removes members and other admins.
+    Admin,
+    /// Everything, including deleting the organization.
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Self; 3] = [Self::Member, Self::Admin, Self::Owner];
+
+    /// Value stored in the database and sent by the membership forms.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Member => "member",
+            Self::Admin => "admin",
+            Self::Owner => "owner",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Member => "Member",
+            Self::Admin => "Admin",
+            Self::Owner => "Owner",
+        }
+    }
+
+    /// Whether someone with this role may invite, remove, or hand out
+    /// `role`: admins and owners, up to their own role.
+    pub fn can_manage(self, role: Self) -> bool {
+        self >= Self::Admin && role <= self
+    }
+
+    /// The roles this role may hand out, for the invite and member forms.
+    pub fn assignable(self) -> Vec<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|role| self.can_manage(*role))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    /// See [`crate::organization::slugify`].
+    pub slug: String,
+    pub created_at: i64,
+}
+
+/// An organization the user belongs to, and their role in it.
+#[derive(Debug, Clone, FromRow)]
+pub struct Affiliation {
+    pub name: String,
+    pub slug: String,
+    pub role: Role,
+}
+
+impl Organization {
+    /// Fails with a unique violation if the slug is taken.
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO organization (id, name, slug, created_at) VALUES (?, ?, ?, ?)",
+            organization.id,
+            organization.name,
+            organization.slug,
+            organization.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_by_id<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Organization,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            name,
+            slug,
+            created_at
+            FROM organization WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    pub async fn get_by_slug<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        slug: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Organization,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            name,
+            slug,
+            created_at
+            FROM organization WHERE slug = ?"#,
+            slug
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// The organizations `user_id` belongs to, by name.
+    pub async fn list_for_user<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Vec<Affiliation>, sqlx::Error> {
+        sqlx::query_as!(
+            Affiliation,
+            r#"SELECT
+            organization.name,
+            organization.slug,
+            membership.role as "role: Role"
+            FROM membership
+            JOIN organization ON organization.id = membership.organization_id
+            WHERE membership.user_id = ?
+            ORDER BY organization.name COLLATE NOCASE"#,
+            user_id
+        )
+        .fetch_all(db)
+        .await
+    }
+}
@@ -0,0 +1,151 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+use super::organization::Role;
+use super::user::User;
+
+/// An emailed invitation to join an organization with `role`.
+#[derive(Debug, Clone, FromRow)]
+pub struct OrganizationInvite {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub email: String,
+    pub role: Role,
+    /// See [`crate::organization::hash_token`].
+    pub token_hash: String,
+    pub invited_by: Uuid,
+    pub expires_at: i64,
+    pub created_at: i64,
+}
+
+impl OrganizationInvite {
+    /// Whether `user` is the one invited: the link only works for the
+    /// account with the address it was sent to, not whoever holds it.
+    pub fn is_for(&self, user: &User) -> bool {
+        user.email
+            .as_deref()
+            .is_some_and(|email| email.eq_ignore_ascii_case(&self.email))
+    }
+
+    /// Saves the invitation, replacing any earlier one to the same
+    /// address, whose link stops working.
+    pub async fn upsert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        invite: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO organization_invite (id, organization_id, email, role, token_hash, invited_by, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (organization_id, email) DO UPDATE SET
+            role = excluded.role,
+            token_hash = excluded.token_hash,
+            invited_by = excluded.invited_by,
+            expires_at = excluded.expires_at,
+            created_at = excluded.created_at",
+            invite.id,
+            invite.organization_id,
+            invite.email,
+            invite.role,
+            invite.token_hash,
+            invite.invited_by,
+            invite.expires_at,
+            invite.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// The organization's invitations, newest first, including expired
+    /// ones.
+    pub async fn list_by_organization<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        organization_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            OrganizationInvite,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            organization_id as "organization_id: uuid::Uuid",
+            email,
+            role as "role: Role",
+            token_hash,
+            invited_by as "invited_by: uuid::Uuid",
+            expires_at,
+            created_at
+            FROM organization_invite WHERE organization_id = ?
+            ORDER BY created_at DESC"#,
+            organization_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// The unexpired invitation with this token.
+    pub async fn get_by_token_hash<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        token_hash: &str,
+        now: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            OrganizationInvite,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            organization_id as "organization_id: uuid::Uuid",
+            email,
+            role as "role: Role",
+            token_hash,
+            invited_by as "invited_by: uuid::Uuid",
+            expires_at,
+            created_at
+            FROM organization_invite WHERE token_hash = ? AND expires_at > ?"#,
+            token_hash,
+            now
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Deletes and returns the unexpired invitation with this token, so
+    /// it's accepted at most once.
+    pub async fn take<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        token_hash: &str,
+        now: i64,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            OrganizationInvite,
+            r#"DELETE FROM organization_invite WHERE token_hash = ? AND expires_at > ?
+            RETURNING
+            id as "id: uuid::Uuid",
+            organization_id as "organization_id: uuid::Uuid",
+            email,
+            role as "role: Role",
+            token_hash,
+            invited_by as "invited_by: uuid::Uuid",
+            expires_at,
+            created_at"#,
+            token_hash,
+            now
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Revokes the invitation if it belongs to the organization. Returns
+    /// `false` otherwise.
+    pub async fn delete<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        organization_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM organization_invite WHERE id = ? AND organization_id = ?",
+            id,
+            organization_id
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// Who may see a profile field.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, sqlx::Type,
+)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    #[default]
+    Everyone,
+    /// Signed-in users only.
+    Members,
+    OnlyMe,
+}
+
+/// Who is looking at a profile, for [`Visibility::allows`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewer {
+    Anonymous,
+    Member,
+    Owner,
+}
+
+impl Viewer {
+    /// Classifies the signed-in user `viewer_id` (if any) looking at
+    /// `owner_id`'s profile.
+    pub fn of(owner_id: Uuid, viewer_id: Option<Uuid>) -> Self {
+        match viewer_id {
+            Some(id) if id == owner_id => Self::Owner,
+            Some(_) => Self::Member,
+            None => Self::Anonymous,
+        }
+    }
+}
+
+impl Visibility {
+    pub const ALL: [Self; 3] = [Self::Everyone, Self::Members, Self::OnlyMe];
+
+    /// Value stored in the database and sent by the privacy form.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Everyone => "everyone",
+            Self::Members => "members",
+            Self::OnlyMe => "only_me",
+        }
+    }
+
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Everyone => "Everyone",
+            Self::Members => "Signed-in users",
+            Self::OnlyMe => "Only me",
+        }
+    }
+
+    pub fn allows(self, viewer: Viewer) -> bool {
+        match self {
+            Self::Everyone => true,
+            Self::Members => viewer != Viewer::Anonymous,
+            Self::OnlyMe => viewer == Viewer::Owner,
+        }
+    }
+}
+
+/// Optional public details about a user. Users who never edited their
+/// profile have no row and get [`Profile::default`].
+#[derive(Debug, Clone, Default, FromRow)]
+pub struct Profile {
+    /// Shown instead of the username when set; empty otherwise.
+    pub display_name: String,
+    pub bio: String,
+    /// One URL per line.
+    pub links: String,
+    pub bio_visibility: Visibility,
+    pub links_visibility: Visibility,
+    pub avatar_visibility: Visibility,
+}
+
+impl Profile {
+    /// The user's profile, or an empty one if they never saved it.
+    pub async fn get<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Self, sqlx::Error> {
+        let profile_opt = sqlx::query_as!(
+            Profile,
+            r#"SELECT
+            display_name,
+            bio,
+            links,
+            bio_visibility as "bio_visibility: Visibility",
+            links_visibility as "links_visibility: Visibility",
+            avatar_visibility as "avatar_visibility: Visibility"
+            FROM profile WHERE user_id = ?"#,
+            user_id
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(profile_opt.unwrap_or_default())
+    }
+
+    /// Creates or replaces the user's profile.
+    pub async fn upsert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        profile: &Self,
+        updated_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO profile (user_id, display_name, bio, links, bio_visibility, links_visibility, avatar_visibility, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+            display_name = excluded.display_name,
+            bio = excluded.bio,
+            links = excluded.links,
+            bio_visibility = excluded.bio_visibility,
+            links_visibility = excluded.links_visibility,
+            avatar_visibility = excluded.avatar_visibility,
+            updated_at = excluded.updated_at",
+            user_id,
+            profile.display_name,
+            profile.bio,
+            profile.links,
+            profile.bio_visibility,
+            profile.links_visibility,
+            profile.avatar_visibility,
+            updated_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// The links as a list, skipping blank lines.
+    pub fn link_list(&self) -> impl Iterator<Item = &str> {
+        self.links
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+    }
+}
@@ -3,6 +3,21 @@ use uuid::Uuid;
 
 use crate::util::current_time_micros;
 
+/// Idle lifetime of a "remember me" session, in microseconds (30 days).
+pub const PERSISTENT_TTL_MICROS: i64 = 30 * 24 * 60 * 60 * 1_000_000;
+
+/// Idle lifetime of a browser-session login, in microseconds (1 day).
+pub const BROWSER_TTL_MICROS: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Minimum time between sliding renewals of one session, in microseconds
+/// (1 hour), so an active user costs one write per hour rather than one per
+/// request.
+pub const RENEWAL_INTERVAL_MICROS: i64 = 60 * 60 * 1_000_000;
+
+/// Minimum time between `last_seen_at` updates of one session, in
+/// microseconds (5 minutes).
+pub const LAST_SEEN_INTERVAL_MICROS: i64 = 5 * 60 * 1_000_000;
+
 #[derive(Debug, Clone, FromRow)]
 pub struct Session {
     pub id: Uuid,
@@ -11,22 +26,59 @@ pub struct Session {
     pub user_agent: String,
     pub created_at: i64,
     pub expires_at: i64,
+    /// Whether the user asked to be remembered beyond the browser session.
+    pub persistent: bool,
+    /// When the session last made a request, throttled to
+    /// [`LAST_SEEN_INTERVAL_MICROS`].
+    pub last_seen_at: i64,
+    /// Client IP of the most recent recorded request.
+    pub last_ip: String,
 }
 
 impl Session {
+    /// How long a session may sit idle before it expires.
+    pub const fn ttl_for(persistent: bool) -> i64 {
+        if persistent {
+            PERSISTENT_TTL_MICROS
+        } else {
+            BROWSER_TTL_MICROS
+        }
+    }
+
+    pub const fn ttl_micros(&self) -> i64 {
+        Self::ttl_for(self.persistent)
+    }
+
+    /// Returns `true` if the session was last extended at least
+    /// [`RENEWAL_INTERVAL_MICROS`] ago.
+    pub const fn needs_renewal(&self, now: i64) -> bool {
+        let remaining = self.expires_at.saturating_sub(now);
+        remaining < self.ttl_micros().saturating_sub(RENEWAL_INTERVAL_MICROS)
+    }
+
+    /// Returns `true` if activity should be recorded: the last update is
+    /// older than [`LAST_SEEN_INTERVAL_MICROS`] or the client IP changed.
+    pub fn needs_touch(&self, now: i64, ip: &str) -> bool {
+        now.saturating_sub(self.last_seen_at) >= LAST_SEEN_INTERVAL_MICROS
+            || self.last_ip != ip
+    }
+
     pub async fn insert<'e, E: SqliteExecutor<'e>>(
         db: E,
         session: &Self,
     ) -> Result<i64, sqlx::Error> {
         sqlx::query!(
-            "INSERT INTO session (id, user_id, ip_address, user_agent, created_at, expires_at)
-        VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO session (id, user_id, ip_address, user_agent, created_at, expires_at, persistent, last_seen_at, last_ip)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
             session.id,
             session.user_id,
             session.ip_address,
             session.user_agent,
             session.created_at,
             session.expires_at,
+            session.persistent,
+            session.last_seen_at,
+            session.last_ip,
         )
         .execute(db)
         .await
@@ -45,7 +97,10 @@ impl Session {
             ip_address,
             user_agent,
             created_at,
-            expires_at
+            expires_at,
+            persistent,
+            last_seen_at,
+            last_ip
             FROM 'session' WHERE id = ?"#,
             id
         )
@@ -63,6 +118,57 @@ impl Session {
             .map(|row| row.rows_affected())
     }
 
+    /// Deletes every session of `user_id` except `keep`, returning the IDs
+    /// of those revoked.
+    pub async fn delete_by_user_id_except<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        keep: Uuid,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"DELETE FROM session WHERE user_id = ? AND id != ?
+            RETURNING id as "id: uuid::Uuid""#,
+            user_id,
+            keep
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Slides the session's expiry forward to `expires_at`.
+    pub async fn renew<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        expires_at: i64,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE session SET expires_at = ? WHERE id = ?",
+            expires_at,
+            id
+        )
+        .execute(db)
+        .await
+        .map(|row| row.rows_affected())
+    }
+
+    /// Records that the session was just used from `ip`.
+    pub async fn touch<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        last_seen_at: i64,
+        ip: &str,
+    ) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE session SET last_seen_at = ?, last_ip = ? WHERE id = ?",
+            last_seen_at,
+            ip,
+            id
+        )
+        .execute(db)
+        .await
+        .map(|row| row.rows_affected())
+    }
+
     pub async fn get_by_user_id<'e, E: SqliteExecutor<'e>>(
         db: E,
         user_id: Uuid,
@@ -76,9 +182,12 @@ impl Session {
             ip_address,
             user_agent,
             created_at,
-            expires_at
+            expires_at,
+            persistent,
+            last_seen_at,
+            last_ip
             FROM 'session' WHERE user_id = ? AND expires_at > ?
-            ORDER BY created_at DESC"#,
+            ORDER BY last_seen_at DESC"#,
             user_id,
             now
         )
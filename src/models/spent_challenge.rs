@@ -0,0 +1,39 @@
+use sqlx::SqliteExecutor;
+
+/// A bot-check challenge that was already accepted once, see
+/// [`crate::bot_check`].
+pub struct SpentChallenge;
+
+impl SpentChallenge {
+    /// Records the nonce as used. Returns `false` if it already was.
+    pub async fn spend<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        nonce: &str,
+        now: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO spent_challenge (nonce, created_at) VALUES (?, ?)
+            ON CONFLICT (nonce) DO NOTHING",
+            nonce,
+            now
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Forgets nonces spent before `cutoff`, whose challenges have expired
+    /// anyway. Returns how many.
+    pub async fn delete_before<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        cutoff: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM spent_challenge WHERE created_at < ?",
+            cutoff
+        )
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,99 @@
+use sqlx::{FromRow, SqliteExecutor};
+use uuid::Uuid;
+
+/// A file a user uploaded. The bytes are in storage under `storage_key`.
+#[derive(Debug, Clone, FromRow)]
+pub struct Upload {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    /// Name the browser sent, sanitised; only used for display and
+    /// `Content-Disposition`.
+    pub filename: String,
+    pub content_type: String,
+    /// Bytes.
+    pub size: i64,
+    pub storage_key: String,
+    pub created_at: i64,
+}
+
+impl Upload {
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        upload: &Self,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO upload (id, user_id, filename, content_type, size, storage_key, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+            upload.id,
+            upload.user_id,
+            upload.filename,
+            upload.content_type,
+            upload.size,
+            upload.storage_key,
+            upload.created_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_by_id<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Upload,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            user_id as "user_id: uuid::Uuid",
+            filename,
+            content_type,
+            size,
+            storage_key,
+            created_at
+            FROM upload WHERE id = ?"#,
+            id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// The user's uploads, newest first.
+    pub async fn get_by_user_id<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        sqlx::query_as!(
+            Upload,
+            r#"SELECT
+            id as "id: uuid::Uuid",
+            user_id as "user_id: uuid::Uuid",
+            filename,
+            content_type,
+            size,
+            storage_key,
+            created_at
+            FROM upload WHERE user_id = ?
+            ORDER BY created_at DESC"#,
+            user_id
+        )
+        .fetch_all(db)
+        .await
+    }
+
+    /// Deletes the upload if `user_id` owns it, returning its storage key
+    /// so the caller can remove the bytes.
+    pub async fn delete_owned<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar!(
+            "DELETE FROM upload WHERE id = ? AND user_id = ? RETURNING storage_key",
+            id,
+            user_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+}
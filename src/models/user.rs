@@ -1,47 +1,74 @@
-use argon2::{Argon2, PasswordHash, PasswordVerifier as _};
-use sqlx::{FromRow, SqliteExecutor};
+use sqlx::{FromRow, SqliteExecutor, SqlitePool};
+use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::password::{self, Verification};
+use crate::username;
+
+use super::username_history::UsernameHistory;
+
 #[derive(Debug, Clone, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
-    pub password_hash: String,
+    /// `None` for accounts that only sign in through an identity provider,
+    /// stored as an empty string.
+    pub password_hash: Option<String>,
     pub email: Option<String>,
     pub created_at: i64,
+    /// Grants access to admin pages such as the audit log.
+    pub is_admin: bool,
 }
 
 impl User {
     /// Inserts the user into the database and returns the new user's ID.
+    ///
+    /// Fails with a unique violation if the username matches an existing
+    /// one case-insensitively or is confusable with it.
     pub async fn insert<'e, E: SqliteExecutor<'e>>(
         db: E,
         user: &Self,
     ) -> Result<(), sqlx::Error> {
+        let canonical = username::canonical(&user.username);
+        let skeleton = username::skeleton(&user.username);
+        let password_hash = user.password_hash.as_deref().unwrap_or_default();
         sqlx::query!(
-            "INSERT INTO user (id, username, password_hash, created_at) VALUES (?, ?, ?, ?)",
+            "INSERT INTO user (id, username, username_canonical, username_skeleton, password_hash, email, created_at, is_admin) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
             user.id,
             user.username,
-            user.password_hash,
-            user.created_at
+            canonical,
+            skeleton,
+            password_hash,
+            user.email,
+            user.created_at,
+            user.is_admin
         )
         .execute(db)
         .await?;
         Ok(())
     }
 
+    /// Looks a user up by username, case-insensitively.
     pub async fn get_by_username<'e, E: SqliteExecutor<'e>>(
         db: E,
         username: &str,
     ) -> Result<Self, sqlx::Error> {
+        let canonical = username::canonical(username);
+        // Accounts that differed only by case before canonical usernames
+        // were introduced have none, and match exactly
         sqlx::query_as!(
             User,
             r#"SELECT
             id as "id: uuid::Uuid",
             username,
-            password_hash,
+            NULLIF(password_hash, '') as "password_hash?: String",
             email,
-            created_at
-            FROM 'user' WHERE username = ?"#,
+            created_at,
+            is_admin
+            FROM 'user'
+            WHERE username_canonical = ?
+            OR (username_canonical IS NULL AND username = ?)"#,
+            canonical,
             username
         )
         .fetch_one(db)
@@ -57,9 +84,10 @@ impl User {
             r#"SELECT
             id as "id: uuid::Uuid",
             username,
-            password_hash,
+            NULLIF(password_hash, '') as "password_hash?: String",
             email,
-            created_at
+            created_at,
+            is_admin
             FROM 'user' WHERE id = ?"#,
             user_id
         )
@@ -67,6 +95,74 @@ impl User {
         .await
     }
 
+    /// Renames the user. Fails with a unique violation like [`Self::insert`].
+    pub async fn update_username<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        new_username: &str,
+    ) -> Result<(), sqlx::Error> {
+        let canonical = username::canonical(new_username);
+        let skeleton = username::skeleton(new_username);
+        sqlx::query!(
+            "UPDATE user SET username = ?, username_canonical = ?, username_skeleton = ? WHERE id = ?",
+            new_username,
+            canonical,
+            skeleton,
+            user_id
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Renames the user and records `old_username` in their history, so
+    /// old profile links redirect and the name is held for them.
+    pub async fn rename(
+        db: &SqlitePool,
+        user_id: Uuid,
+        old_username: &str,
+        new_username: &str,
+        changed_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = db.begin().await?;
+        UsernameHistory::insert(&mut *tx, user_id, old_username, changed_at)
+            .await?;
+        Self::update_username(&mut *tx, user_id, new_username).await?;
+        tx.commit().await
+    }
+
+    /// Computes the canonical form and skeleton for users created before
+    /// they existed. Users that collide with another account are left
+    /// alone and logged; they keep working, but one of them should be
+    /// renamed. Returns how many users were updated.
+    pub async fn backfill_username_keys(
+        db: &SqlitePool,
+    ) -> Result<u64, sqlx::Error> {
+        let pending = sqlx::query!(
+            r#"SELECT id as "id: uuid::Uuid", username FROM user WHERE username_skeleton IS NULL"#
+        )
+        .fetch_all(db)
+        .await?;
+
+        let mut updated: u64 = 0;
+        for row in pending {
+            match Self::update_username(db, row.id, &row.username).await {
+                Ok(()) => updated = updated.saturating_add(1),
+                Err(sqlx::Error::Database(err))
+                    if err.is_unique_violation() =>
+                {
+                    warn!(
+                        user_id = %row.id,
+                        username = row.username,
+                        "Username collides with another account"
+                    );
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(updated)
+    }
+
     pub async fn update_email<'e, E: SqliteExecutor<'e>>(
         db: E,
         user_id: Uuid,
@@ -78,24 +174,52 @@ impl User {
         Ok(())
     }
 
-    /// Checks a usernames+password combination using the database and returns the user if it is valid.
-    /// Returns `None` if the user does not exist or the password is incorrect.
-    pub async fn check_login<'e, E: SqliteExecutor<'e>>(
+    pub async fn update_password_hash<'e, E: SqliteExecutor<'e>>(
         db: E,
+        user_id: Uuid,
+        password_hash: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE user SET password_hash = ? WHERE id = ?",
+            password_hash,
+            user_id
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Checks a usernames+password combination using the database and returns the user if it is valid.
+    /// Returns `None` if the user does not exist, has no password or the
+    /// password is incorrect.
+    ///
+    /// A correct password stored with outdated hashing parameters (or
+    /// without the configured pepper) is transparently rehashed.
+    pub async fn check_login(
+        db: &SqlitePool,
         username: &str,
         password: &str,
     ) -> Option<Self> {
-        match Self::get_by_username(db, username).await {
-            Ok(user) => {
-                let parsed_hash = PasswordHash::new(&user.password_hash)
-                    .expect("Failed to parse hash");
-                Argon2::default()
-                    .verify_password(password.as_bytes(), &parsed_hash)
-                    .ok()
-                    .map(|()| user)
+        let mut user = Self::get_by_username(db, username).await.ok()?;
+        let verification = password::verify_async(
+            password.to_owned(),
+            user.password_hash.clone()?,
+        )
+        .await;
+        match verification {
+            Verification::Invalid => None,
+            Verification::Valid => Some(user),
+            Verification::NeedsRehash => {
+                let new_hash = password::hash_async(password.to_owned()).await;
+                match Self::update_password_hash(db, user.id, &new_hash).await {
+                    Ok(()) => {
+                        info!(user_id = %user.id, "Upgraded password hash");
+                        user.password_hash = Some(new_hash);
+                    }
+                    Err(err) => warn!("Failed to upgrade password hash: {err}"),
+                }
+                Some(user)
             }
-            Err(sqlx::Error::RowNotFound) => None,
-            Err(_err) => None,
         }
     }
 }
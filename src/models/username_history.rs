@@ -0,0 +1,87 @@
+use sqlx::SqliteExecutor;
+use uuid::Uuid;
+
+use crate::username;
+
+/// Usernames users have given up, for redirects and hold periods.
+pub struct UsernameHistory;
+
+impl UsernameHistory {
+    /// Records that `user_id` stopped using `old_username` at `changed_at`.
+    pub async fn insert<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+        old_username: &str,
+        changed_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let canonical = username::canonical(old_username);
+        let skeleton = username::skeleton(old_username);
+        sqlx::query!(
+            "INSERT INTO username_history (user_id, username, username_canonical, username_skeleton, changed_at)
+            VALUES (?, ?, ?, ?, ?)",
+            user_id,
+            old_username,
+            canonical,
+            skeleton,
+            changed_at
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// When the user last changed their username, if ever.
+    pub async fn last_change_at<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        user_id: Uuid,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT MAX(changed_at) as "changed_at: i64" FROM username_history WHERE user_id = ?"#,
+            user_id
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    /// The current username of whoever most recently gave up `old_username`
+    /// (matched case-insensitively).
+    pub async fn current_username_for<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        old_username: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let canonical = username::canonical(old_username);
+        sqlx::query_scalar!(
+            "SELECT user.username FROM username_history
+            JOIN user ON user.id = username_history.user_id
+            WHERE username_history.username_canonical = ?
+            ORDER BY username_history.changed_at DESC
+            LIMIT 1",
+            canonical
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    /// Whether `wanted`, or a lookalike of it, was given up after `since` by
+    /// someone other than `claimant`. Previous owners may always take their
+    /// old names back.
+    pub async fn is_held<'e, E: SqliteExecutor<'e>>(
+        db: E,
+        wanted: &str,
+        since: i64,
+        claimant: Option<Uuid>,
+    ) -> Result<bool, sqlx::Error> {
+        let skeleton = username::skeleton(wanted);
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS(
+                SELECT 1 FROM username_history
+                WHERE username_skeleton = ? AND changed_at > ? AND user_id IS NOT ?
+            ) as "held: bool""#,
+            skeleton,
+            since,
+            claimant
+        )
+        .fetch_one(db)
+        .await
+    }
+}
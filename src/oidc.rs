@@ -0,0 +1,491 @@
+//! Sign-in through external OIDC identity providers.
+//!
+//! Providers are configured from the environment ([`Providers::from_env`])
+//! and found through their discovery document, so any standard provider
+//! works with just an issuer URL and client credentials. Sign-in uses the
+//! authorization code flow with PKCE; the ID token's signature, issuer,
+//! audience, expiry and nonce are all checked before its `sub` claim is
+//! trusted.
+
+use std::error;
+use std::fmt;
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::jwt::{self, JwkSet, JwtError};
+use crate::util::{TLS_SUPPORTED, base_url};
+
+/// Scopes requested from every provider.
+pub const SCOPES: &str = "openid email profile";
+
+/// Clock skew tolerated when checking an ID token's expiry, in seconds.
+const LEEWAY_SECS: i64 = 60;
+
+/// How long a request to a provider may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Characters `application/x-www-form-urlencoded` leaves unescaped.
+const FORM: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'*');
+
+#[derive(Debug)]
+pub enum OidcError {
+    Http(reqwest::Error),
+    /// The provider answered with an unexpected status.
+    Status(u16),
+    /// A discovery, key set or token response was missing something.
+    InvalidResponse(String),
+    /// The ID token failed a check.
+    InvalidToken(String),
+}
+
+impl fmt::Display for OidcError {
+    #[allow(
+        clippy::allow_attributes,
+        clippy::pattern_type_mismatch,
+        reason = "Payloads are borrowed from the error"
+    )]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(err) => write!(f, "provider request: {err}"),
+            Self::Status(status) => {
+                write!(f, "provider returned status {status}")
+            }
+            Self::InvalidResponse(message) => {
+                write!(f, "invalid provider response: {message}")
+            }
+            Self::InvalidToken(message) => {
+                write!(f, "invalid ID token: {message}")
+            }
+        }
+    }
+}
+
+impl error::Error for OidcError {}
+
+impl From<reqwest::Error> for OidcError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl From<JwtError> for OidcError {
+    fn from(err: JwtError) -> Self {
+        Self::InvalidToken(err.to_string())
+    }
+}
+
+/// One provider's settings.
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    /// Used in URLs and stored with linked identities; don't change it
+    /// once users have signed in.
+    pub name: String,
+    /// Shown on buttons, e.g. "Sign in with Google".
+    pub label: String,
+    /// The issuer URL; discovery is fetched from
+    /// `{issuer}/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl ProviderConfig {
+    /// Reads `OIDC_{NAME}_ISSUER`, `_CLIENT_ID`, `_CLIENT_SECRET` and the
+    /// optional `_LABEL` (default: the name).
+    pub fn from_env(name: &str) -> Result<Self, String> {
+        let prefix = format!("OIDC_{}", name.to_ascii_uppercase());
+        let var = |suffix: &str| {
+            let key = format!("{prefix}_{suffix}");
+            dotenvy::var(&key).map_err(|_err| format!("{key} must be set"))
+        };
+        Ok(Self {
+            name: name.to_owned(),
+            label: var("LABEL").unwrap_or_else(|_err| name.to_owned()),
+            issuer: var("ISSUER")?,
+            client_id: var("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET")?,
+        })
+    }
+}
+
+/// The parts of a provider's discovery document used here.
+#[derive(Debug, Clone, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+    #[serde(default)]
+    token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The `aud` claim: one client ID or several.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, client_id: &str) -> bool {
+        #[allow(
+            clippy::allow_attributes,
+            clippy::pattern_type_mismatch,
+            reason = "Payloads are borrowed from the claim"
+        )]
+        match self {
+            Self::One(aud) => aud == client_id,
+            Self::Many(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+/// Verified claims from an ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdClaims {
+    pub iss: String,
+    /// The user's stable identifier at the provider.
+    pub sub: String,
+    pub aud: Audience,
+    /// Authorized party; must be us when present.
+    #[serde(default)]
+    pub azp: Option<String>,
+    /// Expiry, in seconds since the epoch.
+    pub exp: i64,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Some providers send a string rather than a boolean.
+    #[serde(default)]
+    pub email_verified: Option<Value>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl IdClaims {
+    /// The email address, if the provider vouches for it.
+    #[allow(
+        clippy::allow_attributes,
+        clippy::pattern_type_mismatch,
+        reason = "Payloads are borrowed from the claim"
+    )]
+    pub fn verified_email(&self) -> Option<&str> {
+        let verified = match self.email_verified.as_ref() {
+            Some(Value::Bool(flag)) => *flag,
+            Some(Value::String(text)) => text == "true",
+            Some(
+                Value::Null
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_),
+            )
+            | None => false,
+        };
+        self.email.as_deref().filter(|_| verified)
+    }
+}
+
+/// A configured provider, with its discovery document and signing keys
+/// fetched on first use.
+pub struct Provider {
+    config: ProviderConfig,
+    client: Client,
+    metadata: OnceCell<Metadata>,
+    keys: RwLock<JwkSet>,
+}
+
+impl Provider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            config,
+            client: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+            metadata: OnceCell::new(),
+            keys: RwLock::new(JwkSet::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn label(&self) -> &str {
+        &self.config.label
+    }
+
+    /// Where the provider sends the browser back to; register this with
+    /// the provider.
+    pub fn redirect_uri(&self) -> String {
+        format!("{}/auth/{}/callback", base_url(), self.config.name)
+    }
+
+    async fn metadata(&self) -> Result<&Metadata, OidcError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let metadata: Metadata = self.get_json(&url).await?;
+                if metadata.issuer != self.config.issuer {
+                    return Err(OidcError::InvalidResponse(format!(
+                        "discovery issuer {:?} does not match {:?}",
+                        metadata.issuer, self.config.issuer
+                    )));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+    ) -> Result<T, OidcError> {
+        let response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(OidcError::Status(response.status().as_u16()));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// The provider's sign-in page for a login started with `state`,
+    /// `nonce` and the PKCE `code_verifier`.
+    pub async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri().as_str()),
+                ("scope", SCOPES),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", pkce_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|err| {
+            OidcError::InvalidResponse(format!("authorization_endpoint: {err}"))
+        })?;
+        Ok(url.into())
+    }
+
+    /// Redeems an authorization code and returns the verified claims of
+    /// the ID token, which must carry `nonce`. `now` is in seconds.
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+        now: i64,
+    ) -> Result<IdClaims, OidcError> {
+        let metadata = self.metadata().await?;
+        let redirect_uri = self.redirect_uri();
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("code_verifier", code_verifier),
+            ("client_id", self.config.client_id.as_str()),
+        ];
+        let mut request = self.client.post(&metadata.token_endpoint);
+        // `client_secret_basic` is the default when the provider doesn't say
+        if metadata
+            .token_endpoint_auth_methods_supported
+            .iter()
+            .any(|method| method == "client_secret_post")
+        {
+            params.push(("client_secret", self.config.client_secret.as_str()));
+        } else {
+            request = request.basic_auth(
+                form_encode(&self.config.client_id),
+                Some(form_encode(&self.config.client_secret)),
+            );
+        }
+
+        let response = request.form(&params).send().await?;
+        if !response.status().is_success() {
+            return Err(OidcError::Status(response.status().as_u16()));
+        }
+        let tokens: TokenResponse = response.json().await.map_err(|err| {
+            OidcError::InvalidResponse(format!("token response: {err}"))
+        })?;
+        self.verify_id_token(&tokens.id_token, nonce, now).await
+    }
+
+    async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+        now: i64,
+    ) -> Result<IdClaims, OidcError> {
+        let metadata = self.metadata().await?;
+        let kid = jwt::key_id(id_token)?;
+        let known = self.keys.read().await.find(kid.as_deref()).is_some();
+        if !known {
+            // First use, or the provider rotated its keys
+            let fresh: JwkSet = self.get_json(&metadata.jwks_uri).await?;
+            *self.keys.write().await = fresh;
+        }
+        let claims: IdClaims = jwt::verify(id_token, &*self.keys.read().await)?;
+
+        let invalid =
+            |message: &str| Err(OidcError::InvalidToken(message.to_owned()));
+        if claims.iss != metadata.issuer {
+            return invalid("wrong issuer");
+        }
+        if !claims.aud.contains(&self.config.client_id)
+            || claims
+                .azp
+                .as_deref()
+                .is_some_and(|azp| azp != self.config.client_id)
+        {
+            return invalid("wrong audience");
+        }
+        if claims.exp.saturating_add(LEEWAY_SECS) < now {
+            return invalid("expired");
+        }
+        if claims.nonce.as_deref() != Some(nonce) {
+            return invalid("nonce mismatch");
+        }
+        if claims.sub.is_empty() {
+            return invalid("missing subject");
+        }
+        Ok(claims)
+    }
+}
+
+/// Every configured provider, in `OIDC_PROVIDERS` order. Empty (the
+/// default) when social login is off.
+#[derive(Default)]
+pub struct Providers {
+    providers: Vec<Provider>,
+}
+
+impl Providers {
+    pub fn new(configs: Vec<ProviderConfig>) -> Self {
+        Self {
+            providers: configs.into_iter().map(Provider::new).collect(),
+        }
+    }
+
+    /// Providers named in the comma-separated `OIDC_PROVIDERS`, each
+    /// configured by [`ProviderConfig::from_env`]. Fails if any are named
+    /// but the build can't make HTTPS requests.
+    pub fn from_env() -> Result<Self, String> {
+        let names = dotenvy::var("OIDC_PROVIDERS").unwrap_or_default();
+        let mut configs = Vec::new();
+        for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(format!(
+                    "OIDC provider name {name:?} must be letters, digits or '-'"
+                ));
+            }
+            configs.push(ProviderConfig::from_env(name)?);
+        }
+        if !configs.is_empty() && !TLS_SUPPORTED {
+            return Err(String::from(
+                "OIDC providers need HTTPS, but this build has no TLS \
+                support: enable the `tls` feature",
+            ));
+        }
+        Ok(Self::new(configs))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Provider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Provider> {
+        self.providers.iter()
+    }
+}
+
+/// 256 random bits, base64url-encoded: used for `state`, `nonce` and the
+/// PKCE code verifier.
+pub fn random_token() -> String {
+    let mut bytes: [u8; 32] = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The S256 PKCE challenge for `code_verifier`.
+pub fn pkce_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// HTTP Basic client credentials are form-encoded before base64
+/// (RFC 6749 section 2.3.1).
+fn form_encode(value: &str) -> String {
+    utf8_percent_encode(value, FORM).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_challenge_matches_rfc_example() {
+        // RFC 7636 appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn random_tokens_are_unique_and_url_safe() {
+        let first = random_token();
+        assert_eq!(first.len(), 43);
+        assert!(
+            first
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+        assert_ne!(first, random_token());
+    }
+
+    #[test]
+    fn audience_accepts_string_or_list() {
+        let one: Audience = serde_json::from_str(r#""client""#).unwrap();
+        let many: Audience =
+            serde_json::from_str(r#"["other", "client"]"#).unwrap();
+        assert!(one.contains("client"));
+        assert!(many.contains("client"));
+        assert!(!one.contains("other-client"));
+    }
+}
@@ -0,0 +1,102 @@
+//! Organization names, URL slugs and invitation links.
+
+use sha2::{Digest as _, Sha256};
+
+use crate::oidc::random_token;
+use crate::util::base_url;
+
+/// Longest organization name, in characters.
+pub const MAX_NAME_LEN: usize = 60;
+
+/// Longest slug, in bytes (slugs are ASCII).
+pub const MAX_SLUG_LEN: usize = 40;
+
+/// How long an emailed invitation link works.
+pub const INVITE_DAYS: i64 = 7;
+
+/// Slugs that would be shadowed by other routes under `/orgs`.
+const RESERVED_SLUGS: [&str; 3] = ["join", "new", "switcher"];
+
+/// The URL-safe form of `name`: lowercase ASCII letters and digits, with
+/// runs of anything else turned into single dashes, e.g. "Acme & Co." to
+/// `acme-co`.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for word in name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(MAX_SLUG_LEN);
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Why `slug` can't be used, if it can't.
+pub fn check_slug(slug: &str) -> Result<(), String> {
+    if slug.is_empty() {
+        return Err("Use at least one letter or digit.".to_owned());
+    }
+    if slug.len() > MAX_SLUG_LEN {
+        return Err(format!("Use at most {MAX_SLUG_LEN} characters."));
+    }
+    if slugify(slug) != slug {
+        return Err(
+            "Use lowercase letters, digits and single dashes.".to_owned()
+        );
+    }
+    if RESERVED_SLUGS.contains(&slug) {
+        return Err("That URL is reserved.".to_owned());
+    }
+    Ok(())
+}
+
+/// A fresh invitation token for an emailed link.
+pub fn generate_token() -> String {
+    random_token()
+}
+
+/// Hex SHA-256 of an invitation token, as stored.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// The link that accepts an invitation.
+pub fn invite_url(token: &str) -> String {
+    format!("{}/orgs/join/{token}", base_url())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::organization::Role;
+
+    #[test]
+    fn slugs_are_lowercase_words_joined_by_dashes() {
+        assert_eq!(slugify("Acme & Co."), "acme-co");
+        assert_eq!(slugify("  The   Rust Team "), "the-rust-team");
+        assert_eq!(slugify("!!!"), "");
+        assert_eq!(slugify(&"a".repeat(100)).len(), MAX_SLUG_LEN);
+
+        assert_eq!(check_slug("acme-co"), Ok(()));
+        check_slug("Acme").unwrap_err();
+        check_slug("acme--co").unwrap_err();
+        check_slug("-acme").unwrap_err();
+        check_slug("join").unwrap_err();
+        check_slug("").unwrap_err();
+    }
+
+    #[test]
+    fn roles_manage_up_to_their_own() {
+        assert!(!Role::Member.can_manage(Role::Member));
+        assert!(Role::Admin.can_manage(Role::Member));
+        assert!(Role::Admin.can_manage(Role::Admin));
+        assert!(!Role::Admin.can_manage(Role::Owner));
+        assert!(Role::Owner.can_manage(Role::Owner));
+        assert_eq!(Role::Admin.assignable(), [Role::Member, Role::Admin]);
+        assert!(Role::Member.assignable().is_empty());
+    }
+}
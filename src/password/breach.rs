@@ -0,0 +1,120 @@
+//! Offline breached-password check.
+//!
+//! Passwords are looked up by SHA-1 the way the Have I Been Pwned range API
+//! works: the first five hex characters select a bucket, and the remaining
+//! suffix is searched within it. The bundled list covers the most common
+//! leaked passwords; point `BREACHED_PASSWORDS_FILE` at a full HIBP
+//! "ordered by hash" download for wider coverage.
+//!
+//! `data/breached-sha1.txt` is derived from `data/common-passwords.txt`:
+//! each word as is, capitalised and uppercased, with the usual suffixes
+//! (`1`, `12`, `123`, `1234`, `!` and recent years) appended. Run
+//! `just breach-list` to regenerate it after editing the word list.
+
+use std::fs;
+use std::path::Path;
+
+use sha1::{Digest as _, Sha1};
+
+const BUNDLED: &str = include_str!("data/breached-sha1.txt");
+
+/// Hex characters in a bucket prefix.
+const PREFIX_LEN: usize = 5;
+
+/// Sorted, uppercase SHA-1 hex digests of known-breached passwords.
+#[derive(Debug)]
+pub struct BreachList {
+    hashes: Vec<String>,
+}
+
+impl BreachList {
+    /// Parses one digest per line. Anything after a `:` (HIBP's occurrence
+    /// count) is ignored, as are blank lines.
+    pub fn parse(contents: &str) -> Self {
+        let mut hashes: Vec<String> = contents
+            .lines()
+            .filter_map(|line| line.split(':').next())
+            .map(str::trim)
+            .filter(|hash| hash.len() == 40)
+            .map(str::to_ascii_uppercase)
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        Self { hashes }
+    }
+
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        fs::read_to_string(path)
+            .map(|contents| Self::parse(&contents))
+            .map_err(|err| format!("Failed to read {}: {err}", path.display()))
+    }
+
+    /// The digests whose first five characters are `prefix`.
+    fn range(&self, prefix: &str) -> &[String] {
+        let start = self
+            .hashes
+            .partition_point(|hash| hash.get(..PREFIX_LEN) < Some(prefix));
+        let len = self.hashes[start..]
+            .iter()
+            .take_while(|hash| hash.starts_with(prefix))
+            .count();
+        &self.hashes[start..start.saturating_add(len)]
+    }
+
+    /// Whether `password` appears in the list.
+    pub fn contains(&self, password: &str) -> bool {
+        let hex = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hex.split_at(PREFIX_LEN);
+        self.range(prefix)
+            .iter()
+            .any(|hash| hash.get(PREFIX_LEN..) == Some(suffix))
+    }
+
+    pub const fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_list_flags_common_passwords() {
+        let list = BreachList::bundled();
+        assert!(list.contains("password"));
+        assert!(list.contains("Password123"));
+        assert!(list.contains("qwerty"));
+        assert!(!list.contains("correct horse battery staple"));
+    }
+
+    #[test]
+    fn bundled_list_covers_the_strength_dictionary() {
+        let list = BreachList::bundled();
+        for word in include_str!("data/common-passwords.txt")
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        {
+            assert!(list.contains(word), "{word} is missing");
+        }
+    }
+
+    #[test]
+    fn parse_accepts_hibp_counts() {
+        // SHA-1 of "password"
+        let list = BreachList::parse(
+            "5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8:3861493\n\n",
+        );
+        assert_eq!(list.len(), 1);
+        assert!(list.contains("password"));
+        assert!(!list.contains("Password"));
+    }
+}
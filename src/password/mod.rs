@@ -0,0 +1,772 @@
+pub mod breach;
+pub mod strength;
+
+use std::fmt::Display;
+use std::num::NonZero;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::SaltString;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::{
+    Algorithm, Argon2, KeyId, Params, ParamsBuilder, PasswordHash,
+    PasswordHasher as _, PasswordVerifier as _, Version,
+};
+use tokio::sync::Semaphore;
+use tokio::task;
+use tracing::field::Empty;
+use tracing::{Instrument as _, Span, debug, info_span, warn};
+use unicode_normalization::UnicodeNormalization as _;
+
+use breach::BreachList;
+use strength::Strength;
+
+static CONFIG: OnceLock<PasswordConfig> = OnceLock::new();
+
+static POOL: OnceLock<HashPool> = OnceLock::new();
+
+/// Queue waits longer than this are logged as warnings.
+const SLOW_WAIT: Duration = Duration::from_secs(1);
+
+/// The PHC `keyid` of hashes made with the pepper, so verifying knows
+/// whether to mix it in without trying both ways.
+const PEPPER_KEY_ID: &[u8] = b"pepper";
+
+/// Argon2id cost parameters and optional pepper used for new hashes.
+#[derive(Debug, Clone)]
+pub struct PasswordConfig {
+    pub params: Params,
+    /// Server-side secret mixed into every hash (Argon2's secret input).
+    /// Kept out of the database so a leaked `user` table alone can't be
+    /// cracked offline.
+    pub pepper: Option<Vec<u8>>,
+    /// How many hashes may run at once; further requests queue.
+    pub max_concurrent: usize,
+    pub policy: PasswordPolicy,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            params: Params::default(),
+            pepper: None,
+            max_concurrent: default_concurrency(),
+            policy: PasswordPolicy::default(),
+        }
+    }
+}
+
+/// Half the cores, at least one, so hashing alone can't occupy every core.
+fn default_concurrency() -> usize {
+    thread::available_parallelism()
+        .map_or(1, NonZero::get)
+        .div_euclid(2)
+        .max(1)
+}
+
+fn var_parsed<T>(name: &str, default: T) -> Result<T, String>
+where
+    T: FromStr<Err: Display>,
+{
+    match dotenvy::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|err| format!("Invalid {name} {value:?}: {err}")),
+        Err(_) => Ok(default),
+    }
+}
+
+/// NFKC-normalises a password so the same characters typed on different
+/// keyboards or platforms (composed vs decomposed accents, full-width
+/// forms) hash identically. ASCII passwords are unchanged.
+fn normalize(password: &str) -> String {
+    password.nfkc().collect()
+}
+
+/// Rules a new password must satisfy.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    /// In characters, after normalisation.
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Minimum [`Strength::score`], 0-4.
+    pub min_strength: u8,
+    /// Known-breached passwords to reject, or `None` to skip the check.
+    pub breached: Option<Arc<BreachList>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            min_strength: 2,
+            breached: Some(Arc::new(BreachList::bundled())),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Reads `PASSWORD_MIN_LENGTH`, `PASSWORD_MAX_LENGTH`,
+    /// `PASSWORD_MIN_STRENGTH` and `PASSWORD_BREACH_CHECK` (`false` to
+    /// disable). `BREACHED_PASSWORDS_FILE` replaces the bundled breach list
+    /// with a file of SHA-1 digests, such as a Have I Been Pwned download.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let min_length =
+            var_parsed("PASSWORD_MIN_LENGTH", defaults.min_length)?;
+        let max_length =
+            var_parsed("PASSWORD_MAX_LENGTH", defaults.max_length)?;
+        if min_length == 0 || min_length > max_length {
+            return Err(format!(
+                "Invalid password length range {min_length}..={max_length}"
+            ));
+        }
+        let min_strength =
+            var_parsed("PASSWORD_MIN_STRENGTH", defaults.min_strength)?;
+        if min_strength > 4 {
+            return Err(format!(
+                "Invalid PASSWORD_MIN_STRENGTH {min_strength}: must be 0-4"
+            ));
+        }
+        let breached = if var_parsed("PASSWORD_BREACH_CHECK", true)? {
+            match dotenvy::var("BREACHED_PASSWORDS_FILE") {
+                Ok(path) => {
+                    Some(Arc::new(BreachList::from_file(Path::new(&path))?))
+                }
+                Err(_) => defaults.breached,
+            }
+        } else {
+            None
+        };
+        Ok(Self {
+            min_length,
+            max_length,
+            min_strength,
+            breached,
+        })
+    }
+
+    /// Checks a new password, returning why it's unacceptable if it is.
+    /// `user_inputs` (such as the username) count against its strength.
+    pub fn check(
+        &self,
+        password: &str,
+        user_inputs: &[&str],
+    ) -> Result<(), String> {
+        let normalized = normalize(password);
+        let length = normalized.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Err(self.length_problem());
+        }
+        if normalized.chars().any(char::is_control) {
+            return Err(String::from(
+                "Password must not contain control characters.",
+            ));
+        }
+        if self.is_breached(&normalized) {
+            return Err(String::from(
+                "This password has appeared in a data breach. Please choose another.",
+            ));
+        }
+        let strength = strength::estimate(&normalized, user_inputs);
+        if strength.score < self.min_strength {
+            return Err(format!(
+                "Password is too easy to guess. {}",
+                strength.warning.unwrap_or(
+                    "Try a longer passphrase of several uncommon words."
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    /// Why a password of the wrong length is rejected.
+    pub fn length_problem(&self) -> String {
+        format!(
+            "Password must be between {} and {} characters.",
+            self.min_length, self.max_length
+        )
+    }
+
+    fn is_breached(&self, password: &str) -> bool {
+        self.breached
+            .as_ref()
+            .is_some_and(|list| list.contains(password))
+    }
+
+    /// Live feedback for a password being typed: its estimated strength and
+    /// the first rule it breaks, if any.
+    pub fn feedback(&self, password: &str, user_inputs: &[&str]) -> Feedback {
+        Feedback {
+            strength: strength::estimate(&normalize(password), user_inputs),
+            problem: self.check(password, user_inputs).err(),
+        }
+    }
+}
+
+/// What the signup and settings forms show while a password is typed.
+#[derive(Debug, Clone)]
+pub struct Feedback {
+    pub strength: Strength,
+    /// Why the password would be rejected, if it would be.
+    pub problem: Option<String>,
+}
+
+impl PasswordConfig {
+    /// Reads `ARGON2_MEMORY_KIB`, `ARGON2_ITERATIONS`, `ARGON2_PARALLELISM`,
+    /// crate's recommended parameters, no pepper and a hash per two cores.
+    /// crate's recommended parameters, no pepper and one hash per core.
+    /// See [`PasswordPolicy::from_env`] for the policy variables.
+    pub fn from_env() -> Result<Self, String> {
+        let params = Params::new(
+            var_parsed("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST)?,
+            var_parsed("ARGON2_ITERATIONS", Params::DEFAULT_T_COST)?,
+            var_parsed("ARGON2_PARALLELISM", Params::DEFAULT_P_COST)?,
+            None,
+        )
+        .map_err(|err| format!("Invalid Argon2 parameters: {err}"))?;
+        let pepper = dotenvy::var("PASSWORD_PEPPER")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(String::into_bytes);
+        let max_concurrent = match dotenvy::var("HASHING_CONCURRENCY") {
+            Ok(value) => match value.parse::<usize>() {
+                Ok(limit) if limit > 0 => limit,
+                _ => {
+                    return Err(format!(
+                        "Invalid HASHING_CONCURRENCY {value:?}"
+                    ));
+                }
+            },
+            Err(_) => default_concurrency(),
+        };
+        Ok(Self {
+            params,
+            pepper,
+            max_concurrent,
+            policy: PasswordPolicy::from_env()?,
+        })
+    }
+
+    fn argon2<'a>(&self, pepper: Option<&'a [u8]>) -> Argon2<'a> {
+        match pepper {
+            Some(secret) => Argon2::new_with_secret(
+                secret,
+                Algorithm::Argon2id,
+                Version::V0x13,
+                self.peppered_params(),
+            )
+            .expect("Pepper length is within Argon2 limits"),
+            None => Argon2::new(
+                Algorithm::Argon2id,
+                Version::V0x13,
+                self.params.clone(),
+            ),
+        }
+    }
+
+    /// The configured parameters, tagged with [`PEPPER_KEY_ID`].
+    fn peppered_params(&self) -> Params {
+        let mut builder = ParamsBuilder::new();
+        builder
+            .m_cost(self.params.m_cost())
+            .t_cost(self.params.t_cost())
+            .p_cost(self.params.p_cost())
+            .keyid(KeyId::new(PEPPER_KEY_ID).expect("Key ID fits Argon2"));
+        if let Some(len) = self.params.output_len() {
+            builder.output_len(len);
+        }
+        builder.build().expect("Configured parameters are valid")
+    }
+
+    /// Hashes `plaintext_password` with the configured parameters and pepper.
+    pub fn hash(&self, plaintext_password: &str) -> String {
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2(self.pepper.as_deref())
+            .hash_password(normalize(plaintext_password).as_bytes(), &salt)
+            .expect("Hashing with validated parameters cannot fail")
+            .to_string()
+    }
+
+    /// Checks `plaintext_password` against a stored PHC hash string.
+    ///
+    /// Peppered hashes carry [`PEPPER_KEY_ID`], so each attempt runs Argon2
+    /// once. Hashes made before a pepper was configured still verify (and
+    /// are flagged for rehashing). A malformed stored hash, or a peppered one
+    /// with no pepper configured, is logged and treated as a mismatch.
+    pub fn verify(
+        &self,
+        plaintext_password: &str,
+        stored_hash: &str,
+    ) -> Verification {
+        let parsed = match PasswordHash::new(stored_hash) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                warn!("Stored password hash is malformed: {err}");
+                return Verification::Invalid;
+            }
+        };
+
+        let peppered = Params::try_from(&parsed)
+            .is_ok_and(|params| params.keyid() == PEPPER_KEY_ID);
+        let pepper = if peppered {
+            let Some(pepper) = self.pepper.as_deref() else {
+                warn!("Stored password hash needs a pepper but none is set");
+                return Verification::Invalid;
+            };
+            Some(pepper)
+        } else {
+            None
+        };
+        if self
+            .argon2(pepper)
+            .verify_password(normalize(plaintext_password).as_bytes(), &parsed)
+            .is_err()
+        {
+            return Verification::Invalid;
+        }
+
+        let unpeppered_legacy = self.pepper.is_some() && !peppered;
+        if unpeppered_legacy || self.is_weaker(&parsed) {
+            Verification::NeedsRehash
+        } else {
+            Verification::Valid
+        }
+    }
+
+    /// Whether `hash` was made with a different algorithm or cheaper
+    /// parameters than currently configured.
+    fn is_weaker(&self, hash: &PasswordHash<'_>) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        Params::try_from(hash).map_or(true, |params| {
+            params.m_cost() < self.params.m_cost()
+                || params.t_cost() < self.params.t_cost()
+                || params.p_cost() < self.params.p_cost()
+        })
+    }
+}
+
+/// Outcome of checking a password against a stored hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    Invalid,
+    Valid,
+    /// Correct password, but the hash should be replaced with one made with
+    /// the current parameters and pepper.
+    NeedsRehash,
+}
+
+/// Installs the hashing configuration. Call once at startup, before any
+/// hashing; later calls are ignored.
+pub fn init(config: PasswordConfig) {
+    if CONFIG.set(config).is_err() {
+        warn!("Password hashing config already initialised");
+    }
+}
+
+/// The installed configuration, or the defaults if [`init`] wasn't called.
+pub fn config() -> &'static PasswordConfig {
+    CONFIG.get_or_init(PasswordConfig::default)
+}
+
+/// Hashes on the calling thread. Request handlers should use
+/// [`hash_async`] instead.
+pub fn generate_hash(plaintext_password: &str) -> String {
+    config().hash(plaintext_password)
+}
+
+/// Verifies on the calling thread. Request handlers should use
+/// [`verify_async`] instead.
+pub fn verify(plaintext_password: &str, stored_hash: &str) -> Verification {
+    config().verify(plaintext_password, stored_hash)
+}
+
+/// Bounds how many Argon2 computations run at once.
+///
+/// Each hash runs on Tokio's blocking pool so it never occupies an async
+/// worker, and a semaphore caps how many run together. Excess requests
+/// wait their turn, which keeps a login burst from using every core that
+/// page rendering also needs.
+struct HashPool {
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// Point-in-time counters for the hashing pool.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct HashingStats {
+    /// Requests queued for a permit.
+    pub waiting: usize,
+    /// Hashes currently computing.
+    pub running: usize,
+    pub completed: u64,
+    /// Mean time spent queued, over all completed hashes, in microseconds.
+    pub mean_wait_micros: u64,
+    pub max_wait_micros: u64,
+}
+
+fn pool() -> &'static HashPool {
+    POOL.get_or_init(|| HashPool::new(config().max_concurrent))
+}
+
+impl HashPool {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            waiting: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            total_wait_micros: AtomicU64::new(0),
+            max_wait_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for a permit, then runs `work` on the blocking pool.
+    ///
+    /// The permit and the running count go with `work`, so a request
+    /// dropped mid-hash still holds its slot until Argon2 finishes.
+    async fn run<T, F>(&'static self, work: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let span = info_span!("password_hash", queue_wait_us = Empty);
+        let queued_at = Instant::now();
+        let queued = Gauge::enter(&self.waiting);
+        let permit = Arc::clone(&self.permits)
+            .acquire_owned()
+            .instrument(span.clone())
+            .await
+            .expect("Hashing semaphore is never closed");
+        drop(queued);
+
+        let wait = queued_at.elapsed();
+        let wait_micros = u64::try_from(wait.as_micros()).unwrap_or(u64::MAX);
+        log_wait(wait, wait_micros, &span);
+
+        let running = Gauge::enter(&self.running);
+        task::spawn_blocking(move || {
+            let result = span.in_scope(work);
+            drop(running);
+            self.record_completed(wait_micros);
+            drop(permit);
+            result
+        })
+        .await
+        .expect("Password hashing task panicked")
+    }
+
+    fn record_completed(&self, wait_micros: u64) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_micros
+            .fetch_add(wait_micros, Ordering::Relaxed);
+        self.max_wait_micros
+            .fetch_max(wait_micros, Ordering::Relaxed);
+    }
+}
+
+fn log_wait(wait: Duration, wait_micros: u64, span: &Span) {
+    span.record("queue_wait_us", wait_micros);
+    if wait >= SLOW_WAIT {
+        warn!(wait_micros, "Password hashing queue is backed up");
+    } else {
+        debug!(wait_micros, "Password hashing permit acquired");
+    }
+}
+
+/// Holds one count in a pool counter, releasing it when dropped so a
+/// cancelled request doesn't leave it raised.
+struct Gauge<'a>(&'a AtomicUsize);
+
+impl<'a> Gauge<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Gauge<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Hashes on the bounded blocking pool.
+pub async fn hash_async(plaintext_password: String) -> String {
+    pool().run(move || generate_hash(&plaintext_password)).await
+}
+
+/// Verifies on the bounded blocking pool.
+pub async fn verify_async(
+    plaintext_password: String,
+    stored_hash: String,
+) -> Verification {
+    pool()
+        .run(move || verify(&plaintext_password, &stored_hash))
+        .await
+}
+
+/// Current hashing pool counters.
+pub fn stats() -> HashingStats {
+    let pool = pool();
+    let completed = pool.completed.load(Ordering::Relaxed);
+    let total_wait = pool.total_wait_micros.load(Ordering::Relaxed);
+    HashingStats {
+        waiting: pool.waiting.load(Ordering::Relaxed),
+        running: pool.running.load(Ordering::Relaxed),
+        completed,
+        mean_wait_micros: total_wait.checked_div(completed).unwrap_or_default(),
+        max_wait_micros: pool.max_wait_micros.load(Ordering::Relaxed),
+    }
+}
+
+/// Checks a new password against the installed policy. See
+/// [`PasswordPolicy::check`].
+pub fn check_password(
+    password: &str,
+    user_inputs: &[&str],
+) -> Result<(), String> {
+    config().policy.check(password, user_inputs)
+}
+
+/// Strength feedback under the installed policy.
+pub fn feedback(password: &str, user_inputs: &[&str]) -> Feedback {
+    config().policy.feedback(password, user_inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use tokio::time::timeout;
+
+    use super::*;
+
+    #[test]
+    fn password_too_short() {
+        check_password("short", &[]).expect_err("too short");
+        check_password("1234567", &[]).expect_err("too short");
+    }
+
+    #[test]
+    fn password_too_long() {
+        let long_password = "correct horse ".repeat(10);
+        check_password(&long_password, &[]).expect_err("too long");
+    }
+
+    #[test]
+    fn password_unicode_allowed() {
+        check_password("m\u{f6}we \u{1f512} kitesurf", &[])
+            .expect("emoji allowed");
+        check_password(
+            "\u{43f}\u{430}\u{440}\u{43e}\u{43b}\u{44c} \u{43b}\u{438}\u{441}\u{430}",
+            &[],
+        )
+        .expect("Cyrillic allowed");
+        check_password("tab\there kitesurf", &[])
+            .expect_err("control characters rejected");
+    }
+
+    #[test]
+    fn password_breached_or_weak() {
+        let breached =
+            check_password("password123", &[]).expect_err("breached");
+        assert!(breached.contains("data breach"));
+        check_password("12345678", &[]).expect_err("breached");
+        check_password("abcdefghijk", &[]).expect_err("sequence");
+        check_password("janedoe2024", &["janedoe"]).expect_err("username");
+    }
+
+    #[test]
+    fn password_valid() {
+        for password in [
+            "correct horse battery staple",
+            "Tr0ub4dor&3x",
+            &"kq7!".repeat(32),
+        ] {
+            check_password(password, &[]).expect(password);
+        }
+    }
+
+    #[test]
+    fn normalization_makes_hashes_match() {
+        let config = PasswordConfig::default();
+        // "e" + combining acute vs precomposed "\u{e9}"
+        let hash = config.hash("caf\u{65}\u{301} au lait");
+        assert_eq!(
+            config.verify("caf\u{e9} au lait", &hash),
+            Verification::Valid
+        );
+    }
+
+    #[test]
+    #[allow(
+        clippy::allow_attributes,
+        clippy::assertions_on_result_states,
+        reason = "The verification error carries nothing worth printing"
+    )]
+    fn hash_and_verify() {
+        use argon2::{Argon2, PasswordHash, PasswordVerifier as _};
+
+        let password = "testpassword123";
+        let hash = generate_hash(password);
+
+        let parsed = PasswordHash::new(&hash).expect("valid hash");
+        assert!(
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn hash_wrong_password_fails() {
+        use argon2::{Argon2, PasswordHash, PasswordVerifier as _};
+
+        let hash = generate_hash("correctpassword");
+        let parsed = PasswordHash::new(&hash).expect("valid hash");
+
+        assert!(
+            Argon2::default()
+                .verify_password(b"wrongpassword", &parsed)
+                .is_err()
+        );
+    }
+
+    fn config_with(m_cost: u32, pepper: Option<&str>) -> PasswordConfig {
+        PasswordConfig {
+            params: Params::new(m_cost, 2, 1, None).expect("valid params"),
+            pepper: pepper.map(|value| value.as_bytes().to_vec()),
+            max_concurrent: 1,
+            policy: PasswordPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn verify_matching_config_is_valid() {
+        let config = config_with(Params::DEFAULT_M_COST, None);
+        let hash = config.hash("password123");
+        assert_eq!(config.verify("password123", &hash), Verification::Valid);
+        assert_eq!(config.verify("password124", &hash), Verification::Invalid);
+    }
+
+    #[test]
+    fn verify_weaker_params_needs_rehash() {
+        let old = config_with(Params::MIN_M_COST, None);
+        let hash = old.hash("password123");
+        let current = config_with(Params::DEFAULT_M_COST, None);
+        assert_eq!(
+            current.verify("password123", &hash),
+            Verification::NeedsRehash
+        );
+        assert_eq!(current.verify("wrong", &hash), Verification::Invalid);
+    }
+
+    #[test]
+    fn verify_with_pepper() {
+        let peppered = config_with(Params::DEFAULT_M_COST, Some("pepper"));
+        let hash = peppered.hash("password123");
+        assert!(hash.contains("keyid="), "peppered hashes are marked");
+        assert_eq!(peppered.verify("password123", &hash), Verification::Valid);
+        assert_eq!(
+            peppered.verify("password124", &hash),
+            Verification::Invalid
+        );
+
+        let other = config_with(Params::DEFAULT_M_COST, Some("other"));
+        assert_eq!(other.verify("password123", &hash), Verification::Invalid);
+
+        let unpeppered = config_with(Params::DEFAULT_M_COST, None);
+        assert_eq!(
+            unpeppered.verify("password123", &hash),
+            Verification::Invalid
+        );
+    }
+
+    #[test]
+    fn verify_legacy_unpeppered_hash_needs_rehash() {
+        let legacy =
+            config_with(Params::DEFAULT_M_COST, None).hash("password123");
+        assert!(!legacy.contains("keyid="), "unpeppered hashes are unmarked");
+        let peppered = config_with(Params::DEFAULT_M_COST, Some("pepper"));
+        assert_eq!(
+            peppered.verify("password123", &legacy),
+            Verification::NeedsRehash
+        );
+        assert_eq!(
+            peppered.verify("password124", &legacy),
+            Verification::Invalid
+        );
+    }
+
+    #[test]
+    fn verify_malformed_hash_is_invalid() {
+        let config = PasswordConfig::default();
+        assert_eq!(
+            config.verify("password123", "not a hash"),
+            Verification::Invalid
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_leave_the_queue() {
+        let pool: &'static HashPool = Box::leak(Box::new(HashPool::new(1)));
+        let held = Arc::clone(&pool.permits)
+            .acquire_owned()
+            .await
+            .expect("permit");
+        timeout(Duration::from_millis(10), pool.run(|| ()))
+            .await
+            .expect_err("no permit is free");
+        assert_eq!(pool.waiting.load(Ordering::Relaxed), 0);
+        drop(held);
+
+        pool.run(|| ()).await;
+        assert_eq!(pool.running.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.completed.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_hashes_keep_their_permit_until_done() {
+        let pool: &'static HashPool = Box::leak(Box::new(HashPool::new(1)));
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+        timeout(
+            Duration::from_millis(10),
+            pool.run(move || finish_rx.recv()),
+        )
+        .await
+        .expect_err("the hash is still running");
+        assert_eq!(pool.running.load(Ordering::Relaxed), 1);
+        assert_eq!(pool.permits.available_permits(), 0);
+
+        finish_tx.send(()).expect("hash waiting");
+        let _permit = pool.permits.acquire().await.expect("permit");
+        assert_eq!(pool.running.load(Ordering::Relaxed), 0);
+        assert_eq!(pool.completed.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn async_hash_round_trip() {
+        let hash = hash_async("password123".to_owned()).await;
+        assert_eq!(
+            verify_async("password123".to_owned(), hash).await,
+            Verification::Valid
+        );
+        let stats = stats();
+        assert!(stats.completed >= 2);
+        assert_eq!(stats.waiting, 0);
+    }
+}
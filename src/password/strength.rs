@@ -0,0 +1,342 @@
+//! Password strength estimation in the style of zxcvbn.
+//!
+//! The password is split greedily into the cheapest-to-guess patterns
+//! (common passwords, the user's own details, sequences, repeats and years)
+//! and everything left over is costed as brute force. The total, in bits,
+//! maps onto zxcvbn's 0-4 score.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const COMMON_PASSWORDS: &str = include_str!("data/common-passwords.txt");
+
+/// Keyboard rows walked as sequences, e.g. `qwerty` or `asdf`.
+const KEYBOARD_ROWS: [&str; 4] =
+    ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Shortest substring treated as a dictionary word or sequence.
+const MIN_PATTERN_LEN: usize = 3;
+
+/// Longest substring looked up in the dictionary.
+const MAX_WORD_LEN: usize = 20;
+
+/// Minimum bits of entropy for each score above 0, after zxcvbn's guess
+/// thresholds of 10^3, 10^6, 10^8 and 10^10.
+const SCORE_THRESHOLDS: [u32; 4] = [10, 20, 27, 33];
+
+static DICTIONARY: OnceLock<HashMap<String, u32>> = OnceLock::new();
+
+/// Common passwords mapped to their popularity rank (1 = most common).
+fn dictionary() -> &'static HashMap<String, u32> {
+    DICTIONARY.get_or_init(|| {
+        COMMON_PASSWORDS
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .zip(1..)
+            .map(|(word, rank)| (word.to_owned(), rank))
+            .collect()
+    })
+}
+
+/// The kinds of guessable pattern, most damning first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Pattern {
+    UserInput,
+    Dictionary,
+    Sequence,
+    Repeat,
+    Year,
+}
+
+impl Pattern {
+    const fn warning(self) -> &'static str {
+        match self {
+            Self::UserInput => "Avoid including your username.",
+            Self::Dictionary => "Avoid common passwords and words.",
+            Self::Sequence => "Avoid sequences like abc, 1234 or qwerty.",
+            Self::Repeat => "Avoid repeated characters.",
+            Self::Year => "Avoid dates and years.",
+        }
+    }
+}
+
+/// Estimated strength of a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Strength {
+    /// 0 (very weak) to 4 (very strong).
+    pub score: u8,
+    /// Estimated entropy, in bits.
+    pub bits: u32,
+    /// What made the password easier to guess, if anything stood out.
+    pub warning: Option<&'static str>,
+}
+
+impl Strength {
+    pub const fn label(&self) -> &'static str {
+        match self.score {
+            0 => "Very weak",
+            1 => "Weak",
+            2 => "Fair",
+            3 => "Strong",
+            _ => "Very strong",
+        }
+    }
+}
+
+/// Estimates how hard `password` is to guess. `user_inputs` (such as the
+/// username) are treated as the most obvious guesses of all.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let inputs: Vec<String> = user_inputs
+        .iter()
+        .filter(|input| input.chars().count() >= MIN_PATTERN_LEN)
+        .map(|input| input.to_lowercase())
+        .collect();
+
+    // Costs are in tenths of a bit to keep the arithmetic integral
+    let mut cost: u32 = 0;
+    let mut worst: Option<Pattern> = None;
+    let mut start = 0;
+    while start < chars.len() {
+        let rest = &chars[start..];
+        let best = match_user_input(rest, &inputs)
+            .or_else(|| match_dictionary(rest))
+            .into_iter()
+            .chain(match_sequence(rest))
+            .chain(match_repeat(rest))
+            .chain(match_year(rest))
+            .max_by_key(|found| {
+                (found.len, u32::MAX.saturating_sub(found.cost))
+            });
+
+        if let Some(found) = best {
+            cost = cost.saturating_add(found.cost);
+            worst = Some(
+                worst.map_or(found.pattern, |seen| seen.min(found.pattern)),
+            );
+            start = start.saturating_add(found.len);
+        } else {
+            cost = cost.saturating_add(brute_force_cost(chars[start]));
+            start = start.saturating_add(1);
+        }
+    }
+
+    let bits = cost.checked_div(10).unwrap_or_default();
+    let score = SCORE_THRESHOLDS
+        .iter()
+        .take_while(|threshold| bits >= **threshold)
+        .count();
+    Strength {
+        score: u8::try_from(score).unwrap_or(4),
+        bits,
+        warning: worst.map(Pattern::warning),
+    }
+}
+
+struct Match {
+    pattern: Pattern,
+    len: usize,
+    /// Tenths of a bit.
+    cost: u32,
+}
+
+/// Tenths of a bit to guess one character by brute force.
+const fn brute_force_cost(c: char) -> u32 {
+    if c.is_ascii_digit() {
+        33
+    } else if c.is_ascii_alphabetic() {
+        47
+    } else if c.is_ascii() {
+        50
+    } else {
+        70
+    }
+}
+
+/// Tenths of a bit for a choice among `n` options.
+fn log2_cost(n: u32) -> u32 {
+    n.max(1).ilog2().saturating_add(1).saturating_mul(10)
+}
+
+/// Undoes common character substitutions (`p@ssw0rd` -> `password`).
+const fn unleet(c: char) -> char {
+    match c.to_ascii_lowercase() {
+        '4' | '@' => 'a',
+        '3' => 'e',
+        '1' | '!' => 'i',
+        '0' => 'o',
+        '$' | '5' => 's',
+        '7' => 't',
+        other => other,
+    }
+}
+
+/// Extra cost for capitals and substitutions on top of a word's rank.
+fn variation_cost(original: &[char], word: &str) -> u32 {
+    let capitals = original.iter().any(char::is_ascii_uppercase);
+    let substituted = original
+        .iter()
+        .zip(word.chars())
+        .any(|(c, w)| c.to_ascii_lowercase() != w);
+    u32::from(capitals)
+        .saturating_add(u32::from(substituted))
+        .saturating_mul(10)
+}
+
+fn match_user_input(rest: &[char], user_inputs: &[String]) -> Option<Match> {
+    let lowered: String = rest.iter().map(char::to_ascii_lowercase).collect();
+    user_inputs
+        .iter()
+        .filter(|input| lowered.starts_with(input.as_str()))
+        .map(|input| {
+            let len = input.chars().count();
+            Match {
+                pattern: Pattern::UserInput,
+                len,
+                cost: variation_cost(&rest[..len], input).saturating_add(10),
+            }
+        })
+        .max_by_key(|found| found.len)
+}
+
+fn match_dictionary(rest: &[char]) -> Option<Match> {
+    let dictionary = dictionary();
+    let longest = rest.len().min(MAX_WORD_LEN);
+    (MIN_PATTERN_LEN..=longest).rev().find_map(|len| {
+        let plain: String =
+            rest[..len].iter().map(char::to_ascii_lowercase).collect();
+        let unleeted: String =
+            rest[..len].iter().copied().map(unleet).collect();
+        [plain, unleeted].into_iter().find_map(|word| {
+            dictionary.get(&word).map(|rank| Match {
+                pattern: Pattern::Dictionary,
+                len,
+                cost: log2_cost(*rank)
+                    .saturating_add(variation_cost(&rest[..len], &word)),
+            })
+        })
+    })
+}
+
+/// Whether a pair of characters steps up (`Some(true)`) or down
+/// (`Some(false)`) by one code point.
+fn step(pair: &[char]) -> Option<bool> {
+    let [first, second] = *pair else {
+        return None;
+    };
+    let (a, b) = (u32::from(first), u32::from(second));
+    if b == a.saturating_add(1) {
+        Some(true)
+    } else if a == b.saturating_add(1) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn match_sequence(rest: &[char]) -> Option<Match> {
+    let lowered: Vec<char> =
+        rest.iter().map(char::to_ascii_lowercase).collect();
+
+    // Runs of consecutive code points, ascending or descending
+    let run = rest.get(..2).and_then(step).map_or(0, |ascending| {
+        rest.windows(2)
+            .take_while(|pair| step(pair) == Some(ascending))
+            .count()
+            .saturating_add(1)
+    });
+
+    // Walks along a keyboard row, either way
+    let row_len = KEYBOARD_ROWS
+        .iter()
+        .flat_map(|row| {
+            [(*row).to_owned(), row.chars().rev().collect::<String>()]
+        })
+        .map(|row| {
+            (1..=row.len().min(lowered.len()))
+                .rev()
+                .find(|len| {
+                    row.contains(&lowered[..*len].iter().collect::<String>())
+                })
+                .unwrap_or_default()
+        })
+        .max()
+        .unwrap_or_default();
+
+    let len = run.max(row_len);
+    (len >= MIN_PATTERN_LEN).then(|| Match {
+        pattern: Pattern::Sequence,
+        len,
+        cost: log2_cost(u32::try_from(len).unwrap_or(u32::MAX))
+            .saturating_add(30),
+    })
+}
+
+fn match_repeat(rest: &[char]) -> Option<Match> {
+    let first = *rest.first()?;
+    let len = rest.iter().take_while(|c| **c == first).count();
+    (len >= MIN_PATTERN_LEN).then(|| Match {
+        pattern: Pattern::Repeat,
+        len,
+        cost: brute_force_cost(first)
+            .saturating_add(log2_cost(u32::try_from(len).unwrap_or(u32::MAX))),
+    })
+}
+
+fn match_year(rest: &[char]) -> Option<Match> {
+    let digits: String = rest.get(..4)?.iter().collect();
+    let year: u32 = digits.parse().ok()?;
+    (1900..=2099).contains(&year).then_some(Match {
+        pattern: Pattern::Year,
+        len: 4,
+        cost: 70,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_passwords_are_very_weak() {
+        for password in
+            ["password", "password123", "qwerty", "P@ssw0rd", "12345678"]
+        {
+            assert_eq!(estimate(password, &[]).score, 0, "{password}");
+        }
+    }
+
+    #[test]
+    fn patterns_produce_warnings() {
+        assert_eq!(
+            estimate("aaaaaaaa", &[]).warning,
+            Some(Pattern::Repeat.warning())
+        );
+        assert_eq!(
+            estimate("abcdefgh", &[]).warning,
+            Some(Pattern::Sequence.warning())
+        );
+        assert_eq!(
+            estimate("jdoe1990", &[]).warning,
+            Some(Pattern::Year.warning())
+        );
+    }
+
+    #[test]
+    fn username_is_penalised() {
+        let alone = estimate("zebrafinch", &[]);
+        let with_input = estimate("zebrafinch", &["zebrafinch"]);
+        assert!(with_input.bits < alone.bits);
+        assert_eq!(with_input.warning, Some(Pattern::UserInput.warning()));
+    }
+
+    #[test]
+    fn passphrases_are_strong() {
+        let strength = estimate("correct horse battery staple", &[]);
+        assert_eq!(strength.score, 4);
+        assert_eq!(strength.warning, None);
+
+        let unicode = estimate("\u{e9}t\u{e9} \u{e0} la plage bleue", &[]);
+        assert_eq!(unicode.score, 4);
+    }
+}
@@ -0,0 +1,203 @@
+//! Who may create an account, and the invite codes that let people in
+//! when sign-up isn't open to everyone.
+//!
+//! Codes are short enough to type: ten characters from an alphabet
+//! without lookalikes, shown as `XXXXX-XXXXX`. Only a hash is stored.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore as _};
+use sha2::{Digest as _, Sha256};
+use tracing::warn;
+
+use crate::util::base_url;
+
+/// Characters used in invite codes: no 0/O, 1/I/L or 5/S.
+const CODE_ALPHABET: &[u8; 30] = b"ABCDEFGHJKMNPQRTUVWXYZ23456789";
+
+/// The largest multiple of the alphabet's length that fits in a byte.
+const BYTE_LIMIT: u8 = 240;
+
+/// Length of an invite code, not counting the separator.
+const CODE_LEN: usize = 10;
+
+/// Days an invite stays usable when none is chosen.
+pub const DEFAULT_INVITE_DAYS: i64 = 7;
+
+/// Longest an invite can stay usable, in days.
+pub const MAX_INVITE_DAYS: i64 = 30;
+
+/// Most sign-ups one invite can allow when created by an admin.
+pub const MAX_ADMIN_INVITE_USES: i64 = 1000;
+
+/// Most sign-ups one invite can allow when created by anyone else.
+pub const MAX_USER_INVITE_USES: i64 = 5;
+
+static MODE: OnceLock<RegistrationMode> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegistrationMode {
+    /// Anyone can sign up.
+    #[default]
+    Open,
+    /// Sign-up needs an invite code; any user can create invites.
+    InviteOnly,
+    /// Nobody can sign up, with or without an invite.
+    Closed,
+}
+
+impl RegistrationMode {
+    /// Reads `REGISTRATION_MODE`: `open` (the default), `invite-only` or
+    /// `closed`.
+    pub fn from_env() -> Result<Self, String> {
+        dotenvy::var("REGISTRATION_MODE").map_or(Ok(Self::Open), |value| {
+            value.parse().map_err(|()| {
+                format!(
+                    "Invalid REGISTRATION_MODE {value:?}; use open, invite-only or closed"
+                )
+            })
+        })
+    }
+
+    /// Whether new accounts can be created at all.
+    pub const fn is_open_to(self, has_invite: bool) -> bool {
+        match self {
+            Self::Open => true,
+            Self::InviteOnly => has_invite,
+            Self::Closed => false,
+        }
+    }
+
+    /// Whether sign-up asks for an invite code.
+    pub const fn needs_invite(self) -> bool {
+        matches!(self, Self::InviteOnly)
+    }
+
+    /// Whether a user may create invites; admins always can, unless
+    /// sign-up is closed.
+    pub const fn lets_invite(self, is_admin: bool) -> bool {
+        match self {
+            Self::Open => is_admin,
+            Self::InviteOnly => true,
+            Self::Closed => false,
+        }
+    }
+}
+
+impl FromStr for RegistrationMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "open" => Ok(Self::Open),
+            "invite-only" | "invite_only" | "invite" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for RegistrationMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            Self::Open => "open",
+            Self::InviteOnly => "invite-only",
+            Self::Closed => "closed",
+        })
+    }
+}
+
+pub fn init(mode: RegistrationMode) {
+    if MODE.set(mode).is_err() {
+        warn!("Registration mode already initialised");
+    }
+}
+
+/// The installed mode, or [`RegistrationMode::Open`] if [`init`] wasn't
+/// called.
+pub fn mode() -> RegistrationMode {
+    *MODE.get_or_init(RegistrationMode::default)
+}
+
+/// The most uses an invite created by this user may allow.
+pub const fn max_invite_uses(is_admin: bool) -> i64 {
+    if is_admin {
+        MAX_ADMIN_INVITE_USES
+    } else {
+        MAX_USER_INVITE_USES
+    }
+}
+
+/// A fresh invite code, e.g. `K7QXM-2HRTA`.
+pub fn generate_code() -> String {
+    let mut chars = String::with_capacity(CODE_LEN);
+    while chars.len() < CODE_LEN {
+        let mut byte = [0; 1];
+        OsRng.fill_bytes(&mut byte);
+        // Bytes past the last whole multiple of the alphabet's length are
+        // skipped, so every character is equally likely
+        if byte[0] < BYTE_LIMIT
+            && let Some(&c) = CODE_ALPHABET
+                .get(usize::from(byte[0]).rem_euclid(CODE_ALPHABET.len()))
+        {
+            chars.push(char::from(c));
+        }
+    }
+    let (first, second) = chars.split_at(CODE_LEN.div_euclid(2));
+    format!("{first}-{second}")
+}
+
+/// The code as typed, uppercased and without separators or spaces.
+pub fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Hex SHA-256 of the normalized code, as stored.
+pub fn hash_code(code: &str) -> String {
+    hex::encode(Sha256::digest(normalize_code(code).as_bytes()))
+}
+
+/// The sign-up link that fills in `code`.
+pub fn invite_url(code: &str) -> String {
+    format!("{}/signup?invite={code}", base_url())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_match_however_they_are_typed() {
+        let code = generate_code();
+        assert_eq!(code.len(), CODE_LEN + 1);
+        assert!(
+            code.bytes()
+                .all(|b| b == b'-' || CODE_ALPHABET.contains(&b))
+        );
+        let typed =
+            format!(" {} ", code.to_ascii_lowercase().replace('-', " "));
+        assert_eq!(hash_code(&typed), hash_code(&code));
+        assert_ne!(hash_code(&generate_code()), hash_code(&code));
+    }
+
+    #[test]
+    fn modes_decide_who_gets_in() {
+        assert_eq!("Invite-Only".parse(), Ok(RegistrationMode::InviteOnly));
+        assert_eq!("closed".parse(), Ok(RegistrationMode::Closed));
+        assert_eq!("sometimes".parse::<RegistrationMode>(), Err(()));
+
+        assert!(RegistrationMode::Open.is_open_to(false));
+        assert!(!RegistrationMode::InviteOnly.is_open_to(false));
+        assert!(RegistrationMode::InviteOnly.is_open_to(true));
+        assert!(!RegistrationMode::Closed.is_open_to(true));
+
+        assert!(!RegistrationMode::Open.lets_invite(false));
+        assert!(RegistrationMode::InviteOnly.lets_invite(false));
+        assert!(!RegistrationMode::Closed.lets_invite(true));
+    }
+}
@@ -0,0 +1,144 @@
+//! Security response headers and per-request Content-Security-Policy nonces.
+//!
+//! Every response gets a CSP, `X-Frame-Options`, `Referrer-Policy`,
+//! `Permissions-Policy` and `X-Content-Type-Options`, plus HSTS when enabled.
+//! Templates read the current request's nonce with [`csp_nonce`] to mark
+//! inline `<script>`/`<style>` elements as trusted.
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue, header};
+use axum::middleware::{self, Next};
+use axum::response::Response;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+
+tokio::task_local! {
+    static CSP_NONCE: String;
+}
+
+/// Returns the CSP nonce for the request being handled, if any.
+///
+/// ```ignore
+/// html! { script nonce=[csp_nonce()] { (PreEscaped(INLINE_JS)) } }
+/// ```
+pub fn csp_nonce() -> Option<String> {
+    CSP_NONCE.try_with(Clone::clone).ok()
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersConfig {
+    /// Send `Content-Security-Policy-Report-Only` instead of enforcing, so
+    /// violations are reported without breaking pages during rollout.
+    pub csp_report_only: bool,
+    /// Where browsers should POST CSP violation reports.
+    pub csp_report_uri: Option<String>,
+    /// `Strict-Transport-Security` max-age in seconds; `None` omits HSTS.
+    pub hsts_max_age: Option<u64>,
+}
+
+impl SecurityHeadersConfig {
+    /// One year, the minimum accepted by browser HSTS preload lists.
+    const DEFAULT_HSTS_MAX_AGE: u64 = 31_536_000;
+
+    /// Reads `CSP_REPORT_ONLY`, `CSP_REPORT_URI` and `HSTS_MAX_AGE`.
+    ///
+    /// HSTS defaults on in release builds only, since local development
+    /// runs without https.
+    pub fn from_env() -> Self {
+        let default_hsts =
+            (!cfg!(debug_assertions)).then_some(Self::DEFAULT_HSTS_MAX_AGE);
+        Self {
+            csp_report_only: dotenvy::var("CSP_REPORT_ONLY")
+                .is_ok_and(|value| value == "true" || value == "1"),
+            csp_report_uri: dotenvy::var("CSP_REPORT_URI").ok(),
+            hsts_max_age: match dotenvy::var("HSTS_MAX_AGE") {
+                Ok(value) => value.parse().ok().filter(|&max_age| max_age > 0),
+                Err(_) => default_hsts,
+            },
+        }
+    }
+
+    /// Builds the policy for one response.
+    ///
+    /// HTMX is loaded from `'self'`; its indicator `<style>` is admitted
+    /// through the nonce (see `inlineStyleNonce` in the layout's
+    /// `htmx-config`).
+    pub fn content_security_policy(&self, nonce: &str) -> String {
+        let mut policy = format!(
+            "default-src 'self'; \
+             script-src 'self' 'nonce-{nonce}'; \
+             style-src 'self' 'nonce-{nonce}'; \
+             img-src 'self' data:; \
+             connect-src 'self'; \
+             object-src 'none'; \
+             base-uri 'self'; \
+             form-action 'self'; \
+             frame-ancestors 'none'"
+        );
+        if let Some(uri) = self.csp_report_uri.as_deref() {
+            policy.push_str("; report-uri ");
+            policy.push_str(uri);
+        }
+        policy
+    }
+}
+
+async fn set_security_headers(
+    State(config): State<Arc<SecurityHeadersConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let nonce = Uuid::new_v4().simple().to_string();
+    let policy = config.content_security_policy(&nonce);
+
+    let mut response = CSP_NONCE.scope(nonce, next.run(request)).await;
+    let headers = response.headers_mut();
+
+    let csp_header = if config.csp_report_only {
+        header::CONTENT_SECURITY_POLICY_REPORT_ONLY
+    } else {
+        header::CONTENT_SECURITY_POLICY
+    };
+    if let Ok(value) = HeaderValue::from_str(&policy) {
+        headers.insert(csp_header, value);
+    }
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("strict-origin-when-cross-origin"),
+    );
+    headers.insert(
+        HeaderName::from_static("permissions-policy"),
+        HeaderValue::from_static(
+            "camera=(), microphone=(), geolocation=(), payment=()",
+        ),
+    );
+    if let Some(max_age) = config.hsts_max_age
+        && let Ok(value) = HeaderValue::from_str(&format!(
+            "max-age={max_age}; includeSubDomains"
+        ))
+    {
+        headers.insert(header::STRICT_TRANSPORT_SECURITY, value);
+    }
+
+    response
+}
+
+/// Wraps a router so every response carries the security headers.
+pub fn with_security_headers(
+    router: Router<AppState>,
+    config: SecurityHeadersConfig,
+) -> Router<AppState> {
+    router.layer(middleware::from_fn_with_state(
+        Arc::new(config),
+        set_security_headers,
+    ))
+}
@@ -0,0 +1,40 @@
+//! Audit log retention.
+
+use std::time::Duration;
+
+use tokio::time;
+use tracing::warn;
+
+use super::job::{Job, JobSender};
+use crate::util::{MICROS_PER_DAY, current_time_micros};
+
+/// Days audit events are kept when `AUDIT_RETENTION_DAYS` is unset.
+pub const DEFAULT_RETENTION_DAYS: i64 = 365;
+
+const PRUNE_INTERVAL: Duration = Duration::from_hours(24);
+
+/// How long audit events are kept, in microseconds (`AUDIT_RETENTION_DAYS`).
+pub fn retention_micros() -> i64 {
+    let days = dotenvy::var("AUDIT_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+    days.saturating_mul(MICROS_PER_DAY)
+}
+
+/// Enqueues a [`Job::PruneAuditEvents`] once a day, starting immediately.
+///
+/// Spawn this in main.rs alongside the job processor.
+pub async fn schedule_pruning(job_tx: JobSender) {
+    let retention = retention_micros();
+    let mut interval = time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let before = current_time_micros().saturating_sub(retention);
+        if job_tx.send(Job::PruneAuditEvents { before }).is_err() {
+            warn!("Job processor stopped - no longer pruning audit events");
+            return;
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! In-process pub/sub for live updates: handlers publish HTML fragments to
+//! a user, and `/events` streams them to that user's open pages as
+//! Server-Sent Events for HTMX's SSE extension to swap in.
+//!
+//! Nothing is stored: pages that aren't connected when an event is
+//! published never see it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::debug;
+use uuid::Uuid;
+
+/// Events buffered per user; a page that falls further behind skips the
+/// oldest.
+const CAPACITY: usize = 64;
+
+/// Sent to a revoked session's pages, which then stop listening.
+pub const SIGNED_OUT: &str = "signed-out";
+
+/// A fragment for elements whose `sse-swap` (or `hx-trigger="sse:..."`)
+/// names this event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// The SSE event name.
+    pub name: String,
+    /// Swapped in as-is; empty for events that only trigger requests.
+    pub html: String,
+    /// Pages subscribed to another topic skip it; `None` reaches all.
+    pub topic: Option<&'static str>,
+    /// Only that session's pages receive it; `None` reaches all the
+    /// user's sessions.
+    pub session_id: Option<Uuid>,
+}
+
+type Channels = Mutex<HashMap<Uuid, broadcast::Sender<Arc<Event>>>>;
+
+/// One broadcast channel per user with open pages, created on first
+/// subscription and dropped with the last.
+#[derive(Debug, Clone, Default)]
+pub struct Broker {
+    channels: Arc<Channels>,
+}
+
+impl Broker {
+    fn lock(
+        channels: &Channels,
+    ) -> MutexGuard<'_, HashMap<Uuid, broadcast::Sender<Arc<Event>>>> {
+        channels.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Listens for the user's events on behalf of one page. With a
+    /// `topic`, events for other topics are skipped.
+    pub fn subscribe(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        topic: Option<String>,
+    ) -> Subscription {
+        let receiver = Self::lock(&self.channels)
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CAPACITY).0)
+            .subscribe();
+        Subscription {
+            receiver,
+            user_id,
+            session_id,
+            topic,
+            channels: Arc::clone(&self.channels),
+        }
+    }
+
+    /// Sends an event to the user's connected pages. Returns how many
+    /// pages were listening, before topic and session filtering.
+    pub fn publish(&self, user_id: Uuid, event: Event) -> usize {
+        let Some(sender) = Self::lock(&self.channels).get(&user_id).cloned()
+        else {
+            return 0;
+        };
+        sender.send(Arc::new(event)).unwrap_or(0)
+    }
+
+    /// How many pages the user has listening.
+    pub fn subscribers(&self, user_id: Uuid) -> usize {
+        Self::lock(&self.channels)
+            .get(&user_id)
+            .map_or(0, broadcast::Sender::receiver_count)
+    }
+}
+
+/// A page's share of a user's events.
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: broadcast::Receiver<Arc<Event>>,
+    user_id: Uuid,
+    session_id: Uuid,
+    topic: Option<String>,
+    channels: Arc<Channels>,
+}
+
+impl Subscription {
+    fn wants(&self, event: &Event) -> bool {
+        let topic_matches = match (event.topic, self.topic.as_deref()) {
+            (Some(wanted), Some(subscribed)) => wanted == subscribed,
+            _ => true,
+        };
+        topic_matches
+            && event.session_id.is_none_or(|only| only == self.session_id)
+    }
+
+    /// The next event for this page. Events missed by falling behind are
+    /// skipped rather than ending the stream.
+    pub async fn recv(&mut self) -> Option<Arc<Event>> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.wants(&event) => return Some(event),
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!(skipped, "Live update subscriber fell behind");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut channels = Broker::lock(&self.channels);
+        // This subscription's receiver is still counted until it's dropped
+        if channels
+            .get(&self.user_id)
+            .is_some_and(|sender| sender.receiver_count() <= 1)
+        {
+            channels.remove(&self.user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> Event {
+        Event {
+            name: name.to_owned(),
+            html: String::new(),
+            topic: None,
+            session_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn events_reach_only_the_users_matching_pages() {
+        let broker = Broker::default();
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let (laptop, phone) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut everything = broker.subscribe(alice, laptop, None);
+        let mut sessions_page =
+            broker.subscribe(alice, phone, Some("sessions".to_owned()));
+        let _bobs = broker.subscribe(bob, Uuid::new_v4(), None);
+
+        broker.publish(
+            alice,
+            Event {
+                topic: Some("profile"),
+                ..event("profile")
+            },
+        );
+        broker.publish(
+            alice,
+            Event {
+                session_id: Some(laptop),
+                ..event(SIGNED_OUT)
+            },
+        );
+        assert_eq!(broker.publish(alice, event("username")), 2);
+
+        assert_eq!(everything.recv().await.unwrap().name, "profile");
+        assert_eq!(everything.recv().await.unwrap().name, SIGNED_OUT);
+        assert_eq!(everything.recv().await.unwrap().name, "username");
+        assert_eq!(sessions_page.recv().await.unwrap().name, "username");
+    }
+
+    #[test]
+    fn channels_close_with_their_last_subscriber() {
+        let broker = Broker::default();
+        let user = Uuid::new_v4();
+        let first = broker.subscribe(user, Uuid::new_v4(), None);
+        let second = broker.subscribe(user, Uuid::new_v4(), None);
+        assert_eq!(broker.subscribers(user), 2);
+
+        drop(first);
+        assert_eq!(broker.subscribers(user), 1);
+        drop(second);
+        assert_eq!(broker.subscribers(user), 0);
+        assert_eq!(broker.publish(user, event("username")), 0);
+    }
+}
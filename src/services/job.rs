@@ -1,6 +1,8 @@
 use sqlx::SqlitePool;
 use tokio::sync::mpsc;
-use tracing::{info, warn};
+use tracing::{Instrument as _, Span, info, warn};
+
+use crate::models::audit_event::AuditEvent;
 
 /// Background jobs processed asynchronously.
 #[derive(Debug)]
@@ -11,25 +13,85 @@ pub enum Job {
         subject: String,
         body: String,
     },
+    /// Delete audit events created before `before` (microseconds).
+    PruneAuditEvents { before: i64 },
+}
+
+impl Job {
+    /// Short name used for the job's tracing span.
+    pub const fn kind(&self) -> &'static str {
+        match *self {
+            Self::SendEmail { .. } => "send_email",
+            Self::PruneAuditEvents { .. } => "prune_audit_events",
+        }
+    }
+}
+
+/// A queued job along with the span that enqueued it.
+#[derive(Debug)]
+pub struct Envelope {
+    pub job: Job,
+    pub origin: Span,
+}
+
+/// Sending half of the job channel.
+///
+/// Captures the caller's current span so the job's span can link back to
+/// the request that enqueued it.
+#[derive(Debug, Clone)]
+pub struct JobSender(mpsc::UnboundedSender<Envelope>);
+
+impl JobSender {
+    pub fn send(&self, job: Job) -> Result<(), mpsc::error::SendError<Job>> {
+        self.0
+            .send(Envelope {
+                job,
+                origin: Span::current(),
+            })
+            .map_err(|err| mpsc::error::SendError(err.0.job))
+    }
+
+    /// Returns `true` once the job processor has stopped receiving.
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+/// Creates the job channel.
+pub fn channel() -> (JobSender, mpsc::UnboundedReceiver<Envelope>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (JobSender(tx), rx)
 }
 
 /// Runs the job processor, receiving jobs from the channel.
 ///
 /// Spawn this in main.rs:
 /// ```ignore
-/// let (job_tx, job_rx) = mpsc::unbounded_channel();
+/// let (job_tx, job_rx) = services::job::channel();
 /// tokio::spawn(services::job::run(pool.clone(), job_rx));
 /// ```
-pub async fn run(_pool: SqlitePool, mut rx: mpsc::UnboundedReceiver<Job>) {
+pub async fn run(pool: SqlitePool, mut rx: mpsc::UnboundedReceiver<Envelope>) {
     info!("Job processor started");
 
-    while let Some(job) = rx.recv().await {
-        match job {
-            Job::SendEmail { to, subject, body } => {
-                info!(?to, ?subject, ?body, "Sending email (simulated)");
-            }
-        }
+    while let Some(Envelope { job, origin }) = rx.recv().await {
+        let span = tracing::info_span!("job", kind = job.kind());
+        span.follows_from(&origin);
+        process(&pool, job).instrument(span).await;
     }
 
     warn!("Job processor shutting down - channel closed");
 }
+
+async fn process(pool: &SqlitePool, job: Job) {
+    match job {
+        Job::SendEmail { to, subject, body } => {
+            info!(?to, ?subject, ?body, "Sending email (simulated)");
+        }
+        Job::PruneAuditEvents { before } => {
+            match AuditEvent::delete_before(pool, before).await {
+                Ok(deleted) => info!(deleted, "Pruned audit events"),
+                Err(err) => warn!("Failed to prune audit events: {err}"),
+            }
+        }
+    }
+}
@@ -1,3 +1,6 @@
+pub mod audit;
+pub mod events;
 pub mod job;
+pub mod notification;
 
 pub use job::Job;
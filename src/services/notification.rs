@@ -0,0 +1,107 @@
+//! Notifying users in the app and by email, as each prefers.
+
+use sqlx::SqlitePool;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::events::{Broker, Event};
+use super::job::{Job, JobSender};
+use crate::models::notification::{Notification, NotificationKind};
+use crate::models::notification_preference::NotificationPreference;
+use crate::models::user::User;
+use crate::util::{base_url, current_time_micros};
+
+/// Published to the user's open pages when a notification is stored, so
+/// the navbar bell refreshes.
+pub const EVENT: &str = "notifications";
+
+/// What to tell the user.
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub kind: NotificationKind,
+    /// Also the email's subject.
+    pub title: String,
+    /// Plain text; blank lines separate paragraphs.
+    pub body: String,
+    /// A path on this site, linked in the app and in full in the email.
+    pub link: Option<String>,
+}
+
+/// Notifies a user through the channels they've enabled for the notice's
+/// kind: stored for the notifications page, emailed if they have an
+/// address, or both.
+///
+/// Failures are logged rather than returned, so a lost notification never
+/// fails the action it's about.
+pub async fn notify(
+    db: &SqlitePool,
+    job_tx: &JobSender,
+    events: &Broker,
+    user_id: Uuid,
+    notice: &Notice,
+) {
+    if let Err(err) = deliver(db, job_tx, events, user_id, notice).await {
+        warn!(kind = notice.kind.as_str(), "Failed to notify user: {err}");
+    }
+}
+
+async fn deliver(
+    db: &SqlitePool,
+    job_tx: &JobSender,
+    events: &Broker,
+    user_id: Uuid,
+    notice: &Notice,
+) -> Result<(), sqlx::Error> {
+    let user = User::get_by_id(db, user_id).await?;
+    let preference =
+        NotificationPreference::get(db, user_id, notice.kind).await?;
+
+    if preference.in_app {
+        let notification = Notification {
+            id: Uuid::new_v4(),
+            user_id,
+            kind: notice.kind,
+            title: notice.title.clone(),
+            body: notice.body.clone(),
+            link: notice.link.clone(),
+            read_at: None,
+            created_at: current_time_micros(),
+        };
+        Notification::insert(db, &notification).await?;
+        events.publish(
+            user_id,
+            Event {
+                name: EVENT.to_owned(),
+                html: String::new(),
+                topic: None,
+                session_id: None,
+            },
+        );
+    }
+
+    if preference.email
+        && let Some(email) = user.email
+    {
+        let job = Job::SendEmail {
+            to: email,
+            subject: notice.title.clone(),
+            body: email_body(&user.username, notice),
+        };
+        if let Err(err) = job_tx.send(job) {
+            warn!(
+                kind = notice.kind.as_str(),
+                "Failed to enqueue notification email: {err}"
+            );
+        }
+    }
+    Ok(())
+}
+
+fn email_body(username: &str, notice: &Notice) -> String {
+    let link = notice
+        .link
+        .as_ref()
+        .map(|path| format!("\n{}{path}\n", base_url()))
+        .unwrap_or_default();
+    format!("Hi {username},\n\n{}\n{link}", notice.body)
+}
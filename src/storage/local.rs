@@ -0,0 +1,71 @@
+//! Files under a directory on the local filesystem.
+
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+use tokio::fs;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{Storage, StorageError, check_key};
+
+/// Stores each object as a file at `root/key`.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// `root` is created on first write.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(
+        &self,
+        key: &str,
+        _content_type: &str,
+        data: Bytes,
+    ) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // Write beside the target and rename, so readers never see a
+        // partial file
+        let partial =
+            path.with_file_name(format!(".{}.partial", Uuid::new_v4()));
+        fs::write(&partial, &data).await?;
+        if let Err(err) = fs::rename(&partial, &path).await {
+            if let Err(cleanup_err) = fs::remove_file(&partial).await {
+                warn!("Failed to remove partial upload: {cleanup_err}");
+            }
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StorageError> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
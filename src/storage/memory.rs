@@ -0,0 +1,51 @@
+//! Objects held in memory, for tests and throwaway instances.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+
+use super::{SharedStorage, Storage, StorageError, check_key};
+
+/// Keeps objects in a map; everything is lost when the process exits.
+#[derive(Default)]
+pub struct MemoryStorage {
+    objects: Mutex<HashMap<String, Bytes>>,
+}
+
+impl MemoryStorage {
+    /// An empty store, ready to put in `AppState`.
+    pub fn shared() -> SharedStorage {
+        Arc::new(Self::default())
+    }
+
+    fn objects(&self) -> MutexGuard<'_, HashMap<String, Bytes>> {
+        self.objects.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn put(
+        &self,
+        key: &str,
+        _content_type: &str,
+        data: Bytes,
+    ) -> Result<(), StorageError> {
+        check_key(key)?;
+        self.objects().insert(key.to_owned(), data);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StorageError> {
+        check_key(key)?;
+        Ok(self.objects().get(key).cloned())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        check_key(key)?;
+        self.objects().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Pluggable storage for uploaded files.
+//!
+//! Handlers reach a [`Storage`] through `AppState`, so the same code runs
+//! against the local filesystem in development and an S3-compatible bucket
+//! in production. [`from_env`] picks the backend from `STORAGE_BACKEND`.
+//! File metadata (owner, name, type) lives in the database; backends only
+//! map keys to bytes.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::body::Bytes;
+
+pub mod local;
+pub mod memory;
+pub mod s3;
+
+/// Where uploaded bytes are kept.
+///
+/// Keys are relative, `/`-separated paths of ASCII letters, digits, `-`,
+/// `_` and `.`; see [`check_key`].
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Stores `data` under `key`, replacing any existing object.
+    async fn put(
+        &self,
+        key: &str,
+        content_type: &str,
+        data: Bytes,
+    ) -> Result<(), StorageError>;
+
+    /// The object stored under `key`, or `None` if there isn't one.
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StorageError>;
+
+    /// Removes the object under `key`. Missing objects are not an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// The storage handle kept in `AppState`.
+pub type SharedStorage = Arc<dyn Storage>;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// The key isn't a safe relative path.
+    InvalidKey(String),
+    /// A required setting is missing or malformed.
+    Config(String),
+    Io(io::Error),
+    Http(reqwest::Error),
+    /// The object store answered with an unexpected status.
+    Status(u16),
+}
+
+impl fmt::Display for StorageError {
+    #[allow(
+        clippy::allow_attributes,
+        clippy::pattern_type_mismatch,
+        reason = "Payloads are borrowed from the error"
+    )]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKey(key) => {
+                write!(f, "invalid storage key '{key}'")
+            }
+            Self::Config(message) => write!(f, "storage config: {message}"),
+            Self::Io(err) => write!(f, "storage I/O: {err}"),
+            Self::Http(err) => write!(f, "object store request: {err}"),
+            Self::Status(status) => {
+                write!(f, "object store returned status {status}")
+            }
+        }
+    }
+}
+
+impl error::Error for StorageError {}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Http(err)
+    }
+}
+
+/// Rejects keys that could escape the storage root or need escaping:
+/// empty keys, absolute paths, `.`/`..` segments and anything beyond
+/// `[A-Za-z0-9._-]` between slashes.
+pub fn check_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| {
+                    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+                })
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_owned()))
+    }
+}
+
+/// The backend named by `STORAGE_BACKEND`: `local` (the default, under
+/// `UPLOAD_DIR`) or `s3` (see [`s3::S3Config::from_env`]).
+pub fn from_env() -> Result<SharedStorage, StorageError> {
+    let backend =
+        dotenvy::var("STORAGE_BACKEND").unwrap_or_else(|_| "local".to_owned());
+    match backend.as_str() {
+        "local" => {
+            let root = dotenvy::var("UPLOAD_DIR")
+                .unwrap_or_else(|_| "uploads".to_owned());
+            Ok(Arc::new(local::LocalStorage::new(root)))
+        }
+        "s3" => Ok(Arc::new(s3::S3Storage::new(s3::S3Config::from_env()?)?)),
+        other => Err(StorageError::Config(format!(
+            "unknown STORAGE_BACKEND {other:?}, expected \"local\" or \"s3\""
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_must_be_safe_relative_paths() {
+        for key in ["uploads/abc/def.png", "a", "a-b_c.d"] {
+            check_key(key).expect(key);
+        }
+        for key in ["", "/etc/passwd", "a/../b", "a//b", "a/./b", "a b", "a\\b"]
+        {
+            check_key(key).expect_err(key);
+        }
+    }
+}
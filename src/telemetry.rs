@@ -0,0 +1,119 @@
+//! Request tracing and optional OpenTelemetry export.
+//!
+//! Spans are always logged locally. Setting `OTEL_EXPORTER_OTLP_ENDPOINT`
+//! (e.g. `http://localhost:4318`) additionally exports them over OTLP/HTTP.
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderName, Request};
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tower_http::request_id::{
+    MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer,
+};
+use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
+use tracing::{Level, Span, debug, field};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::app_state::AppState;
+
+/// Name reported as `service.name` on exported spans.
+pub const SERVICE_NAME: &str = "basic_site";
+
+/// Builds an OTLP tracer provider if `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+///
+/// Also installs the W3C `traceparent` propagator globally so incoming
+/// requests can continue a trace started by an upstream service.
+pub fn tracer_provider() -> Option<SdkTracerProvider> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let endpoint = dotenvy::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()?;
+    let traces_url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let exporter = match SpanExporter::builder()
+        .with_http()
+        .with_endpoint(traces_url)
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(err) => {
+            // Logging isn't initialised yet, so report directly.
+            eprintln!("Failed to build OTLP exporter, export disabled: {err}");
+            return None;
+        }
+    };
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder().with_service_name(SERVICE_NAME).build(),
+        )
+        .build();
+    Some(provider)
+}
+
+/// Adapts request headers for the OpenTelemetry propagator.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.to_str().ok()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+/// Creates the span for an incoming request.
+///
+/// The request ID comes from the `X-Request-Id` header, which
+/// [`with_request_tracing`] fills in when the client didn't send one.
+/// A valid `traceparent` header makes the span a child of the remote trace.
+pub fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        %request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+        session_id = field::Empty,
+        user_id = field::Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    // Fails when no OpenTelemetry layer is installed, in which case there is
+    // nothing to attach the remote parent to.
+    if let Err(err) = span.set_parent(parent) {
+        debug!("Remote trace context not attached: {err}");
+    }
+    span
+}
+
+/// Wraps a router with request IDs and per-request tracing spans.
+///
+/// An incoming `X-Request-Id` is kept, otherwise a UUID is generated; either
+/// way it is echoed back on the response.
+pub fn with_request_tracing(router: Router<AppState>) -> Router<AppState> {
+    let trace_layer = TraceLayer::new_for_http()
+        .make_span_with(request_span)
+        .on_request(DefaultOnRequest::new().level(Level::DEBUG))
+        .on_response(DefaultOnResponse::new().level(Level::INFO));
+
+    // Layers added last run first: assign the ID, open the span, then copy
+    // the ID onto the response.
+    router
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(trace_layer)
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
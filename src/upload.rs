@@ -0,0 +1,243 @@
+//! Upload limits, filename handling and signed download URLs.
+//!
+//! Download links are capabilities: `/uploads/{id}?expires=..&signature=..`
+//! carries an HMAC over the ID and expiry, so a link can be handed to
+//! someone without an account and stops working once it expires.
+
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Largest accepted file when `UPLOAD_MAX_BYTES` is unset.
+pub const DEFAULT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// Accepted content types when `UPLOAD_ALLOWED_TYPES` is unset.
+pub const DEFAULT_ALLOWED_TYPES: [&str; 6] = [
+    "application/pdf",
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "text/plain",
+];
+
+/// Lifetime of download links when `UPLOAD_URL_TTL_SECS` is unset.
+pub const DEFAULT_URL_TTL_SECS: i64 = 15 * 60;
+
+/// Longest stored filename, in characters.
+const MAX_FILENAME_CHARS: usize = 255;
+
+static CONFIG: OnceLock<UploadConfig> = OnceLock::new();
+
+#[derive(Clone)]
+pub struct UploadConfig {
+    max_bytes: usize,
+    /// Lowercase MIME types, without parameters.
+    allowed_types: Vec<String>,
+    /// How long a signed download URL stays valid, in microseconds.
+    url_ttl_micros: i64,
+    signing_key: Vec<u8>,
+}
+
+impl Default for UploadConfig {
+    /// Default limits with a random signing key, so links don't survive a
+    /// restart.
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            allowed_types: DEFAULT_ALLOWED_TYPES
+                .iter()
+                .map(|&mime| mime.to_owned())
+                .collect(),
+            url_ttl_micros: DEFAULT_URL_TTL_SECS.saturating_mul(1_000_000),
+            signing_key: [Uuid::new_v4(), Uuid::new_v4()]
+                .iter()
+                .flat_map(|random| random.into_bytes())
+                .collect(),
+        }
+    }
+}
+
+impl UploadConfig {
+    /// Reads `UPLOAD_MAX_BYTES`, `UPLOAD_ALLOWED_TYPES` (comma-separated),
+    /// `UPLOAD_URL_TTL_SECS` and `UPLOAD_URL_SECRET`.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let max_bytes = match dotenvy::var("UPLOAD_MAX_BYTES") {
+            Ok(value) => match value.parse::<usize>() {
+                Ok(limit) if limit > 0 => limit,
+                _ => return Err(format!("Invalid UPLOAD_MAX_BYTES {value:?}")),
+            },
+            Err(_) => defaults.max_bytes,
+        };
+        let allowed_types = match dotenvy::var("UPLOAD_ALLOWED_TYPES") {
+            Ok(value) => value
+                .split(',')
+                .map(|mime| mime.trim().to_ascii_lowercase())
+                .filter(|mime| !mime.is_empty())
+                .collect(),
+            Err(_) => defaults.allowed_types,
+        };
+        let url_ttl_micros = match dotenvy::var("UPLOAD_URL_TTL_SECS") {
+            Ok(value) => match value.parse::<i64>() {
+                Ok(secs) if secs > 0 => secs.saturating_mul(1_000_000),
+                _ => {
+                    return Err(format!(
+                        "Invalid UPLOAD_URL_TTL_SECS {value:?}"
+                    ));
+                }
+            },
+            Err(_) => defaults.url_ttl_micros,
+        };
+        let signing_key = match dotenvy::var("UPLOAD_URL_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                warn!(
+                    "UPLOAD_URL_SECRET is not set; download links won't survive a restart"
+                );
+                defaults.signing_key
+            }
+        };
+        Ok(Self {
+            max_bytes,
+            allowed_types,
+            url_ttl_micros,
+            signing_key,
+        })
+    }
+
+    /// Largest accepted file, in bytes.
+    pub const fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Accepted MIME types, lowercase and without parameters.
+    pub fn allowed_types(&self) -> &[String] {
+        &self.allowed_types
+    }
+
+    /// Whether files declared as `content_type` may be uploaded. Parameters
+    /// such as `; charset=utf-8` are ignored.
+    pub fn allows(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.allowed_types.contains(&essence)
+    }
+
+    fn mac(&self, upload_id: Uuid, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.signing_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{upload_id}:{expires}").as_bytes());
+        mac
+    }
+
+    /// A download link for the upload, valid until `now` plus the TTL.
+    pub fn signed_url(&self, upload_id: Uuid, now: i64) -> String {
+        let expires = now.saturating_add(self.url_ttl_micros);
+        let signature =
+            hex::encode(self.mac(upload_id, expires).finalize().into_bytes());
+        format!("/uploads/{upload_id}?expires={expires}&signature={signature}")
+    }
+
+    /// Whether a link's `expires` and `signature` are genuine and current.
+    pub fn verify(
+        &self,
+        upload_id: Uuid,
+        expires: i64,
+        signature: &str,
+        now: i64,
+    ) -> bool {
+        let Ok(bytes) = hex::decode(signature) else {
+            return false;
+        };
+        expires > now
+            && self.mac(upload_id, expires).verify_slice(&bytes).is_ok()
+    }
+}
+
+pub fn init(config: UploadConfig) {
+    if CONFIG.set(config).is_err() {
+        warn!("Upload config already initialised");
+    }
+}
+
+/// The installed configuration, or the defaults if [`init`] wasn't called.
+pub fn config() -> &'static UploadConfig {
+    CONFIG.get_or_init(UploadConfig::default)
+}
+
+/// Where an upload's bytes are stored.
+pub fn storage_key(user_id: Uuid, upload_id: Uuid) -> String {
+    format!("uploads/{user_id}/{upload_id}")
+}
+
+/// The last path component of a browser-supplied filename, without
+/// control characters and quotes, at most 255 characters. Never empty.
+pub fn sanitize_filename(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+    let trimmed = cleaned.trim();
+    if trimmed.is_empty() || trimmed == "." || trimmed == ".." {
+        "file".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_urls_expire_and_resist_tampering() {
+        let config = UploadConfig::default();
+        let id = Uuid::new_v4();
+        let url = config.signed_url(id, 1_000);
+        let query = url.split_once('?').expect("has query").1;
+        let (raw_expires, signature) = query
+            .strip_prefix("expires=")
+            .and_then(|rest| rest.split_once("&signature="))
+            .expect("expires and signature");
+        let expires: i64 = raw_expires.parse().expect("numeric expiry");
+
+        assert!(config.verify(id, expires, signature, 1_000));
+        assert!(!config.verify(id, expires, signature, expires));
+        assert!(!config.verify(id, expires + 1, signature, 1_000));
+        assert!(!config.verify(Uuid::new_v4(), expires, signature, 1_000));
+        assert!(!config.verify(id, expires, "not-hex", 1_000));
+        assert!(
+            !UploadConfig::default().verify(id, expires, signature, 1_000),
+            "another key must not accept the signature"
+        );
+    }
+
+    #[test]
+    fn content_types_ignore_parameters_and_case() {
+        let config = UploadConfig::default();
+        assert!(config.allows("text/plain; charset=utf-8"));
+        assert!(config.allows("IMAGE/PNG"));
+        assert!(!config.allows("text/html"));
+        assert!(!config.allows("image/svg+xml"));
+    }
+
+    #[test]
+    fn filenames_are_sanitised() {
+        assert_eq!(sanitize_filename("report.pdf"), "report.pdf");
+        assert_eq!(sanitize_filename("C:\\Users\\jane\\cv.pdf"), "cv.pdf");
+        assert_eq!(sanitize_filename("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_filename("a\"b\nc.txt"), "abc.txt");
+        assert_eq!(sanitize_filename(""), "file");
+        assert_eq!(sanitize_filename(".."), "file");
+    }
+}
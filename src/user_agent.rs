@@ -0,0 +1,80 @@
+//! Human-readable device labels parsed from `User-Agent` strings.
+
+use woothee::parser::Parser;
+
+/// Browser, operating system and device class of a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub browser: String,
+    pub os: String,
+    pub kind: &'static str,
+}
+
+impl Device {
+    /// Parses a raw `User-Agent` header. Fields woothee can't identify
+    /// fall back to generic labels rather than failing.
+    pub fn parse(user_agent: &str) -> Self {
+        let Some(result) = Parser::new().parse(user_agent) else {
+            return Self {
+                browser: "Unknown browser".to_owned(),
+                os: "unknown OS".to_owned(),
+                kind: "Unknown",
+            };
+        };
+
+        let known = |value: &str, fallback: &str| {
+            if value.is_empty() || value == "UNKNOWN" {
+                fallback.to_owned()
+            } else {
+                value.to_owned()
+            }
+        };
+
+        Self {
+            browser: known(result.name, "Unknown browser"),
+            os: known(result.os, "unknown OS"),
+            kind: match result.category {
+                "pc" => "Desktop",
+                "smartphone" | "mobilephone" => "Mobile",
+                "appliance" => "Appliance",
+                "crawler" => "Bot",
+                _ => "Unknown",
+            },
+        }
+    }
+
+    /// Short label such as "Firefox on Linux".
+    pub fn label(&self) -> String {
+        format!("{} on {}", self.browser, self.os)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_desktop_firefox() {
+        let device = Device::parse(
+            "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+        );
+        assert_eq!(device.label(), "Firefox on Linux");
+        assert_eq!(device.kind, "Desktop");
+    }
+
+    #[test]
+    fn parses_mobile_safari() {
+        let device = Device::parse(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+        );
+        assert_eq!(device.label(), "Safari on iPhone");
+        assert_eq!(device.kind, "Mobile");
+    }
+
+    #[test]
+    fn unknown_agent_falls_back() {
+        let device = Device::parse("TestAgent/1.0");
+        assert_eq!(device.label(), "Unknown browser on unknown OS");
+        assert_eq!(device.kind, "Unknown");
+    }
+}
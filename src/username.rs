@@ -0,0 +1,268 @@
+//! Username canonicalisation, confusable detection and reserved names.
+//!
+//! A username is displayed as typed but compared in two folded forms:
+//! the canonical form (NFKC, lowercase) decides who is "the same user" for
+//! login and profile URLs, and the skeleton additionally folds characters
+//! that look alike (Cyrillic "а" and Latin "a", "l" and "1", "rn" and "m")
+//! so nobody can register a lookalike of an existing name.
+
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use unicode_normalization::UnicodeNormalization as _;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::util::days_from_env;
+
+/// Longest username, in characters.
+pub const MAX_LENGTH: usize = 20;
+
+/// Days between username changes when `USERNAME_CHANGE_COOLDOWN_DAYS` is
+/// unset.
+pub const DEFAULT_CHANGE_COOLDOWN_DAYS: i64 = 30;
+
+/// Days a released username stays reserved for its previous owner when
+/// `USERNAME_HOLD_DAYS` is unset.
+pub const DEFAULT_HOLD_DAYS: i64 = 90;
+
+/// Names that collide with routes or imply authority. Compared by
+/// canonical form and by skeleton, so "ADMIN" and "r00t" are reserved too.
+pub const RESERVED: &[&str] = &[
+    "about",
+    "admin",
+    "administrator",
+    "api",
+    "assets",
+    "healthz",
+    "help",
+    "login",
+    "logout",
+    "moderator",
+    "null",
+    "official",
+    "password",
+    "readyz",
+    "root",
+    "security",
+    "session",
+    "sessions",
+    "settings",
+    "signup",
+    "staff",
+    "static",
+    "support",
+    "system",
+    "undefined",
+    "user",
+    "users",
+];
+
+/// Characters folded to the Latin letter they're mistaken for, applied
+/// before lowercasing so capital "I" can map to "l". A small hand-picked
+/// subset of Unicode's confusables data covering Latin lookalikes in
+/// Cyrillic and Greek, plus digits.
+const CONFUSABLES: &[(char, char)] = &[
+    ('0', 'o'),
+    ('1', 'l'),
+    ('I', 'l'),
+    // Cyrillic capitals
+    ('\u{405}', 's'),
+    ('\u{406}', 'l'),
+    ('\u{408}', 'j'),
+    ('\u{410}', 'a'),
+    ('\u{412}', 'b'),
+    ('\u{415}', 'e'),
+    ('\u{41a}', 'k'),
+    ('\u{41c}', 'm'),
+    ('\u{41d}', 'h'),
+    ('\u{41e}', 'o'),
+    ('\u{420}', 'p'),
+    ('\u{421}', 'c'),
+    ('\u{422}', 't'),
+    ('\u{423}', 'y'),
+    ('\u{425}', 'x'),
+    // Cyrillic small letters
+    ('\u{430}', 'a'),
+    ('\u{435}', 'e'),
+    ('\u{43e}', 'o'),
+    ('\u{440}', 'p'),
+    ('\u{441}', 'c'),
+    ('\u{443}', 'y'),
+    ('\u{445}', 'x'),
+    ('\u{455}', 's'),
+    ('\u{456}', 'i'),
+    ('\u{458}', 'j'),
+    ('\u{4bb}', 'h'),
+    ('\u{4cf}', 'l'),
+    ('\u{501}', 'd'),
+    ('\u{51b}', 'q'),
+    ('\u{51d}', 'w'),
+    // Greek capitals
+    ('\u{391}', 'a'),
+    ('\u{392}', 'b'),
+    ('\u{395}', 'e'),
+    ('\u{396}', 'z'),
+    ('\u{397}', 'h'),
+    ('\u{399}', 'l'),
+    ('\u{39a}', 'k'),
+    ('\u{39c}', 'm'),
+    ('\u{39d}', 'n'),
+    ('\u{39f}', 'o'),
+    ('\u{3a1}', 'p'),
+    ('\u{3a4}', 't'),
+    ('\u{3a5}', 'y'),
+    ('\u{3a7}', 'x'),
+    // Greek small letters
+    ('\u{3b1}', 'a'),
+    ('\u{3b9}', 'i'),
+    ('\u{3ba}', 'k'),
+    ('\u{3bd}', 'v'),
+    ('\u{3bf}', 'o'),
+    ('\u{3c1}', 'p'),
+    ('\u{3c5}', 'u'),
+];
+
+/// Letter sequences that render like a single letter.
+const CONFUSABLE_SEQUENCES: [(&str, &str); 2] = [("rn", "m"), ("vv", "w")];
+
+/// The form usernames are looked up by: NFKC-normalised and lowercased.
+pub fn canonical(username: &str) -> String {
+    username.nfkc().collect::<String>().to_lowercase()
+}
+
+/// `typed` cut to [`MAX_LENGTH`] characters, for recording a login attempt
+/// without storing whatever length was sent.
+pub fn truncated(typed: &str) -> &str {
+    typed
+        .char_indices()
+        .nth(MAX_LENGTH)
+        .and_then(|(end, _)| typed.get(..end))
+        .unwrap_or(typed)
+}
+
+/// The visual skeleton: accents stripped, lookalike characters folded to
+/// Latin and lowercased.
+///
+/// Two usernames with the same skeleton are confusable. Capitals are
+/// folded before lowercasing ("I" becomes "l"), so case-insensitive
+/// equality is the canonical form's job, not this one's.
+pub fn skeleton(username: &str) -> String {
+    let folded: String = username
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(|c| {
+            let mapped = CONFUSABLES
+                .iter()
+                .find(|&&(from, _)| from == c)
+                .map_or(c, |&(_, to)| to);
+            mapped.to_lowercase()
+        })
+        .collect();
+    CONFUSABLE_SEQUENCES
+        .iter()
+        .fold(folded, |acc, &(from, to)| acc.replace(from, to))
+}
+
+/// Whether the username is, or looks like, a reserved name.
+pub fn is_reserved(username: &str) -> bool {
+    let folded = canonical(username);
+    let target = skeleton(username);
+    RESERVED
+        .iter()
+        .any(|name| *name == folded || skeleton(name) == target)
+}
+
+/// Minimum time between a user's username changes, in microseconds
+/// (`USERNAME_CHANGE_COOLDOWN_DAYS`; 0 disables the cooldown).
+pub fn change_cooldown_micros() -> i64 {
+    days_from_env(
+        "USERNAME_CHANGE_COOLDOWN_DAYS",
+        DEFAULT_CHANGE_COOLDOWN_DAYS,
+    )
+}
+
+/// How long a released username (and its lookalikes) can't be claimed by
+/// anyone but its previous owner, in microseconds (`USERNAME_HOLD_DAYS`).
+pub fn hold_micros() -> i64 {
+    days_from_env("USERNAME_HOLD_DAYS", DEFAULT_HOLD_DAYS)
+}
+
+/// `/users/{username}`, percent-encoded so it's safe in a `Location` header.
+pub fn profile_path(username: &str) -> String {
+    format!("/users/{}", utf8_percent_encode(username, NON_ALPHANUMERIC))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+}
+
+fn script(c: char) -> Option<Script> {
+    match u32::from(c) {
+        0x41..=0x5a | 0x61..=0x7a | 0xc0..=0x24f | 0x1e00..=0x1eff => {
+            Some(Script::Latin)
+        }
+        0x370..=0x3ff | 0x1f00..=0x1fff => Some(Script::Greek),
+        0x400..=0x52f => Some(Script::Cyrillic),
+        _ => None,
+    }
+}
+
+/// Whether the username mixes Latin, Greek and Cyrillic letters, the
+/// usual recipe for a homoglyph. Digits and other scripts don't count.
+pub fn is_mixed_script(username: &str) -> bool {
+    let mut scripts = username.chars().filter_map(script);
+    scripts
+        .next()
+        .is_some_and(|first| scripts.any(|other| other != first))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_folds_case_and_width() {
+        assert_eq!(canonical("JaneDoe"), "janedoe");
+        // Full-width "ＪＡＮＥ"
+        assert_eq!(canonical("\u{ff2a}\u{ff21}\u{ff2e}\u{ff25}"), "jane");
+    }
+
+    #[test]
+    fn skeleton_folds_lookalikes() {
+        assert_eq!(skeleton("paypal"), skeleton("PAYPAI"));
+        assert_eq!(skeleton("root"), skeleton("r00t"));
+        assert_eq!(skeleton("modern"), skeleton("modem"));
+        // Cyrillic "а" and "о"
+        assert_eq!(skeleton("\u{430}pple"), skeleton("apple"));
+        assert_eq!(skeleton("g\u{43e}\u{43e}gle"), "google");
+        // Accents
+        assert_eq!(skeleton("jos\u{e9}"), "jose");
+        assert_ne!(skeleton("janedoe"), skeleton("johndoe"));
+    }
+
+    #[test]
+    fn reserved_names_include_lookalikes() {
+        assert!(is_reserved("admin"));
+        assert!(is_reserved("Settings"));
+        assert!(is_reserved("ADMIN"));
+        assert!(is_reserved("r00t"));
+        assert!(is_reserved("\u{430}dmin"));
+        assert!(!is_reserved("janedoe"));
+    }
+
+    #[test]
+    fn profile_path_is_percent_encoded() {
+        assert_eq!(profile_path("janedoe"), "/users/janedoe");
+        assert_eq!(profile_path("jos\u{e9}"), "/users/jos%C3%A9");
+    }
+
+    #[test]
+    fn mixed_scripts_are_detected() {
+        assert!(is_mixed_script("p\u{430}ypal"));
+        assert!(is_mixed_script("\u{3b1}lpha"));
+        assert!(!is_mixed_script("janedoe42"));
+        assert!(!is_mixed_script("\u{434}\u{436}\u{43e}\u{43d}"));
+        assert!(!is_mixed_script("\u{65e5}\u{672c}user"));
+    }
+}
@@ -1,5 +1,7 @@
 use std::time;
 
+use ::time::OffsetDateTime;
+
 /// Returns the current time in microseconds.
 pub fn current_time_micros() -> i64 {
     let micros = time::SystemTime::now()
@@ -8,3 +10,38 @@ pub fn current_time_micros() -> i64 {
         .as_micros();
     i64::try_from(micros).unwrap()
 }
+
+pub const MICROS_PER_DAY: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// Reads a whole number of days from the environment variable `name`,
+/// returned in microseconds. Zero is allowed; unset, unparseable or
+/// negative values fall back to `default_days`.
+pub fn days_from_env(name: &str, default_days: i64) -> i64 {
+    dotenvy::var(name)
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(default_days)
+        .saturating_mul(MICROS_PER_DAY)
+}
+
+/// Whether outgoing requests can use `https://`: the `tls` feature, on by
+/// default.
+pub const TLS_SUPPORTED: bool = cfg!(feature = "tls");
+
+/// Public origin of the site, used for links in emails (`BASE_URL`).
+pub fn base_url() -> String {
+    dotenvy::var("BASE_URL")
+        .unwrap_or_else(|_| "http://localhost:3000".to_owned())
+}
+
+/// Formats a microsecond timestamp as e.g. `2026-10-19 14:05 UTC`.
+pub fn format_utc(timestamp_micros: i64) -> String {
+    let nanos = i128::from(timestamp_micros).saturating_mul(1_000);
+    match OffsetDateTime::from_unix_timestamp_nanos(nanos) {
+        Ok(dt) => {
+            format!("{} {:02}:{:02} UTC", dt.date(), dt.hour(), dt.minute())
+        }
+        Err(_) => "unknown time".to_owned(),
+    }
+}
@@ -0,0 +1,285 @@
+//! Typed validation for form payloads.
+//!
+//! Payloads implement [`Validate`], collecting problems into a
+//! [`FieldErrors`] map keyed by form field name. Form components render
+//! whatever the map holds next to the matching input, so handlers never
+//! pass individual message strings around.
+
+use std::collections::BTreeMap;
+
+use reqwest::Url;
+
+use crate::username::{MAX_LENGTH, is_mixed_script, is_reserved};
+
+/// Validation problems, at most one message per form field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldErrors {
+    errors: BTreeMap<&'static str, String>,
+}
+
+impl FieldErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A map holding a single error, for failures found after validation
+    /// (a taken username, a wrong current password).
+    pub fn single<M: Into<String>>(field: &'static str, message: M) -> Self {
+        let mut errors = Self::new();
+        errors.add(field, message);
+        errors
+    }
+
+    /// Records an error for `field`, unless it already has one; the first
+    /// rule to fail is the one worth showing.
+    pub fn add<M: Into<String>>(&mut self, field: &'static str, message: M) {
+        self.errors.entry(field).or_insert_with(|| message.into());
+    }
+
+    /// Records the error from a rule's result, if it failed.
+    pub fn check(&mut self, field: &'static str, result: Result<(), String>) {
+        if let Err(message) = result {
+            self.add(field, message);
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.errors.get(field).map(String::as_str)
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        self.errors.contains_key(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Iterates over `(field, message)` pairs in field name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.errors
+            .iter()
+            .map(|(field, message)| (*field, message.as_str()))
+    }
+
+    /// `Ok` if nothing was recorded.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+/// A payload that can check its own fields.
+pub trait Validate {
+    /// What validation needs beyond the payload itself, such as the
+    /// signed-in user. `()` for payloads that stand alone.
+    type Context: ?Sized;
+
+    fn validate(&self, context: &Self::Context) -> Result<(), FieldErrors>;
+}
+
+/// 5-20 letters or digits from a single alphabet, and not a reserved name.
+/// Uniqueness (including lookalikes) is enforced by the database.
+pub fn username(value: &str) -> Result<(), String> {
+    let length = value.chars().count();
+    if !(5..=MAX_LENGTH).contains(&length)
+        || !value.chars().all(char::is_alphanumeric)
+    {
+        Err(String::from(
+            "Username must be between 5 and 20 characters, and only contain letters / numbers.",
+        ))
+    } else if is_mixed_script(value) {
+        Err(String::from(
+            "Username must not mix letters from different alphabets.",
+        ))
+    } else if is_reserved(value) {
+        Err(String::from("That username is reserved."))
+    } else {
+        Ok(())
+    }
+}
+
+/// A plausible address: `local@domain.tld`, no whitespace, at most 254
+/// bytes. Deliverability is for the verification email to prove.
+pub fn email(value: &str) -> Result<(), String> {
+    let plausible = value.len() <= 254
+        && !value.chars().any(char::is_whitespace)
+        && value.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.split_once('.').is_some_and(|(host, tld)| {
+                    !host.is_empty() && !tld.is_empty()
+                })
+                && !domain.ends_with('.')
+        });
+    if plausible {
+        Ok(())
+    } else {
+        Err(String::from(
+            "Enter a valid email address, like name@example.com.",
+        ))
+    }
+}
+
+/// An absolute `http` or `https` URL with a host, at most 200 bytes.
+pub fn link(value: &str) -> Result<(), String> {
+    let host = value
+        .strip_prefix("https://")
+        .or_else(|| value.strip_prefix("http://"))
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .unwrap_or_default();
+    let plausible = value.len() <= 200
+        && !host.is_empty()
+        && !value.chars().any(|c| c.is_whitespace() || c.is_control());
+    if plausible {
+        Ok(())
+    } else {
+        Err(format!(
+            "{value} isn't a web address; links start with https://"
+        ))
+    }
+}
+
+/// Where an app may receive authorization codes: an absolute `https` URL
+/// without a fragment, or `http` on the loopback host for local
+/// development. At most 500 bytes.
+pub fn redirect_uri(value: &str) -> Result<(), String> {
+    let acceptable = value.len() <= 500
+        && Url::parse(value).is_ok_and(|url| {
+            let loopback = matches!(
+                url.host_str(),
+                Some("localhost" | "127.0.0.1" | "[::1]")
+            );
+            (url.scheme() == "https" || (url.scheme() == "http" && loopback))
+                && url.has_host()
+                && url.fragment().is_none()
+        });
+    if acceptable {
+        Ok(())
+    } else {
+        Err(format!(
+            "{value} can't be a redirect URI; use an https:// address without a #fragment"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_error_per_field_wins() {
+        let mut errors = FieldErrors::new();
+        errors.add("username", "first");
+        errors.add("username", "second");
+        errors.check("password", Ok(()));
+        errors.check("password", Err("weak".to_owned()));
+
+        assert_eq!(errors.get("username"), Some("first"));
+        assert_eq!(errors.get("password"), Some("weak"));
+        assert!(!errors.contains("email"));
+        assert_eq!(
+            errors.iter().collect::<Vec<_>>(),
+            [("password", "weak"), ("username", "first")]
+        );
+        errors.into_result().expect_err("errors were recorded");
+        FieldErrors::new()
+            .into_result()
+            .expect("nothing was recorded");
+    }
+
+    #[test]
+    fn username_too_short() {
+        username("abcd").expect_err("too short");
+        username("a").expect_err("too short");
+    }
+
+    #[test]
+    fn username_too_long() {
+        username(&"a".repeat(21)).expect_err("too long");
+    }
+
+    #[test]
+    fn username_invalid_chars() {
+        for value in ["user_name", "user-name", "user name", "user@name"] {
+            username(value).expect_err(value);
+        }
+    }
+
+    #[test]
+    fn username_reserved_or_mixed_script() {
+        let reserved = username("Settings").expect_err("reserved");
+        assert!(reserved.contains("reserved"));
+        username("p\u{430}ypal").expect_err("Latin and Cyrillic");
+    }
+
+    #[test]
+    fn username_valid() {
+        for value in [
+            "validuser",
+            "user123",
+            "12345",
+            &"a".repeat(20),
+            "\u{434}\u{436}\u{43e}\u{43d}\u{43d}\u{438}",
+        ] {
+            username(value).expect(value);
+        }
+    }
+
+    #[test]
+    fn email_plausibility() {
+        for value in ["jane@example.com", "j.doe+tag@mail.example.co.uk"] {
+            email(value).expect(value);
+        }
+        for value in [
+            "",
+            "jane",
+            "@example.com",
+            "jane@",
+            "jane@example",
+            "jane@example.",
+            "jane@@example.com",
+            "jane doe@example.com",
+        ] {
+            email(value).expect_err(value);
+        }
+    }
+
+    #[test]
+    fn link_requires_web_scheme_and_host() {
+        for value in ["https://example.com", "http://example.com/a?b#c"] {
+            link(value).expect(value);
+        }
+        for value in [
+            "example.com",
+            "https://",
+            "https:///path",
+            "javascript:alert(1)",
+            "ftp://example.com",
+            "https://exa mple.com",
+        ] {
+            link(value).expect_err(value);
+        }
+        link(&format!("https://example.com/{}", "a".repeat(200)))
+            .expect_err("too long");
+    }
+
+    #[test]
+    fn redirect_uri_requires_https_except_on_loopback() {
+        for value in [
+            "https://app.example.com/callback",
+            "http://localhost:8080/callback",
+            "http://127.0.0.1/cb?tenant=1",
+        ] {
+            redirect_uri(value).expect(value);
+        }
+        for value in [
+            "http://app.example.com/callback",
+            "https://app.example.com/callback#done",
+            "/callback",
+            "javascript:alert(1)",
+            "com.example.app:/callback",
+        ] {
+            redirect_uri(value).expect_err(value);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Embedded static assets with content-hashed URLs.
+//!
+//! `build.rs` fingerprints every file in `static/` and pre-compresses it
+//! with gzip and brotli. Templates link assets through [`url`], which yields
+//! the fingerprinted path; those responses are cached as immutable. The
+//! plain names are revalidated with an `ETag` instead, both under
+//! [`PREFIX`] and, for the files served before fingerprinting, at the site
+//! root so existing links keep working.
+
+use axum::extract::Path;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri, header};
+use axum::response::{IntoResponse as _, Response};
+
+/// A file from `static/`, embedded at compile time.
+pub struct Asset {
+    /// File name in `static/`, e.g. `pico.min.css`.
+    pub name: &'static str,
+    /// Name with the content hash inserted, e.g. `pico.min.0123abcd.css`.
+    pub hashed_name: &'static str,
+    pub content_type: &'static str,
+    /// Quoted content hash, usable directly as an `ETag`.
+    pub etag: &'static str,
+    pub identity: &'static [u8],
+    pub gzip: &'static [u8],
+    pub brotli: &'static [u8],
+}
+
+pub static ASSETS: &[Asset] = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+/// URL prefix that static assets are served under.
+pub const PREFIX: &str = "/static";
+
+/// Files that used to be served from the site root, e.g. `/htmx.min.js`.
+pub const ROOT_NAMES: [&str; 3] =
+    ["pico.min.css", "pico.colors.min.css", "htmx.min.js"];
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "public, no-cache";
+
+/// Returns the fingerprinted URL for an asset in `static/`.
+///
+/// Unknown names fall back to the plain path so a typo shows up as a 404
+/// rather than a panic.
+pub fn url(name: &str) -> String {
+    let file = ASSETS
+        .iter()
+        .find(|asset| asset.name == name)
+        .map_or(name, |asset| asset.hashed_name);
+    format!("{PREFIX}/{file}")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+    Identity,
+}
+
+impl Encoding {
+    /// The `Content-Encoding` value, if the body is encoded.
+    const fn content_encoding(self) -> Option<&'static str> {
+        match self {
+            Self::Brotli => Some("br"),
+            Self::Gzip => Some("gzip"),
+            Self::Identity => None,
+        }
+    }
+
+    const fn body(self, asset: &Asset) -> &'static [u8] {
+        match self {
+            Self::Brotli => asset.brotli,
+            Self::Gzip => asset.gzip,
+            Self::Identity => asset.identity,
+        }
+    }
+}
+
+/// The asset's `ETag` for one encoding: each encoding is a different
+/// representation, so each gets its own strong tag.
+fn etag(asset: &Asset, encoding: Encoding) -> String {
+    match encoding.content_encoding() {
+        Some(coding) => {
+            format!("{}-{coding}\"", asset.etag.trim_end_matches('"'))
+        }
+        None => asset.etag.to_owned(),
+    }
+}
+
+/// Picks the best encoding the client accepts, preferring brotli.
+fn negotiate(headers: &HeaderMap) -> Encoding {
+    let Some(accept) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Encoding::Identity;
+    };
+
+    let accepts = |coding: &str| {
+        accept.split(',').any(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            parts.next() == Some(coding)
+                && parts.all(|param| {
+                    param
+                        .strip_prefix("q=")
+                        .and_then(|q| q.parse::<f32>().ok())
+                        .is_none_or(|q| q > 0.0)
+                })
+        })
+    };
+
+    if accepts("br") {
+        Encoding::Brotli
+    } else if accepts("gzip") {
+        Encoding::Gzip
+    } else {
+        Encoding::Identity
+    }
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+}
+
+pub async fn get(Path(file): Path<String>, headers: HeaderMap) -> Response {
+    if let Some(asset) = ASSETS.iter().find(|asset| asset.hashed_name == file) {
+        respond(asset, IMMUTABLE, &headers)
+    } else if let Some(asset) = ASSETS.iter().find(|asset| asset.name == file) {
+        respond(asset, REVALIDATE, &headers)
+    } else {
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+/// One of [`ROOT_NAMES`] at its old URL, revalidated like a plain name.
+pub async fn get_root(uri: Uri, headers: HeaderMap) -> Response {
+    let name = uri.path().trim_start_matches('/');
+    match ASSETS.iter().find(|asset| asset.name == name) {
+        Some(asset) => respond(asset, REVALIDATE, &headers),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn respond(
+    asset: &Asset,
+    cache_control: &'static str,
+    headers: &HeaderMap,
+) -> Response {
+    let encoding = negotiate(headers);
+    let etag = etag(asset, encoding);
+    if not_modified(headers, &etag) {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag.as_str()),
+                (header::CACHE_CONTROL, cache_control),
+                (header::VARY, "Accept-Encoding"),
+            ],
+        )
+            .into_response();
+    }
+
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, asset.content_type),
+            (header::CACHE_CONTROL, cache_control),
+            (header::ETAG, etag.as_str()),
+            (header::VARY, "Accept-Encoding"),
+        ],
+        encoding.body(asset),
+    )
+        .into_response();
+    if let Some(coding) = encoding.content_encoding() {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static(coding));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept_encoding(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers
+            .insert(header::ACCEPT_ENCODING, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn negotiate_prefers_brotli() {
+        assert_eq!(
+            negotiate(&accept_encoding("gzip, deflate, br")),
+            Encoding::Brotli
+        );
+    }
+
+    #[test]
+    fn negotiate_respects_q_zero() {
+        assert_eq!(negotiate(&accept_encoding("br;q=0, gzip")), Encoding::Gzip);
+    }
+
+    #[test]
+    fn negotiate_identity_without_header() {
+        assert_eq!(negotiate(&HeaderMap::new()), Encoding::Identity);
+    }
+
+    #[test]
+    fn each_encoding_has_its_own_etag() {
+        let asset = &ASSETS[0];
+        let tags = [Encoding::Brotli, Encoding::Gzip, Encoding::Identity]
+            .map(|encoding| etag(asset, encoding));
+        assert_eq!(tags[2], asset.etag);
+        assert_ne!(tags[0], tags[1]);
+        assert_ne!(tags[0], tags[2]);
+        assert!(
+            tags.iter()
+                .all(|tag| tag.starts_with('"') && tag.ends_with('"'))
+        );
+    }
+
+    #[test]
+    fn url_is_fingerprinted() {
+        let href = url("htmx.min.js");
+        let hash = href
+            .strip_prefix("/static/htmx.min.")
+            .and_then(|rest| rest.strip_suffix(".js"))
+            .expect("hash should sit between the stem and extension");
+        assert_eq!(hash.len(), 16);
+    }
+
+    #[test]
+    fn every_static_file_is_registered() {
+        let names: Vec<_> = ASSETS.iter().map(|asset| asset.name).collect();
+        assert!(names.contains(&"pico.min.css"));
+        assert!(names.contains(&"pico.colors.min.css"));
+        assert!(names.contains(&"htmx.min.js"));
+    }
+}
@@ -0,0 +1,87 @@
+//! Security audit log: recording events and the pages that show them.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
+use crate::models::audit_event::{AuditEvent, AuditFilter, AuditKind};
+use crate::models::user::User;
+use crate::util::current_time_micros;
+
+use super::pages;
+
+/// Events shown per page on the activity views.
+const PAGE_SIZE: i64 = 100;
+
+/// Appends an event to the audit log.
+///
+/// Failures are logged rather than returned: losing an audit record must
+/// not fail the action being audited.
+pub async fn record(
+    state: &AppState,
+    client: &ClientInfo,
+    user_id: Option<Uuid>,
+    username: &str,
+    kind: AuditKind,
+    detail: String,
+) {
+    let event = AuditEvent {
+        id: 0,
+        user_id,
+        username: username.to_owned(),
+        kind,
+        ip_address: client.ip.clone(),
+        user_agent: client.user_agent.clone(),
+        detail,
+        created_at: current_time_micros(),
+    };
+    if let Err(err) = AuditEvent::insert(&state.db, &event).await {
+        warn!(kind = kind.as_str(), "Failed to record audit event: {err}");
+    }
+}
+
+/// The current user's own security activity.
+pub async fn security_activity(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match AuditEvent::get_by_user_id(&state.db, user.id, PAGE_SIZE).await {
+        Ok(events) => {
+            pages::security_activity(&user.username, user.is_admin, &events)
+                .into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Every user's security activity, filterable by username and event kind.
+pub async fn admin_log(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    Query(filter): Query<AuditFilter>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !user.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match AuditEvent::search(&state.db, &filter, PAGE_SIZE).await {
+        Ok(events) => {
+            pages::admin_audit(&user.username, &filter, &events).into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
@@ -0,0 +1,198 @@
+//! Admin pages for registering the apps that sign users in through this
+//! site's OIDC provider.
+
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse as _, Redirect, Response};
+use serde::Deserialize;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
+use crate::issuer;
+use crate::models::{
+    audit_event::AuditKind, oauth_client::OAuthClient, user::User,
+};
+use crate::oidc;
+use crate::util::{current_time_micros, format_utc};
+use crate::validation::{self, FieldErrors, Validate};
+
+use super::components::{self, ClientCredentials, ClientDisplay};
+use super::{audit, pages};
+
+/// Longest app name, in characters.
+const MAX_NAME_CHARS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterClientPayload {
+    name: String,
+    /// One per line.
+    redirect_uris: String,
+}
+
+impl RegisterClientPayload {
+    fn uris(&self) -> impl Iterator<Item = &str> {
+        self.redirect_uris
+            .lines()
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+    }
+}
+
+impl Validate for RegisterClientPayload {
+    type Context = ();
+
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+            errors.add(
+                "name",
+                format!("Name must be 1 to {MAX_NAME_CHARS} characters."),
+            );
+        }
+        if self.uris().next().is_none() {
+            errors.add("redirect_uris", "Add at least one redirect URI.");
+        }
+        for uri in self.uris() {
+            errors.check("redirect_uris", validation::redirect_uri(uri));
+        }
+        errors.into_result()
+    }
+}
+
+async fn client_displays(
+    state: &AppState,
+) -> Result<Vec<ClientDisplay>, sqlx::Error> {
+    Ok(OAuthClient::list(&state.db)
+        .await?
+        .into_iter()
+        .map(|client| ClientDisplay {
+            redirect_uris: client
+                .redirect_uri_list()
+                .map(str::to_owned)
+                .collect(),
+            created_at: format_utc(client.created_at),
+            id: client.id,
+            name: client.name,
+        })
+        .collect())
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !user.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match client_displays(&state).await {
+        Ok(clients) => {
+            pages::admin_clients(&user.username, &clients).into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Registers an app and shows its secret, once.
+pub async fn create(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    client_info: ClientInfo,
+    Form(form): Form<RegisterClientPayload>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !user.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let registered = if let Err(errors) = form.validate(&()) {
+        Err(errors)
+    } else {
+        let credentials = ClientCredentials {
+            client_id: oidc::random_token(),
+            secret: oidc::random_token(),
+        };
+        let client = OAuthClient {
+            id: credentials.client_id.clone(),
+            name: form.name.trim().to_owned(),
+            secret_hash: issuer::hash_secret(&credentials.secret),
+            redirect_uris: form.uris().collect::<Vec<_>>().join("\n"),
+            created_by: Some(user.id),
+            created_at: current_time_micros(),
+        };
+        if let Err(err) = OAuthClient::insert(&state.db, &client).await {
+            return internal_error(err).into_response();
+        }
+        audit::record(
+            &state,
+            &client_info,
+            Some(user.id),
+            &user.username,
+            AuditKind::ClientRegistered,
+            client.name,
+        )
+        .await;
+        Ok(credentials)
+    };
+
+    let clients = match client_displays(&state).await {
+        Ok(clients) => clients,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    match registered {
+        Ok(credentials) => components::clients_section(
+            &clients,
+            ("", ""),
+            &FieldErrors::new(),
+            Some(&credentials),
+        )
+        .into_response(),
+        Err(errors) => components::clients_section(
+            &clients,
+            (&form.name, &form.redirect_uris),
+            &errors,
+            None,
+        )
+        .into_response(),
+    }
+}
+
+/// Removes an app; the row is swapped out with the empty response.
+pub async fn delete(
+    Path(client_id): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    client_info: ClientInfo,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !user.is_admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let client = match OAuthClient::get(&state.db, &client_id).await {
+        Ok(Some(client)) => client,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => return internal_error(err).into_response(),
+    };
+    if let Err(err) = OAuthClient::delete(&state.db, &client.id).await {
+        return internal_error(err).into_response();
+    }
+    audit::record(
+        &state,
+        &client_info,
+        Some(user.id),
+        &user.username,
+        AuditKind::ClientRemoved,
+        client.name,
+    )
+    .await;
+    StatusCode::OK.into_response()
+}
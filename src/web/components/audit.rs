@@ -0,0 +1,52 @@
+//! Audit event table shared by the security activity and admin pages.
+
+use maud::{Markup, html};
+
+use crate::models::audit_event::AuditEvent;
+use crate::user_agent::Device;
+use crate::util::format_utc;
+
+/// Renders audit events, newest first. `show_user` adds a username column
+/// for the admin view.
+pub fn audit_table(events: &[AuditEvent], show_user: bool) -> Markup {
+    html! {
+        @if events.is_empty() {
+            p { "No activity recorded." }
+        } @else {
+            table {
+                thead {
+                    tr {
+                        th { "Time" }
+                        @if show_user {
+                            th { "User" }
+                        }
+                        th { "Event" }
+                        th { "Device" }
+                        th { "IP Address" }
+                    }
+                }
+                tbody {
+                    @for event in events {
+                        tr {
+                            td { (format_utc(event.created_at)) }
+                            @if show_user {
+                                td { (event.username) }
+                            }
+                            td {
+                                (event.kind.label())
+                                @if !event.detail.is_empty() {
+                                    br;
+                                    small { (event.detail) }
+                                }
+                            }
+                            td title=(event.user_agent) {
+                                (Device::parse(&event.user_agent).label())
+                            }
+                            td { (event.ip_address) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
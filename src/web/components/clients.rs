@@ -0,0 +1,77 @@
+//! The admin section for apps that sign users in through this site.
+
+use maud::{Markup, html};
+
+use crate::validation::FieldErrors;
+
+use super::forms::client_form;
+use super::{ClientCredentials, ClientDisplay};
+
+/// Swapped as a whole after each registration. `draft` is the rejected
+/// submission's name and redirect URIs, kept in the form; `registered`
+/// holds a new app's credentials, shown only this once.
+pub fn clients_section(
+    clients: &[ClientDisplay],
+    draft: (&str, &str),
+    errors: &FieldErrors,
+    registered: Option<&ClientCredentials>,
+) -> Markup {
+    html! {
+        section #clients {
+            @if let Some(credentials) = registered {
+                article {
+                    p { "Registered. Copy the secret now: it won't be shown again." }
+                    dl {
+                        dt { "Client ID" }
+                        dd { code { (credentials.client_id) } }
+                        dt { "Client secret" }
+                        dd { code { (credentials.secret) } }
+                    }
+                }
+            }
+            (client_form(draft.0, draft.1, errors))
+            @if clients.is_empty() {
+                p { "No apps registered yet." }
+            } @else {
+                table {
+                    thead {
+                        tr {
+                            th { "App" }
+                            th { "Redirect URIs" }
+                            th { "Registered" }
+                            th { "Actions" }
+                        }
+                    }
+                    tbody {
+                        @for client in clients {
+                            tr {
+                                td {
+                                    (client.name)
+                                    br;
+                                    small { code { (client.id) } }
+                                }
+                                td {
+                                    @for uri in &client.redirect_uris {
+                                        (uri) br;
+                                    }
+                                }
+                                td { (client.created_at) }
+                                td {
+                                    button
+                                        hx-delete={ "/admin/clients/" (client.id) }
+                                        hx-target="closest tr"
+                                        hx-swap="outerHTML"
+                                        hx-confirm={ "Remove " (client.name) "? Its users will have to sign in again." }
+                                        class="secondary"
+                                    {
+                                        "Remove"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -2,12 +2,29 @@
 
 use maud::{Markup, html};
 
-pub fn login_form(username: &str, error_message: &str) -> Markup {
+use crate::bot_check::Challenge;
+use crate::validation::FieldErrors;
+
+use super::bot_check::bot_check_fields;
+use super::{aria_invalid, message, password_feedback};
+
+/// `next` is carried through as a hidden field; see
+/// [`crate::web::login::local_path`]. `challenge` is set once there have
+/// been too many failed attempts.
+pub fn login_form(
+    username: &str,
+    error_message: &str,
+    next: Option<&str>,
+    challenge: Option<&Challenge>,
+) -> Markup {
     let has_error = !error_message.is_empty();
     html! {
         article hx-target="this" hx-swap="outerHTML" {
             header { h1 { "Login" } }
             form hx-post="/session" method="post" {
+                @if let Some(path) = next {
+                    input type="hidden" name="next" value=(path);
+                }
                 fieldset {
                     label {
                         "Username "
@@ -23,7 +40,12 @@ pub fn login_form(username: &str, error_message: &str) -> Markup {
                             small { (error_message) }
                         }
                     }
+                    label {
+                        input name="remember_me" type="checkbox" role="switch" value="true";
+                        "Remember me"
+                    }
                 }
+                (bot_check_fields(challenge))
                 button type="submit" { "Log in" }
             }
             footer { "Don't have an account? " a href="/signup" { "Sign up" } }
@@ -31,33 +53,46 @@ pub fn login_form(username: &str, error_message: &str) -> Markup {
     }
 }
 
+/// `invite` is the code entered so far, or `None` when sign-up doesn't
+/// ask for one. `challenge` is a fresh bot-check challenge; a rejected
+/// one is reported under the `challenge` field.
 pub fn signup_form(
     username: &str,
-    username_message: &str,
-    password_message: &str,
+    invite: Option<&str>,
+    challenge: Option<&Challenge>,
+    errors: &FieldErrors,
 ) -> Markup {
     html! {
         article hx-target="this" hx-swap="outerHTML" {
             header { h1 { "Sign up" } }
             form hx-post="/signup" method="post" {
                 fieldset {
+                    @if let Some(code) = invite {
+                        label {
+                            "Invite code"
+                            input name="invite" type="text" placeholder="XXXXX-XXXXX" value=(code) required autocomplete="off"
+                                aria-invalid=[aria_invalid(errors, "invite", None)];
+                            (message(errors, "invite", None))
+                        }
+                    }
                     label {
                         "Username"
                         input name="username" type="text" placeholder="Username" value=(username) required autofocus autocomplete="username"
-                            aria-invalid=[(!username_message.is_empty()).then_some("true")];
-                        @if !username_message.is_empty() {
-                            small { (username_message) }
-                        }
+                            aria-invalid=[aria_invalid(errors, "username", None)];
+                        (message(errors, "username", None))
                     }
                     label {
                         "Password"
                         input name="password" type="password" placeholder="Password" required autocomplete="new-password"
-                            aria-invalid=[(!password_message.is_empty()).then_some("true")];
-                        @if !password_message.is_empty() {
-                            small { (password_message) }
-                        }
+                            hx-post="/password/strength" hx-trigger="input changed delay:300ms"
+                            hx-target="#password-feedback" hx-swap="outerHTML"
+                            aria-invalid=[aria_invalid(errors, "password", None)];
+                        (message(errors, "password", None))
+                        (password_feedback(None))
                     }
                 }
+                (bot_check_fields(challenge))
+                (message(errors, "challenge", None))
                 button type="submit" { "Sign up" }
             }
             footer { "Already have an account? " a href="/login" { "Log in" } }
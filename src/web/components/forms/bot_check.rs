@@ -0,0 +1,25 @@
+//! Hidden fields for the bot checks on the signup and login forms.
+
+use maud::{Markup, html};
+
+use crate::bot_check::{self, Challenge, HONEYPOT_FIELD};
+
+/// The honeypot, plus `challenge` for `proof-of-work.js` to solve. The
+/// form's submit button stays disabled until it's solved. Renders nothing
+/// when bot checks are off.
+pub fn bot_check_fields(challenge: Option<&Challenge>) -> Markup {
+    html! {
+        @if bot_check::config().is_enabled() {
+            div hidden aria-hidden="true" {
+                label {
+                    "Leave this empty"
+                    input type="text" name=(HONEYPOT_FIELD) value="" tabindex="-1" autocomplete="off";
+                }
+            }
+        }
+        @if let Some(issued) = challenge {
+            input type="hidden" name="pow_challenge" value=(issued.token) data-pow-difficulty=(issued.difficulty);
+            input type="hidden" name="pow_solution" value="";
+        }
+    }
+}
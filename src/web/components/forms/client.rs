@@ -0,0 +1,39 @@
+//! App registration form for the OIDC provider's admin page.
+
+use maud::{Markup, html};
+
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message};
+
+/// Posts to `/admin/clients` and replaces `#clients` with the response.
+/// Fields keep their values when there are errors.
+pub fn client_form(
+    name: &str,
+    redirect_uris: &str,
+    errors: &FieldErrors,
+) -> Markup {
+    html! {
+        form hx-post="/admin/clients" hx-target="#clients" hx-swap="outerHTML" method="post" action="/admin/clients" {
+            label for="name" {
+                "App name"
+                input type="text" id="name" name="name" placeholder="Shown to users when they sign in" value=(name) required maxlength="100"
+                    aria-invalid=[aria_invalid(errors, "name", None)];
+                (message(errors, "name", None))
+            }
+            label for="redirect_uris" {
+                "Redirect URIs"
+                textarea id="redirect_uris" name="redirect_uris" rows="3" placeholder="https://app.example.com/callback" required
+                    aria-invalid=[aria_invalid(errors, "redirect_uris", None)] {
+                    (redirect_uris)
+                }
+                @if errors.contains("redirect_uris") {
+                    (message(errors, "redirect_uris", None))
+                } @else {
+                    small { "One per line, matched exactly." }
+                }
+            }
+            button type="submit" { "Register app" }
+        }
+    }
+}
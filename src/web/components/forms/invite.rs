@@ -0,0 +1,50 @@
+//! Invite creation form.
+
+use maud::{Markup, html};
+
+use crate::registration::{DEFAULT_INVITE_DAYS, MAX_INVITE_DAYS};
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message};
+
+/// Posts to `/invites` and replaces `#invites` with the response.
+/// `max_uses` is the most this user may allow; the draft is the rejected
+/// submission's email, uses and days.
+pub fn invite_form(
+    draft: (&str, &str, &str),
+    max_uses: i64,
+    errors: &FieldErrors,
+) -> Markup {
+    let (email, uses, days) = draft;
+    html! {
+        form hx-post="/invites" hx-target="#invites" hx-swap="outerHTML" method="post" action="/invites" {
+            label for="invite-email" {
+                "Email (optional)"
+                input type="email" id="invite-email" name="email" placeholder="friend@example.com" value=(email) autocomplete="off"
+                    aria-invalid=[aria_invalid(errors, "email", None)];
+                @if errors.contains("email") {
+                    (message(errors, "email", None))
+                } @else {
+                    small { "We'll send them the sign-up link." }
+                }
+            }
+            div class="grid" {
+                label for="max_uses" {
+                    "Uses"
+                    input type="number" id="max_uses" name="max_uses" min="1" max=(max_uses) required
+                        value=(if uses.is_empty() { "1" } else { uses })
+                        aria-invalid=[aria_invalid(errors, "max_uses", None)];
+                    (message(errors, "max_uses", None))
+                }
+                label for="days" {
+                    "Expires after (days)"
+                    input type="number" id="days" name="days" min="1" max=(MAX_INVITE_DAYS) required
+                        value=(if days.is_empty() { DEFAULT_INVITE_DAYS.to_string() } else { days.to_owned() })
+                        aria-invalid=[aria_invalid(errors, "days", None)];
+                    (message(errors, "days", None))
+                }
+            }
+            button type="submit" { "Create invite" }
+        }
+    }
+}
@@ -2,9 +2,56 @@
 //!
 //! These return HTML fragments, not full pages.
 //! Use with hx-swap to replace form content on submit.
+//!
+//! Validation errors arrive as a [`FieldErrors`] map and are rendered under
+//! the input with the same `name`, via [`aria_invalid`] and [`message`].
 
 mod auth;
+mod bot_check;
+mod client;
+mod invite;
+mod notification;
+mod organization;
+mod password;
+mod profile;
 mod settings;
+mod upload;
+
+use maud::{Markup, html};
+
+use crate::validation::FieldErrors;
 
 pub use auth::{login_form, signup_form};
+pub use client::client_form;
+pub use invite::invite_form;
+pub use notification::notification_preferences_form;
+pub use organization::{org_invite_form, organization_form};
+pub use password::password_feedback;
+pub use profile::{avatar_form, profile_form};
 pub use settings::{email_form, password_form, username_form};
+pub use upload::upload_form;
+
+/// `aria-invalid` for a field: `"true"` if it has an error, `"false"` if it
+/// has a success message, absent otherwise.
+fn aria_invalid(
+    errors: &FieldErrors,
+    field: &str,
+    success: Option<&str>,
+) -> Option<&'static str> {
+    if errors.contains(field) {
+        Some("true")
+    } else {
+        success.map(|_| "false")
+    }
+}
+
+/// The message shown under a field: its error, else `success`.
+fn message(errors: &FieldErrors, field: &str, success: Option<&str>) -> Markup {
+    html! {
+        @if let Some(error) = errors.get(field) {
+            small { (error) }
+        } @else if let Some(text) = success {
+            small { (text) }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Notification delivery preferences.
+
+use maud::{Markup, html};
+
+use crate::models::notification_preference::NotificationPreference;
+
+/// One row per kind with an in-app and an email checkbox, named
+/// `{kind}.in_app` and `{kind}.email`. `saved` confirms the last change.
+pub fn notification_preferences_form(
+    preferences: &[NotificationPreference],
+    saved: bool,
+) -> Markup {
+    html! {
+        form hx-post="/notifications/preferences" hx-swap="outerHTML" method="post" action="/notifications/preferences" {
+            table {
+                thead {
+                    tr {
+                        th { "Notify me about" }
+                        th { "In the app" }
+                        th { "By email" }
+                    }
+                }
+                tbody {
+                    @for preference in preferences {
+                        @let kind = preference.kind;
+                        tr {
+                            td { (kind.label()) }
+                            td {
+                                input type="checkbox" name={ (kind.as_str()) ".in_app" } checked[preference.in_app]
+                                    aria-label={ (kind.label()) " in the app" };
+                            }
+                            td {
+                                input type="checkbox" name={ (kind.as_str()) ".email" } checked[preference.email]
+                                    aria-label={ (kind.label()) " by email" };
+                            }
+                        }
+                    }
+                }
+            }
+            button type="submit" { "Save preferences" }
+            @if saved {
+                small { "Preferences saved." }
+            }
+        }
+    }
+}
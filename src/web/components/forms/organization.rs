@@ -0,0 +1,75 @@
+//! Organization creation and invitation forms.
+
+use maud::{Markup, html};
+
+use crate::models::organization::Role;
+use crate::organization::MAX_SLUG_LEN;
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message};
+
+/// Posts to `/orgs`, which redirects to the new organization or swaps the
+/// form back with errors. The draft is the rejected name and slug.
+pub fn organization_form(draft: (&str, &str), errors: &FieldErrors) -> Markup {
+    let (name, slug) = draft;
+    html! {
+        form hx-post="/orgs" hx-swap="outerHTML" method="post" action="/orgs" {
+            label for="org-name" {
+                "Name"
+                input type="text" id="org-name" name="name" value=(name) required
+                    aria-invalid=[aria_invalid(errors, "name", None)];
+                (message(errors, "name", None))
+            }
+            label for="org-slug" {
+                "URL"
+                input type="text" id="org-slug" name="slug" value=(slug) maxlength=(MAX_SLUG_LEN)
+                    placeholder="acme-co" autocomplete="off"
+                    aria-invalid=[aria_invalid(errors, "slug", None)];
+                @if errors.contains("slug") {
+                    (message(errors, "slug", None))
+                } @else {
+                    small { "Optional; made from the name if left empty." }
+                }
+            }
+            button type="submit" { "Create organization" }
+        }
+    }
+}
+
+/// Posts to the organization's invitations and replaces `#org-invites`.
+/// `roles` are those the inviter may hand out; the draft is the rejected
+/// email and role.
+pub fn org_invite_form(
+    slug: &str,
+    roles: &[Role],
+    draft: (&str, Role),
+    errors: &FieldErrors,
+) -> Markup {
+    let (email, role) = draft;
+    let action = format!("/orgs/{slug}/invites");
+    html! {
+        form hx-post=(action) hx-target="#org-invites" hx-swap="outerHTML" method="post" action=(action) {
+            div class="grid" {
+                label for="org-invite-email" {
+                    "Email"
+                    input type="email" id="org-invite-email" name="email" value=(email) required
+                        placeholder="colleague@example.com" autocomplete="off"
+                        aria-invalid=[aria_invalid(errors, "email", None)];
+                    (message(errors, "email", None))
+                }
+                label for="org-invite-role" {
+                    "Role"
+                    select id="org-invite-role" name="role"
+                        aria-invalid=[aria_invalid(errors, "role", None)]
+                    {
+                        @for option in roles {
+                            option value=(option.as_str()) selected[*option == role] { (option.label()) }
+                        }
+                    }
+                    (message(errors, "role", None))
+                }
+            }
+            button type="submit" { "Send invitation" }
+        }
+    }
+}
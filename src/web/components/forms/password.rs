@@ -0,0 +1,25 @@
+//! Live password strength feedback, swapped in as the user types.
+
+use maud::{Markup, html};
+
+use crate::password::Feedback;
+
+/// The element new-password inputs target with `hx-target`. `None` renders
+/// it empty, ready for the first swap.
+pub fn password_feedback(feedback_opt: Option<&Feedback>) -> Markup {
+    html! {
+        div id="password-feedback" aria-live="polite" {
+            @if let Some(feedback) = feedback_opt {
+                progress value=(feedback.strength.score) max="4" {}
+                small {
+                    (feedback.strength.label())
+                    @if let Some(problem) = feedback.problem.as_deref() {
+                        ": " (problem)
+                    } @else if let Some(warning) = feedback.strength.warning {
+                        ". " (warning)
+                    }
+                }
+            }
+        }
+    }
+}
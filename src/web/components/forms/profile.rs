@@ -0,0 +1,97 @@
+//! Profile and avatar settings forms.
+
+use maud::{Markup, html};
+
+use crate::avatar;
+use crate::models::profile::{Profile, Visibility};
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message};
+
+/// A "Visible to" select for one profile field.
+fn visibility_select(name: &str, label: &str, current: Visibility) -> Markup {
+    html! {
+        label for=(name) {
+            (label)
+            select id=(name) name=(name) {
+                @for visibility in Visibility::ALL {
+                    option value=(visibility.as_str()) selected[visibility == current] {
+                        (visibility.label())
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn profile_form(
+    profile: &Profile,
+    errors: &FieldErrors,
+    success: Option<&str>,
+) -> Markup {
+    html! {
+        form hx-post="/settings/profile" hx-swap="outerHTML" method="post" action="/settings/profile" {
+            label for="display_name" {
+                "Display Name"
+                input type="text" id="display_name" name="display_name" placeholder="Shown instead of your username" value=(profile.display_name) maxlength="50"
+                    aria-invalid=[aria_invalid(errors, "display_name", None)];
+                (message(errors, "display_name", None))
+            }
+            label for="bio" {
+                "Bio"
+                textarea id="bio" name="bio" rows="4" maxlength="500" placeholder="A few words about yourself"
+                    aria-invalid=[aria_invalid(errors, "bio", None)] {
+                    (profile.bio)
+                }
+                (message(errors, "bio", None))
+            }
+            label for="links" {
+                "Links"
+                textarea id="links" name="links" rows="3" placeholder="https://example.com (one per line)"
+                    aria-invalid=[aria_invalid(errors, "links", None)] {
+                    (profile.links)
+                }
+                (message(errors, "links", None))
+            }
+            fieldset {
+                legend { "Who can see" }
+                (visibility_select("bio_visibility", "Bio", profile.bio_visibility))
+                (visibility_select("links_visibility", "Links", profile.links_visibility))
+                (visibility_select("avatar_visibility", "Avatar", profile.avatar_visibility))
+            }
+            @if let Some(text) = success {
+                p { small { (text) } }
+            }
+            button type="submit" { "Update Profile" }
+        }
+    }
+}
+
+/// `avatar_url` is the current avatar, if there is one.
+pub fn avatar_form(
+    avatar_url: Option<&str>,
+    errors: &FieldErrors,
+    success: Option<&str>,
+) -> Markup {
+    html! {
+        form hx-post="/settings/avatar" hx-encoding="multipart/form-data" hx-swap="outerHTML" method="post" action="/settings/avatar" enctype="multipart/form-data" {
+            @if let Some(url) = avatar_url {
+                img src=(url) alt="Your avatar" width="96" height="96";
+            }
+            label for="avatar" {
+                "Avatar"
+                input type="file" id="avatar" name="avatar" accept=(avatar::ACCEPTED_TYPES.join(",")) required
+                    aria-invalid=[aria_invalid(errors, "avatar", success)];
+                (message(errors, "avatar", success))
+            }
+            div role="group" {
+                button type="submit" { "Upload Avatar" }
+                @if avatar_url.is_some() {
+                    button type="button" class="secondary" hx-delete="/settings/avatar" hx-target="closest form" hx-swap="outerHTML" hx-confirm="Remove your avatar?" {
+                        "Remove"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,26 @@
 //! Settings form components (username, email, password).
+//!
+//! Each takes the submission's [`FieldErrors`] and an optional success
+//! message, shown under the form's main field.
 
 use maud::{Markup, html};
 
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message, password_feedback};
+
 pub fn username_form(
     new_username: &str,
-    username_message: &str,
-    username_is_success: bool,
+    errors: &FieldErrors,
+    success: Option<&str>,
 ) -> Markup {
-    let aria_invalid = (!username_message.is_empty())
-        .then_some(if username_is_success { "false" } else { "true" });
     html! {
         form hx-post="/settings/username" hx-swap="outerHTML" method="post" action="/settings/username" {
             label for="new_username" {
                 "New Username"
                 input type="text" id="new_username" name="new_username" placeholder="Enter new username" value=(new_username) required minlength="5" maxlength="20"
-                    aria-invalid=[aria_invalid];
-                @if !username_message.is_empty() {
-                    small { (username_message) }
-                }
+                    aria-invalid=[aria_invalid(errors, "new_username", success)];
+                (message(errors, "new_username", success))
             }
             button type="submit" { "Update Username" }
         }
@@ -26,68 +29,56 @@ pub fn username_form(
 
 pub fn email_form(
     current_email: &str,
-    message: &str,
-    is_success: bool,
+    errors: &FieldErrors,
+    success: Option<&str>,
 ) -> Markup {
-    let aria_invalid = (!message.is_empty()).then_some(if is_success {
-        "false"
-    } else {
-        "true"
-    });
     html! {
         form hx-post="/settings/email" hx-swap="outerHTML" method="post" action="/settings/email" {
             label for="email" {
                 "Email"
                 input type="email" id="email" name="email" placeholder="Enter email address" value=(current_email) autocomplete="email"
-                    aria-invalid=[aria_invalid];
-                @if !message.is_empty() {
-                    small { (message) }
-                }
+                    aria-invalid=[aria_invalid(errors, "email", success)];
+                (message(errors, "email", success))
             }
             button type="submit" { "Update Email" }
         }
     }
 }
 
+/// `has_password` is false for accounts created through an identity
+/// provider, which set a first password without confirming an old one.
+/// `warning` flags a change that only partly succeeded.
 pub fn password_form(
-    current_password_message: &str,
-    new_password_message: &str,
-    current_password_is_success: bool,
-    new_password_is_success: bool,
+    has_password: bool,
+    errors: &FieldErrors,
+    success: Option<&str>,
+    warning: Option<&str>,
 ) -> Markup {
-    let current_aria = (!current_password_message.is_empty()).then_some(
-        if current_password_is_success {
-            "false"
-        } else {
-            "true"
-        },
-    );
-    let new_aria = (!new_password_message.is_empty()).then_some(
-        if new_password_is_success {
-            "false"
-        } else {
-            "true"
-        },
-    );
     html! {
         form hx-post="/settings/password" hx-swap="outerHTML" method="post" action="/settings/password" {
-            label for="current_password" {
-                "Current Password"
-                input type="password" id="current_password" name="current_password" placeholder="Enter current password" required
-                    aria-invalid=[current_aria];
-                @if !current_password_message.is_empty() {
-                    small { (current_password_message) }
+            @if has_password {
+                label for="current_password" {
+                    "Current Password"
+                    input type="password" id="current_password" name="current_password" placeholder="Enter current password" required
+                        aria-invalid=[aria_invalid(errors, "current_password", None)];
+                    (message(errors, "current_password", None))
                 }
             }
             label for="new_password" {
                 "New Password"
-                input type="password" id="new_password" name="new_password" placeholder="Enter new password" required minlength="8" maxlength="60"
-                    aria-invalid=[new_aria];
-                @if !new_password_message.is_empty() {
-                    small { (new_password_message) }
-                }
+                input type="password" id="new_password" name="new_password" placeholder="Enter new password" required autocomplete="new-password"
+                    hx-post="/password/strength" hx-trigger="input changed delay:300ms"
+                    hx-target="#password-feedback" hx-swap="outerHTML" hx-params="new_password"
+                    aria-invalid=[aria_invalid(errors, "new_password", success)];
+                (message(errors, "new_password", success))
+                (password_feedback(None))
+            }
+            @if let Some(text) = warning {
+                p role="alert" { (text) }
+            }
+            button type="submit" {
+                @if has_password { "Update Password" } @else { "Set Password" }
             }
-            button type="submit" { "Update Password" }
         }
     }
 }
@@ -0,0 +1,27 @@
+//! File upload form with a progress bar.
+
+use maud::{Markup, html};
+
+use crate::extractors::upload::FIELD;
+use crate::upload;
+use crate::validation::FieldErrors;
+
+use super::{aria_invalid, message};
+
+/// Posts to `/uploads` and replaces `#uploads` with the response. The
+/// progress bar is driven by `upload-progress.js`.
+pub fn upload_form(errors: &FieldErrors, success: Option<&str>) -> Markup {
+    let accept = upload::config().allowed_types().join(",");
+    html! {
+        form hx-post="/uploads" hx-encoding="multipart/form-data" hx-target="#uploads" hx-swap="outerHTML" data-upload-progress method="post" action="/uploads" enctype="multipart/form-data" {
+            label for=(FIELD) {
+                "File"
+                input type="file" id=(FIELD) name=(FIELD) accept=(accept) required
+                    aria-invalid=[aria_invalid(errors, FIELD, success)];
+                (message(errors, FIELD, success))
+            }
+            progress value="0" max="100" hidden {}
+            button type="submit" { "Upload" }
+        }
+    }
+}
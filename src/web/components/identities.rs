@@ -0,0 +1,67 @@
+//! Sign-in provider components: login buttons and the settings section
+//! for linking accounts.
+
+use maud::{Markup, html};
+
+use super::{IdentityDisplay, ProviderDisplay};
+
+/// "Sign in with ..." links, one per configured provider. `verb` is
+/// "Sign in" or "Sign up".
+pub fn provider_buttons(providers: &[ProviderDisplay], verb: &str) -> Markup {
+    html! {
+        @if !providers.is_empty() {
+            section {
+                @for provider in providers {
+                    a href={ "/auth/" (provider.name) } role="button" class="secondary outline" {
+                        (verb) " with " (provider.label)
+                    }
+                    " "
+                }
+            }
+        }
+    }
+}
+
+/// Swapped as a whole after unlinking. Connecting is a plain form post,
+/// since it leaves the site for the provider.
+pub fn identities_section(
+    identities: &[IdentityDisplay],
+    error: Option<&str>,
+) -> Markup {
+    html! {
+        section #identities hx-target="this" hx-swap="outerHTML" {
+            h2 { "Sign-in methods" }
+            table {
+                tbody {
+                    @for identity in identities {
+                        tr {
+                            td { (identity.label) }
+                            @if let Some(account) = identity.linked_as.as_deref() {
+                                td { "Connected as " (account) }
+                                td {
+                                    button
+                                        hx-delete={ "/settings/identities/" (identity.name) }
+                                        hx-confirm={ "Disconnect " (identity.label) "?" }
+                                        class="secondary"
+                                    {
+                                        "Disconnect"
+                                    }
+                                }
+                            } @else {
+                                td { "Not connected" }
+                                td {
+                                    form method="post" action={ "/settings/identities/" (identity.name) } {
+                                        button type="submit" { "Connect" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            @if let Some(message) = error {
+                p { small { (message) } }
+            }
+        }
+    }
+}
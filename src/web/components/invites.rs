@@ -0,0 +1,81 @@
+//! The invites page's list of codes and the form for new ones.
+
+use maud::{Markup, html};
+
+use crate::validation::FieldErrors;
+
+use super::forms::invite_form;
+use super::{InviteDisplay, NewInvite};
+
+/// Swapped as a whole after each new invite. `draft` and `max_uses` are
+/// as for [`invite_form`]; `created` is a new invite's code, shown only
+/// this once.
+pub fn invites_section(
+    invites: &[InviteDisplay],
+    draft: (&str, &str, &str),
+    max_uses: i64,
+    errors: &FieldErrors,
+    created: Option<&NewInvite>,
+) -> Markup {
+    html! {
+        section #invites {
+            @if let Some(invite) = created {
+                article {
+                    p {
+                        @if let Some(email) = invite.emailed_to.as_deref() {
+                            "Sent to " (email) ". "
+                        }
+                        "Copy the code now: it won't be shown again."
+                    }
+                    dl {
+                        dt { "Invite code" }
+                        dd { code { (invite.code) } }
+                        dt { "Sign-up link" }
+                        dd { code { (invite.url) } }
+                    }
+                }
+            }
+            (invite_form(draft, max_uses, errors))
+            @if invites.is_empty() {
+                p { "You haven't invited anyone yet." }
+            } @else {
+                table {
+                    thead {
+                        tr {
+                            th { "Sent to" }
+                            th { "Used" }
+                            th { "Expires" }
+                            th { "Actions" }
+                        }
+                    }
+                    tbody {
+                        @for invite in invites {
+                            tr {
+                                td { (invite.email.as_deref().unwrap_or("Not emailed")) }
+                                td { (invite.uses) }
+                                td {
+                                    (invite.expires_at)
+                                    @if !invite.usable {
+                                        br;
+                                        small { "No longer usable" }
+                                    }
+                                }
+                                td {
+                                    button
+                                        hx-delete={ "/invites/" (invite.id) }
+                                        hx-target="closest tr"
+                                        hx-swap="outerHTML"
+                                        hx-confirm="Revoke this invite? Accounts already created with it are kept."
+                                        class="secondary"
+                                    {
+                                        "Revoke"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,20 +1,56 @@
 use maud::{DOCTYPE, Markup, html};
 
+use crate::registration::{self, RegistrationMode};
+use crate::security_headers::csp_nonce;
+use crate::services::events::SIGNED_OUT;
+use crate::web::assets;
+
+use super::OrganizationDisplay;
+
+/// The stream every signed-in page listens to.
+const EVENTS_PATH: &str = "/events";
+
+/// HTMX settings compatible with the Content-Security-Policy: no `eval`, and
+/// its injected indicator `<style>` carries the request's nonce.
+fn htmx_config() -> String {
+    match csp_nonce() {
+        Some(nonce) => {
+            format!(r#"{{"allowEval":false,"inlineStyleNonce":"{nonce}"}}"#)
+        }
+        None => {
+            r#"{"allowEval":false,"includeIndicatorStyles":false}"#.to_owned()
+        }
+    }
+}
+
 pub fn base(username: &str, content: &Markup) -> Markup {
+    let signed_in = !username.is_empty();
     html! {
         (DOCTYPE)
         html lang="en" data-theme="light" {
             head {
                 meta charset="UTF-8";
                 meta name="viewport" content="width=device-width, initial-scale=1.0";
+                meta name="htmx-config" content=(htmx_config());
                 title { "Basic Site" }
-                link rel="stylesheet" href="/pico.min.css";
-                link rel="stylesheet" href="/pico.colors.min.css";
-                script src="/htmx.min.js" {}
+                link rel="stylesheet" href=(assets::url("pico.min.css"));
+                link rel="stylesheet" href=(assets::url("pico.colors.min.css"));
+                script src=(assets::url("htmx.min.js")) {}
+                @if signed_in {
+                    script src=(assets::url("sse.js")) {}
+                }
             }
-            body {
+            // Signed-in pages take live updates into elements with a
+            // matching `sse-swap`
+            body hx-ext=[signed_in.then_some("sse")]
+                sse-connect=[signed_in.then_some(EVENTS_PATH)]
+                sse-close=[signed_in.then_some(SIGNED_OUT)]
+            {
                 (navbar(username))
                 main class="container" {
+                    @if signed_in {
+                        div sse-swap=(SIGNED_OUT) {}
+                    }
                     (content)
                 }
             }
@@ -34,9 +70,23 @@ pub fn navbar(username: &str) -> Markup {
                 li { a href="/about" { "About" } }
                 @if username.is_empty() {
                     li { a href="/login" { "Log in" } }
-                    li { a href="/signup" { "Sign up" } }
+                    @if registration::mode() != RegistrationMode::Closed {
+                        li { a href="/signup" { "Sign up" } }
+                    }
                 } @else {
-                    li { a href={ "/users/" (username) } { (username) } }
+                    li sse-swap="username" { (user_link(username)) }
+                    li { a href="/uploads" { "Files" } }
+                    // Replaced by the switcher once the page has loaded
+                    li hx-get="/orgs/switcher" hx-trigger="load" hx-swap="outerHTML" {
+                        a href="/orgs" { "Organizations" }
+                    }
+                    // Replaced by the bell with its unread count
+                    li hx-get="/notifications/bell" hx-trigger="load" hx-swap="outerHTML" {
+                        a href="/notifications" { "Notifications" }
+                    }
+                    @if registration::mode().needs_invite() {
+                        li { a href="/invites" { "Invites" } }
+                    }
                     li { a href="/settings" { "Settings" } }
                     li {
                         a href="/" hx-delete="/session" hx-trigger="click" hx-swap="none" class="secondary" {
@@ -48,3 +98,67 @@ pub fn navbar(username: &str) -> Markup {
         }
     }
 }
+
+/// The navbar's link to the user's profile, pushed to open pages when
+/// they rename themselves.
+pub fn user_link(username: &str) -> Markup {
+    html! {
+        a href={ "/users/" (username) } { (username) }
+    }
+}
+
+/// Shown on a session's open pages once it's been revoked elsewhere.
+pub fn signed_out_notice() -> Markup {
+    html! {
+        article role="alert" {
+            "This device was signed out from another session. "
+            a href="/login" { "Log in again" }
+        }
+    }
+}
+
+/// Shown on a session's open pages once it has expired or been ended
+/// without a notice, e.g. by a password change.
+pub fn session_ended_notice() -> Markup {
+    html! {
+        article role="alert" {
+            "This session has ended. "
+            a href="/login" { "Log in again" }
+        }
+    }
+}
+
+/// The navbar's organization menu, named after `current` (a slug) when
+/// the page belongs to one of the user's organizations.
+pub fn org_switcher(
+    organizations: &[OrganizationDisplay],
+    current: Option<&str>,
+) -> Markup {
+    let current_name = organizations
+        .iter()
+        .find(|organization| Some(organization.slug.as_str()) == current)
+        .map_or("Organizations", |organization| organization.name.as_str());
+    html! {
+        li {
+            @if organizations.is_empty() {
+                a href="/orgs" { "Organizations" }
+            } @else {
+                details class="dropdown" {
+                    summary { (current_name) }
+                    ul dir="rtl" {
+                        @for organization in organizations {
+                            li {
+                                a href={ "/orgs/" (organization.slug) }
+                                    aria-current=[(Some(organization.slug.as_str()) == current).then_some("page")]
+                                {
+                                    (organization.name)
+                                }
+                            }
+                        }
+                        li { a href="/orgs" { "All organizations" } }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,17 +1,164 @@
+mod audit;
+mod clients;
 mod forms;
+mod identities;
+mod invites;
 mod layout;
+mod notifications;
+mod organizations;
+mod profile;
+mod sessions;
+mod uploads;
 
+pub use audit::audit_table;
+pub use clients::clients_section;
 pub use forms::{
-    email_form, login_form, password_form, signup_form, username_form,
+    avatar_form, email_form, login_form, notification_preferences_form,
+    organization_form, password_feedback, password_form, profile_form,
+    signup_form, username_form,
 };
-pub use layout::base;
+pub use identities::{identities_section, provider_buttons};
+pub use invites::invites_section;
+pub use layout::{
+    base, org_switcher, session_ended_notice, signed_out_notice, user_link,
+};
+pub use notifications::{
+    notification_bell, notification_item, notifications_section,
+};
+pub use organizations::{
+    members_section, org_invites_section, organizations_list,
+};
+pub use profile::profile_card;
+pub use sessions::sessions_table;
+pub use uploads::uploads_section;
+
+use crate::models::organization::Role;
 
 /// Display struct for rendering session info in templates.
 pub struct SessionDisplay {
     pub id: String,
-    pub ip_address: String,
+    /// Browser and OS, e.g. "Firefox on Linux".
+    pub device: String,
+    /// Device class, e.g. "Desktop" or "Mobile".
+    pub device_kind: &'static str,
+    /// Raw `User-Agent`, shown on hover.
     pub user_agent: String,
+    pub last_ip: String,
+    pub last_active: String,
     pub created_at: String,
     pub expires_at: String,
     pub is_current: bool,
 }
+
+/// A profile with the fields the viewer may not see already removed.
+pub struct ProfileDisplay {
+    pub username: String,
+    /// Falls back to the username when none is set.
+    pub display_name: String,
+    pub bio: Option<String>,
+    pub links: Vec<String>,
+    pub avatar_url: Option<String>,
+}
+
+/// Display struct for an uploaded file, with a signed download link.
+pub struct UploadDisplay {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    /// Human-readable, e.g. "1.5 MB".
+    pub size: String,
+    pub uploaded_at: String,
+    pub url: String,
+}
+
+/// An identity provider users can sign in with.
+pub struct ProviderDisplay {
+    pub name: String,
+    pub label: String,
+}
+
+/// A provider on the settings page, and whether the user linked it.
+pub struct IdentityDisplay {
+    pub name: String,
+    pub label: String,
+    /// How the linked account is shown (its email, or "your account");
+    /// `None` if not linked.
+    pub linked_as: Option<String>,
+}
+
+/// An app registered with this site's OIDC provider.
+pub struct ClientDisplay {
+    pub id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub created_at: String,
+}
+
+/// A newly registered app's credentials, shown once.
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub secret: String,
+}
+
+/// An invite on the invites page.
+pub struct InviteDisplay {
+    pub id: String,
+    pub email: Option<String>,
+    /// e.g. "1 of 5".
+    pub uses: String,
+    pub expires_at: String,
+    /// False once it has expired or been used up.
+    pub usable: bool,
+}
+
+/// A newly created invite's code, shown once.
+pub struct NewInvite {
+    pub code: String,
+    pub url: String,
+    pub emailed_to: Option<String>,
+}
+
+/// An organization the user belongs to.
+pub struct OrganizationDisplay {
+    pub name: String,
+    pub slug: String,
+    pub role: &'static str,
+}
+
+/// A member on an organization's page, with what the viewer may do to
+/// them.
+pub struct MemberDisplay {
+    pub user_id: String,
+    pub username: String,
+    pub role: Role,
+    pub joined_at: String,
+    /// Roles the viewer may switch this member to; empty if the viewer
+    /// can't change it.
+    pub assignable: Vec<Role>,
+    pub removable: bool,
+    pub is_you: bool,
+}
+
+/// A pending invitation on an organization's page.
+pub struct OrgInviteDisplay {
+    pub id: String,
+    pub email: String,
+    pub role: &'static str,
+    pub expires_at: String,
+    /// False once it has expired.
+    pub usable: bool,
+    /// Whether the viewer's role lets them revoke it.
+    pub revocable: bool,
+}
+
+/// A notification on the notifications page.
+pub struct NotificationDisplay {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    /// A path with more detail, if any.
+    pub link: Option<String>,
+    pub link_label: &'static str,
+    pub created_at: String,
+    pub unread: bool,
+}
@@ -0,0 +1,103 @@
+//! The navbar bell and the notifications list.
+
+use maud::{Markup, PreEscaped, html};
+
+use crate::services::notification;
+
+use super::NotificationDisplay;
+
+/// Counts above this show as "99+".
+const MAX_SHOWN_COUNT: i64 = 99;
+
+/// The navbar's link to the notifications page with the unread count.
+///
+/// Refreshes itself when a notification arrives or a response triggers
+/// `notifications-changed`, and every minute in case a live update was
+/// missed.
+pub fn notification_bell(unread: i64) -> Markup {
+    let label = match unread {
+        0 => "Notifications".to_owned(),
+        1 => "Notifications, 1 unread".to_owned(),
+        _ => format!("Notifications, {unread} unread"),
+    };
+    html! {
+        li hx-get="/notifications/bell" hx-trigger={ "every 60s, notifications-changed from:body, sse:" (notification::EVENT) } hx-swap="outerHTML" {
+            a href="/notifications" aria-label=(label) title=(label) {
+                // Bell emoji, escaped to keep the source ASCII
+                (PreEscaped("&#x1F514;"))
+                @if unread > MAX_SHOWN_COUNT {
+                    sup { (MAX_SHOWN_COUNT) "+" }
+                } @else if unread > 0 {
+                    sup { (unread) }
+                }
+            }
+        }
+    }
+}
+
+/// A single notification; swapped on its own when marked read.
+pub fn notification_item(notification: &NotificationDisplay) -> Markup {
+    html! {
+        article id={ "notification-" (notification.id) } {
+            header {
+                @if notification.unread {
+                    strong { (notification.title) }
+                } @else {
+                    (notification.title)
+                }
+                br;
+                small { (notification.created_at) }
+            }
+            @for paragraph in notification.body.split("\n\n") {
+                p {
+                    @for (index, line) in paragraph.lines().enumerate() {
+                        @if index > 0 {
+                            br;
+                        }
+                        (line)
+                    }
+                }
+            }
+            @if notification.link.is_some() || notification.unread {
+                footer {
+                    @if let Some(href) = notification.link.as_deref() {
+                        a href=(href) role="button" class="outline" { (notification.link_label) }
+                        " "
+                    }
+                    @if notification.unread {
+                        button
+                            hx-post={ "/notifications/" (notification.id) "/read" }
+                            hx-target={ "#notification-" (notification.id) }
+                            hx-swap="outerHTML"
+                            class="secondary"
+                        {
+                            "Mark read"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Swapped as a whole by "Mark all read".
+pub fn notifications_section(notifications: &[NotificationDisplay]) -> Markup {
+    let any_unread =
+        notifications.iter().any(|notification| notification.unread);
+    html! {
+        section #notifications {
+            @if notifications.is_empty() {
+                p { "You have no notifications." }
+            } @else {
+                @if any_unread {
+                    button hx-post="/notifications/read" hx-target="#notifications" hx-swap="outerHTML" class="secondary" {
+                        "Mark all read"
+                    }
+                }
+                @for notification in notifications {
+                    (notification_item(notification))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! An organization's members and pending invitations.
+
+use maud::{Markup, html};
+
+use crate::models::organization::Role;
+use crate::validation::FieldErrors;
+
+use super::forms::org_invite_form;
+use super::{MemberDisplay, OrgInviteDisplay, OrganizationDisplay};
+
+/// The user's organizations, linked to their pages.
+pub fn organizations_list(organizations: &[OrganizationDisplay]) -> Markup {
+    html! {
+        @if organizations.is_empty() {
+            p { "You aren't in any organizations yet." }
+        } @else {
+            table {
+                thead {
+                    tr {
+                        th { "Organization" }
+                        th { "Your role" }
+                    }
+                }
+                tbody {
+                    @for organization in organizations {
+                        tr {
+                            td { a href={ "/orgs/" (organization.slug) } { (organization.name) } }
+                            td { (organization.role) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Swapped as a whole after a role change or removal. `error` explains a
+/// refused change, such as the last owner leaving.
+pub fn members_section(
+    slug: &str,
+    members: &[MemberDisplay],
+    error: Option<&str>,
+) -> Markup {
+    html! {
+        section #members {
+            @if let Some(text) = error {
+                p role="alert" { (text) }
+            }
+            table {
+                thead {
+                    tr {
+                        th { "Member" }
+                        th { "Role" }
+                        th { "Joined" }
+                        th { "Actions" }
+                    }
+                }
+                tbody {
+                    @for member in members {
+                        @let url = format!("/orgs/{slug}/members/{}", member.user_id);
+                        tr {
+                            td {
+                                a href={ "/users/" (member.username) } { (member.username) }
+                                @if member.is_you {
+                                    " (you)"
+                                }
+                            }
+                            td {
+                                @if member.assignable.is_empty() {
+                                    (member.role.label())
+                                } @else {
+                                    select name="role" aria-label={ "Role of " (member.username) }
+                                        hx-post=(url) hx-trigger="change" hx-target="#members" hx-swap="outerHTML"
+                                    {
+                                        @for option in &member.assignable {
+                                            option value=(option.as_str()) selected[*option == member.role] { (option.label()) }
+                                        }
+                                    }
+                                }
+                            }
+                            td { (member.joined_at) }
+                            td {
+                                @if member.is_you {
+                                    button hx-delete=(url) hx-target="#members" hx-swap="outerHTML" hx-confirm="Leave this organization?" class="secondary" {
+                                        "Leave"
+                                    }
+                                } @else if member.removable {
+                                    button
+                                        hx-delete=(url)
+                                        hx-target="#members"
+                                        hx-swap="outerHTML"
+                                        hx-confirm={ "Remove " (member.username) " from this organization?" }
+                                        class="secondary"
+                                    {
+                                        "Remove"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Swapped as a whole after each invitation. `roles` and `draft` are as
+/// for [`org_invite_form`]; `sent_to` confirms a new invitation.
+pub fn org_invites_section(
+    slug: &str,
+    invites: &[OrgInviteDisplay],
+    roles: &[Role],
+    draft: (&str, Role),
+    errors: &FieldErrors,
+    sent_to: Option<&str>,
+) -> Markup {
+    html! {
+        section #org-invites {
+            @if let Some(email) = sent_to {
+                p { "Invitation sent to " (email) "." }
+            }
+            (org_invite_form(slug, roles, draft, errors))
+            @if !invites.is_empty() {
+                table {
+                    thead {
+                        tr {
+                            th { "Invited" }
+                            th { "Role" }
+                            th { "Expires" }
+                            th { "Actions" }
+                        }
+                    }
+                    tbody {
+                        @for invite in invites {
+                            tr {
+                                td { (invite.email) }
+                                td { (invite.role) }
+                                td {
+                                    (invite.expires_at)
+                                    @if !invite.usable {
+                                        br;
+                                        small { "Expired" }
+                                    }
+                                }
+                                td {
+                                    @if invite.revocable {
+                                        button
+                                            hx-delete={ "/orgs/" (slug) "/invites/" (invite.id) }
+                                            hx-target="closest tr"
+                                            hx-swap="outerHTML"
+                                            hx-confirm="Revoke this invitation?"
+                                            class="secondary"
+                                        {
+                                            "Revoke"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Profile card shown on public and owner profile pages.
+
+use maud::{Markup, html};
+
+use super::ProfileDisplay;
+
+/// Links are user-supplied, so they don't pass on ranking or a window
+/// reference.
+const LINK_REL: &str = "nofollow noopener ugc";
+
+pub fn profile_card(profile: &ProfileDisplay) -> Markup {
+    html! {
+        article #profile {
+            header {
+                @if let Some(url) = profile.avatar_url.as_deref() {
+                    img src=(url) alt="" width="96" height="96";
+                }
+                hgroup {
+                    h1 { (profile.display_name) }
+                    p { "@" (profile.username) }
+                }
+            }
+            @if let Some(bio) = profile.bio.as_deref() {
+                @for paragraph in bio.split("\n\n") {
+                    p { (paragraph) }
+                }
+            }
+            @if !profile.links.is_empty() {
+                ul {
+                    @for link in &profile.links {
+                        li {
+                            a href=(link) rel=(LINK_REL) {
+                                (link.trim_start_matches("https://").trim_start_matches("http://"))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Active sessions table shown on the profile page.
+
+use maud::{Markup, html};
+
+use super::SessionDisplay;
+
+/// Renders the user's sessions with per-row revoke buttons.
+///
+/// The current session can't be revoked from here (that's what logging out
+/// is for); "Sign out everywhere else" replaces the whole table.
+pub fn sessions_table(sessions: &[SessionDisplay]) -> Markup {
+    let has_others = sessions.iter().any(|session| !session.is_current);
+    html! {
+        section #sessions {
+            @if !sessions.is_empty() {
+                table {
+                    thead {
+                        tr {
+                            th { "Device" }
+                            th { "Last IP Address" }
+                            th { "Created" }
+                            th { "Expires" }
+                            th { "Actions" }
+                        }
+                    }
+                    tbody {
+                        @for session in sessions {
+                            // Removed when the session is revoked from another page
+                            tr data-theme=[session.is_current.then_some("primary")]
+                                sse-swap={ "session-revoked-" (session.id) } hx-swap="delete"
+                            {
+                                td title=(session.user_agent) {
+                                    (session.device) " — last active " (session.last_active)
+                                    br;
+                                    small { (session.device_kind) }
+                                }
+                                td { (session.last_ip) }
+                                td { (session.created_at) }
+                                td { (session.expires_at) }
+                                td {
+                                    @if session.is_current {
+                                        mark { "This device" }
+                                    } @else {
+                                        button
+                                            hx-delete={"/sessions/" (session.id)}
+                                            hx-target="closest tr"
+                                            hx-swap="outerHTML swap:1s"
+                                            hx-confirm="Are you sure you want to revoke this session?"
+                                            data-theme="outline"
+                                            role="button"
+                                        {
+                                            "Revoke"
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                @if has_others {
+                    button
+                        hx-delete="/sessions"
+                        hx-target="#sessions"
+                        hx-swap="outerHTML"
+                        hx-confirm="Sign out of every other device?"
+                        class="secondary"
+                    {
+                        "Sign out everywhere else"
+                    }
+                }
+            } @else {
+                p { "No active sessions found." }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! The uploads section: upload form and the user's files.
+
+use maud::{Markup, html};
+
+use crate::validation::FieldErrors;
+
+use super::UploadDisplay;
+use super::forms::upload_form;
+
+/// Swapped as a whole after each upload, so the new file appears in the
+/// list and the form resets.
+pub fn uploads_section(
+    uploads: &[UploadDisplay],
+    errors: &FieldErrors,
+    success: Option<&str>,
+) -> Markup {
+    html! {
+        section #uploads {
+            (upload_form(errors, success))
+            @if uploads.is_empty() {
+                p { "No files uploaded yet." }
+            } @else {
+                table {
+                    thead {
+                        tr {
+                            th { "File" }
+                            th { "Size" }
+                            th { "Uploaded" }
+                            th { "Actions" }
+                        }
+                    }
+                    tbody {
+                        @for upload in uploads {
+                            tr {
+                                td {
+                                    a href=(upload.url) { (upload.filename) }
+                                    br;
+                                    small { (upload.content_type) }
+                                }
+                                td { (upload.size) }
+                                td { (upload.uploaded_at) }
+                                td {
+                                    button
+                                        hx-delete={"/uploads/" (upload.id)}
+                                        hx-target="closest tr"
+                                        hx-swap="outerHTML"
+                                        hx-confirm="Delete this file?"
+                                        class="secondary"
+                                    {
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                p { small { "Download links expire; reload the page for fresh ones." } }
+            }
+        }
+    }
+}
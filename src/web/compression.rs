@@ -0,0 +1,42 @@
+//! Negotiated response compression for pages and JSON.
+
+use axum::body::HttpBody;
+use axum::http::{Response, header};
+use tower_http::compression::predicate::SizeAbove;
+use tower_http::compression::{CompressionLayer, Predicate};
+
+/// Bodies smaller than this are sent as-is. Most HTMX partials (a re-rendered
+/// form, an empty swap) land below it, where the framing overhead and CPU
+/// cost outweigh any savings.
+const MIN_SIZE: u16 = 1024;
+
+/// Compresses HTML and JSON bodies above [`MIN_SIZE`].
+///
+/// Static assets are excluded by content type; they are served
+/// pre-compressed by `web::assets`.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressiblePage;
+
+impl Predicate for CompressiblePage {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let is_page = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| {
+                content_type.starts_with("text/html")
+                    || content_type.starts_with("application/json")
+            });
+        is_page && SizeAbove::new(MIN_SIZE).should_compress(response)
+    }
+}
+
+/// Layer negotiating brotli, zstd or gzip from `Accept-Encoding`.
+pub fn layer() -> CompressionLayer<CompressiblePage> {
+    CompressionLayer::new()
+        .no_deflate()
+        .compress_when(CompressiblePage)
+}
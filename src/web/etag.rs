@@ -0,0 +1,94 @@
+//! `ETag` generation and `If-None-Match` handling for cacheable pages.
+//!
+//! Pages still depend on the logged-in user (the navbar), so responses are
+//! marked `private` and revalidated on every use; a match returns an empty
+//! `304 Not Modified` instead of the rendered page. Pages carrying a CSP
+//! nonce aren't routed through here: a `304` gets a fresh nonce in its
+//! headers that the cached body doesn't match.
+
+use std::fmt::Write as _;
+
+use axum::body::{self, Body};
+use axum::extract::Request;
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse as _, Response};
+use sha2::{Digest as _, Sha256};
+use tracing::warn;
+
+/// Largest page body that will be buffered for hashing.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Weak because the compression layer may re-encode the representation.
+fn compute_etag(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let mut etag = String::from("W/\"");
+    for byte in digest.iter().take(8) {
+        write!(etag, "{byte:02x}").expect("writing to a String cannot fail");
+    }
+    etag.push('"');
+    etag
+}
+
+fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    let weak_tag = |tag: &str| tag.trim_start_matches("W/").to_owned();
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_tag(tag) == weak_tag(etag))
+        })
+}
+
+/// Middleware adding an `ETag` to successful `GET` pages and answering
+/// matching conditional requests with `304`.
+///
+/// HTMX requests are passed through untouched: their fragments are swapped
+/// into the page by script and never served from the browser cache.
+pub async fn conditional_get(request: Request, next: Next) -> Response {
+    let is_cacheable_request =
+        matches!(*request.method(), Method::GET | Method::HEAD)
+            && !request.headers().contains_key("hx-request");
+    if !is_cacheable_request {
+        return next.run(request).await;
+    }
+
+    let request_headers = request.headers().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, response_body) = response.into_parts();
+    let bytes = match body::to_bytes(response_body, MAX_BODY).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            warn!("Failed to buffer page for ETag: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let etag = compute_etag(&bytes);
+    let Ok(etag_value) = HeaderValue::from_str(&etag) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let cache_control = HeaderValue::from_static("private, no-cache");
+
+    if matches(&request_headers, &etag) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        let headers = not_modified.headers_mut();
+        headers.insert(header::ETAG, etag_value);
+        headers.insert(header::CACHE_CONTROL, cache_control);
+        return not_modified;
+    }
+
+    parts.headers.insert(header::ETAG, etag_value);
+    parts
+        .headers
+        .entry(header::CACHE_CONTROL)
+        .or_insert(cache_control);
+    Response::from_parts(parts, Body::from(bytes))
+}
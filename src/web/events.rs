@@ -0,0 +1,140 @@
+//! The Server-Sent Events stream behind live updates. Every signed-in page
+//! connects through htmx's SSE extension (`static/sse.js`, vendored from
+//! `htmx-ext-sse`); handlers publish to it with
+//! [`Broker::publish`](crate::services::events::Broker::publish).
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, Sse};
+use axum::response::{IntoResponse as _, Response};
+use futures_util::stream::{self, Stream, StreamExt as _};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tokio::time::{self, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::extractors::session::CurrentSession;
+use crate::models::session::Session;
+use crate::services::events::{Event as Published, SIGNED_OUT, Subscription};
+use crate::util::current_time_micros;
+
+use super::components;
+
+/// Sent every few seconds, so proxies don't close the stream as idle. Each
+/// one first checks the session is still valid.
+pub const HEARTBEAT: &str = "heartbeat";
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long browsers wait before reconnecting a dropped stream.
+const RETRY: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Skip events published for other topics.
+    topic: Option<String>,
+}
+
+/// Streams the user's events until the page closes or its session ends.
+/// Signed-out requests get 204, which tells browsers to stop reconnecting.
+pub async fn stream(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+    current_opt: Option<CurrentSession>,
+) -> Response {
+    let Some(CurrentSession { user, session }) = current_opt else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    let subscription = state.events.subscribe(user.id, session.id, query.topic);
+    let hello = heartbeat().retry(RETRY);
+    let rest = events(subscription, state.db, session.id);
+    Sse::new(stream::once(async { Ok(hello) }).chain(rest)).into_response()
+}
+
+/// What the stream sends next.
+enum Wake {
+    Published(Arc<Published>),
+    Heartbeat,
+}
+
+fn heartbeat() -> Event {
+    Event::default().event(HEARTBEAT).data(HEARTBEAT)
+}
+
+/// The subscription's events and heartbeats, ending after [`SIGNED_OUT`] or
+/// once a heartbeat finds the session revoked or expired.
+fn events(
+    subscription: Subscription,
+    db: SqlitePool,
+    session_id: Uuid,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let publications =
+        stream::unfold(subscription, |mut listening| async move {
+            let published = listening.recv().await?;
+            Some((Wake::Published(published), listening))
+        });
+    let first_beat = Instant::now().checked_add(HEARTBEAT_INTERVAL);
+    let beats = time::interval_at(
+        first_beat.unwrap_or_else(Instant::now),
+        HEARTBEAT_INTERVAL,
+    );
+    let heartbeats = stream::unfold(beats, |mut interval| async move {
+        interval.tick().await;
+        Some((Wake::Heartbeat, interval))
+    });
+    let merged = stream::select(publications, heartbeats).boxed();
+
+    stream::unfold(Some(merged), move |open| {
+        let pool = db.clone();
+        async move {
+            let mut wakes = open?;
+            let (event, ends) = match wakes.next().await? {
+                Wake::Published(published) => {
+                    (to_event(&published), published.name == SIGNED_OUT)
+                }
+                Wake::Heartbeat
+                    if session_is_valid(&pool, session_id).await =>
+                {
+                    (heartbeat(), false)
+                }
+                Wake::Heartbeat => {
+                    let notice =
+                        components::session_ended_notice().into_string();
+                    let ended = Event::default().event(SIGNED_OUT).data(notice);
+                    (ended, true)
+                }
+            };
+            Some((Ok(event), (!ends).then_some(wakes)))
+        }
+    })
+}
+
+fn to_event(published: &Published) -> Event {
+    // Browsers drop events without data, so events that only trigger
+    // requests carry their name
+    let data = if published.html.is_empty() {
+        &published.name
+    } else {
+        &published.html
+    };
+    Event::default().event(&published.name).data(data)
+}
+
+/// Whether the session still exists and hasn't expired. A failed lookup
+/// keeps the stream open rather than signing the page out.
+async fn session_is_valid(db: &SqlitePool, session_id: Uuid) -> bool {
+    match Session::get_by_id(db, session_id).await {
+        Ok(found) => found
+            .is_some_and(|session| session.expires_at > current_time_micros()),
+        Err(err) => {
+            warn!(%session_id, "Failed to recheck a streaming session: {err}");
+            true
+        }
+    }
+}
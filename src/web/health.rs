@@ -0,0 +1,102 @@
+//! Liveness and readiness probes for load balancers and process supervisors.
+
+use std::fmt::Display;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::app_state::AppState;
+use crate::db;
+use crate::password::{self, HashingStats};
+
+/// One check's outcome. Failure details are logged rather than returned,
+/// since the probe is unauthenticated.
+#[derive(Serialize)]
+pub struct Check {
+    ok: bool,
+}
+
+impl Check {
+    const fn pass() -> Self {
+        Self { ok: true }
+    }
+
+    fn fail(check: &'static str, error: impl Display) -> Self {
+        warn!(check, "Readiness check failed: {error}");
+        Self { ok: false }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Checks {
+    database: Check,
+    migrations: Check,
+    job_processor: Check,
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    status: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    status: &'static str,
+    checks: Checks,
+    /// Informational only; a busy hashing queue doesn't fail readiness.
+    password_hashing: HashingStats,
+}
+
+/// The process is up and serving requests.
+pub async fn healthz() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+/// The process can do useful work: the database answers, the schema is
+/// current and the job processor is still receiving.
+pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let database = match sqlx::query("SELECT 1").execute(&state.db).await {
+        Ok(_) => Check::pass(),
+        Err(err) => Check::fail("database", err),
+    };
+
+    let migrations = match db::pending_migrations(&state.db).await {
+        Ok(pending) if pending.is_empty() => Check::pass(),
+        Ok(pending) => {
+            Check::fail("migrations", format!("pending: {pending:?}"))
+        }
+        Err(err) => Check::fail("migrations", err),
+    };
+
+    let job_processor = if state.job_tx.is_closed() {
+        Check::fail("job_processor", "job channel closed")
+    } else {
+        Check::pass()
+    };
+
+    let checks = Checks {
+        database,
+        migrations,
+        job_processor,
+    };
+    let ready =
+        checks.database.ok && checks.migrations.ok && checks.job_processor.ok;
+    let (status_code, status) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            checks,
+            password_hashing: password::stats(),
+        }),
+    )
+}
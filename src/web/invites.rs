@@ -0,0 +1,223 @@
+//! Invite codes for invite-only registration, created by users (or, in
+//! any mode but closed, admins).
+
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse as _, Redirect, Response};
+use serde::Deserialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::models::{invite::Invite, user::User};
+use crate::registration::{self, MAX_INVITE_DAYS};
+use crate::services::job::Job;
+use crate::util::{MICROS_PER_DAY, current_time_micros, format_utc};
+use crate::validation::{self, FieldErrors, Validate};
+
+use super::components::{self, InviteDisplay, NewInvite};
+use super::pages;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInvitePayload {
+    /// Optional; the sign-up link is emailed here.
+    #[serde(default)]
+    email: String,
+    max_uses: String,
+    days: String,
+}
+
+impl CreateInvitePayload {
+    fn email(&self) -> Option<&str> {
+        Some(self.email.trim()).filter(|email| !email.is_empty())
+    }
+
+    fn max_uses(&self) -> Option<i64> {
+        self.max_uses.trim().parse().ok()
+    }
+
+    fn days(&self) -> Option<i64> {
+        self.days.trim().parse().ok()
+    }
+}
+
+impl Validate for CreateInvitePayload {
+    /// The most uses this user may allow.
+    type Context = i64;
+
+    fn validate(&self, context: &i64) -> Result<(), FieldErrors> {
+        let max_uses = *context;
+        let mut errors = FieldErrors::new();
+        if let Some(email) = self.email() {
+            errors.check("email", validation::email(email));
+        }
+        if !self
+            .max_uses()
+            .is_some_and(|uses| (1..=max_uses).contains(&uses))
+        {
+            errors.add("max_uses", format!("Choose 1 to {max_uses} uses."));
+        }
+        if !self
+            .days()
+            .is_some_and(|days| (1..=MAX_INVITE_DAYS).contains(&days))
+        {
+            errors.add("days", format!("Choose 1 to {MAX_INVITE_DAYS} days."));
+        }
+        errors.into_result()
+    }
+}
+
+async fn invite_displays(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<Vec<InviteDisplay>, sqlx::Error> {
+    let now = current_time_micros();
+    Ok(Invite::list_by_creator(&state.db, user_id)
+        .await?
+        .into_iter()
+        .map(|invite| InviteDisplay {
+            id: invite.id.to_string(),
+            uses: format!("{} of {}", invite.use_count, invite.max_uses),
+            expires_at: format_utc(invite.expires_at),
+            usable: invite.is_usable(now),
+            email: invite.email,
+        })
+        .collect())
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !registration::mode().lets_invite(user.is_admin) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match invite_displays(&state, user.id).await {
+        Ok(invites) => pages::invites(
+            &user.username,
+            &invites,
+            registration::max_invite_uses(user.is_admin),
+        )
+        .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Creates an invite, emails its link if an address was given, and shows
+/// the code once.
+pub async fn create(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    Form(form): Form<CreateInvitePayload>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    if !registration::mode().lets_invite(user.is_admin) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let max_uses = registration::max_invite_uses(user.is_admin);
+
+    let created = if let Err(errors) = form.validate(&max_uses) {
+        Err(errors)
+    } else {
+        let code = registration::generate_code();
+        let now = current_time_micros();
+        let days = form.days().unwrap_or(registration::DEFAULT_INVITE_DAYS);
+        let invite = Invite {
+            id: Uuid::new_v4(),
+            code_hash: registration::hash_code(&code),
+            created_by: user.id,
+            email: form.email().map(str::to_owned),
+            max_uses: form.max_uses().unwrap_or(1),
+            use_count: 0,
+            expires_at: now.saturating_add(days.saturating_mul(MICROS_PER_DAY)),
+            created_at: now,
+        };
+        if let Err(err) = Invite::insert(&state.db, &invite).await {
+            return internal_error(err).into_response();
+        }
+        let url = registration::invite_url(&code);
+        if let Some(email) = invite.email.as_deref() {
+            send_invite(&state, &user.username, email, &code, &url, days);
+        }
+        Ok(NewInvite {
+            code,
+            url,
+            emailed_to: invite.email,
+        })
+    };
+
+    let invites = match invite_displays(&state, user.id).await {
+        Ok(invites) => invites,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    match created {
+        Ok(new_invite) => components::invites_section(
+            &invites,
+            ("", "", ""),
+            max_uses,
+            &FieldErrors::new(),
+            Some(&new_invite),
+        )
+        .into_response(),
+        Err(errors) => components::invites_section(
+            &invites,
+            (&form.email, &form.max_uses, &form.days),
+            max_uses,
+            &errors,
+            None,
+        )
+        .into_response(),
+    }
+}
+
+fn send_invite(
+    state: &AppState,
+    inviter: &str,
+    email: &str,
+    code: &str,
+    url: &str,
+    days: i64,
+) {
+    let body = format!(
+        "Hi,\n\n\
+        {inviter} has invited you to create an account. Sign up here:\n\n\
+        {url}\n\n\
+        or enter the invite code {code} on the sign-up page. The invite \
+        expires in {days} days.\n"
+    );
+    let job = Job::SendEmail {
+        to: email.to_owned(),
+        subject: format!("{inviter} invited you to sign up"),
+        body,
+    };
+    if let Err(err) = state.job_tx.send(job) {
+        warn!("Failed to enqueue invite email: {err}");
+    }
+}
+
+/// Revokes one of the user's invites; the row is swapped out with the
+/// empty response.
+pub async fn delete(
+    Path(raw_id): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let Ok(invite_id) = Uuid::parse_str(&raw_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    match Invite::delete_owned(&state.db, invite_id, user.id).await {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
@@ -1,16 +1,74 @@
+use axum::extract::{Query, State};
 use axum::response::{IntoResponse, Redirect};
+use serde::Deserialize;
 
+use crate::app_state::AppState;
+use crate::bot_check::{self, Challenge};
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
 use crate::models::user::User;
+use crate::util::current_time_micros;
 
-use super::{components, pages};
+use super::{components, oidc, pages};
 
-pub async fn get(user: Option<User>) -> impl IntoResponse {
-    let Some(_) = user else {
-        return pages::login_page().into_response();
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    /// Where to go after logging in, see [`local_path`].
+    next: Option<String>,
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    user: Option<User>,
+    client: ClientInfo,
+    Query(query): Query<LoginQuery>,
+) -> impl IntoResponse {
+    let next = query.next.as_deref().and_then(local_path);
+    if user.is_some() {
+        return Redirect::to(next.unwrap_or("/")).into_response();
+    }
+    let challenge = match login_challenge(&state, "", &client.ip).await {
+        Ok(challenge) => challenge,
+        Err(err) => return internal_error(err).into_response(),
     };
-    Redirect::to("/").into_response()
+    pages::login_page(
+        &oidc::provider_displays(&state),
+        next,
+        challenge.as_ref(),
+    )
+    .into_response()
+}
+
+/// A challenge for the login form, if `username` or `ip` has failed to
+/// log in too often lately.
+pub async fn login_challenge(
+    state: &AppState,
+    username: &str,
+    ip: &str,
+) -> Result<Option<Challenge>, sqlx::Error> {
+    let now = current_time_micros();
+    let needed =
+        bot_check::login_needs_challenge(&state.db, username, ip, now).await?;
+    Ok(needed
+        .then(|| Challenge::issue(bot_check::Form::Login, now))
+        .flatten())
+}
+
+pub fn login_form(
+    username: &str,
+    error_message: &str,
+    next: Option<&str>,
+    challenge: Option<&Challenge>,
+) -> impl IntoResponse {
+    components::login_form(username, error_message, next, challenge)
 }
 
-pub fn login_form(username: &str, error_message: &str) -> impl IntoResponse {
-    components::login_form(username, error_message)
+/// `next` if it's a path on this site, so a crafted login link can't send
+/// the user somewhere else afterwards.
+pub fn local_path(next: &str) -> Option<&str> {
+    let is_local = next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.starts_with("/\\")
+        && !next.chars().any(char::is_control);
+    is_local.then_some(next)
 }
@@ -1,54 +1,66 @@
 //! Web routes.
 use axum::{
     Router,
-    http::header,
-    response::IntoResponse,
+    extract::DefaultBodyLimit,
+    middleware::from_fn,
     routing::{delete, get, post},
 };
 
 use crate::app_state::AppState;
+use crate::avatar;
+use crate::extractors::session::refresh_session_cookie;
+use crate::extractors::upload;
 
 mod about;
+pub mod assets;
+mod audit;
+mod clients;
 pub mod components;
+pub mod compression;
+mod etag;
+mod events;
+mod health;
 mod home;
+mod invites;
 mod login;
+mod notifications;
+mod oauth;
+mod oidc;
+mod organizations;
 pub mod pages;
+mod password;
 mod profile;
 mod session;
 mod settings;
 mod signup;
+mod uploads;
 
 use about::about;
 use home::home;
 use profile::profile;
 
-async fn get_pico_css() -> impl IntoResponse {
-    (
-        [(header::CONTENT_TYPE, "text/css")],
-        include_str!("../../static/pico.min.css"),
-    )
-}
+/// Shown for unique violations and held names alike, so the form doesn't
+/// reveal which names were recently released.
+const USERNAME_TAKEN: &str =
+    "Username is taken or too similar to an existing one";
 
-async fn get_pico_colors() -> impl IntoResponse {
-    (
-        [(header::CONTENT_TYPE, "text/css")],
-        include_str!("../../static/pico.colors.min.css"),
-    )
-}
+/// Shown whatever tripped the bot checks, so bots can't tell which.
+const BOT_SUSPECTED: &str =
+    "We couldn't tell you apart from a bot. Wait a moment, then try again.";
 
-async fn get_htmx() -> impl IntoResponse {
-    (
-        [(header::CONTENT_TYPE, "text/javascript")],
-        include_str!("../../static/htmx.min.js"),
+/// Static assets (CSS, JS) - no request logging
+pub fn static_router() -> Router<AppState> {
+    assets::ROOT_NAMES.iter().fold(
+        Router::new().route("/static/{file}", get(assets::get)),
+        |router, name| router.route(&format!("/{name}"), get(assets::get_root)),
     )
 }
 
-/// Static assets (CSS, JS) - no request logging
-pub fn static_router() -> Router<AppState> {
+/// Health probes - no request logging, they're polled constantly.
+pub fn health_router() -> Router<AppState> {
     Router::new()
-        .route("/pico.min.css", get(get_pico_css))
-        .route("/pico.colors.min.css", get(get_pico_colors))
-        .route("/htmx.min.js", get(get_htmx))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
 }
 
 /// Dynamic routes - with request logging
@@ -57,12 +69,96 @@ pub fn router() -> Router<AppState> {
         .route("/", get(home))
         .route("/about", get(about))
         .route("/users/{username}", get(profile))
+        .route(
+            "/users/{username}/avatar",
+            get(profile::avatar).layer(from_fn(etag::conditional_get)),
+        )
         .route("/login", get(login::get))
+        .route("/auth/{provider}", get(oidc::start))
+        .route("/.well-known/openid-configuration", get(oauth::discovery))
+        .route("/oauth/jwks", get(oauth::jwks))
+        .route(
+            "/oauth/authorize",
+            get(oauth::authorize).post(oauth::consent),
+        )
+        .route("/oauth/token", post(oauth::token))
+        .route("/auth/{provider}/callback", get(oidc::callback))
         .route("/session", post(session::post).delete(session::delete))
+        .route("/sessions", delete(session::delete_others))
         .route("/sessions/{session_id}", delete(session::delete_by_id))
+        .route(
+            "/sessions/revoke/{token}",
+            get(session::confirm_revoke_from_alert)
+                .post(session::revoke_from_alert),
+        )
         .route("/signup", get(signup::get).post(signup::post))
+        .route("/password/strength", post(password::strength))
         .route("/settings", get(settings::get))
         .route("/settings/username", post(settings::update_username))
         .route("/settings/password", post(settings::update_password))
         .route("/settings/email", post(settings::update_email))
+        .route("/settings/profile", post(settings::update_profile))
+        .route(
+            "/settings/avatar",
+            // Room for the multipart framing around a maximum-size image
+            post(settings::update_avatar)
+                .layer(DefaultBodyLimit::max(
+                    avatar::MAX_UPLOAD_BYTES.saturating_add(64 * 1024),
+                ))
+                .delete(settings::delete_avatar),
+        )
+        .route(
+            "/settings/identities/{provider}",
+            post(oidc::link).delete(oidc::unlink),
+        )
+        .route("/settings/security", get(audit::security_activity))
+        .route(
+            "/uploads",
+            get(uploads::list)
+                .post(uploads::create)
+                .layer(DefaultBodyLimit::max(upload::body_limit())),
+        )
+        .route(
+            "/uploads/{upload_id}",
+            get(uploads::download).delete(uploads::delete),
+        )
+        .route("/events", get(events::stream))
+        .route("/notifications", get(notifications::list))
+        .route("/notifications/bell", get(notifications::bell))
+        .route("/notifications/read", post(notifications::mark_all_read))
+        .route(
+            "/notifications/preferences",
+            post(notifications::update_preferences),
+        )
+        .route(
+            "/notifications/{notification_id}/read",
+            post(notifications::mark_read),
+        )
+        .route("/invites", get(invites::list).post(invites::create))
+        .route("/invites/{invite_id}", delete(invites::delete))
+        .route(
+            "/orgs",
+            get(organizations::list).post(organizations::create),
+        )
+        .route("/orgs/switcher", get(organizations::switcher))
+        .route(
+            "/orgs/join/{token}",
+            get(organizations::join_page).post(organizations::join),
+        )
+        .route("/orgs/{org_slug}", get(organizations::show))
+        .route("/orgs/{org_slug}/invites", post(organizations::invite))
+        .route(
+            "/orgs/{org_slug}/invites/{invite_id}",
+            delete(organizations::revoke_invite),
+        )
+        .route(
+            "/orgs/{org_slug}/members/{user_id}",
+            post(organizations::update_member)
+                .delete(organizations::remove_member),
+        )
+        .route("/admin/audit", get(audit::admin_log))
+        .route("/admin/clients", get(clients::list).post(clients::create))
+        .route("/admin/clients/{client_id}", delete(clients::delete))
+        .layer(from_fn(refresh_session_cookie))
+        .layer(compression::layer())
 }
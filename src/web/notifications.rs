@@ -0,0 +1,173 @@
+//! The notifications page, the navbar bell and delivery preferences.
+//! Notifications themselves are created through
+//! [`crate::services::notification::notify`].
+
+use std::collections::HashMap;
+
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse as _, Redirect, Response};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::models::{
+    notification::{Notification, NotificationKind},
+    notification_preference::NotificationPreference,
+    user::User,
+};
+use crate::util::{current_time_micros, format_utc};
+
+use super::components::{self, NotificationDisplay};
+use super::pages;
+
+/// Notifications listed on the page, newest first.
+const PAGE_SIZE: i64 = 50;
+
+/// Tells the bell to refresh its count.
+const CHANGED: (&str, &str) = ("HX-Trigger", "notifications-changed");
+
+fn display(notification: Notification) -> NotificationDisplay {
+    NotificationDisplay {
+        id: notification.id.to_string(),
+        title: notification.title,
+        body: notification.body,
+        link: notification.link,
+        link_label: notification.kind.link_label(),
+        created_at: format_utc(notification.created_at),
+        unread: notification.read_at.is_none(),
+    }
+}
+
+async fn displays(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<Vec<NotificationDisplay>, sqlx::Error> {
+    Ok(Notification::list_by_user(&state.db, user_id, PAGE_SIZE)
+        .await?
+        .into_iter()
+        .map(display)
+        .collect())
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let notifications = match displays(&state, user.id).await {
+        Ok(notifications) => notifications,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    match NotificationPreference::list_by_user(&state.db, user.id).await {
+        Ok(preferences) => {
+            pages::notifications(&user.username, &notifications, &preferences)
+                .into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// The navbar bell, loaded after the page and polled from then on.
+pub async fn bell(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        // Leaves the plain link in place
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    match Notification::count_unread(&state.db, user.id).await {
+        Ok(unread) => components::notification_bell(unread).into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+pub async fn mark_read(
+    Path(raw_id): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let Ok(notification_id) = Uuid::parse_str(&raw_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let now = current_time_micros();
+    match Notification::mark_read(&state.db, notification_id, user.id, now)
+        .await
+    {
+        Ok(Some(notification)) => (
+            [CHANGED],
+            components::notification_item(&display(notification)),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+pub async fn mark_all_read(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let now = current_time_micros();
+    if let Err(err) = Notification::mark_all_read(&state.db, user.id, now).await
+    {
+        return internal_error(err).into_response();
+    }
+    match displays(&state, user.id).await {
+        Ok(notifications) => {
+            ([CHANGED], components::notifications_section(&notifications))
+                .into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Saves every kind's preference from the form's checkboxes, which are
+/// only submitted when checked.
+pub async fn update_preferences(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let checked = |kind: NotificationKind, channel: &str| {
+        form.contains_key(&format!("{}.{channel}", kind.as_str()))
+    };
+    let preferences: Vec<NotificationPreference> = NotificationKind::ALL
+        .into_iter()
+        .map(|kind| NotificationPreference {
+            kind,
+            email: checked(kind, "email"),
+            in_app: checked(kind, "in_app"),
+        })
+        .collect();
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    for preference in &preferences {
+        if let Err(err) =
+            NotificationPreference::upsert(&mut *tx, user.id, preference).await
+        {
+            return internal_error(err).into_response();
+        }
+    }
+    if let Err(err) = tx.commit().await {
+        return internal_error(err).into_response();
+    }
+    components::notification_preferences_form(&preferences, true)
+        .into_response()
+}
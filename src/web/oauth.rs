@@ -0,0 +1,543 @@
+//! This site as an OIDC provider: discovery, signing keys, the
+//! authorization and consent pages, and the token endpoint. Userinfo is
+//! served under `/api/v1`.
+//!
+//! Only the authorization-code flow with S256 PKCE is supported, for
+//! confidential clients registered from `/admin/clients`.
+
+use axum::Form;
+use axum::extract::{OriginalUri, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse as _, Json, Redirect, Response};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use percent_encoding::{
+    NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::warn;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
+use crate::extractors::session::CurrentSession;
+use crate::issuer::{
+    self, AccessTokenClaims, IdTokenClaims, SCOPES, UserClaims,
+};
+use crate::jwt::{self, JwkSet};
+use crate::models::{
+    audit_event::AuditKind,
+    oauth_client::OAuthClient,
+    oauth_code::{CODE_TTL_MICROS, OAuthCode},
+    oauth_consent::OAuthConsent,
+    profile::Profile,
+    user::User,
+};
+use crate::oidc;
+use crate::util::{base_url, current_time_micros};
+
+use super::{audit, pages};
+
+/// An authorization request, as sent by the client and as posted back
+/// from the consent page. Missing parameters are empty.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthorizeParams {
+    #[serde(default)]
+    client_id: String,
+    #[serde(default)]
+    redirect_uri: String,
+    #[serde(default)]
+    response_type: String,
+    #[serde(default)]
+    scope: String,
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    nonce: String,
+    #[serde(default)]
+    code_challenge: String,
+    #[serde(default)]
+    code_challenge_method: String,
+}
+
+impl AuthorizeParams {
+    /// The parameters the consent page posts back.
+    fn fields(&self) -> [(&'static str, &str); 8] {
+        [
+            ("client_id", &self.client_id),
+            ("redirect_uri", &self.redirect_uri),
+            ("response_type", &self.response_type),
+            ("scope", &self.scope),
+            ("state", &self.state),
+            ("nonce", &self.nonce),
+            ("code_challenge", &self.code_challenge),
+            ("code_challenge_method", &self.code_challenge_method),
+        ]
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConsentForm {
+    #[serde(flatten)]
+    params: AuthorizeParams,
+    /// "allow" or "deny".
+    decision: String,
+}
+
+/// An authorization request that passed every check.
+struct Authorization {
+    client: OAuthClient,
+    params: AuthorizeParams,
+    scopes: Vec<&'static str>,
+}
+
+impl Authorization {
+    fn scope(&self) -> String {
+        self.scopes.join(" ")
+    }
+}
+
+/// Sends the browser back to the client with `params` (and the request's
+/// `state`) added to its redirect URI.
+fn redirect_to_client(
+    redirect_uri: &str,
+    request_state: &str,
+    params: &[(&str, &str)],
+) -> Response {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        return authorize_error("", "The app's redirect URI is invalid.");
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        for &(name, value) in params {
+            query.append_pair(name, value);
+        }
+        if !request_state.is_empty() {
+            query.append_pair("state", request_state);
+        }
+    }
+    Redirect::to(url.as_str()).into_response()
+}
+
+fn authorize_error(username: &str, message: &str) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        pages::authorize_error(username, message),
+    )
+        .into_response()
+}
+
+/// Checks the request. Problems with the client or redirect URI are shown
+/// to the user, since the redirect URI can't be trusted; any others go
+/// back to the client as an `error` parameter.
+async fn check(
+    state: &AppState,
+    username: &str,
+    params: AuthorizeParams,
+) -> Result<Authorization, Response> {
+    let client = match OAuthClient::get(&state.db, &params.client_id).await {
+        Ok(Some(client)) => client,
+        Ok(None) => {
+            return Err(authorize_error(
+                username,
+                "The app isn't registered with this site.",
+            ));
+        }
+        Err(err) => return Err(internal_error(err).into_response()),
+    };
+    if !client.allows_redirect(&params.redirect_uri) {
+        return Err(authorize_error(
+            username,
+            "The app asked to return to an address it didn't register.",
+        ));
+    }
+
+    let scopes = issuer::supported_scopes(&params.scope);
+    let problem = if params.response_type != "code" {
+        Some((
+            "unsupported_response_type",
+            "Only response_type=code is supported",
+        ))
+    } else if !scopes.contains(&"openid") {
+        Some(("invalid_scope", "The openid scope is required"))
+    } else if params.code_challenge.is_empty()
+        || params.code_challenge_method != "S256"
+    {
+        Some((
+            "invalid_request",
+            "PKCE with code_challenge_method=S256 is required",
+        ))
+    } else {
+        None
+    };
+    if let Some((error, description)) = problem {
+        return Err(redirect_to_client(
+            &params.redirect_uri,
+            &params.state,
+            &[("error", error), ("error_description", description)],
+        ));
+    }
+
+    Ok(Authorization {
+        client,
+        params,
+        scopes,
+    })
+}
+
+/// Issues a code for the request and sends it to the client.
+async fn issue_code(
+    state: &AppState,
+    authorization: &Authorization,
+    current: &CurrentSession,
+) -> Response {
+    let now = current_time_micros();
+    if let Err(err) =
+        OAuthCode::delete_before(&state.db, now.saturating_sub(CODE_TTL_MICROS))
+            .await
+    {
+        warn!("Failed to prune unused authorization codes: {err}");
+    }
+
+    let code = oidc::random_token();
+    let params = &authorization.params;
+    let record = OAuthCode {
+        code_hash: issuer::hash_secret(&code),
+        client_id: authorization.client.id.clone(),
+        user_id: current.user.id,
+        redirect_uri: params.redirect_uri.clone(),
+        scope: authorization.scope(),
+        nonce: Some(params.nonce.clone()).filter(|nonce| !nonce.is_empty()),
+        code_challenge: params.code_challenge.clone(),
+        auth_time: current.session.created_at,
+        created_at: now,
+    };
+    if let Err(err) = OAuthCode::insert(&state.db, &record).await {
+        return internal_error(err).into_response();
+    }
+    redirect_to_client(&params.redirect_uri, &params.state, &[("code", &code)])
+}
+
+/// The discovery document clients configure themselves from.
+pub async fn discovery() -> Json<Value> {
+    let base = base_url();
+    Json(json!({
+        "issuer": issuer::issuer(),
+        "authorization_endpoint": format!("{base}/oauth/authorize"),
+        "token_endpoint": format!("{base}/oauth/token"),
+        "userinfo_endpoint": issuer::userinfo_url(),
+        "jwks_uri": format!("{base}/oauth/jwks"),
+        "scopes_supported": SCOPES,
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": [jwt::ALGORITHM],
+        "token_endpoint_auth_methods_supported": [
+            "client_secret_basic",
+            "client_secret_post",
+        ],
+        "code_challenge_methods_supported": ["S256"],
+        "claims_supported": [
+            "sub",
+            "preferred_username",
+            "name",
+            "email",
+            "email_verified",
+        ],
+    }))
+}
+
+/// The public keys ID and access tokens are signed with.
+pub async fn jwks() -> Json<JwkSet> {
+    Json(issuer::config().jwks())
+}
+
+/// Starts an authorization: signs the user in if needed, then asks for
+/// consent unless they already gave it for these scopes.
+pub async fn authorize(
+    State(state): State<AppState>,
+    current_opt: Option<CurrentSession>,
+    OriginalUri(uri): OriginalUri,
+    Query(params): Query<AuthorizeParams>,
+) -> Response {
+    let username = current_opt
+        .as_ref()
+        .map(|signed_in| signed_in.user.username.clone())
+        .unwrap_or_default();
+    let authorization = match check(&state, &username, params).await {
+        Ok(authorization) => authorization,
+        Err(response) => return response,
+    };
+
+    let Some(current) = current_opt else {
+        // The session cookie is SameSite=Strict, so it's missing when the
+        // app sent the browser here. Going on from a page of our own
+        // brings it back, or shows the login form if there's no session.
+        let back_here = uri.to_string();
+        let login = format!(
+            "/login?next={}",
+            utf8_percent_encode(&back_here, NON_ALPHANUMERIC)
+        );
+        return pages::continue_to(&login).into_response();
+    };
+
+    let client_id = &authorization.client.id;
+    match OAuthConsent::get_scope(&state.db, current.user.id, client_id).await {
+        Ok(Some(granted))
+            if issuer::covers(&granted, &authorization.scopes) =>
+        {
+            issue_code(&state, &authorization, &current).await
+        }
+        Ok(_) => pages::consent(
+            &current.user.username,
+            &authorization.client.name,
+            &authorization.scopes,
+            &authorization.params.fields(),
+        )
+        .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// The answer from the consent page.
+pub async fn consent(
+    State(state): State<AppState>,
+    current_opt: Option<CurrentSession>,
+    client: ClientInfo,
+    Form(form): Form<ConsentForm>,
+) -> Response {
+    let Some(current) = current_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let authorization =
+        match check(&state, &current.user.username, form.params).await {
+            Ok(authorization) => authorization,
+            Err(response) => return response,
+        };
+    let params = &authorization.params;
+    if form.decision != "allow" {
+        return redirect_to_client(
+            &params.redirect_uri,
+            &params.state,
+            &[
+                ("error", "access_denied"),
+                ("error_description", "The user declined"),
+            ],
+        );
+    }
+
+    let consent = OAuthConsent {
+        user_id: current.user.id,
+        client_id: authorization.client.id.clone(),
+        scope: authorization.scope(),
+        created_at: current_time_micros(),
+    };
+    if let Err(err) = OAuthConsent::upsert(&state.db, &consent).await {
+        return internal_error(err).into_response();
+    }
+    audit::record(
+        &state,
+        &client,
+        Some(current.user.id),
+        &current.user.username,
+        AuditKind::AppAuthorized,
+        format!("{} ({})", authorization.client.name, consent.scope),
+    )
+    .await;
+    issue_code(&state, &authorization, &current).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenParams {
+    #[serde(default)]
+    grant_type: String,
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    redirect_uri: String,
+    #[serde(default)]
+    code_verifier: String,
+    /// For `client_secret_post`; HTTP Basic credentials take precedence.
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// An OAuth error response from the token endpoint.
+fn token_error(status: StatusCode, error: &str, description: &str) -> Response {
+    let mut response = (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(json!({ "error": error, "error_description": description })),
+    )
+        .into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            header::HeaderValue::from_static(r#"Basic realm="token""#),
+        );
+    }
+    response
+}
+
+/// Undoes the form encoding applied to HTTP Basic client credentials
+/// (RFC 6749 section 2.3.1).
+fn form_decode(value: &str) -> String {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// The client ID and secret from an `Authorization: Basic` header, else
+/// from the form.
+fn client_credentials(
+    headers: &HeaderMap,
+    params: &TokenParams,
+) -> Option<(String, String)> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "));
+    if let Some(encoded) = basic {
+        let decoded = STANDARD.decode(encoded.trim()).ok()?;
+        let text = String::from_utf8(decoded).ok()?;
+        let (id, secret) = text.split_once(':')?;
+        return Some((form_decode(id), form_decode(secret)));
+    }
+    Some((params.client_id.clone()?, params.client_secret.clone()?))
+}
+
+/// Whether the token request may exchange `code`: it was issued to this
+/// client for the same redirect URI, and the PKCE verifier matches.
+fn redeemable(
+    code: &OAuthCode,
+    client: &OAuthClient,
+    params: &TokenParams,
+) -> bool {
+    let challenge = oidc::pkce_challenge(&params.code_verifier);
+    let same_client = code.client_id == client.id;
+    same_client
+        && code.redirect_uri == params.redirect_uri
+        && code.code_challenge == challenge
+}
+
+/// Exchanges an authorization code for an ID token and an access token.
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(params): Form<TokenParams>,
+) -> Response {
+    let Some((client_id, secret)) = client_credentials(&headers, &params)
+    else {
+        return token_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid_client",
+            "Client authentication is required",
+        );
+    };
+    let client = match OAuthClient::get(&state.db, &client_id).await {
+        Ok(Some(client))
+            if client.secret_hash == issuer::hash_secret(&secret) =>
+        {
+            client
+        }
+        Ok(_) => {
+            return token_error(
+                StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "Unknown client or wrong secret",
+            );
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+    if params.grant_type != "authorization_code" {
+        return token_error(
+            StatusCode::BAD_REQUEST,
+            "unsupported_grant_type",
+            "Only authorization_code is supported",
+        );
+    }
+
+    let now = current_time_micros();
+    let code = match OAuthCode::take(
+        &state.db,
+        &issuer::hash_secret(&params.code),
+        now.saturating_sub(CODE_TTL_MICROS),
+    )
+    .await
+    {
+        Ok(Some(code)) if redeemable(&code, &client, &params) => code,
+        Ok(_) => {
+            return token_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+                "The code is invalid, expired, already used, or doesn't match",
+            );
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    match issue_tokens(&state, &code, now.div_euclid(1_000_000)).await {
+        Ok(body) => {
+            ([(header::CACHE_CONTROL, "no-store")], Json(body)).into_response()
+        }
+        Err(response) => response,
+    }
+}
+
+/// The token response for an exchanged code.
+async fn issue_tokens(
+    state: &AppState,
+    code: &OAuthCode,
+    now_secs: i64,
+) -> Result<Value, Response> {
+    let user = match User::get_by_id(&state.db, code.user_id).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => {
+            return Err(token_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_grant",
+                "The account no longer exists",
+            ));
+        }
+        Err(err) => return Err(internal_error(err).into_response()),
+    };
+    let profile = Profile::get(&state.db, user.id)
+        .await
+        .map_err(|err| internal_error(err).into_response())?;
+
+    let config = issuer::config();
+    let expires_at = now_secs.saturating_add(config.token_ttl_secs());
+    let id_token = IdTokenClaims {
+        iss: issuer::issuer(),
+        aud: code.client_id.clone(),
+        exp: expires_at,
+        iat: now_secs,
+        auth_time: code.auth_time.div_euclid(1_000_000),
+        nonce: code.nonce.clone(),
+        user: UserClaims::new(&user, &profile, &code.scope),
+    };
+    let access_token = AccessTokenClaims {
+        iss: issuer::issuer(),
+        sub: user.id.to_string(),
+        aud: issuer::userinfo_url(),
+        client_id: code.client_id.clone(),
+        scope: code.scope.clone(),
+        exp: expires_at,
+        iat: now_secs,
+    };
+    let key = config.signing_key();
+    let sign_both = jwt::sign(&id_token, key).and_then(|id| {
+        jwt::sign(&access_token, key).map(|access| (id, access))
+    });
+    let (id_jwt, access_jwt) =
+        sign_both.map_err(|err| internal_error(err).into_response())?;
+    Ok(json!({
+        "access_token": access_jwt,
+        "token_type": "Bearer",
+        "expires_in": config.token_ttl_secs(),
+        "id_token": id_jwt,
+        "scope": code.scope,
+    }))
+}
@@ -0,0 +1,596 @@
+//! Sign-in, sign-up and account linking through OIDC identity providers.
+//!
+//! [`start`] and [`link`] send the browser to the provider; it returns to
+//! [`callback`], which finishes whichever of the three was started.
+
+use std::iter;
+use std::net::SocketAddr;
+
+use axum::extract::{ConnectInfo, Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse as _, Redirect, Response};
+use axum_extra::TypedHeader;
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::headers::UserAgent;
+use serde::Deserialize;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
+use crate::models::{
+    audit_event::AuditKind,
+    identity::Identity,
+    oidc_login::{LOGIN_TTL_MICROS, OidcLogin},
+    user::User,
+    username_history::UsernameHistory,
+};
+use crate::oidc::{self, IdClaims, Provider};
+use crate::registration;
+use crate::username;
+use crate::util::current_time_micros;
+use crate::validation;
+
+use super::components::{IdentityDisplay, ProviderDisplay};
+use super::{audit, components, pages, session};
+
+/// Carries the login's `state` between starting and the callback.
+const STATE_COOKIE: &str = "oidc_state";
+
+/// Prefix for generated usernames when the provider suggests nothing
+/// usable.
+const FALLBACK_USERNAME: &str = "member";
+
+/// Random suffixes tried on the provider's suggested username, and then
+/// on [`FALLBACK_USERNAME`], before giving up.
+const SUFFIX_ATTEMPTS: usize = 5;
+
+/// The configured providers, for login and signup buttons.
+pub fn provider_displays(state: &AppState) -> Vec<ProviderDisplay> {
+    state
+        .oidc
+        .iter()
+        .map(|provider| ProviderDisplay {
+            name: provider.name().to_owned(),
+            label: provider.label().to_owned(),
+        })
+        .collect()
+}
+
+/// Every configured provider, with the user's linked account there if
+/// any. Identities at providers no longer configured are listed too, so
+/// they can still be removed.
+pub async fn identity_displays(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<Vec<IdentityDisplay>, sqlx::Error> {
+    let identities = Identity::get_by_user_id(&state.db, user_id).await?;
+    let linked_as = |identity: &Identity| {
+        identity
+            .email
+            .clone()
+            .unwrap_or_else(|| "your account".to_owned())
+    };
+
+    let mut displays: Vec<IdentityDisplay> = state
+        .oidc
+        .iter()
+        .map(|provider| IdentityDisplay {
+            name: provider.name().to_owned(),
+            label: provider.label().to_owned(),
+            linked_as: identities
+                .iter()
+                .find(|identity| identity.provider == provider.name())
+                .map(linked_as),
+        })
+        .collect();
+    for identity in identities {
+        if state.oidc.get(&identity.provider).is_none() {
+            displays.push(IdentityDisplay {
+                name: identity.provider.clone(),
+                label: identity.provider.clone(),
+                linked_as: Some(linked_as(&identity)),
+            });
+        }
+    }
+    Ok(displays)
+}
+
+fn state_cookie(value: String) -> Cookie<'static> {
+    // Lax, unlike the session cookie: it has to come back on the
+    // provider's cross-site redirect to the callback
+    const IS_RELEASE: bool = !cfg!(debug_assertions);
+    Cookie::build((STATE_COOKIE, value))
+        .path("/auth")
+        .same_site(SameSite::Lax)
+        .secure(IS_RELEASE)
+        .http_only(true)
+        .max_age(time::Duration::microseconds(LOGIN_TTL_MICROS))
+        .build()
+}
+
+fn failure(status: StatusCode, username: &str, message: &str) -> Response {
+    (status, pages::sign_in_failed(username, message)).into_response()
+}
+
+/// Records a new login and sends the browser to the provider.
+async fn begin(
+    state: &AppState,
+    jar: CookieJar,
+    provider: &Provider,
+    link_user_id: Option<Uuid>,
+) -> Response {
+    let now = current_time_micros();
+    if let Err(err) = OidcLogin::delete_before(
+        &state.db,
+        now.saturating_sub(LOGIN_TTL_MICROS),
+    )
+    .await
+    {
+        warn!("Failed to prune abandoned sign-ins: {err}");
+    }
+
+    let login = OidcLogin {
+        state: oidc::random_token(),
+        provider: provider.name().to_owned(),
+        nonce: oidc::random_token(),
+        code_verifier: oidc::random_token(),
+        link_user_id,
+        created_at: now,
+    };
+    let url = match provider
+        .authorization_url(&login.state, &login.nonce, &login.code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(err) => {
+            error!(
+                provider = provider.name(),
+                "Failed to start sign-in: {err}"
+            );
+            return failure(
+                StatusCode::BAD_GATEWAY,
+                "",
+                "That sign-in provider isn't reachable right now. Try again later.",
+            );
+        }
+    };
+    if let Err(err) = OidcLogin::insert(&state.db, &login).await {
+        return internal_error(err).into_response();
+    }
+    (jar.add(state_cookie(login.state)), Redirect::to(&url)).into_response()
+}
+
+/// Starts signing in (or up) with a provider.
+pub async fn start(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user_opt: Option<User>,
+) -> Response {
+    if user_opt.is_some() {
+        return Redirect::to("/").into_response();
+    }
+    let Some(provider) = state.oidc.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    begin(&state, jar, provider, None).await
+}
+
+/// Starts linking a provider to the signed-in user's account.
+pub async fn link(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let Some(provider) = state.oidc.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    begin(&state, jar, provider, Some(user.id)).await
+}
+
+#[derive(Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    /// Set instead of `code` when the user cancelled or the provider
+    /// refused.
+    error: Option<String>,
+}
+
+/// Checks that the callback finishes a login this browser started at
+/// `provider`, then redeems the code for verified ID token claims.
+async fn verify_callback(
+    state: &AppState,
+    provider: &Provider,
+    params: CallbackParams,
+    cookie_state: Option<&str>,
+    now: i64,
+) -> Result<(OidcLogin, IdClaims), Response> {
+    let incomplete = || {
+        failure(
+            StatusCode::BAD_REQUEST,
+            "",
+            "The sign-in link is incomplete.",
+        )
+    };
+    let returned_state = params.state.ok_or_else(incomplete)?;
+    if cookie_state != Some(returned_state.as_str()) {
+        return Err(failure(
+            StatusCode::BAD_REQUEST,
+            "",
+            "This sign-in was started in another browser or has expired. Please try again.",
+        ));
+    }
+    let login = match OidcLogin::take(
+        &state.db,
+        &returned_state,
+        now.saturating_sub(LOGIN_TTL_MICROS),
+    )
+    .await
+    {
+        Ok(Some(login)) if login.provider == provider.name() => login,
+        Ok(_) => {
+            return Err(failure(
+                StatusCode::BAD_REQUEST,
+                "",
+                "This sign-in has expired or was already used. Please try again.",
+            ));
+        }
+        Err(err) => return Err(internal_error(err).into_response()),
+    };
+
+    if let Some(reason) = params.error {
+        warn!(
+            provider = provider.name(),
+            reason, "Provider declined sign-in"
+        );
+        return Err(failure(
+            StatusCode::BAD_REQUEST,
+            "",
+            &format!("{} didn't complete the sign-in.", provider.label()),
+        ));
+    }
+    let code = params.code.ok_or_else(incomplete)?;
+    match provider
+        .exchange_code(
+            &code,
+            &login.code_verifier,
+            &login.nonce,
+            now.saturating_div(1_000_000),
+        )
+        .await
+    {
+        Ok(claims) => Ok((login, claims)),
+        Err(err) => {
+            warn!(provider = provider.name(), "Sign-in rejected: {err}");
+            Err(failure(
+                StatusCode::BAD_REQUEST,
+                "",
+                &format!("Couldn't verify your {} sign-in.", provider.label()),
+            ))
+        }
+    }
+}
+
+/// Where the provider sends the browser back to.
+///
+/// The session cookie is `SameSite=Strict` and so absent here; a link is
+/// tied to its user through the stored login instead, which only the
+/// browser holding the state cookie can complete.
+pub async fn callback(
+    Path(name): Path<String>,
+    Query(params): Query<CallbackParams>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    client: ClientInfo,
+) -> Response {
+    let now = current_time_micros();
+    let Some(provider) = state.oidc.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let cookie_state = jar
+        .get(STATE_COOKIE)
+        .map(|cookie| cookie.value().to_owned());
+    let cleared = jar.remove(Cookie::build(STATE_COOKIE).path("/auth"));
+
+    let (login, claims) = match verify_callback(
+        &state,
+        provider,
+        params,
+        cookie_state.as_deref(),
+        now,
+    )
+    .await
+    {
+        Ok(verified) => verified,
+        Err(response) => return (cleared, response).into_response(),
+    };
+    if let Some(user_id) = login.link_user_id {
+        let response =
+            finish_link(&state, &client, provider, &claims, user_id, now).await;
+        return (cleared, response).into_response();
+    }
+
+    let user = match Identity::get(&state.db, provider.name(), &claims.sub)
+        .await
+    {
+        Ok(Some(identity)) => {
+            match User::get_by_id(&state.db, identity.user_id).await {
+                Ok(user) => user,
+                Err(err) => return internal_error(err).into_response(),
+            }
+        }
+        Ok(None) if !registration::mode().is_open_to(false) => {
+            let message = if registration::mode().needs_invite() {
+                format!(
+                    "New accounts need an invite. Sign up with your invite code, then connect {} from settings.",
+                    provider.label()
+                )
+            } else {
+                "This site isn't taking new accounts right now.".to_owned()
+            };
+            let response = failure(StatusCode::FORBIDDEN, "", &message);
+            return (cleared, response).into_response();
+        }
+        Ok(None) => match sign_up(&state, provider, &claims, now).await {
+            Ok(Some(user)) => user,
+            Ok(None) => {
+                let response = failure(
+                    StatusCode::CONFLICT,
+                    "",
+                    "Couldn't pick a username for your new account. Sign up with a password instead.",
+                );
+                return (cleared, response).into_response();
+            }
+            Err(err) => return internal_error(err).into_response(),
+        },
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    let new_session = match session::create_session(
+        &state.db, user.id, now, addr, user_agent, true,
+    )
+    .await
+    {
+        Ok(new_session) => new_session,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let mut detail = format!("With {}", provider.label());
+    if new_session.new_device {
+        detail.push_str(", new device");
+    }
+    audit::record(
+        &state,
+        &client,
+        Some(user.id),
+        &user.username,
+        AuditKind::Login,
+        detail,
+    )
+    .await;
+    if new_session.new_device
+        && let Err(err) =
+            session::send_login_alert(&state, &user, &new_session.session).await
+    {
+        warn!("Failed to send login alert: {err}");
+    }
+
+    (cleared.add(new_session.cookie), pages::continue_to("/")).into_response()
+}
+
+/// Links the provider account in `claims` to `user_id`.
+async fn finish_link(
+    state: &AppState,
+    client: &ClientInfo,
+    provider: &Provider,
+    claims: &IdClaims,
+    user_id: Uuid,
+    now: i64,
+) -> Response {
+    let user = match User::get_by_id(&state.db, user_id).await {
+        Ok(user) => user,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    match Identity::get(&state.db, provider.name(), &claims.sub).await {
+        Ok(None) => {}
+        Ok(Some(existing)) if existing.user_id == user.id => {
+            return pages::continue_to("/settings").into_response();
+        }
+        Ok(Some(_)) => {
+            return failure(
+                StatusCode::CONFLICT,
+                &user.username,
+                &format!(
+                    "That {} account is already linked to another user.",
+                    provider.label()
+                ),
+            );
+        }
+        Err(err) => return internal_error(err).into_response(),
+    }
+
+    let identity = Identity {
+        provider: provider.name().to_owned(),
+        subject: claims.sub.clone(),
+        user_id: user.id,
+        email: claims.verified_email().map(str::to_owned),
+        created_at: now,
+    };
+    match Identity::insert(&state.db, &identity).await {
+        Ok(()) => {}
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            return failure(
+                StatusCode::CONFLICT,
+                &user.username,
+                &format!(
+                    "You already have a {} account connected. Disconnect it first.",
+                    provider.label()
+                ),
+            );
+        }
+        Err(err) => return internal_error(err).into_response(),
+    }
+    audit::record(
+        state,
+        client,
+        Some(user.id),
+        &user.username,
+        AuditKind::IdentityLinked,
+        provider.label().to_owned(),
+    )
+    .await;
+    pages::continue_to("/settings").into_response()
+}
+
+/// Usernames to try for a new account: the provider's suggestion, then
+/// the suggestion with random suffixes, then [`FALLBACK_USERNAME`] with
+/// suffixes.
+fn username_candidates(claims: &IdClaims) -> Vec<String> {
+    let email_local = claims
+        .email
+        .as_deref()
+        .and_then(|email| email.split('@').next());
+    let suggestion = claims
+        .preferred_username
+        .as_deref()
+        .or(email_local)
+        .or(claims.name.as_deref())
+        .unwrap_or_default();
+    // Room for a four-character suffix within the 20-character limit
+    let base: String = suggestion
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .take(16)
+        .collect();
+    let suffixed = |prefix: &str| {
+        let suffix: String = Uuid::new_v4()
+            .simple()
+            .to_string()
+            .chars()
+            .take(4)
+            .collect();
+        format!("{prefix}{suffix}")
+    };
+
+    let mut candidates = vec![base.clone()];
+    candidates
+        .extend(iter::repeat_with(|| suffixed(&base)).take(SUFFIX_ATTEMPTS));
+    candidates.extend(
+        iter::repeat_with(|| suffixed(FALLBACK_USERNAME)).take(SUFFIX_ATTEMPTS),
+    );
+    candidates.retain(|candidate| validation::username(candidate).is_ok());
+    candidates
+}
+
+/// Creates a passwordless account linked to the provider account in
+/// `claims`. Returns `None` if no generated username was free.
+async fn sign_up(
+    state: &AppState,
+    provider: &Provider,
+    claims: &IdClaims,
+    now: i64,
+) -> Result<Option<User>, sqlx::Error> {
+    let email = claims
+        .verified_email()
+        .filter(|email| validation::email(email).is_ok())
+        .map(str::to_owned);
+    let held_since = now.saturating_sub(username::hold_micros());
+
+    for candidate in username_candidates(claims) {
+        if UsernameHistory::is_held(&state.db, &candidate, held_since, None)
+            .await?
+        {
+            continue;
+        }
+        let user = User {
+            id: Uuid::new_v4(),
+            username: candidate,
+            password_hash: None,
+            email: email.clone(),
+            created_at: now,
+            is_admin: false,
+        };
+        let identity = Identity {
+            provider: provider.name().to_owned(),
+            subject: claims.sub.clone(),
+            user_id: user.id,
+            email: email.clone(),
+            created_at: now,
+        };
+
+        let mut tx = state.db.begin().await?;
+        match User::insert(&mut *tx, &user).await {
+            Ok(()) => {}
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                continue;
+            }
+            Err(err) => return Err(err),
+        }
+        Identity::insert(&mut *tx, &identity).await?;
+        tx.commit().await?;
+        return Ok(Some(user));
+    }
+    Ok(None)
+}
+
+/// Disconnects a provider from the signed-in user's account, as long as
+/// they keep another way to sign in.
+pub async fn unlink(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    client: ClientInfo,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let identities = match Identity::get_by_user_id(&state.db, user.id).await {
+        Ok(identities) => identities,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    if !identities.iter().any(|identity| identity.provider == name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let error = if user.password_hash.is_none() && identities.len() < 2 {
+        Some(
+            "Set a password or connect another provider before disconnecting this one.",
+        )
+    } else {
+        match Identity::delete(&state.db, user.id, &name).await {
+            Ok(_) => {
+                let label = state.oidc.get(&name).map_or_else(
+                    || name.clone(),
+                    |provider| provider.label().to_owned(),
+                );
+                audit::record(
+                    &state,
+                    &client,
+                    Some(user.id),
+                    &user.username,
+                    AuditKind::IdentityUnlinked,
+                    label,
+                )
+                .await;
+                None
+            }
+            Err(err) => return internal_error(err).into_response(),
+        }
+    };
+
+    match identity_displays(&state, user.id).await {
+        Ok(displays) => {
+            components::identities_section(&displays, error).into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
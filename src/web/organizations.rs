@@ -0,0 +1,698 @@
+//! Organizations: creating them, their members and roles, and emailed
+//! invitations. Routes under `/orgs/{org_slug}` use [`OrgMember`], which
+//! turns away anyone who isn't a member.
+
+use axum::Form;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse as _, Redirect, Response};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::organization::OrgMember;
+use crate::models::{
+    membership::Membership,
+    notification::NotificationKind,
+    organization::{Affiliation, Organization, Role},
+    organization_invite::OrganizationInvite,
+    user::User,
+};
+use crate::organization::{self, INVITE_DAYS, MAX_NAME_LEN};
+use crate::services::job::Job;
+use crate::services::notification::{self, Notice};
+use crate::util::{MICROS_PER_DAY, current_time_micros, format_utc};
+use crate::validation::{self, FieldErrors, Validate};
+
+use super::components::{
+    self, MemberDisplay, OrgInviteDisplay, OrganizationDisplay,
+};
+use super::pages;
+
+const SLUG_TAKEN: &str = "Another organization already uses that URL.";
+
+const LAST_OWNER: &str =
+    "Make someone else an owner before leaving: every organization needs one.";
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrganizationPayload {
+    name: String,
+    /// Optional; made from the name when empty.
+    #[serde(default)]
+    slug: String,
+}
+
+impl CreateOrganizationPayload {
+    fn name(&self) -> &str {
+        self.name.trim()
+    }
+
+    fn slug(&self) -> String {
+        match self.slug.trim() {
+            "" => organization::slugify(self.name()),
+            slug => slug.to_owned(),
+        }
+    }
+}
+
+impl Validate for CreateOrganizationPayload {
+    type Context = ();
+
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let name_len = self.name().chars().count();
+        if (1..=MAX_NAME_LEN).contains(&name_len) {
+            errors.check("slug", organization::check_slug(&self.slug()));
+        } else {
+            errors.add("name", format!("Use 1 to {MAX_NAME_LEN} characters."));
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvitePayload {
+    email: String,
+    role: Role,
+}
+
+impl Validate for InvitePayload {
+    /// The inviter's role.
+    type Context = Role;
+
+    fn validate(&self, context: &Role) -> Result<(), FieldErrors> {
+        let inviter = *context;
+        let mut errors = FieldErrors::new();
+        errors.check("email", validation::email(self.email.trim()));
+        if !inviter.can_manage(self.role) {
+            errors.add("role", "You can't invite people with that role.");
+        }
+        errors.into_result()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RolePayload {
+    role: Role,
+}
+
+fn organization_displays(
+    affiliations: Vec<Affiliation>,
+) -> Vec<OrganizationDisplay> {
+    affiliations
+        .into_iter()
+        .map(|affiliation| OrganizationDisplay {
+            name: affiliation.name,
+            slug: affiliation.slug,
+            role: affiliation.role.label(),
+        })
+        .collect()
+}
+
+async fn member_displays(
+    state: &AppState,
+    viewer: &OrgMember,
+) -> Result<Vec<MemberDisplay>, sqlx::Error> {
+    Ok(Membership::list_members(&state.db, viewer.organization.id)
+        .await?
+        .into_iter()
+        .map(|member| {
+            let is_you = member.user_id == viewer.user.id;
+            let manageable = !is_you && viewer.role.can_manage(member.role);
+            MemberDisplay {
+                user_id: member.user_id.to_string(),
+                username: member.username,
+                role: member.role,
+                joined_at: format_utc(member.created_at),
+                assignable: if manageable {
+                    viewer.role.assignable()
+                } else {
+                    Vec::new()
+                },
+                removable: manageable,
+                is_you,
+            }
+        })
+        .collect())
+}
+
+async fn invite_displays(
+    state: &AppState,
+    viewer: &OrgMember,
+) -> Result<Vec<OrgInviteDisplay>, sqlx::Error> {
+    let now = current_time_micros();
+    Ok(OrganizationInvite::list_by_organization(
+        &state.db,
+        viewer.organization.id,
+    )
+    .await?
+    .into_iter()
+    .map(|invite| OrgInviteDisplay {
+        id: invite.id.to_string(),
+        email: invite.email,
+        role: invite.role.label(),
+        expires_at: format_utc(invite.expires_at),
+        usable: invite.expires_at > now,
+        revocable: viewer.role.can_manage(invite.role),
+    })
+    .collect())
+}
+
+/// The members section, re-rendered after a change; `error` explains a
+/// refused one.
+async fn members_response(
+    state: &AppState,
+    viewer: &OrgMember,
+    error: Option<&str>,
+) -> Response {
+    match member_displays(state, viewer).await {
+        Ok(members) => components::members_section(
+            &viewer.organization.slug,
+            &members,
+            error,
+        )
+        .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    match Organization::list_for_user(&state.db, user.id).await {
+        Ok(affiliations) => pages::organizations(
+            &user.username,
+            &organization_displays(affiliations),
+        )
+        .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Creates an organization with the user as its owner.
+pub async fn create(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    Form(form): Form<CreateOrganizationPayload>,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let rejected = |errors: &FieldErrors| {
+        components::organization_form((&form.name, &form.slug), errors)
+            .into_response()
+    };
+    if let Err(errors) = form.validate(&()) {
+        return rejected(&errors);
+    }
+
+    let now = current_time_micros();
+    let created = Organization {
+        id: Uuid::new_v4(),
+        name: form.name().to_owned(),
+        slug: form.slug(),
+        created_at: now,
+    };
+    let owner = Membership {
+        organization_id: created.id,
+        user_id: user.id,
+        role: Role::Owner,
+        created_at: now,
+    };
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    match Organization::insert(&mut *tx, &created).await {
+        Ok(()) => {}
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            return rejected(&FieldErrors::single("slug", SLUG_TAKEN));
+        }
+        Err(err) => return internal_error(err).into_response(),
+    }
+    if let Err(err) = Membership::insert(&mut *tx, &owner).await {
+        return internal_error(err).into_response();
+    }
+    if let Err(err) = tx.commit().await {
+        return internal_error(err).into_response();
+    }
+    [("HX-Redirect", format!("/orgs/{}", created.slug))].into_response()
+}
+
+pub async fn show(
+    State(state): State<AppState>,
+    viewer: OrgMember,
+) -> Response {
+    let members = match member_displays(&state, &viewer).await {
+        Ok(members) => members,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let roles = viewer.role.assignable();
+    let invites = if roles.is_empty() {
+        None
+    } else {
+        match invite_displays(&state, &viewer).await {
+            Ok(invites) => Some(invites),
+            Err(err) => return internal_error(err).into_response(),
+        }
+    };
+    pages::organization(
+        &viewer.user.username,
+        &viewer.organization.name,
+        &viewer.organization.slug,
+        viewer.role,
+        &members,
+        invites
+            .as_deref()
+            .map(|pending| (pending, roles.as_slice())),
+    )
+    .into_response()
+}
+
+/// Emails an invitation link, replacing any earlier one to the address.
+pub async fn invite(
+    State(state): State<AppState>,
+    viewer: OrgMember,
+    Form(form): Form<InvitePayload>,
+) -> Response {
+    if let Err(status) = viewer.require(Role::Admin) {
+        return status.into_response();
+    }
+    let email = form.email.trim();
+    let validated = form.validate(&viewer.role);
+    if validated.is_ok() {
+        let token = organization::generate_token();
+        let now = current_time_micros();
+        let pending = OrganizationInvite {
+            id: Uuid::new_v4(),
+            organization_id: viewer.organization.id,
+            email: email.to_owned(),
+            role: form.role,
+            token_hash: organization::hash_token(&token),
+            invited_by: viewer.user.id,
+            expires_at: now
+                .saturating_add(INVITE_DAYS.saturating_mul(MICROS_PER_DAY)),
+            created_at: now,
+        };
+        if let Err(err) = OrganizationInvite::upsert(&state.db, &pending).await
+        {
+            return internal_error(err).into_response();
+        }
+        send_invite(&state, &viewer, email, form.role, &token);
+    }
+
+    let invites = match invite_displays(&state, &viewer).await {
+        Ok(invites) => invites,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let roles = viewer.role.assignable();
+    match validated {
+        Ok(()) => components::org_invites_section(
+            &viewer.organization.slug,
+            &invites,
+            &roles,
+            ("", Role::Member),
+            &FieldErrors::new(),
+            Some(email),
+        )
+        .into_response(),
+        Err(errors) => components::org_invites_section(
+            &viewer.organization.slug,
+            &invites,
+            &roles,
+            (&form.email, form.role),
+            &errors,
+            None,
+        )
+        .into_response(),
+    }
+}
+
+fn send_invite(
+    state: &AppState,
+    viewer: &OrgMember,
+    email: &str,
+    role: Role,
+    token: &str,
+) {
+    let inviter = &viewer.user.username;
+    let name = &viewer.organization.name;
+    let body = format!(
+        "Hi,\n\n\
+        {inviter} has invited you to join {name} as {role}. Accept here:\n\n\
+        {url}\n\n\
+        You'll need an account on this site. The link expires in \
+        {INVITE_DAYS} days.\n",
+        role = role.label().to_lowercase(),
+        url = organization::invite_url(token),
+    );
+    let job = Job::SendEmail {
+        to: email.to_owned(),
+        subject: format!("{inviter} invited you to join {name}"),
+        body,
+    };
+    if let Err(err) = state.job_tx.send(job) {
+        warn!("Failed to enqueue organization invite email: {err}");
+    }
+}
+
+/// Revokes a pending invitation; the row is swapped out with the empty
+/// response.
+pub async fn revoke_invite(
+    Path((_, raw_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    viewer: OrgMember,
+) -> Response {
+    if let Err(status) = viewer.require(Role::Admin) {
+        return status.into_response();
+    }
+    let Ok(invite_id) = Uuid::parse_str(&raw_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let pending = match OrganizationInvite::list_by_organization(
+        &state.db,
+        viewer.organization.id,
+    )
+    .await
+    {
+        Ok(pending) => pending,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let Some(found) = pending.iter().find(|invite| invite.id == invite_id)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !viewer.role.can_manage(found.role) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match OrganizationInvite::delete(
+        &state.db,
+        invite_id,
+        viewer.organization.id,
+    )
+    .await
+    {
+        Ok(true) => StatusCode::OK.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// The member's ID and role, if `raw_id` names a member of the viewer's
+/// organization.
+async fn find_member(
+    state: &AppState,
+    viewer: &OrgMember,
+    raw_id: &str,
+) -> Result<(Uuid, Role), Response> {
+    let Ok(user_id) = Uuid::parse_str(raw_id) else {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    };
+    match Membership::role_of(&state.db, viewer.organization.id, user_id).await
+    {
+        Ok(Some(role)) => Ok((user_id, role)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(err) => Err(internal_error(err).into_response()),
+    }
+}
+
+/// Changes another member's role, up to the viewer's own.
+pub async fn update_member(
+    Path((_, raw_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    viewer: OrgMember,
+    Form(form): Form<RolePayload>,
+) -> Response {
+    let (user_id, current) = match find_member(&state, &viewer, &raw_id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    // Owners can't demote themselves, so an owner always remains
+    if user_id == viewer.user.id
+        || !viewer.role.can_manage(current)
+        || !viewer.role.can_manage(form.role)
+    {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Err(err) = Membership::update_role(
+        &state.db,
+        viewer.organization.id,
+        user_id,
+        form.role,
+    )
+    .await
+    {
+        return internal_error(err).into_response();
+    }
+    let name = &viewer.organization.name;
+    let notice = Notice {
+        kind: NotificationKind::Organization,
+        title: format!("Your role in {name} changed"),
+        body: format!(
+            "{} made you {} in {name}.",
+            viewer.user.username,
+            form.role.label().to_lowercase()
+        ),
+        link: Some(format!("/orgs/{}", viewer.organization.slug)),
+    };
+    notification::notify(
+        &state.db,
+        &state.job_tx,
+        &state.events,
+        user_id,
+        &notice,
+    )
+    .await;
+    members_response(&state, &viewer, None).await
+}
+
+/// Removes another member, or lets the viewer leave unless they're the
+/// last owner.
+pub async fn remove_member(
+    Path((_, raw_id)): Path<(String, String)>,
+    State(state): State<AppState>,
+    viewer: OrgMember,
+) -> Response {
+    let (user_id, current) = match find_member(&state, &viewer, &raw_id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let leaving = user_id == viewer.user.id;
+    if !leaving && !viewer.role.can_manage(current) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    match Membership::delete(&state.db, viewer.organization.id, user_id).await {
+        Ok(true) => {}
+        Ok(false) if current == Role::Owner => {
+            return members_response(&state, &viewer, Some(LAST_OWNER)).await;
+        }
+        Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => return internal_error(err).into_response(),
+    }
+    if leaving {
+        [("HX-Redirect", "/orgs")].into_response()
+    } else {
+        let name = &viewer.organization.name;
+        let notice = Notice {
+            kind: NotificationKind::Organization,
+            title: format!("You were removed from {name}"),
+            body: format!(
+                "{} removed you from {name}. You'll need a new invitation to \
+                rejoin.",
+                viewer.user.username
+            ),
+            link: None,
+        };
+        notification::notify(
+            &state.db,
+            &state.job_tx,
+            &state.events,
+            user_id,
+            &notice,
+        )
+        .await;
+        members_response(&state, &viewer, None).await
+    }
+}
+
+/// The organization an unexpired invitation is for, and the role it
+/// grants.
+async fn pending_invite(
+    state: &AppState,
+    token: &str,
+) -> Result<Option<(Organization, OrganizationInvite)>, sqlx::Error> {
+    let now = current_time_micros();
+    let token_hash = organization::hash_token(token);
+    let Some(pending) =
+        OrganizationInvite::get_by_token_hash(&state.db, &token_hash, now)
+            .await?
+    else {
+        return Ok(None);
+    };
+    Ok(Organization::get_by_id(&state.db, pending.organization_id)
+        .await?
+        .map(|found| (found, pending)))
+}
+
+/// The page for an invitation sent to another address than the user's.
+fn not_invited(username: &str) -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        pages::invitation_for_someone_else(username),
+    )
+        .into_response()
+}
+
+/// Asks a signed-in user to accept the invitation from an emailed link.
+pub async fn join_page(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    uri: Uri,
+) -> Response {
+    let Some(user) = user_opt else {
+        // The session cookie is SameSite=Strict, so it's missing when the
+        // link was opened from an email. Going on from a page of our own
+        // brings it back, or shows the login form if there's no session.
+        let login = format!(
+            "/login?next={}",
+            utf8_percent_encode(uri.path(), NON_ALPHANUMERIC)
+        );
+        return pages::continue_to(&login).into_response();
+    };
+    match pending_invite(&state, &token).await {
+        Ok(Some((_, pending))) if !pending.is_for(&user) => {
+            not_invited(&user.username)
+        }
+        Ok(Some((found, pending))) => pages::join_organization(
+            &user.username,
+            Some((&found.name, pending.role)),
+            uri.path(),
+        )
+        .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            pages::join_organization(&user.username, None, uri.path()),
+        )
+            .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Accepts an invitation, which then stops working. Only the account with
+/// the invited address can accept it. Members who accept another
+/// invitation keep their current role.
+pub async fn join(
+    Path(token): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    uri: Uri,
+) -> Response {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let now = current_time_micros();
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let invitation = OrganizationInvite::take(
+        &mut *tx,
+        &organization::hash_token(&token),
+        now,
+    )
+    .await;
+    let accepted = match invitation {
+        Ok(Some(accepted)) => accepted,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                pages::join_organization(&user.username, None, uri.path()),
+            )
+                .into_response();
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+    // Dropping the transaction puts the invitation back
+    if !accepted.is_for(&user) {
+        return not_invited(&user.username);
+    }
+    let membership = Membership {
+        organization_id: accepted.organization_id,
+        user_id: user.id,
+        role: accepted.role,
+        created_at: now,
+    };
+    let added = match Membership::insert(&mut *tx, &membership).await {
+        Ok(added) => added,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let joined =
+        match Organization::get_by_id(&mut *tx, accepted.organization_id).await
+        {
+            Ok(Some(joined)) => joined,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return internal_error(err).into_response(),
+        };
+    if let Err(err) = tx.commit().await {
+        return internal_error(err).into_response();
+    }
+    let destination = format!("/orgs/{}", joined.slug);
+    // Existing members keep their role, so there's no news for the inviter
+    if !added {
+        return Redirect::to(&destination).into_response();
+    }
+    let notice = Notice {
+        kind: NotificationKind::Organization,
+        title: format!("{} joined {}", user.username, joined.name),
+        body: format!(
+            "{} accepted your invitation and joined {} as {}.",
+            user.username,
+            joined.name,
+            accepted.role.label().to_lowercase()
+        ),
+        link: Some(destination.clone()),
+    };
+    notification::notify(
+        &state.db,
+        &state.job_tx,
+        &state.events,
+        accepted.invited_by,
+        &notice,
+    )
+    .await;
+    Redirect::to(&destination).into_response()
+}
+
+/// The navbar's organization menu, loaded after the page. The page's own
+/// organization, if any, is read from the URL HTMX reports.
+pub async fn switcher(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(user) = user_opt else {
+        // Leaves the plain link in place
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    let current = headers
+        .get("HX-Current-URL")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|url| url.split_once("/orgs/"))
+        .and_then(|(_, rest)| rest.split(['/', '?', '#']).next());
+    match Organization::list_for_user(&state.db, user.id).await {
+        Ok(affiliations) => components::org_switcher(
+            &organization_displays(affiliations),
+            current,
+        )
+        .into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
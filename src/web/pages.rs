@@ -3,10 +3,26 @@
 //! These return complete HTML documents wrapped in the base layout.
 //! For HTMX partial updates, use components directly.
 
-use maud::{Markup, html};
+use maud::{DOCTYPE, Markup, html};
+use uuid::Uuid;
 
+use crate::bot_check::{self, Challenge};
+use crate::models::audit_event::{AuditEvent, AuditFilter, AuditKind};
+use crate::models::notification_preference::NotificationPreference;
+use crate::models::organization::Role;
+use crate::models::profile::Profile;
+use crate::validation::FieldErrors;
+
+use super::assets;
 use super::components::{
-    SessionDisplay, base, email_form, login_form, password_form, signup_form,
+    ClientDisplay, IdentityDisplay, InviteDisplay, MemberDisplay,
+    NotificationDisplay, OrgInviteDisplay, OrganizationDisplay, ProfileDisplay,
+    ProviderDisplay, SessionDisplay, UploadDisplay, audit_table, avatar_form,
+    base, clients_section, email_form, identities_section, invites_section,
+    login_form, members_section, notification_preferences_form,
+    notifications_section, org_invites_section, organization_form,
+    organizations_list, password_form, profile_card, profile_form,
+    provider_buttons, sessions_table, signup_form, uploads_section,
     username_form,
 };
 
@@ -39,79 +55,482 @@ pub fn about(username: &str) -> Markup {
     )
 }
 
-pub fn login_page() -> Markup {
-    base("", &login_form("", ""))
+/// `challenge` is set when this address has failed to log in too often.
+pub fn login_page(
+    providers: &[ProviderDisplay],
+    next: Option<&str>,
+    challenge: Option<&Challenge>,
+) -> Markup {
+    base(
+        "",
+        &html! {
+            (login_form("", "", next, challenge))
+            (provider_buttons(providers, "Sign in"))
+            (proof_of_work_script())
+        },
+    )
+}
+
+/// Solves challenges in forms, including ones swapped in after a failed
+/// attempt.
+fn proof_of_work_script() -> Markup {
+    html! {
+        @if bot_check::config().is_enabled() {
+            script src=(assets::url("proof-of-work.js")) {}
+        }
+    }
+}
+
+/// `invite` is as for [`signup_form`]: `Some` (perhaps empty) when an
+/// invite code is required.
+pub fn signup_page(
+    providers: &[ProviderDisplay],
+    invite: Option<&str>,
+    challenge: Option<&Challenge>,
+) -> Markup {
+    base(
+        "",
+        &html! {
+            (signup_form("", invite, challenge, &FieldErrors::new()))
+            (provider_buttons(providers, "Sign up"))
+            (proof_of_work_script())
+        },
+    )
 }
 
-pub fn signup_page() -> Markup {
-    base("", &signup_form("", "", ""))
+pub fn signup_closed() -> Markup {
+    base(
+        "",
+        &html! {
+            article {
+                header { h1 { "Sign up" } }
+                p { "This site isn't taking new accounts right now." }
+                footer { "Already have an account? " a href="/login" { "Log in" } }
+            }
+        },
+    )
 }
 
-pub fn settings(username: &str, email: Option<&str>) -> Markup {
+/// `has_password` is false for accounts created through an identity
+/// provider that haven't set one yet.
+pub fn settings(
+    username: &str,
+    email: Option<&str>,
+    profile: &Profile,
+    avatar_url: Option<&str>,
+    has_password: bool,
+    identities: &[IdentityDisplay],
+) -> Markup {
     base(
         username,
         &html! {
             h1 { "Settings" }
             section {
-                (username_form("", "", false))
+                h2 { "Profile" }
+                (profile_form(profile, &FieldErrors::new(), None))
+            }
+            section {
+                (avatar_form(avatar_url, &FieldErrors::new(), None))
+            }
+            section {
+                h2 { "Account" }
+                (username_form("", &FieldErrors::new(), None))
             }
             section {
-                (email_form(email.unwrap_or(""), "", false))
+                (email_form(email.unwrap_or(""), &FieldErrors::new(), None))
             }
             section {
-                (password_form("", "", false, false))
+                (password_form(has_password, &FieldErrors::new(), None, None))
             }
+            @if !identities.is_empty() {
+                (identities_section(identities, None))
+            }
+            p { a href="/settings/security" { "Security activity" } }
         },
     )
 }
 
-pub fn profile(username: &str, sessions: &[SessionDisplay]) -> Markup {
+/// The owner's view of their profile: every field, plus their sessions.
+pub fn profile(
+    username: &str,
+    profile: &ProfileDisplay,
+    sessions: &[SessionDisplay],
+) -> Markup {
     base(
         username,
         &html! {
-            h1 { "Hello, " (username) "!" }
+            div sse-swap="profile" { (profile_card(profile)) }
+            p { a href="/settings" { "Edit profile and privacy" } }
             h2 { "Active Sessions" }
-            @if !sessions.is_empty() {
-                table {
-                    thead {
-                        tr {
-                            th { "Device/Browser" }
-                            th { "IP Address" }
-                            th { "Created" }
-                            th { "Expires" }
-                            th { "Actions" }
-                        }
-                    }
-                    tbody {
-                        @for session in sessions {
-                            tr data-theme=[session.is_current.then_some("primary")] {
-                                td { (session.user_agent) }
-                                td { (session.ip_address) }
-                                td { (session.created_at) }
-                                td { (session.expires_at) }
-                                td {
-                                    @if session.is_current {
-                                        small { "Current session" }
-                                    } @else {
-                                        button
-                                            hx-delete={"/sessions/" (session.id)}
-                                            hx-target="closest tr"
-                                            hx-swap="outerHTML swap:1s"
-                                            hx-confirm="Are you sure you want to revoke this session?"
-                                            data-theme="outline"
-                                            role="button"
-                                        {
-                                            "Revoke"
-                                        }
-                                    }
-                                }
+            (sessions_table(sessions))
+        },
+    )
+}
+
+/// Someone else's profile, showing only what its owner lets `username`
+/// (empty when signed out) see.
+pub fn public_profile(username: &str, profile: &ProfileDisplay) -> Markup {
+    base(username, &profile_card(profile))
+}
+
+pub fn user_not_found(username: &str) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "User not found" }
+            p { "There's no one here by that name." }
+        },
+    )
+}
+
+pub fn uploads(username: &str, uploads: &[UploadDisplay]) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Files" }
+            (uploads_section(uploads, &FieldErrors::new(), None))
+            script src=(assets::url("upload-progress.js")) {}
+        },
+    )
+}
+
+/// Shown when a sign-in or account link through a provider fails.
+pub fn sign_in_failed(username: &str, message: &str) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Sign-in failed" }
+            p { (message) }
+            p {
+                @if username.is_empty() {
+                    a href="/login" { "Back to log in" }
+                } @else {
+                    a href="/settings" { "Back to settings" }
+                }
+            }
+        },
+    )
+}
+
+/// A page that immediately moves on to `location`.
+///
+/// Used instead of a redirect at the end of a provider sign-in: the
+/// session cookie is `SameSite=Strict`, so browsers leave it off
+/// redirects in a chain that started on the provider's site. This page is
+/// same-site, so the next request carries it.
+pub fn continue_to(location: &str) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta http-equiv="refresh" content={ "0;url=" (location) };
+                title { "Signing in" }
+            }
+            body {
+                p { a href=(location) { "Continue" } }
+            }
+        }
+    }
+}
+
+pub fn confirm_session_revoke(username: &str, token: Uuid) -> Markup {
+    base(
+        username,
+        &html! {
+            article {
+                header { h1 { "Sign out that device?" } }
+                p {
+                    "This ends the sign-in you were emailed about. Do it if "
+                    "you don't recognise it; you can sign in again anywhere "
+                    "that was you."
+                }
+                form method="post" action={ "/sessions/revoke/" (token) } {
+                    button type="submit" { "Sign out that device" }
+                }
+            }
+        },
+    )
+}
+
+pub fn session_revoked(username: &str, revoked: bool) -> Markup {
+    base(
+        username,
+        &html! {
+            @if revoked {
+                h1 { "Device signed out" }
+                p {
+                    "That sign-in has been ended. If you didn't sign in, "
+                    "someone else knows your password: log in and change it "
+                    "from " a href="/settings" { "settings" } " right away."
+                }
+            } @else {
+                h1 { "Nothing to sign out" }
+                p {
+                    "This link has already been used or that session has "
+                    "ended. You can review your active sessions on your profile."
+                }
+            }
+        },
+    )
+}
+
+pub fn security_activity(
+    username: &str,
+    is_admin: bool,
+    events: &[AuditEvent],
+) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Security activity" }
+            p {
+                "Recent sign-ins and account changes. If you don't recognise "
+                "something, change your password and review your "
+                a href={ "/users/" (username) } { "active sessions" } "."
+            }
+            @if is_admin {
+                p { a href="/admin/audit" { "All users' activity" } }
+            }
+            (audit_table(events, false))
+        },
+    )
+}
+
+pub fn admin_audit(
+    username: &str,
+    filter: &AuditFilter,
+    events: &[AuditEvent],
+) -> Markup {
+    let selected_kind = filter.kind.as_deref().unwrap_or_default();
+    base(
+        username,
+        &html! {
+            h1 { "Audit log" }
+            form method="get" action="/admin/audit" {
+                fieldset role="group" {
+                    input
+                        type="text"
+                        name="username"
+                        placeholder="Username"
+                        value=(filter.username.as_deref().unwrap_or_default());
+                    select name="kind" aria-label="Event" {
+                        option value="" { "All events" }
+                        @for kind in AuditKind::ALL {
+                            option
+                                value=(kind.as_str())
+                                selected[kind.as_str() == selected_kind]
+                            {
+                                (kind.label())
                             }
                         }
                     }
+                    button type="submit" { "Filter" }
+                }
+            }
+            (audit_table(events, true))
+        },
+    )
+}
+
+pub fn admin_clients(username: &str, clients: &[ClientDisplay]) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Apps" }
+            p {
+                "Apps registered here can sign users in with their accounts "
+                "on this site, using OpenID Connect."
+            }
+            (clients_section(clients, ("", ""), &FieldErrors::new(), None))
+        },
+    )
+}
+
+/// `max_uses` is the most sign-ups this user may allow per invite.
+pub fn invites(
+    username: &str,
+    invites: &[InviteDisplay],
+    max_uses: i64,
+) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Invites" }
+            p {
+                "An invite code lets someone sign up while registration is "
+                "invite-only. Create one per person, or one with several uses "
+                "for a group."
+            }
+            (invites_section(invites, ("", "", ""), max_uses, &FieldErrors::new(), None))
+        },
+    )
+}
+
+pub fn organizations(
+    username: &str,
+    organizations: &[OrganizationDisplay],
+) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Organizations" }
+            (organizations_list(organizations))
+            h2 { "New organization" }
+            p { "You'll be its owner, and can invite others by email." }
+            (organization_form(("", ""), &FieldErrors::new()))
+        },
+    )
+}
+
+pub fn notifications(
+    username: &str,
+    notifications: &[NotificationDisplay],
+    preferences: &[NotificationPreference],
+) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Notifications" }
+            (notifications_section(notifications))
+            h2 { "Delivery" }
+            p { "Emails go to the address in your settings, if you've added one." }
+            (notification_preferences_form(preferences, false))
+        },
+    )
+}
+
+/// `invites` is shown only to members who may invite: the pending
+/// invitations and the roles the viewer may hand out.
+pub fn organization(
+    username: &str,
+    name: &str,
+    slug: &str,
+    role: Role,
+    members: &[MemberDisplay],
+    invites: Option<(&[OrgInviteDisplay], &[Role])>,
+) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { (name) }
+            p { "You're " (role.label().to_lowercase()) " here." }
+            h2 { "Members" }
+            (members_section(slug, members, None))
+            @if let Some((pending, roles)) = invites {
+                h2 { "Invitations" }
+                p {
+                    "Invitations are emailed as a link that works for "
+                    "whoever opens it, for a week."
+                }
+                (org_invites_section(slug, pending, roles, ("", Role::Member), &FieldErrors::new(), None))
+            }
+        },
+    )
+}
+
+/// Asks the user to accept an invitation to `organization` as `role`, or
+/// explains that the link no longer works if `invite` is `None`.
+pub fn join_organization(
+    username: &str,
+    invite: Option<(&str, Role)>,
+    action: &str,
+) -> Markup {
+    base(
+        username,
+        &html! {
+            @if let Some((organization, role)) = invite {
+                article {
+                    header { h1 { "Join " (organization) "?" } }
+                    p {
+                        "You've been invited to join " (organization) " as "
+                        (role.label().to_lowercase()) "."
+                    }
+                    form method="post" action=(action) {
+                        button type="submit" { "Join " (organization) }
+                    }
+                    footer { small { "Signed in as " (username) "." } }
                 }
             } @else {
-                p { "No active sessions found." }
+                h1 { "Invitation not found" }
+                p {
+                    "This invitation has expired, was revoked or has already "
+                    "been accepted. Ask for a new one."
+                }
             }
         },
     )
 }
+
+/// Turns away a user following an invitation meant for another address.
+pub fn invitation_for_someone_else(username: &str) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "This invitation isn't for you" }
+            p {
+                "It was sent to a different email address. Sign in with the "
+                "account that uses that address, or ask for an invitation to "
+                "the address in your settings."
+            }
+        },
+    )
+}
+
+/// What a scope lets an app see, for the consent page.
+const fn scope_description(scope: &str) -> &'static str {
+    match scope.as_bytes() {
+        b"profile" => "Your username and display name",
+        b"email" => "Your email address",
+        _ => "Who you are on this site",
+    }
+}
+
+/// Asks whether `client_name` may sign the user in and see `scopes`.
+/// `request` is the authorization request, posted back with the answer.
+pub fn consent(
+    username: &str,
+    client_name: &str,
+    scopes: &[&str],
+    request: &[(&str, &str)],
+) -> Markup {
+    base(
+        username,
+        &html! {
+            article {
+                header { h1 { "Sign in to " (client_name) "?" } }
+                p { (client_name) " will be able to see:" }
+                ul {
+                    @for scope in scopes {
+                        li { (scope_description(scope)) }
+                    }
+                }
+                form method="post" action="/oauth/authorize" {
+                    @for &(name, value) in request {
+                        input type="hidden" name=(name) value=(value);
+                    }
+                    div role="group" {
+                        button type="submit" name="decision" value="deny" class="secondary" { "Cancel" }
+                        button type="submit" name="decision" value="allow" { "Allow" }
+                    }
+                }
+                footer {
+                    small { "Signed in as " (username) ". Only allow apps you trust." }
+                }
+            }
+        },
+    )
+}
+
+/// Shown instead of returning to an app whose request can't be trusted
+/// with a redirect: an unknown app or an unregistered redirect URI.
+pub fn authorize_error(username: &str, message: &str) -> Markup {
+    base(
+        username,
+        &html! {
+            h1 { "Can't sign in to that app" }
+            p { (message) }
+            p { a href="/" { "Back to the home page" } }
+        },
+    )
+}
@@ -0,0 +1,57 @@
+use axum::{Form, response::IntoResponse};
+use serde::Deserialize;
+
+use crate::models::user::User;
+use crate::password::strength::Strength;
+use crate::password::{self, Feedback};
+
+use super::components;
+
+/// Fields posted by the signup and settings password inputs.
+#[derive(Deserialize, Debug)]
+pub struct StrengthPayload {
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    new_password: String,
+    #[serde(default)]
+    username: String,
+}
+
+/// Live strength feedback for a password being typed.
+pub async fn strength(
+    user: Option<User>,
+    Form(form): Form<StrengthPayload>,
+) -> impl IntoResponse {
+    let candidate = if form.password.is_empty() {
+        form.new_password
+    } else {
+        form.password
+    };
+    if candidate.is_empty() {
+        return components::password_feedback(None);
+    }
+
+    // Normalising and estimating take time in the length, so stop early
+    let policy = &password::config().policy;
+    if candidate.chars().count() > policy.max_length {
+        let too_long = Feedback {
+            strength: Strength {
+                score: 0,
+                bits: 0,
+                warning: None,
+            },
+            problem: Some(policy.length_problem()),
+        };
+        return components::password_feedback(Some(&too_long));
+    }
+
+    // Signup sends the username being chosen; settings has the current one
+    let username = if form.username.is_empty() {
+        user.map(|u| u.username).unwrap_or_default()
+    } else {
+        form.username
+    };
+    let feedback = password::feedback(&candidate, &[&username]);
+    components::password_feedback(Some(&feedback))
+}
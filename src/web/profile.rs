@@ -2,53 +2,214 @@ use std::time::{Duration, SystemTime};
 
 use axum::{
     extract::{Path, State},
+    http::{StatusCode, header},
     response::{IntoResponse, Redirect},
 };
 
+use tracing::warn;
+use uuid::Uuid;
+
 use crate::app_state::AppState;
-use crate::models::{session::Session, user::User};
+use crate::error::internal_error;
+use crate::extractors::session::CurrentSession;
+use crate::models::{
+    avatar::Avatar,
+    profile::{Profile, Viewer},
+    session::Session,
+    user::User,
+    username_history::UsernameHistory,
+};
+use crate::services::events::Event;
+use crate::user_agent::Device;
+use crate::username::profile_path;
+
+use super::{
+    components::{self, ProfileDisplay, SessionDisplay},
+    pages,
+};
 
-use super::{components::SessionDisplay, pages};
+/// Topic of live updates to profile pages.
+pub const PROFILE_TOPIC: &str = "profile";
 
 pub async fn profile(
     Path(username): Path<String>,
     State(state): State<AppState>,
-    user_opt: Option<User>,
+    current_opt: Option<CurrentSession>,
 ) -> impl IntoResponse {
-    let Some(user) = user_opt else {
-        return Redirect::to("/login").into_response();
+    let viewer_name = current_opt
+        .as_ref()
+        .map(|current| current.user.username.as_str())
+        .unwrap_or_default();
+
+    // A previous username permanently redirects to its owner's current one,
+    // unless someone has since taken the name
+    let profile_user = match User::get_by_username(&state.db, &username).await {
+        Ok(found) => found,
+        Err(sqlx::Error::RowNotFound) => {
+            return match UsernameHistory::current_username_for(
+                &state.db, &username,
+            )
+            .await
+            {
+                Ok(Some(current)) => (
+                    StatusCode::MOVED_PERMANENTLY,
+                    [(header::LOCATION, profile_path(&current))],
+                )
+                    .into_response(),
+                Ok(None) => {
+                    (StatusCode::NOT_FOUND, pages::user_not_found(viewer_name))
+                        .into_response()
+                }
+                Err(err) => internal_error(err).into_response(),
+            };
+        }
+        Err(err) => return internal_error(err).into_response(),
     };
 
-    if user.username != username {
-        return Redirect::to(&format!("/users/{}", user.username))
+    // Lookups are case-insensitive; show the name as its owner typed it
+    if profile_user.username != username {
+        return Redirect::to(&profile_path(&profile_user.username))
             .into_response();
     }
 
-    let sessions = match Session::get_by_user_id(&state.db, user.id).await {
-        Ok(sessions) => sessions
-            .into_iter()
-            .map(|session| SessionDisplay {
-                id: session.id.to_string(),
-                ip_address: session.ip_address,
-                user_agent: truncate_user_agent(&session.user_agent),
-                created_at: format_timestamp(session.created_at),
-                expires_at: format_timestamp(session.expires_at),
-                is_current: false,
-            })
-            .collect(),
-        Err(_) => Vec::new(),
+    let viewer = Viewer::of(
+        profile_user.id,
+        current_opt.as_ref().map(|current| current.user.id),
+    );
+    let display = match profile_display(&state, &profile_user, viewer).await {
+        Ok(display) => display,
+        Err(err) => return internal_error(err).into_response(),
     };
 
-    pages::profile(&user.username, &sessions).into_response()
+    match current_opt {
+        Some(CurrentSession { user, session }) if viewer == Viewer::Owner => {
+            let sessions = session_displays(&state, user.id, session.id).await;
+            pages::profile(&user.username, &display, &sessions).into_response()
+        }
+        _ => pages::public_profile(viewer_name, &display).into_response(),
+    }
 }
 
-fn truncate_user_agent(user_agent: &str) -> String {
-    if user_agent.len() > 50 {
-        let mut truncated = user_agent.chars().take(47).collect::<String>();
-        truncated.push_str("...");
-        truncated
+/// Serves a user's avatar, if its visibility lets the viewer see it.
+pub async fn avatar(
+    Path(username): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let profile_user = match User::get_by_username(&state.db, &username).await {
+        Ok(found) => found,
+        Err(sqlx::Error::RowNotFound) => {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let profile = match Profile::get(&state.db, profile_user.id).await {
+        Ok(profile) => profile,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let viewer =
+        Viewer::of(profile_user.id, user_opt.map(|signed_in| signed_in.id));
+    if !profile.avatar_visibility.allows(viewer) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    match Avatar::get(&state.db, profile_user.id).await {
+        Ok(Some(image)) => {
+            ([(header::CONTENT_TYPE, image.content_type)], image.data)
+                .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// `/users/{username}/avatar`, versioned so a new upload isn't hidden by a
+/// cached copy of the old one.
+pub fn avatar_url(username: &str, updated_at: i64) -> String {
+    format!("{}/avatar?v={updated_at}", profile_path(username))
+}
+
+/// The user's profile with the fields `viewer` may not see removed.
+async fn profile_display(
+    state: &AppState,
+    user: &User,
+    viewer: Viewer,
+) -> Result<ProfileDisplay, sqlx::Error> {
+    let profile = Profile::get(&state.db, user.id).await?;
+    let avatar_url = if profile.avatar_visibility.allows(viewer) {
+        Avatar::updated_at(&state.db, user.id)
+            .await?
+            .map(|updated_at| avatar_url(&user.username, updated_at))
     } else {
-        user_agent.to_owned()
+        None
+    };
+    let bio = (profile.bio_visibility.allows(viewer)
+        && !profile.bio.is_empty())
+    .then(|| profile.bio.clone());
+    let links = if profile.links_visibility.allows(viewer) {
+        profile.link_list().map(str::to_owned).collect()
+    } else {
+        Vec::new()
+    };
+    let display_name = if profile.display_name.is_empty() {
+        user.username.clone()
+    } else {
+        profile.display_name
+    };
+    Ok(ProfileDisplay {
+        username: user.username.clone(),
+        display_name,
+        bio,
+        links,
+        avatar_url,
+    })
+}
+
+/// Pushes the owner's view of their profile card to their open profile
+/// pages after an edit.
+pub async fn publish_profile(state: &AppState, user: &User) {
+    match profile_display(state, user, Viewer::Owner).await {
+        Ok(display) => {
+            state.events.publish(
+                user.id,
+                Event {
+                    name: "profile".to_owned(),
+                    html: components::profile_card(&display).into_string(),
+                    topic: Some(PROFILE_TOPIC),
+                    session_id: None,
+                },
+            );
+        }
+        Err(err) => warn!("Failed to publish profile update: {err}"),
+    }
+}
+
+/// Loads the user's active sessions for display, marking `current_id`.
+pub async fn session_displays(
+    state: &AppState,
+    user_id: Uuid,
+    current_id: Uuid,
+) -> Vec<SessionDisplay> {
+    match Session::get_by_user_id(&state.db, user_id).await {
+        Ok(sessions) => sessions
+            .into_iter()
+            .map(|session| {
+                let device = Device::parse(&session.user_agent);
+                SessionDisplay {
+                    id: session.id.to_string(),
+                    device: device.label(),
+                    device_kind: device.kind,
+                    user_agent: session.user_agent,
+                    last_ip: session.last_ip,
+                    last_active: format_timestamp(session.last_seen_at)
+                        .to_lowercase(),
+                    created_at: format_timestamp(session.created_at),
+                    expires_at: format_timestamp(session.expires_at),
+                    is_current: session.id == current_id,
+                }
+            })
+            .collect(),
+        Err(_) => Vec::new(),
     }
 }
 
@@ -1,66 +1,227 @@
 use axum::extract::{ConnectInfo, Path, State};
-use axum::{Form, http::StatusCode, response::IntoResponse};
-use axum_extra::TypedHeader;
-use axum_extra::extract::{
-    CookieJar,
-    cookie::{Cookie, SameSite},
+use axum::{
+    Form,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
+use axum_extra::TypedHeader;
+use axum_extra::extract::{CookieJar, cookie::Cookie};
 use axum_extra::headers::UserAgent;
 use serde::Deserialize;
-use sqlx::SqliteExecutor;
+use sqlx::SqlitePool;
 use std::net::SocketAddr;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use crate::app_state::AppState;
+use crate::bot_check::{self, Rejection, Submission};
 use crate::error::internal_error;
-use crate::models::{session::Session, user::User};
-use crate::util::current_time_micros;
-
-use super::login;
-
-fn build_session_cookie(session_id: Uuid) -> Cookie<'static> {
-    // This is a workaround so when we're testing locally
-    // without https
-    const IS_RELEASE: bool = !cfg!(debug_assertions);
-    Cookie::build(("session_id", session_id.to_string()))
-        .path("/")
-        .same_site(SameSite::Strict)
-        .secure(IS_RELEASE)
-        .http_only(true)
-        .max_age(time::Duration::WEEK)
-        .build()
-        .into_owned()
+use crate::extractors::client::ClientInfo;
+use crate::extractors::session::{
+    CurrentSession, SESSION_COOKIE, build_session_cookie,
+};
+use crate::models::{
+    audit_event::AuditKind, known_device::KnownDevice, login_alert::LoginAlert,
+    notification::NotificationKind, session::Session, user::User,
+};
+use crate::services::events::{Broker, Event, SIGNED_OUT};
+use crate::services::notification::{self, Notice};
+use crate::user_agent::Device;
+use crate::username;
+use crate::util::{current_time_micros, format_utc};
+
+use super::{BOT_SUSPECTED, audit, components, login, pages, profile};
+
+/// Topic of live updates to session tables.
+pub const SESSIONS_TOPIC: &str = "sessions";
+
+/// A freshly created session and the cookie that carries it.
+pub struct NewSession {
+    pub session: Session,
+    pub cookie: Cookie<'static>,
+    /// Whether this is the user's first login from this IP and user agent.
+    pub new_device: bool,
 }
 
 /// Create a new session for the user, insert it into the database,
 /// and return the associated cookie for it.
-pub async fn create_session<'e, E: SqliteExecutor<'e>>(
-    db: E,
+///
+/// A `persistent` session survives browser restarts; otherwise the cookie
+/// lasts until the browser closes.
+pub async fn create_session(
+    db: &SqlitePool,
     user_id: Uuid,
     time: i64,
-    ip_address: String,
+    addr: SocketAddr,
     user_agent: UserAgent,
-) -> Result<Cookie<'static>, sqlx::Error> {
-    let id = uuid::Uuid::new_v4();
-    // This constant conversion always succeeds (1 week in microseconds fits in i64)
-    let expire_micros =
-        i64::try_from(time::Duration::WEEK.whole_microseconds()).unwrap();
+    persistent: bool,
+) -> Result<NewSession, sqlx::Error> {
     let session = Session {
-        id,
+        id: Uuid::new_v4(),
         user_id,
-        ip_address,
+        ip_address: addr.to_string(),
         user_agent: user_agent.to_string(),
         created_at: time,
-        expires_at: time.wrapping_add(expire_micros),
+        expires_at: time.saturating_add(Session::ttl_for(persistent)),
+        persistent,
+        last_seen_at: time,
+        last_ip: addr.ip().to_string(),
+    };
+    // Together, so a session is never left without its device recorded
+    let mut tx = db.begin().await?;
+    Session::insert(&mut *tx, &session).await?;
+    let new_device = KnownDevice::record(
+        &mut *tx,
+        user_id,
+        &session.last_ip,
+        &session.user_agent,
+        time,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(NewSession {
+        cookie: build_session_cookie(session.id, persistent),
+        session,
+        new_device,
+    })
+}
+
+/// Notifies the user of a login from a new device, with a link that
+/// revokes the session.
+pub async fn send_login_alert(
+    state: &AppState,
+    user: &User,
+    session: &Session,
+) -> Result<(), sqlx::Error> {
+    let alert = LoginAlert {
+        token: Uuid::new_v4(),
+        session_id: session.id,
+        created_at: session.created_at,
+    };
+    LoginAlert::insert(&state.db, &alert).await?;
+
+    let device = Device::parse(&session.user_agent);
+    let body = format!(
+        "Your account was just signed in to from a new device.\n\n\
+        Device: {device}\n\
+        IP address: {ip}\n\
+        Time: {time}\n\n\
+        If this was you, you can ignore this. If it wasn't, sign that \
+        device out with the link below and then change your password.",
+        device = device.label(),
+        ip = session.last_ip,
+        time = format_utc(session.created_at),
+    );
+    let notice = Notice {
+        kind: NotificationKind::SignIn,
+        title: "New sign-in to your account".to_owned(),
+        body,
+        link: Some(format!("/sessions/revoke/{}", alert.token)),
     };
-    Session::insert(db, &session).await?;
-    Ok(build_session_cookie(id))
+    notification::notify(
+        &state.db,
+        &state.job_tx,
+        &state.events,
+        user.id,
+        &notice,
+    )
+    .await;
+    Ok(())
+}
+
+/// Tells the user's open pages that sessions were revoked: their rows
+/// leave session tables, and the revoked sessions' own pages learn they
+/// were signed out.
+pub fn publish_revoked(events: &Broker, user_id: Uuid, revoked: &[Uuid]) {
+    let notice = components::signed_out_notice().into_string();
+    for &session_id in revoked {
+        events.publish(
+            user_id,
+            Event {
+                name: format!("session-revoked-{session_id}"),
+                html: String::new(),
+                topic: Some(SESSIONS_TOPIC),
+                session_id: None,
+            },
+        );
+        events.publish(
+            user_id,
+            Event {
+                name: SIGNED_OUT.to_owned(),
+                html: notice.clone(),
+                topic: None,
+                session_id: Some(session_id),
+            },
+        );
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct CreateSessionPayload {
     username: String,
     password: String,
+    #[serde(default)]
+    remember_me: bool,
+    /// Where to go once logged in, see [`login::local_path`].
+    next: Option<String>,
+    /// The honeypot, see [`bot_check::HONEYPOT_FIELD`].
+    #[serde(default)]
+    website: String,
+    #[serde(default)]
+    pow_challenge: String,
+    #[serde(default)]
+    pow_solution: String,
+}
+
+/// Why the login looks automated, if it does. The honeypot always
+/// applies; the challenge only once there have been too many failures.
+async fn bot_rejection(
+    state: &AppState,
+    client: &ClientInfo,
+    form: &CreateSessionPayload,
+    now: i64,
+) -> Result<Option<Rejection>, sqlx::Error> {
+    let submission = Submission {
+        honeypot: &form.website,
+        challenge: &form.pow_challenge,
+        solution: &form.pow_solution,
+    };
+    if bot_check::login_needs_challenge(
+        &state.db,
+        &form.username,
+        &client.ip,
+        now,
+    )
+    .await?
+    {
+        bot_check::check(&state.db, bot_check::Form::Login, &submission, now)
+            .await
+    } else {
+        Ok((!bot_check::passes_honeypot(&submission))
+            .then_some(Rejection::Honeypot))
+    }
+}
+
+/// The login form again with `error_message`, and a challenge if there
+/// have now been too many failures.
+async fn retry_login(
+    state: &AppState,
+    client: &ClientInfo,
+    form: &CreateSessionPayload,
+    error_message: &str,
+) -> Response {
+    let challenge =
+        match login::login_challenge(state, &form.username, &client.ip).await {
+            Ok(challenge) => challenge,
+            Err(err) => return internal_error(err).into_response(),
+        };
+    login::login_form(
+        &form.username,
+        error_message,
+        form.next.as_deref().and_then(login::local_path),
+        challenge.as_ref(),
+    )
+    .into_response()
 }
 
 /// Create a new session (login)
@@ -68,49 +229,180 @@ pub async fn post(
     jar: CookieJar,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    client: ClientInfo,
     state: State<AppState>,
     Form(form): Form<CreateSessionPayload>,
 ) -> impl IntoResponse {
     let created_at = current_time_micros();
+    let rejection =
+        match bot_rejection(&state, &client, &form, created_at).await {
+            Ok(rejection) => rejection,
+            Err(err) => return internal_error(err).into_response(),
+        };
+    if let Some(reason) = rejection {
+        info!(reason = reason.as_str(), "Rejected automated login");
+        return retry_login(&state, &client, &form, BOT_SUSPECTED).await;
+    }
+
     let Some(user) =
         User::check_login(&state.db, &form.username, &form.password).await
     else {
-        return login::login_form(
-            &form.username,
+        // Attribute the attempt to the account if there is one, so it shows
+        // up in that user's security activity
+        let target_id = User::get_by_username(&state.db, &form.username)
+            .await
+            .ok()
+            .map(|target| target.id);
+        audit::record(
+            &state,
+            &client,
+            target_id,
+            username::truncated(&form.username),
+            AuditKind::LoginFailed,
+            String::new(),
+        )
+        .await;
+        return retry_login(
+            &state,
+            &client,
+            &form,
             "Invalid username or password",
         )
-        .into_response();
+        .await;
     };
 
-    match create_session(
+    let new_session = match create_session(
         &state.db,
         user.id,
         created_at,
-        addr.to_string(),
+        addr,
         user_agent,
+        form.remember_me,
     )
     .await
     {
-        Ok(cookie) => ([("HX-Redirect", "/")], jar.add(cookie)).into_response(),
+        Ok(new_session) => new_session,
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    let detail = if new_session.new_device {
+        "New device".to_owned()
+    } else {
+        String::new()
+    };
+    audit::record(
+        &state,
+        &client,
+        Some(user.id),
+        &user.username,
+        AuditKind::Login,
+        detail,
+    )
+    .await;
+
+    if new_session.new_device
+        && let Err(err) =
+            send_login_alert(&state, &user, &new_session.session).await
+    {
+        warn!("Failed to send login alert: {err}");
+    }
+
+    let next = form
+        .next
+        .as_deref()
+        .and_then(login::local_path)
+        .unwrap_or("/")
+        .to_owned();
+    ([("HX-Redirect", next)], jar.add(new_session.cookie)).into_response()
+}
+
+/// "This wasn't me" link from a new-login email: asks before signing that
+/// session out, since mail scanners and prefetchers follow links too.
+///
+/// Needs no login, since the person clicking may not be signed in; the
+/// token itself is the credential.
+pub async fn confirm_revoke_from_alert(
+    Path(raw_token): Path<String>,
+    State(state): State<AppState>,
+    user: Option<User>,
+) -> impl IntoResponse {
+    let username = user.map(|u| u.username).unwrap_or_default();
+    let Ok(token) = Uuid::parse_str(&raw_token) else {
+        return pages::session_revoked(&username, false).into_response();
+    };
+    match LoginAlert::is_pending(&state.db, token).await {
+        Ok(true) => {
+            pages::confirm_session_revoke(&username, token).into_response()
+        }
+        Ok(false) => pages::session_revoked(&username, false).into_response(),
         Err(err) => internal_error(err).into_response(),
     }
 }
 
+/// Signs out the session a new-login email was sent for, once confirmed.
+pub async fn revoke_from_alert(
+    Path(raw_token): Path<String>,
+    State(state): State<AppState>,
+    client: ClientInfo,
+    user: Option<User>,
+) -> impl IntoResponse {
+    let username = user.map(|u| u.username).unwrap_or_default();
+    let Ok(token) = Uuid::parse_str(&raw_token) else {
+        return pages::session_revoked(&username, false).into_response();
+    };
+
+    let (session_id, owner_id) =
+        match LoginAlert::revoke_session(&state.db, token).await {
+            Ok(Some(revoked)) => revoked,
+            Ok(None) => {
+                return pages::session_revoked(&username, false)
+                    .into_response();
+            }
+            Err(err) => return internal_error(err).into_response(),
+        };
+    publish_revoked(&state.events, owner_id, &[session_id]);
+
+    if let Ok(owner) = User::get_by_id(&state.db, owner_id).await {
+        audit::record(
+            &state,
+            &client,
+            Some(owner.id),
+            &owner.username,
+            AuditKind::SessionRevoked,
+            "From new sign-in email".to_owned(),
+        )
+        .await;
+    }
+    pages::session_revoked(&username, true).into_response()
+}
+
 /// Delete the current user's session (logout)
 pub async fn delete(
     jar: CookieJar,
     state: State<AppState>,
-    _user: Option<User>, // Triggers extractor to record session/user in span
+    client: ClientInfo,
+    current_opt: Option<CurrentSession>,
 ) -> impl IntoResponse {
-    if let Some(session_cookie) = jar.get("session_id")
+    if let Some(session_cookie) = jar.get(SESSION_COOKIE)
         && let Ok(session_id) = Uuid::parse_str(session_cookie.value())
         && let Err(err) = Session::delete_by_id(&state.db, session_id).await
     {
         return internal_error(err).into_response();
     }
+    if let Some(CurrentSession { user, .. }) = current_opt {
+        audit::record(
+            &state,
+            &client,
+            Some(user.id),
+            &user.username,
+            AuditKind::Logout,
+            String::new(),
+        )
+        .await;
+    }
     (
         [("HX-Redirect", "/")],
-        jar.remove(Cookie::build("session_id")),
+        jar.remove(Cookie::build(SESSION_COOKIE)),
     )
         .into_response()
 }
@@ -119,9 +411,14 @@ pub async fn delete(
 pub async fn delete_by_id(
     Path(session_id): Path<String>,
     State(state): State<AppState>,
-    user_opt: Option<User>,
+    client: ClientInfo,
+    current_opt: Option<CurrentSession>,
 ) -> impl IntoResponse {
-    let Some(user) = user_opt else {
+    let Some(CurrentSession {
+        user,
+        session: current,
+    }) = current_opt
+    else {
         return StatusCode::UNAUTHORIZED.into_response();
     };
 
@@ -129,6 +426,11 @@ pub async fn delete_by_id(
         return StatusCode::BAD_REQUEST.into_response();
     };
 
+    // Revoking the session in use is what logging out is for
+    if session_uuid == current.id {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
     // Verify the session belongs to the authenticated user
     let session = match Session::get_by_id(&state.db, session_uuid).await {
         Ok(Some(s)) => s,
@@ -140,8 +442,53 @@ pub async fn delete_by_id(
         return StatusCode::FORBIDDEN.into_response();
     }
 
-    match Session::delete_by_id(&state.db, session_uuid).await {
-        Ok(_) => "".into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    if Session::delete_by_id(&state.db, session_uuid)
+        .await
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
+    audit::record(
+        &state,
+        &client,
+        Some(user.id),
+        &user.username,
+        AuditKind::SessionRevoked,
+        Device::parse(&session.user_agent).label(),
+    )
+    .await;
+    publish_revoked(&state.events, user.id, &[session_uuid]);
+    "".into_response()
+}
+
+/// Delete every session except the current one ("sign out everywhere else").
+pub async fn delete_others(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    current_opt: Option<CurrentSession>,
+) -> impl IntoResponse {
+    let Some(CurrentSession { user, session }) = current_opt else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let revoked =
+        match Session::delete_by_user_id_except(&state.db, user.id, session.id)
+            .await
+        {
+            Ok(revoked) => revoked,
+            Err(err) => return internal_error(err).into_response(),
+        };
+    audit::record(
+        &state,
+        &client,
+        Some(user.id),
+        &user.username,
+        AuditKind::SessionRevoked,
+        format!("Signed out everywhere else ({} sessions)", revoked.len()),
+    )
+    .await;
+    publish_revoked(&state.events, user.id, &revoked);
+
+    let sessions = profile::session_displays(&state, user.id, session.id).await;
+    components::sessions_table(&sessions).into_response()
 }
@@ -1,22 +1,71 @@
 use axum::Form;
-use axum::extract::State;
+use axum::extract::{Multipart, State};
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Redirect};
 use serde::Deserialize;
 use tracing::error;
+use uuid::Uuid;
 
 use crate::app_state::AppState;
-use crate::models::user::User;
+use crate::avatar;
+use crate::error::internal_error;
+use crate::extractors::client::ClientInfo;
+use crate::extractors::session::CurrentSession;
+use crate::models::{
+    audit_event::AuditKind,
+    avatar::Avatar,
+    notification::NotificationKind,
+    profile::{Profile, Visibility},
+    session::Session,
+    user::User,
+    username_history::UsernameHistory,
+};
 use crate::password;
 use crate::services::Job;
+use crate::services::events::Event;
+use crate::services::notification::{self, Notice};
+use crate::username;
+use crate::util::{current_time_micros, format_utc};
+use crate::validation::{self, FieldErrors, Validate};
 
-use super::{components, pages};
+use super::profile::avatar_url;
+use super::session::publish_revoked;
+use super::{USERNAME_TAKEN, audit, components, oidc, pages, profile};
 
-pub async fn get(user_opt: Option<User>) -> impl IntoResponse {
-    match user_opt {
-        Some(user) => pages::settings(&user.username, user.email.as_deref())
-            .into_response(),
-        None => Redirect::to("/login").into_response(),
-    }
+/// Most links a profile can list.
+const MAX_LINKS: usize = 5;
+
+pub async fn get(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let profile = match Profile::get(&state.db, user.id).await {
+        Ok(profile) => profile,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let avatar_updated_at = match Avatar::updated_at(&state.db, user.id).await {
+        Ok(updated_at) => updated_at,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let current_avatar = avatar_updated_at
+        .map(|updated_at| avatar_url(&user.username, updated_at));
+    let identities = match oidc::identity_displays(&state, user.id).await {
+        Ok(identities) => identities,
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    pages::settings(
+        &user.username,
+        user.email.as_deref(),
+        &profile,
+        current_avatar.as_deref(),
+        user.password_hash.is_some(),
+        &identities,
+    )
+    .into_response()
 }
 
 #[derive(Deserialize)]
@@ -26,6 +75,8 @@ pub struct UpdateUsernamePayload {
 
 #[derive(Deserialize)]
 pub struct UpdatePasswordPayload {
+    /// Absent when a passwordless account sets its first password.
+    #[serde(default)]
     current_password: String,
     new_password: String,
 }
@@ -35,8 +86,149 @@ pub struct UpdateEmailPayload {
     email: String,
 }
 
+#[derive(Deserialize)]
+pub struct UpdateProfilePayload {
+    display_name: String,
+    bio: String,
+    links: String,
+    bio_visibility: Visibility,
+    links_visibility: Visibility,
+    avatar_visibility: Visibility,
+}
+
+impl UpdateProfilePayload {
+    /// The profile to store: fields trimmed, blank link lines dropped and
+    /// line endings normalised.
+    fn to_profile(&self) -> Profile {
+        let links: Vec<&str> = self
+            .links
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        Profile {
+            display_name: self.display_name.trim().to_owned(),
+            bio: self.bio.trim().replace("\r\n", "\n"),
+            links: links.join("\n"),
+            bio_visibility: self.bio_visibility,
+            links_visibility: self.links_visibility,
+            avatar_visibility: self.avatar_visibility,
+        }
+    }
+}
+
+impl Validate for UpdateUsernamePayload {
+    type Context = ();
+
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        errors.check("new_username", validation::username(&self.new_username));
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdatePasswordPayload {
+    /// The account whose password is changing; its username counts
+    /// against the new password's strength.
+    type Context = User;
+
+    fn validate(&self, context: &User) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        if context.password_hash.is_some() && self.current_password.is_empty() {
+            errors.add("current_password", "Enter your current password");
+        }
+        errors.check(
+            "new_password",
+            password::check_password(&self.new_password, &[&context.username]),
+        );
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdateEmailPayload {
+    type Context = ();
+
+    /// An empty address is valid: it removes the email.
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let email = self.email.trim();
+        if !email.is_empty() {
+            errors.check("email", validation::email(email));
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for UpdateProfilePayload {
+    type Context = ();
+
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        let profile = self.to_profile();
+        if profile.display_name.chars().count() > 50 {
+            errors.add(
+                "display_name",
+                "Display name must be at most 50 characters.",
+            );
+        }
+        if profile.bio.chars().count() > 500 {
+            errors.add("bio", "Bio must be at most 500 characters.");
+        }
+        if profile.link_list().count() > MAX_LINKS {
+            errors.add("links", format!("List at most {MAX_LINKS} links."));
+        }
+        for link in profile.link_list() {
+            errors.check("links", validation::link(link));
+        }
+        errors.into_result()
+    }
+}
+
+/// Enforces the cooldown between changes and the hold on recently
+/// released names, which need the database.
+async fn check_username_available(
+    state: &AppState,
+    user: &User,
+    wanted: &str,
+    now: i64,
+) -> Result<(), FieldErrors> {
+    let lookup_failed = |err: sqlx::Error| {
+        error!("Failed to check username history: {err}");
+        FieldErrors::single("new_username", "Failed to update username")
+    };
+
+    let last_change = UsernameHistory::last_change_at(&state.db, user.id)
+        .await
+        .map_err(lookup_failed)?;
+    let next_allowed = last_change.map(|changed_at| {
+        changed_at.saturating_add(username::change_cooldown_micros())
+    });
+    if let Some(allowed_at) = next_allowed
+        && allowed_at > now
+    {
+        return Err(FieldErrors::single(
+            "new_username",
+            format!(
+                "You can change your username again after {}",
+                format_utc(allowed_at)
+            ),
+        ));
+    }
+
+    let held_since = now.saturating_sub(username::hold_micros());
+    let held =
+        UsernameHistory::is_held(&state.db, wanted, held_since, Some(user.id))
+            .await
+            .map_err(lookup_failed)?;
+    if held {
+        return Err(FieldErrors::single("new_username", USERNAME_TAKEN));
+    }
+    Ok(())
+}
+
 pub async fn update_username(
     State(state): State<AppState>,
+    client: ClientInfo,
     user_opt: Option<User>,
     Form(form): Form<UpdateUsernamePayload>,
 ) -> impl IntoResponse {
@@ -44,39 +236,75 @@ pub async fn update_username(
         return Redirect::to("/login").into_response();
     };
 
-    let username_error = password::validate_username(&form.new_username);
-    if !username_error.is_empty() {
+    if let Err(errors) = form.validate(&()) {
+        return components::username_form(&form.new_username, &errors, None)
+            .into_response();
+    }
+    if form.new_username == user.username {
         return components::username_form(
             &form.new_username,
-            &username_error,
-            false,
+            &FieldErrors::single(
+                "new_username",
+                "That's already your username",
+            ),
+            None,
         )
         .into_response();
     }
 
-    let query_result = sqlx::query!(
-        "UPDATE user SET username = ? WHERE id = ?",
-        form.new_username,
-        user.id
+    let now = current_time_micros();
+    if let Err(errors) =
+        check_username_available(&state, &user, &form.new_username, now).await
+    {
+        return components::username_form(&form.new_username, &errors, None)
+            .into_response();
+    }
+
+    let query_result = User::rename(
+        &state.db,
+        user.id,
+        &user.username,
+        &form.new_username,
+        now,
     )
-    .execute(&state.db)
     .await;
 
     match query_result {
-        Ok(_) => (
-            [("HX-Trigger", "username-updated")],
-            components::username_form(
-                &form.new_username,
-                "Username updated successfully!",
-                true,
-            ),
-        )
-            .into_response(),
+        Ok(()) => {
+            audit::record(
+                &state,
+                &client,
+                Some(user.id),
+                &user.username,
+                AuditKind::UsernameChanged,
+                format!("{} to {}", user.username, form.new_username),
+            )
+            .await;
+            state.events.publish(
+                user.id,
+                Event {
+                    name: "username".to_owned(),
+                    html: components::user_link(&form.new_username)
+                        .into_string(),
+                    topic: None,
+                    session_id: None,
+                },
+            );
+            (
+                [("HX-Trigger", "username-updated")],
+                components::username_form(
+                    &form.new_username,
+                    &FieldErrors::new(),
+                    Some("Username updated successfully!"),
+                ),
+            )
+                .into_response()
+        }
         Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
             components::username_form(
                 &form.new_username,
-                "Username already taken",
-                false,
+                &FieldErrors::single("new_username", USERNAME_TAKEN),
+                None,
             )
             .into_response()
         }
@@ -84,67 +312,153 @@ pub async fn update_username(
             error!("Failed to update username: {}", err);
             components::username_form(
                 &form.new_username,
-                "Failed to update username",
-                false,
+                &FieldErrors::single(
+                    "new_username",
+                    "Failed to update username",
+                ),
+                None,
             )
             .into_response()
         }
     }
 }
 
+/// Signs the user out everywhere but `keep`. Returns whether that worked.
+async fn revoke_other_sessions(
+    state: &AppState,
+    user_id: Uuid,
+    keep: Uuid,
+) -> bool {
+    match Session::delete_by_user_id_except(&state.db, user_id, keep).await {
+        Ok(revoked) => {
+            publish_revoked(&state.events, user_id, &revoked);
+            true
+        }
+        Err(err) => {
+            error!("Failed to revoke other sessions: {}", err);
+            false
+        }
+    }
+}
+
+fn password_changed_notice(signed_out: bool) -> Notice {
+    let devices = if signed_out {
+        "and your other devices were signed out"
+    } else {
+        "but your other devices could not be signed out"
+    };
+    Notice {
+        kind: NotificationKind::Account,
+        title: "Your password was changed".to_owned(),
+        body: format!(
+            "Your password was just changed, {devices}. If this wasn't you, \
+            reset your password and review your account's recent activity."
+        ),
+        link: Some("/settings/security".to_owned()),
+    }
+}
+
 pub async fn update_password(
     State(state): State<AppState>,
-    user_opt: Option<User>,
+    client: ClientInfo,
+    current_opt: Option<CurrentSession>,
     Form(form): Form<UpdatePasswordPayload>,
 ) -> impl IntoResponse {
-    let Some(user) = user_opt else {
+    let Some(CurrentSession { user, session }) = current_opt else {
         return Redirect::to("/login").into_response();
     };
 
-    let password_error = password::validate_password(&form.new_password);
-    if !password_error.is_empty() {
-        return components::password_form("", &password_error, false, false)
+    let has_password = user.password_hash.is_some();
+    if let Err(errors) = form.validate(&user) {
+        return components::password_form(has_password, &errors, None, None)
             .into_response();
     }
 
-    let valid_login =
+    // Accounts without a password have nothing to confirm; signing in
+    // through their identity provider proved who they are
+    let valid_login = if has_password {
         User::check_login(&state.db, &user.username, &form.current_password)
-            .await;
-    if valid_login.is_none() {
+            .await
+            .is_some()
+    } else {
+        true
+    };
+    if !valid_login {
         return components::password_form(
-            "Current password is incorrect",
-            "",
-            false,
-            false,
+            has_password,
+            &FieldErrors::single(
+                "current_password",
+                "Current password is incorrect",
+            ),
+            None,
+            None,
         )
         .into_response();
     }
 
-    let new_password_hash = password::generate_hash(&form.new_password);
+    let new_password_hash =
+        password::hash_async(form.new_password.clone()).await;
 
-    let query_result = sqlx::query!(
-        "UPDATE user SET password_hash = ? WHERE id = ?",
-        new_password_hash,
-        user.id
-    )
-    .execute(&state.db)
-    .await;
+    let query_result =
+        User::update_password_hash(&state.db, user.id, &new_password_hash)
+            .await;
 
     match query_result {
-        Ok(_) => components::password_form(
-            "",
-            "Password updated successfully!",
-            false,
-            true,
-        )
-        .into_response(),
+        Ok(()) => {
+            // Anyone holding another session may have known the old password
+            let signed_out =
+                revoke_other_sessions(&state, user.id, session.id).await;
+            let (detail, success, warning) = if signed_out {
+                (
+                    "Other sessions signed out",
+                    "Password updated successfully! Other devices have been signed out.",
+                    None,
+                )
+            } else {
+                (
+                    "Other sessions could not be signed out",
+                    "Password updated successfully!",
+                    Some(
+                        "Your other devices are still signed in. Sign them \
+                        out under Active Sessions.",
+                    ),
+                )
+            };
+            audit::record(
+                &state,
+                &client,
+                Some(user.id),
+                &user.username,
+                AuditKind::PasswordChanged,
+                detail.to_owned(),
+            )
+            .await;
+            notification::notify(
+                &state.db,
+                &state.job_tx,
+                &state.events,
+                user.id,
+                &password_changed_notice(signed_out),
+            )
+            .await;
+            components::password_form(
+                true,
+                &FieldErrors::new(),
+                Some(success),
+                warning,
+            )
+            .into_response()
+        }
         Err(err) => {
             error!("Failed to update password: {}", err);
             components::password_form(
-                "",
-                "Failed to update password",
-                false,
-                false,
+                has_password,
+                &FieldErrors::single(
+                    "new_password",
+                    "Failed to update password",
+                ),
+                None,
+                None,
             )
             .into_response()
         }
@@ -153,6 +467,7 @@ pub async fn update_password(
 
 pub async fn update_email(
     State(state): State<AppState>,
+    client: ClientInfo,
     user_opt: Option<User>,
     Form(form): Form<UpdateEmailPayload>,
 ) -> impl IntoResponse {
@@ -161,10 +476,26 @@ pub async fn update_email(
     };
 
     let email = form.email.trim();
+    if let Err(errors) = form.validate(&()) {
+        return components::email_form(email, &errors, None).into_response();
+    }
     let email_opt = if email.is_empty() { None } else { Some(email) };
 
     match User::update_email(&state.db, user.id, email_opt).await {
         Ok(()) => {
+            let detail = match email_opt {
+                Some(addr) => format!("Set to {addr}"),
+                None => "Removed".to_owned(),
+            };
+            audit::record(
+                &state,
+                &client,
+                Some(user.id),
+                &user.username,
+                AuditKind::EmailChanged,
+                detail,
+            )
+            .await;
             // Send verification email if email was provided
             if let Some(addr) = email_opt
                 && state
@@ -179,13 +510,185 @@ pub async fn update_email(
             {
                 error!("Failed to queue verification email");
             }
-            components::email_form(email, "Email updated!", true)
-                .into_response()
+            components::email_form(
+                email,
+                &FieldErrors::new(),
+                Some("Email updated!"),
+            )
+            .into_response()
         }
         Err(err) => {
             error!("Failed to update email: {}", err);
-            components::email_form(email, "Failed to update email", false)
-                .into_response()
+            components::email_form(
+                email,
+                &FieldErrors::single("email", "Failed to update email"),
+                None,
+            )
+            .into_response()
+        }
+    }
+}
+
+pub async fn update_profile(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    Form(form): Form<UpdateProfilePayload>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let profile = form.to_profile();
+    if let Err(errors) = form.validate(&()) {
+        return components::profile_form(&profile, &errors, None)
+            .into_response();
+    }
+
+    match Profile::upsert(&state.db, user.id, &profile, current_time_micros())
+        .await
+    {
+        Ok(()) => {
+            profile::publish_profile(&state, &user).await;
+            components::profile_form(
+                &profile,
+                &FieldErrors::new(),
+                Some("Profile updated!"),
+            )
+            .into_response()
+        }
+        Err(err) => {
+            error!("Failed to update profile: {}", err);
+            components::profile_form(
+                &profile,
+                &FieldErrors::single(
+                    "display_name",
+                    "Failed to update profile",
+                ),
+                None,
+            )
+            .into_response()
+        }
+    }
+}
+
+/// Reads the `avatar` file field, checking its declared type and size.
+async fn read_avatar_upload(
+    mut multipart: Multipart,
+) -> Result<Vec<u8>, String> {
+    let too_large =
+        || format!("Images can be at most {} MB.", avatar::MAX_UPLOAD_MB);
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => return Err(String::from("Choose an image to upload.")),
+            Err(err) if err.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                return Err(too_large());
+            }
+            Err(err) => return Err(format!("Upload failed: {err}")),
+        };
+        if field.name() != Some("avatar") {
+            continue;
+        }
+        let accepted = field
+            .content_type()
+            .is_some_and(|mime| avatar::ACCEPTED_TYPES.contains(&mime));
+        if !accepted {
+            return Err(String::from(
+                "That file isn't a PNG, JPEG, GIF or WebP image.",
+            ));
+        }
+        return match field.bytes().await {
+            Ok(bytes) if bytes.is_empty() => {
+                Err(String::from("Choose an image to upload."))
+            }
+            Ok(bytes) if bytes.len() > avatar::MAX_UPLOAD_BYTES => {
+                Err(too_large())
+            }
+            Ok(bytes) => Ok(bytes.to_vec()),
+            Err(err) if err.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                Err(too_large())
+            }
+            Err(err) => Err(format!("Upload failed: {err}")),
+        };
+    }
+}
+
+pub async fn update_avatar(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    let current_avatar = match Avatar::updated_at(&state.db, user.id).await {
+        Ok(updated_at) => {
+            updated_at.map(|previous| avatar_url(&user.username, previous))
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    let processed = match read_avatar_upload(multipart).await {
+        Ok(bytes) => avatar::process_async(bytes).await,
+        Err(message) => Err(message),
+    };
+    let data = match processed {
+        Ok(data) => data,
+        Err(message) => {
+            return components::avatar_form(
+                current_avatar.as_deref(),
+                &FieldErrors::single("avatar", message),
+                None,
+            )
+            .into_response();
+        }
+    };
+
+    let image = Avatar {
+        content_type: avatar::CONTENT_TYPE.to_owned(),
+        data,
+        updated_at: current_time_micros(),
+    };
+    match Avatar::upsert(&state.db, user.id, &image).await {
+        Ok(()) => {
+            profile::publish_profile(&state, &user).await;
+            components::avatar_form(
+                Some(&avatar_url(&user.username, image.updated_at)),
+                &FieldErrors::new(),
+                Some("Avatar updated!"),
+            )
+            .into_response()
+        }
+        Err(err) => {
+            error!("Failed to store avatar: {}", err);
+            components::avatar_form(
+                current_avatar.as_deref(),
+                &FieldErrors::single("avatar", "Failed to update avatar"),
+                None,
+            )
+            .into_response()
+        }
+    }
+}
+
+pub async fn delete_avatar(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+
+    match Avatar::delete(&state.db, user.id).await {
+        Ok(()) => {
+            profile::publish_profile(&state, &user).await;
+            components::avatar_form(
+                None,
+                &FieldErrors::new(),
+                Some("Avatar removed."),
+            )
+            .into_response()
         }
+        Err(err) => internal_error(err).into_response(),
     }
 }
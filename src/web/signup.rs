@@ -1,34 +1,104 @@
-use axum::extract::{ConnectInfo, State};
+use axum::extract::{ConnectInfo, Query, State};
+use axum::http::StatusCode;
 use axum::{
     Form,
-    response::{IntoResponse, Redirect},
+    response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::TypedHeader;
 use axum_extra::extract::CookieJar;
 use axum_extra::headers::UserAgent;
 use serde::Deserialize;
 use std::net::SocketAddr;
+use tracing::info;
 use uuid::Uuid;
 
 use crate::app_state::AppState;
+use crate::bot_check::{self, Challenge, Submission};
 use crate::error::internal_error;
-use crate::models::user::User;
+use crate::models::{
+    invite::Invite, user::User, username_history::UsernameHistory,
+};
 use crate::password;
+use crate::registration::{self, RegistrationMode};
+use crate::username;
 use crate::util::current_time_micros;
+use crate::validation::{self, FieldErrors, Validate};
 
-use super::{components, pages};
+use super::{BOT_SUSPECTED, USERNAME_TAKEN, components, oidc, pages};
 
-pub async fn get(user: Option<User>) -> impl IntoResponse {
-    let Some(_) = user else {
-        return pages::signup_page().into_response();
-    };
-    Redirect::to("/").into_response()
+/// Shown when an invite code doesn't match a usable invite.
+const INVITE_INVALID: &str = "That invite code is invalid, used up or expired.";
+
+#[derive(Deserialize, Debug)]
+pub struct SignupQuery {
+    /// Filled in from an invite link.
+    invite: Option<String>,
+}
+
+pub async fn get(
+    State(state): State<AppState>,
+    user: Option<User>,
+    Query(query): Query<SignupQuery>,
+) -> impl IntoResponse {
+    if user.is_some() {
+        return Redirect::to("/").into_response();
+    }
+    let mode = registration::mode();
+    if mode == RegistrationMode::Closed {
+        return pages::signup_closed().into_response();
+    }
+    let challenge =
+        Challenge::issue(bot_check::Form::Signup, current_time_micros());
+    if mode.needs_invite() {
+        // Provider sign-ups can't carry an invite, so they're left out
+        let code = query.invite.unwrap_or_default();
+        return pages::signup_page(&[], Some(&code), challenge.as_ref())
+            .into_response();
+    }
+    pages::signup_page(
+        &oidc::provider_displays(&state),
+        None,
+        challenge.as_ref(),
+    )
+    .into_response()
 }
 
 #[derive(Deserialize, Debug)]
 pub struct FormPayload {
     username: String,
     password: String,
+    /// Required when registration is invite-only.
+    #[serde(default)]
+    invite: String,
+    /// The honeypot, see [`bot_check::HONEYPOT_FIELD`].
+    #[serde(default)]
+    website: String,
+    #[serde(default)]
+    pow_challenge: String,
+    #[serde(default)]
+    pow_solution: String,
+}
+
+impl FormPayload {
+    /// The invite as entered, for re-rendering the form.
+    fn invite_field(&self) -> Option<&str> {
+        registration::mode()
+            .needs_invite()
+            .then_some(self.invite.as_str())
+    }
+
+    /// The form again with `errors` and a fresh challenge.
+    fn rejected(&self, errors: &FieldErrors) -> Response {
+        let challenge =
+            Challenge::issue(bot_check::Form::Signup, current_time_micros());
+        components::signup_form(
+            &self.username,
+            self.invite_field(),
+            challenge.as_ref(),
+            errors,
+        )
+        .into_response()
+    }
 }
 
 pub async fn post(
@@ -38,59 +108,122 @@ pub async fn post(
     State(state): State<AppState>,
     Form(form): Form<FormPayload>,
 ) -> impl IntoResponse {
-    if let Err((username_message, password_message)) = validate_inputs(&form) {
-        return components::signup_form(
-            &form.username,
-            &username_message,
-            &password_message,
-        )
-        .into_response();
+    let mode = registration::mode();
+    if mode == RegistrationMode::Closed {
+        return StatusCode::FORBIDDEN.into_response();
     }
-
     let created_at = current_time_micros();
-    let password_hash = password::generate_hash(&form.password);
+    let submission = Submission {
+        honeypot: &form.website,
+        challenge: &form.pow_challenge,
+        solution: &form.pow_solution,
+    };
+    match bot_check::check(
+        &state.db,
+        bot_check::Form::Signup,
+        &submission,
+        created_at,
+    )
+    .await
+    {
+        Ok(None) => {}
+        Ok(Some(rejection)) => {
+            info!(reason = rejection.as_str(), "Rejected automated sign-up");
+            return form
+                .rejected(&FieldErrors::single("challenge", BOT_SUSPECTED));
+        }
+        Err(err) => return internal_error(err).into_response(),
+    }
+    if let Err(errors) = form.validate(&()) {
+        return form.rejected(&errors);
+    }
+
+    let held_since = created_at.saturating_sub(username::hold_micros());
+    match UsernameHistory::is_held(&state.db, &form.username, held_since, None)
+        .await
+    {
+        Ok(false) => {}
+        Ok(true) => {
+            return form
+                .rejected(&FieldErrors::single("username", USERNAME_TAKEN));
+        }
+        Err(err) => return internal_error(err).into_response(),
+    }
+    let password_hash = password::hash_async(form.password.clone()).await;
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    // Used up in the same transaction, so a taken username gives it back
+    let invite = if mode.needs_invite() {
+        match Invite::redeem(
+            &mut *tx,
+            &registration::hash_code(&form.invite),
+            created_at,
+        )
+        .await
+        {
+            Ok(Some(invite)) => Some(invite),
+            Ok(None) => {
+                return form
+                    .rejected(&FieldErrors::single("invite", INVITE_INVALID));
+            }
+            Err(err) => return internal_error(err).into_response(),
+        }
+    } else {
+        None
+    };
 
     let uuid = Uuid::new_v4();
     let user = User {
         id: uuid,
         username: form.username.clone(),
-        password_hash,
-        email: None,
+        password_hash: Some(password_hash),
+        email: invite.and_then(|used| used.email),
         created_at,
+        is_admin: false,
     };
-    match User::insert(&state.db, &user).await {
+    match User::insert(&mut *tx, &user).await {
         Ok(user_id) => user_id,
         Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
-            return components::signup_form(
-                &form.username,
-                "Username already taken",
-                "",
-            )
-            .into_response();
+            return form
+                .rejected(&FieldErrors::single("username", USERNAME_TAKEN));
         }
         Err(err) => return internal_error(err).into_response(),
     }
+    if let Err(err) = tx.commit().await {
+        return internal_error(err).into_response();
+    }
 
     match super::session::create_session(
-        &state.db,
-        user.id,
-        created_at,
-        addr.to_string(),
-        user_agent,
+        &state.db, user.id, created_at, addr, user_agent, true,
     )
     .await
     {
-        Ok(cookie) => ([("HX-Redirect", "/")], jar.add(cookie)).into_response(),
+        Ok(new_session) => {
+            ([("HX-Redirect", "/")], jar.add(new_session.cookie))
+                .into_response()
+        }
         Err(err) => internal_error(err).into_response(),
     }
 }
 
-fn validate_inputs(form: &FormPayload) -> Result<(), (String, String)> {
-    let username_message = password::validate_username(&form.username);
-    let password_message = password::validate_password(&form.password);
-    if !username_message.is_empty() || !password_message.is_empty() {
-        Err((username_message, password_message))
-    } else {
-        Ok(())
+impl Validate for FormPayload {
+    type Context = ();
+
+    fn validate(&self, _context: &()) -> Result<(), FieldErrors> {
+        let mut errors = FieldErrors::new();
+        if registration::mode().needs_invite()
+            && registration::normalize_code(&self.invite).is_empty()
+        {
+            errors.add("invite", "Enter your invite code.");
+        }
+        errors.check("username", validation::username(&self.username));
+        errors.check(
+            "password",
+            password::check_password(&self.password, &[&self.username]),
+        );
+        errors.into_result()
     }
 }
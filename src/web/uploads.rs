@@ -0,0 +1,211 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Redirect};
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::error::internal_error;
+use crate::extractors::upload::{
+    FIELD, FileUpload, UploadRejection, format_size,
+};
+use crate::models::{upload::Upload, user::User};
+use crate::upload;
+use crate::util::{current_time_micros, format_utc};
+use crate::validation::FieldErrors;
+
+use super::{
+    components::{self, UploadDisplay},
+    pages,
+};
+
+#[derive(Deserialize)]
+pub struct SignedQuery {
+    expires: i64,
+    signature: String,
+}
+
+/// The user's uploads with freshly signed download links.
+async fn upload_displays(
+    state: &AppState,
+    user_id: Uuid,
+) -> Result<Vec<UploadDisplay>, sqlx::Error> {
+    let now = current_time_micros();
+    let config = upload::config();
+    Ok(Upload::get_by_user_id(&state.db, user_id)
+        .await?
+        .into_iter()
+        .map(|file| UploadDisplay {
+            id: file.id.to_string(),
+            url: config.signed_url(file.id, now),
+            size: format_size(usize::try_from(file.size).unwrap_or_default()),
+            uploaded_at: format_utc(file.created_at),
+            filename: file.filename,
+            content_type: file.content_type,
+        })
+        .collect())
+}
+
+pub async fn list(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+    match upload_displays(&state, user.id).await {
+        Ok(uploads) => pages::uploads(&user.username, &uploads).into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+pub async fn create(
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+    file_result: Result<FileUpload, UploadRejection>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return Redirect::to("/login").into_response();
+    };
+
+    let outcome = match file_result {
+        Ok(file) => store(&state, user.id, file).await,
+        Err(rejection) => Err(rejection.message()),
+    };
+    let (errors, success) = match outcome {
+        Ok(filename) => {
+            (FieldErrors::new(), Some(format!("Uploaded {filename}.")))
+        }
+        Err(message) => (FieldErrors::single(FIELD, message), None),
+    };
+
+    match upload_displays(&state, user.id).await {
+        Ok(uploads) => {
+            components::uploads_section(&uploads, &errors, success.as_deref())
+                .into_response()
+        }
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+
+/// Writes the bytes, then the metadata row; returns the stored filename or
+/// a message for the user.
+async fn store(
+    state: &AppState,
+    user_id: Uuid,
+    file: FileUpload,
+) -> Result<String, String> {
+    let id = Uuid::new_v4();
+    let record = Upload {
+        id,
+        user_id,
+        filename: file.filename,
+        content_type: file.content_type,
+        size: i64::try_from(file.data.len()).unwrap_or(i64::MAX),
+        storage_key: upload::storage_key(user_id, id),
+        created_at: current_time_micros(),
+    };
+
+    if let Err(err) = state
+        .storage
+        .put(&record.storage_key, &record.content_type, file.data)
+        .await
+    {
+        error!("Failed to store upload: {err}");
+        return Err("Failed to store the file. Please try again.".to_owned());
+    }
+    if let Err(err) = Upload::insert(&state.db, &record).await {
+        error!("Failed to record upload: {err}");
+        if let Err(cleanup_err) =
+            state.storage.delete(&record.storage_key).await
+        {
+            error!("Failed to remove orphaned upload: {cleanup_err}");
+        }
+        return Err("Failed to store the file. Please try again.".to_owned());
+    }
+    Ok(record.filename)
+}
+
+/// Serves a file to anyone holding a valid signed link. Always sent as an
+/// attachment so uploaded HTML or SVG can't run in the site's origin.
+pub async fn download(
+    Path(raw_id): Path<String>,
+    Query(query): Query<SignedQuery>,
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    let Ok(upload_id) = Uuid::parse_str(&raw_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let now = current_time_micros();
+    if !upload::config().verify(upload_id, query.expires, &query.signature, now)
+    {
+        return (StatusCode::FORBIDDEN, "This link is invalid or has expired")
+            .into_response();
+    }
+
+    let record = match Upload::get_by_id(&state.db, upload_id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let data = match state.storage.get(&record.storage_key).await {
+        Ok(Some(data)) => data,
+        Ok(None) => {
+            error!(%upload_id, "Upload metadata has no stored bytes");
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        Err(err) => return internal_error(err).into_response(),
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, record.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&record.filename),
+            ),
+            (header::CACHE_CONTROL, "private, no-cache".to_owned()),
+        ],
+        data,
+    )
+        .into_response()
+}
+
+/// `attachment` with an ASCII fallback name and the exact UTF-8 name.
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && c != '\\' { c } else { '_' })
+        .collect();
+    format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        utf8_percent_encode(filename, NON_ALPHANUMERIC)
+    )
+}
+
+pub async fn delete(
+    Path(raw_id): Path<String>,
+    State(state): State<AppState>,
+    user_opt: Option<User>,
+) -> impl IntoResponse {
+    let Some(user) = user_opt else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let Ok(upload_id) = Uuid::parse_str(&raw_id) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    match Upload::delete_owned(&state.db, upload_id, user.id).await {
+        Ok(Some(storage_key)) => {
+            if let Err(err) = state.storage.delete(&storage_key).await {
+                error!("Failed to delete stored upload {storage_key}: {err}");
+            }
+            // Empty body: HTMX swaps the row out
+            StatusCode::OK.into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
@@ -0,0 +1,11 @@
+// Fills the <progress> inside forms marked data-upload-progress while HTMX
+// sends the request body.
+document.addEventListener("htmx:xhr:progress", (event) => {
+  const form = event.target.closest("[data-upload-progress]");
+  const bar = form && form.querySelector("progress");
+  if (!bar || !event.detail.lengthComputable) {
+    return;
+  }
+  bar.hidden = false;
+  bar.value = Math.round((event.detail.loaded / event.detail.total) * 100);
+});
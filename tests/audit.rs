@@ -0,0 +1,235 @@
+//! Integration tests for the security audit log.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    clippy::indexing_slicing,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::body::{self, Body};
+use axum::http::{Request, StatusCode, header};
+use basic_site::models::audit_event::{AuditEvent, AuditFilter, AuditKind};
+use basic_site::models::session::Session;
+use basic_site::models::user::User;
+use basic_site::services::job::{self, Job};
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+
+use common::{
+    app, form_request, insert_session, new_user, send, setup_test_db,
+};
+
+async fn insert_user(db: &SqlitePool, username: &str, is_admin: bool) -> User {
+    let user = User {
+        is_admin,
+        ..new_user(username)
+    };
+    User::insert(db, &user).await.expect("insert failed");
+    user
+}
+
+fn event(username: &str, kind: AuditKind, created_at: i64) -> AuditEvent {
+    AuditEvent {
+        id: 0,
+        user_id: None,
+        username: username.to_owned(),
+        kind,
+        ip_address: "127.0.0.1".to_owned(),
+        user_agent: "TestAgent/1.0".to_owned(),
+        detail: String::new(),
+        created_at,
+    }
+}
+
+async fn login(db: SqlitePool, password: &str) {
+    login_as(db, "audited", password).await;
+}
+
+async fn login_as(db: SqlitePool, username: &str, password: &str) {
+    let form = format!("username={username}&password={password}");
+    send(&db, form_request("/session", "", &form)).await;
+}
+
+async fn get_page(
+    db: SqlitePool,
+    uri: &str,
+    session: &Session,
+) -> (StatusCode, String) {
+    let request = Request::get(uri)
+        .header(header::COOKIE, format!("session_id={}", session.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db).oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn logins_and_failures_are_recorded() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "audited", false).await;
+
+    login(db.clone(), "wrongpassword").await;
+    login(db.clone(), "password123").await;
+
+    let events = AuditEvent::get_by_user_id(&db, user.id, 10)
+        .await
+        .expect("get failed");
+    let kinds: Vec<AuditKind> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(kinds, [AuditKind::Login, AuditKind::LoginFailed]);
+    assert_eq!(events[0].ip_address, "127.0.0.1");
+    assert_eq!(events[0].user_agent, "TestAgent/1.0");
+}
+
+#[tokio::test]
+async fn security_activity_lists_own_events() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "audited", false).await;
+    login(db.clone(), "password123").await;
+    let session = insert_session(&db, user.id).await;
+
+    let (status, page) = get_page(db, "/settings/security", &session).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Signed in"));
+    assert!(!page.contains("All users"));
+}
+
+#[tokio::test]
+async fn admin_log_requires_admin() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "regular", false).await;
+    let session = insert_session(&db, user.id).await;
+
+    let (status, _) = get_page(db, "/admin/audit", &session).await;
+
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn admin_log_filters_events() {
+    let db = setup_test_db().await;
+    let admin = insert_user(&db, "boss", true).await;
+    let session = insert_session(&db, admin.id).await;
+    let now = current_time_micros();
+    for (username, kind) in [
+        ("alice", AuditKind::Login),
+        ("alice", AuditKind::PasswordChanged),
+        ("mallory", AuditKind::LoginFailed),
+    ] {
+        AuditEvent::insert(&db, &event(username, kind, now))
+            .await
+            .expect("insert failed");
+    }
+
+    let (status, page) =
+        get_page(db.clone(), "/admin/audit?username=alice&kind=", &session)
+            .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Password changed"));
+    assert!(!page.contains("mallory"));
+
+    let filter = AuditFilter {
+        username: None,
+        kind: Some("login_failed".to_owned()),
+    };
+    let events = AuditEvent::search(&db, &filter, 10)
+        .await
+        .expect("search failed");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].username, "mallory");
+}
+
+#[tokio::test]
+async fn admin_log_matches_usernames_in_any_case() {
+    let db = setup_test_db().await;
+    let now = current_time_micros();
+    for username in ["Alice", "alice", "mallory"] {
+        AuditEvent::insert(&db, &event(username, AuditKind::LoginFailed, now))
+            .await
+            .expect("insert failed");
+    }
+
+    let filter = AuditFilter {
+        username: Some("ALICE".to_owned()),
+        kind: None,
+    };
+    let events = AuditEvent::search(&db, &filter, 10)
+        .await
+        .expect("search failed");
+
+    let mut usernames: Vec<&str> =
+        events.iter().map(|event| event.username.as_str()).collect();
+    usernames.sort_unstable();
+    assert_eq!(usernames, ["Alice", "alice"]);
+}
+
+#[tokio::test]
+async fn failed_login_usernames_are_truncated() {
+    let db = setup_test_db().await;
+    let typed = "x".repeat(500);
+
+    login_as(db.clone(), &typed, "password123").await;
+
+    let filter = AuditFilter {
+        username: None,
+        kind: Some("login_failed".to_owned()),
+    };
+    let events = AuditEvent::search(&db, &filter, 10)
+        .await
+        .expect("search failed");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].username, "x".repeat(20));
+}
+
+#[tokio::test]
+async fn audit_events_are_append_only() {
+    let db = setup_test_db().await;
+    let id = AuditEvent::insert(
+        &db,
+        &event("alice", AuditKind::Login, current_time_micros()),
+    )
+    .await
+    .expect("insert failed");
+
+    let err =
+        sqlx::query("UPDATE audit_event SET username = 'eve' WHERE id = ?")
+            .bind(id)
+            .execute(&db)
+            .await
+            .expect_err("update should be rejected");
+
+    assert!(err.to_string().contains("append-only"));
+}
+
+#[tokio::test]
+async fn prune_job_deletes_old_events() {
+    let db = setup_test_db().await;
+    let now = current_time_micros();
+    AuditEvent::insert(&db, &event("alice", AuditKind::Login, now - 1_000))
+        .await
+        .expect("insert failed");
+    AuditEvent::insert(&db, &event("alice", AuditKind::Logout, now))
+        .await
+        .expect("insert failed");
+
+    let (job_tx, job_rx) = job::channel();
+    job_tx
+        .send(Job::PruneAuditEvents { before: now })
+        .expect("send failed");
+    drop(job_tx);
+    job::run(db.clone(), job_rx).await;
+
+    let events = AuditEvent::search(&db, &AuditFilter::default(), 10)
+        .await
+        .expect("search failed");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].kind, AuditKind::Logout);
+}
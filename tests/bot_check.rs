@@ -0,0 +1,167 @@
+//! Integration tests for the bot checks on signup and login. The config is
+//! process-wide, so every test here runs with the checks on.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use basic_site::bot_check::{self, BotCheckConfig, Challenge, Form};
+use basic_site::models::user::User;
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+
+use common::{body_text, form_request, get, insert_user, send};
+
+/// A passphrase that passes the password policy, form-encoded.
+const PASSWORD: &str = "caf%C3%A9+m%C3%B6we+kitesurf";
+
+const DIFFICULTY: u32 = 8;
+const MIN_FILL_SECS: i64 = 2;
+const LOGIN_FAILURES: i64 = 2;
+
+const SUSPECTED: &str = "couldn't tell you apart from a bot";
+
+async fn setup_test_db() -> SqlitePool {
+    bot_check::init(BotCheckConfig::default().with_thresholds(
+        DIFFICULTY,
+        MIN_FILL_SECS,
+        LOGIN_FAILURES,
+    ));
+    common::setup_test_db().await
+}
+
+/// A challenge issued `secs_ago`, and the counter that solves it.
+fn solved(form: Form, secs_ago: i64) -> (String, u64) {
+    let issued_at = current_time_micros() - secs_ago * 1_000_000;
+    let challenge = Challenge::issue(form, issued_at).expect("checks are on");
+    let counter = bot_check::solve(&challenge.token, challenge.difficulty);
+    (challenge.token, counter)
+}
+
+fn signup_body(username: &str, challenge: &(String, u64)) -> String {
+    format!(
+        "username={username}&password={PASSWORD}&website=&pow_challenge={}&pow_solution={}",
+        challenge.0, challenge.1
+    )
+}
+
+#[tokio::test]
+async fn signup_page_embeds_a_challenge_and_honeypot() {
+    let db = setup_test_db().await;
+    let page = body_text(send(&db, get("/signup", "")).await).await;
+    assert!(page.contains(&format!(r#"data-pow-difficulty="{DIFFICULTY}""#)));
+    assert!(page.contains(r#"name="website""#));
+    assert!(page.contains("proof-of-work"));
+}
+
+#[tokio::test]
+async fn solved_challenges_sign_up_once() {
+    let db = setup_test_db().await;
+    let challenge = solved(Form::Signup, MIN_FILL_SECS + 1);
+
+    let solver = signup_body("solver", &challenge);
+    let accepted = send(&db, form_request("/signup", "", &solver)).await;
+    assert_eq!(accepted.headers()["hx-redirect"], "/");
+
+    let replay = signup_body("replayer", &challenge);
+    let replayed = send(&db, form_request("/signup", "", &replay)).await;
+    let html = body_text(replayed).await;
+    assert!(html.contains(SUSPECTED), "{html}");
+    assert!(
+        html.contains("data-pow-difficulty"),
+        "a fresh challenge is issued"
+    );
+    User::get_by_username(&db, "replayer")
+        .await
+        .expect_err("user should not be created");
+}
+
+#[tokio::test]
+async fn signups_that_look_automated_are_rejected() {
+    let db = setup_test_db().await;
+    let (old_token, old_counter) = solved(Form::Signup, 60 * 60);
+    let (fast_token, fast_counter) = solved(Form::Signup, 0);
+    let (login_token, login_counter) = solved(Form::Login, MIN_FILL_SECS + 1);
+    let (token, counter) = solved(Form::Signup, MIN_FILL_SECS + 1);
+    let unsolved = (0..counter)
+        .next_back()
+        .map_or_else(|| "not-a-number".to_owned(), |c| c.to_string());
+
+    let attempts = [
+        format!(
+            "username=honeypot1&password={PASSWORD}&website=http%3A%2F%2Fspam.example&pow_challenge={token}&pow_solution={counter}"
+        ),
+        format!("username=missing1&password={PASSWORD}"),
+        format!(
+            "username=unsolved1&password={PASSWORD}&pow_challenge={token}&pow_solution={unsolved}"
+        ),
+        format!(
+            "username=tooquick1&password={PASSWORD}&pow_challenge={fast_token}&pow_solution={fast_counter}"
+        ),
+        format!(
+            "username=expired1&password={PASSWORD}&pow_challenge={old_token}&pow_solution={old_counter}"
+        ),
+        format!(
+            "username=wrongform1&password={PASSWORD}&pow_challenge={login_token}&pow_solution={login_counter}"
+        ),
+        format!(
+            "username=forged1&password={PASSWORD}&pow_challenge={}.{}&pow_solution={counter}",
+            token.rsplit_once('.').unwrap().0,
+            "0".repeat(64)
+        ),
+    ];
+    for attempt in attempts {
+        let response = send(&db, form_request("/signup", "", &attempt)).await;
+        let html = body_text(response).await;
+        assert!(html.contains(SUSPECTED), "{attempt}: {html}");
+    }
+    assert!(
+        sqlx::query_scalar!("SELECT COUNT(*) FROM user")
+            .fetch_one(&db)
+            .await
+            .unwrap()
+            == 0
+    );
+}
+
+#[tokio::test]
+async fn logins_are_challenged_after_repeated_failures() {
+    let db = setup_test_db().await;
+    insert_user(&db, "guarded").await;
+
+    let wrong = "username=guarded&password=nope";
+    let first = send(&db, form_request("/session", "", wrong)).await;
+    assert!(!body_text(first).await.contains("data-pow-difficulty"));
+    let second = send(&db, form_request("/session", "", wrong)).await;
+    assert!(body_text(second).await.contains("data-pow-difficulty"));
+
+    // The right password isn't enough without solving the challenge
+    let right = "username=guarded&password=password123";
+    let unsolved = send(&db, form_request("/session", "", right)).await;
+    assert!(!unsolved.headers().contains_key("hx-redirect"));
+    assert!(body_text(unsolved).await.contains(SUSPECTED));
+
+    let page = body_text(send(&db, get("/login", "")).await).await;
+    assert!(
+        page.contains("data-pow-difficulty"),
+        "this address is challenged"
+    );
+
+    let (token, counter) = solved(Form::Login, MIN_FILL_SECS + 1);
+    let solved_form =
+        format!("{right}&pow_challenge={token}&pow_solution={counter}");
+    let accepted = send(&db, form_request("/session", "", &solved_form)).await;
+    assert_eq!(accepted.headers()["hx-redirect"], "/");
+}
+
+#[tokio::test]
+async fn login_honeypot_applies_before_any_failures() {
+    let db = setup_test_db().await;
+    let honeypot = "username=anybody&password=whatever&website=spam";
+    let response = send(&db, form_request("/session", "", honeypot)).await;
+    assert!(body_text(response).await.contains(SUSPECTED));
+}
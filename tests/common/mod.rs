@@ -0,0 +1,152 @@
+//! Helpers shared by the integration tests: an in-memory database, the
+//! application around it, requests to it, and signed-in users.
+#![allow(dead_code, reason = "Each test file uses a different subset")]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::{self, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, Response, header};
+use basic_site::app_state::AppState;
+use basic_site::db::MIGRATOR;
+use basic_site::models::session::{PERSISTENT_TTL_MICROS, Session};
+use basic_site::models::user::User;
+use basic_site::password::generate_hash;
+use basic_site::services::events::Broker;
+use basic_site::services::job::{self, Envelope};
+use basic_site::storage::memory::MemoryStorage;
+use basic_site::util::current_time_micros;
+use basic_site::web;
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+/// Creates an in-memory `SQLite` database with migrations applied.
+pub async fn setup_test_db() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create in-memory database");
+    MIGRATOR.run(&pool).await.expect("Failed to run migrations");
+    pool
+}
+
+/// Application state over `db` with in-memory storage and no OIDC
+/// providers, and the receiving end of its job queue.
+pub fn state(db: SqlitePool) -> (AppState, UnboundedReceiver<Envelope>) {
+    let (job_tx, job_rx) = job::channel();
+    let state = AppState {
+        db,
+        job_tx,
+        storage: MemoryStorage::shared(),
+        oidc: Arc::default(),
+        events: Broker::default(),
+    };
+    (state, job_rx)
+}
+
+/// The site's router over `db`, dropping any queued jobs.
+pub fn app(db: SqlitePool) -> Router {
+    web::router().with_state(state(db).0)
+}
+
+/// The site's router over `db`, with the jobs its handlers queue.
+pub fn app_with_jobs(db: SqlitePool) -> (Router, UnboundedReceiver<Envelope>) {
+    let (state, job_rx) = state(db);
+    (web::router().with_state(state), job_rx)
+}
+
+/// Sends `request` through [`app`] over `db`.
+pub async fn send(db: &SqlitePool, request: Request<Body>) -> Response<Body> {
+    app(db.clone()).oneshot(request).await.unwrap()
+}
+
+/// A GET from 127.0.0.1 with the session `cookie`; empty for none.
+pub fn get(uri: &str, cookie: &str) -> Request<Body> {
+    Request::get(uri)
+        .header(header::COOKIE, cookie)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// A form POST from 127.0.0.1 with the session `cookie`; empty for none.
+pub fn form_request(uri: &str, cookie: &str, form: &str) -> Request<Body> {
+    Request::post(uri)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::USER_AGENT, "TestAgent/1.0")
+        .header(header::COOKIE, cookie)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::from(form.to_owned()))
+        .unwrap()
+}
+
+/// A DELETE with the session `cookie`.
+pub fn delete(uri: &str, cookie: &str) -> Request<Body> {
+    Request::delete(uri)
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap()
+}
+
+pub async fn body_text(response: Response<Body>) -> String {
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// A user with the password "password123" and no email, not yet stored.
+pub fn new_user(username: &str) -> User {
+    User {
+        id: Uuid::new_v4(),
+        username: username.to_owned(),
+        password_hash: Some(generate_hash("password123")),
+        email: None,
+        created_at: current_time_micros(),
+        is_admin: false,
+    }
+}
+
+pub async fn insert_user(db: &SqlitePool, username: &str) -> User {
+    let user = new_user(username);
+    User::insert(db, &user).await.expect("insert failed");
+    user
+}
+
+/// A remembered session for `user_id` from 127.0.0.1.
+pub async fn insert_session(db: &SqlitePool, user_id: Uuid) -> Session {
+    let now = current_time_micros();
+    let session = Session {
+        id: Uuid::new_v4(),
+        user_id,
+        ip_address: "127.0.0.1:12345".to_owned(),
+        user_agent: "TestAgent/1.0".to_owned(),
+        created_at: now,
+        expires_at: now.saturating_add(PERSISTENT_TTL_MICROS),
+        persistent: true,
+        last_seen_at: now,
+        last_ip: "127.0.0.1".to_owned(),
+    };
+    Session::insert(db, &session)
+        .await
+        .expect("session insert failed");
+    session
+}
+
+/// Logs `user` in and returns the session cookie header value.
+pub async fn sign_in(db: &SqlitePool, user: &User) -> String {
+    let session = insert_session(db, user.id).await;
+    format!("session_id={}", session.id)
+}
+
+/// A signed-in user; returns the user and their session cookie.
+pub async fn signed_in(db: &SqlitePool, username: &str) -> (User, String) {
+    let user = insert_user(db, username).await;
+    let cookie = sign_in(db, &user).await;
+    (user, cookie)
+}
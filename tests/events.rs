@@ -0,0 +1,197 @@
+//! Integration tests for the live update stream and what handlers push to
+//! it.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use std::time::Duration;
+
+use axum::Router;
+use axum::body::{Body, BodyDataStream};
+use axum::http::{Request, StatusCode, header};
+use basic_site::models::session::Session;
+use basic_site::models::user::User;
+use basic_site::services::events::Broker;
+use basic_site::web;
+use futures_util::StreamExt as _;
+use sqlx::SqlitePool;
+use tokio::time::{self, timeout};
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{
+    body_text, form_request, insert_session, insert_user, setup_test_db,
+};
+
+/// A router whose requests share one broker, returned alongside it.
+fn app(db: SqlitePool) -> (Router, Broker) {
+    let (state, _job_rx) = common::state(db);
+    let events = state.events.clone();
+    (web::router().with_state(state), events)
+}
+
+/// Signs the user in on another device; returns its session cookie.
+async fn sign_in(db: &SqlitePool, user: &User) -> (Uuid, String) {
+    let session = insert_session(db, user.id).await;
+    (session.id, format!("session_id={}", session.id))
+}
+
+async fn open_stream(router: &Router, cookie: &str) -> BodyDataStream {
+    let request = Request::get("/events")
+        .header(header::COOKIE, cookie)
+        .body(Body::empty())
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK, "the stream opened");
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream",
+        "the stream is SSE"
+    );
+    response.into_body().into_data_stream()
+}
+
+/// The stream's next frame, or `None` once it has ended.
+async fn next_frame(stream: &mut BodyDataStream) -> Option<String> {
+    let chunk = timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("a frame within the timeout")?;
+    Some(String::from_utf8(chunk.unwrap().to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn streams_start_with_a_heartbeat_and_need_a_session() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "streamer").await;
+    let (_, cookie) = sign_in(&db, &user).await;
+    let (router, events) = app(db);
+
+    let mut stream = open_stream(&router, &cookie).await;
+    let hello = next_frame(&mut stream).await.unwrap();
+    assert!(hello.contains("event: heartbeat\n"));
+    assert!(hello.contains("retry: 3000\n"));
+    assert_eq!(events.subscribers(user.id), 1);
+
+    drop(stream);
+    assert_eq!(events.subscribers(user.id), 0);
+
+    let request = Request::get("/events").body(Body::empty()).unwrap();
+    let signed_out = router.oneshot(request).await.unwrap();
+    assert_eq!(signed_out.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn revoking_a_session_updates_the_users_open_pages() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "twodevices").await;
+    let (laptop, laptop_cookie) = sign_in(&db, &user).await;
+    let (_phone, phone_cookie) = sign_in(&db, &user).await;
+    let (router, _events) = app(db);
+
+    let mut on_laptop = open_stream(&router, &laptop_cookie).await;
+    let mut on_phone = open_stream(&router, &phone_cookie).await;
+    next_frame(&mut on_laptop).await;
+    next_frame(&mut on_phone).await;
+
+    let request = Request::delete(format!("/sessions/{laptop}"))
+        .header(header::COOKIE, &phone_cookie)
+        .body(Body::empty())
+        .unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let removed = next_frame(&mut on_phone).await.unwrap();
+    assert!(removed.contains(&format!("event: session-revoked-{laptop}\n")));
+
+    let row_removed = next_frame(&mut on_laptop).await.unwrap();
+    assert!(row_removed.contains("event: session-revoked-"));
+    let notice = next_frame(&mut on_laptop).await.unwrap();
+    assert!(notice.contains("event: signed-out\n"));
+    assert!(notice.contains("This device was signed out"));
+    assert_eq!(next_frame(&mut on_laptop).await, None);
+}
+
+#[tokio::test]
+async fn streams_end_once_the_session_is_no_longer_valid() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "lapsed").await;
+    let (deleted, deleted_cookie) = sign_in(&db, &user).await;
+    let (expired, expired_cookie) = sign_in(&db, &user).await;
+    let (_kept, kept_cookie) = sign_in(&db, &user).await;
+    let (router, _events) = app(db.clone());
+
+    let mut streams = Vec::new();
+    for cookie in [&deleted_cookie, &expired_cookie, &kept_cookie] {
+        let mut opened = open_stream(&router, cookie).await;
+        next_frame(&mut opened).await;
+        streams.push(opened);
+    }
+    // Ended without publishing anything, like a password change
+    Session::delete_by_id(&db, deleted).await.unwrap();
+    Session::renew(&db, expired, 0).await.unwrap();
+
+    time::pause();
+    time::advance(Duration::from_secs(15)).await;
+    let [mut on_deleted, mut on_expired, mut on_kept] =
+        <[BodyDataStream; 3]>::try_from(streams).unwrap();
+    for ending in [&mut on_deleted, &mut on_expired] {
+        let chunk = ending.next().await.unwrap().unwrap();
+        let notice = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(notice.contains("event: signed-out\n"), "{notice}");
+        assert!(notice.contains("This session has ended"));
+        assert!(ending.next().await.is_none());
+    }
+    let beat = on_kept.next().await.unwrap().unwrap();
+    assert!(
+        String::from_utf8(beat.to_vec())
+            .unwrap()
+            .contains("heartbeat")
+    );
+}
+
+#[tokio::test]
+async fn renaming_pushes_the_new_navbar_link() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "oldname").await;
+    let (_, cookie) = sign_in(&db, &user).await;
+    let (router, _events) = app(db);
+
+    let mut stream = open_stream(&router, &cookie).await;
+    next_frame(&mut stream).await;
+
+    let request =
+        form_request("/settings/username", &cookie, "new_username=newname");
+    router.clone().oneshot(request).await.unwrap();
+
+    let frame = next_frame(&mut stream).await.unwrap();
+    assert!(frame.contains("event: username\n"));
+    assert!(frame.contains(r#"data: <a href="/users/newname">newname</a>"#));
+}
+
+#[tokio::test]
+async fn signed_in_pages_connect_to_the_stream() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "reader").await;
+    let (session_id, cookie) = sign_in(&db, &user).await;
+    let (router, _events) = app(db);
+
+    let profile = Request::get("/users/reader")
+        .header(header::COOKIE, &cookie)
+        .body(Body::empty())
+        .unwrap();
+    let page = body_text(router.clone().oneshot(profile).await.unwrap()).await;
+    assert!(page.contains(r#"sse-connect="/events""#));
+    assert!(page.contains(r#"sse-swap="profile""#));
+    assert!(
+        page.contains(&format!(r#"sse-swap="session-revoked-{session_id}""#))
+    );
+
+    let about = Request::get("/about").body(Body::empty()).unwrap();
+    let signed_out = body_text(router.oneshot(about).await.unwrap()).await;
+    assert!(!signed_out.contains("sse-connect"));
+}
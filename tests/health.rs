@@ -0,0 +1,119 @@
+//! Integration tests for the health and readiness probes.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::{self, Body};
+use axum::http::{Request, StatusCode};
+use basic_site::app_state::AppState;
+use basic_site::db::MIGRATOR;
+use basic_site::services::events::Broker;
+use basic_site::services::job;
+use basic_site::storage::memory::MemoryStorage;
+use basic_site::web;
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqlitePoolOptions;
+use tower::ServiceExt as _;
+
+async fn setup_test_db(migrate: bool) -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create in-memory database");
+
+    if migrate {
+        MIGRATOR.run(&pool).await.expect("Failed to run migrations");
+    }
+
+    pool
+}
+
+async fn get(app: Router, uri: &str) -> (StatusCode, String) {
+    let response = app
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn healthz_ok() {
+    let (job_tx, _job_rx) = job::channel();
+    let state = AppState {
+        db: setup_test_db(false).await,
+        job_tx,
+        storage: MemoryStorage::shared(),
+        oidc: Arc::default(),
+        events: Broker::default(),
+    };
+    let app = web::health_router().with_state(state);
+
+    let (status, body) = get(app, "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, r#"{"status":"ok"}"#);
+}
+
+#[tokio::test]
+async fn readyz_ready() {
+    let (job_tx, _job_rx) = job::channel();
+    let state = AppState {
+        db: setup_test_db(true).await,
+        job_tx,
+        storage: MemoryStorage::shared(),
+        oidc: Arc::default(),
+        events: Broker::default(),
+    };
+    let app = web::health_router().with_state(state);
+
+    let (status, body) = get(app, "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""status":"ready""#));
+    assert!(body.contains(r#""password_hashing":{"waiting":0"#));
+}
+
+#[tokio::test]
+async fn readyz_unavailable_without_migrations() {
+    let (job_tx, _job_rx) = job::channel();
+    let state = AppState {
+        db: setup_test_db(false).await,
+        job_tx,
+        storage: MemoryStorage::shared(),
+        oidc: Arc::default(),
+        events: Broker::default(),
+    };
+    let app = web::health_router().with_state(state);
+
+    let (status, body) = get(app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body.contains(r#""database":{"ok":true}"#));
+    assert!(body.contains(r#""migrations":{"ok":false}"#));
+    assert!(!body.contains("pending"), "details stay in the logs");
+}
+
+#[tokio::test]
+async fn readyz_unavailable_when_job_processor_stopped() {
+    let (job_tx, job_rx) = job::channel();
+    drop(job_rx);
+    let state = AppState {
+        db: setup_test_db(true).await,
+        job_tx,
+        storage: MemoryStorage::shared(),
+        oidc: Arc::default(),
+        events: Broker::default(),
+    };
+    let app = web::health_router().with_state(state);
+
+    let (status, body) = get(app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body.contains(r#""job_processor":{"ok":false}"#));
+    assert!(!body.contains("closed"), "details stay in the logs");
+}
@@ -0,0 +1,198 @@
+//! Integration tests for invite-only registration. The registration mode
+//! is process-wide, so every test here runs invite-only.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use basic_site::models::invite::Invite;
+use basic_site::models::user::User;
+use basic_site::registration::{self, RegistrationMode};
+use basic_site::services::job::Job;
+use basic_site::util::{MICROS_PER_DAY, current_time_micros};
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{app_with_jobs, body_text, form_request, send, signed_in};
+
+/// A passphrase that passes the password policy, form-encoded.
+const PASSWORD: &str = "caf%C3%A9+m%C3%B6we+kitesurf";
+
+async fn setup_test_db() -> SqlitePool {
+    registration::init(RegistrationMode::InviteOnly);
+    common::setup_test_db().await
+}
+
+async fn sign_up(db: &SqlitePool, username: &str, invite: &str) -> String {
+    let form =
+        format!("username={username}&password={PASSWORD}&invite={invite}");
+    let response = send(db, form_request("/signup", "", &form)).await;
+    if response.headers().contains_key("hx-redirect") {
+        "signed up".to_owned()
+    } else {
+        body_text(response).await
+    }
+}
+
+/// Inserts an invite for `code` created by `creator`.
+async fn insert_invite(
+    db: &SqlitePool,
+    creator: &User,
+    code: &str,
+    max_uses: i64,
+    expires_at: i64,
+) {
+    let invite = Invite {
+        id: Uuid::new_v4(),
+        code_hash: registration::hash_code(code),
+        created_by: creator.id,
+        email: None,
+        max_uses,
+        use_count: 0,
+        expires_at,
+        created_at: current_time_micros(),
+    };
+    Invite::insert(db, &invite).await.expect("insert invite");
+}
+
+/// The code shown after creating an invite.
+fn shown_code(html: &str) -> String {
+    html.split("<dd><code>")
+        .nth(1)
+        .and_then(|rest| rest.split('<').next())
+        .expect("a code is shown")
+        .to_owned()
+}
+
+#[tokio::test]
+async fn signup_page_asks_for_the_invite_from_the_link() {
+    let db = setup_test_db().await;
+    let request = Request::get("/signup?invite=K7QXM-2HRTA")
+        .body(Body::empty())
+        .unwrap();
+    let page = body_text(send(&db, request).await).await;
+    assert!(page.contains("Invite code"));
+    assert!(page.contains(r#"value="K7QXM-2HRTA""#));
+}
+
+#[tokio::test]
+async fn signup_needs_a_valid_invite() {
+    let db = setup_test_db().await;
+    let (inviter, _cookie) = signed_in(&db, "inviter").await;
+    let now = current_time_micros();
+    insert_invite(&db, &inviter, "EXPRD-CODE2", 5, now - 1).await;
+
+    let missing = sign_up(&db, "newcomer", "").await;
+    assert!(missing.contains("Enter your invite code."), "{missing}");
+    let bogus = sign_up(&db, "newcomer", "NOPE2-NOPE3").await;
+    assert!(bogus.contains("invalid, used up or expired"), "{bogus}");
+    let expired = sign_up(&db, "newcomer", "EXPRD-CODE2").await;
+    assert!(expired.contains("invalid, used up or expired"));
+    User::get_by_username(&db, "newcomer")
+        .await
+        .expect_err("user should not be created");
+}
+
+#[tokio::test]
+async fn invites_are_used_up_but_failed_signups_give_uses_back() {
+    let db = setup_test_db().await;
+    let (inviter, cookie) = signed_in(&db, "hostess").await;
+    let created =
+        send(&db, form_request("/invites", &cookie, "max_uses=1&days=7")).await;
+    let code = shown_code(&body_text(created).await);
+
+    // A taken username doesn't spend the invite
+    let taken = sign_up(&db, "hostess", &code).await;
+    assert!(taken.contains("Username is taken"), "{taken}");
+
+    let typed = code.to_ascii_lowercase().replace('-', "");
+    assert_eq!(sign_up(&db, "guest", &typed).await, "signed up");
+    let again = sign_up(&db, "latecomer", &code).await;
+    assert!(again.contains("invalid, used up or expired"));
+
+    let stored = Invite::list_by_creator(&db, inviter.id).await.unwrap();
+    assert_eq!(stored[0].use_count, 1);
+    let request = Request::get("/invites")
+        .header(header::COOKIE, &cookie)
+        .body(Body::empty())
+        .unwrap();
+    let page = body_text(send(&db, request).await).await;
+    assert!(page.contains("1 of 1"));
+    assert!(page.contains("No longer usable"));
+}
+
+#[tokio::test]
+async fn emailed_invites_send_the_link_and_set_the_address() {
+    let db = setup_test_db().await;
+    let (_inviter, cookie) = signed_in(&db, "sender").await;
+    let (router, mut job_rx) = app_with_jobs(db.clone());
+    let created = router
+        .oneshot(form_request(
+            "/invites",
+            &cookie,
+            "email=friend%40example.com&max_uses=1&days=3",
+        ))
+        .await
+        .unwrap();
+    let html = body_text(created).await;
+    assert!(html.contains("Sent to friend@example.com."));
+    let code = shown_code(&html);
+
+    let envelope = job_rx.try_recv().expect("an email was queued");
+    let Job::SendEmail { to, subject, body } = envelope.job else {
+        panic!("expected an email");
+    };
+    assert_eq!(to, "friend@example.com");
+    assert!(subject.contains("sender"));
+    assert!(body.contains(&registration::invite_url(&code)));
+
+    assert_eq!(sign_up(&db, "friend", &code).await, "signed up");
+    let user = User::get_by_username(&db, "friend").await.unwrap();
+    assert_eq!(user.email.as_deref(), Some("friend@example.com"));
+}
+
+#[tokio::test]
+async fn members_get_smaller_invites_than_admins() {
+    let db = setup_test_db().await;
+    let (_user, cookie) = signed_in(&db, "regular").await;
+    let response = send(
+        &db,
+        form_request("/invites", &cookie, "max_uses=50&days=90"),
+    )
+    .await;
+    let html = body_text(response).await;
+    assert!(html.contains("Choose 1 to 5 uses."), "{html}");
+    assert!(html.contains("Choose 1 to 30 days."));
+    assert!(html.contains(r#"value="50""#), "drafts are kept");
+}
+
+#[tokio::test]
+async fn only_the_creator_revokes_an_invite() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "owner").await;
+    let (_other, other_cookie) = signed_in(&db, "other").await;
+    let expires_at = current_time_micros() + MICROS_PER_DAY;
+    insert_invite(&db, &owner, "RVKED-CODE3", 5, expires_at).await;
+    let invite_id = Invite::list_by_creator(&db, owner.id).await.unwrap()[0].id;
+
+    let revoke = |cookie: &str| {
+        Request::delete(format!("/invites/{invite_id}"))
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let refused = send(&db, revoke(&other_cookie)).await;
+    assert_eq!(refused.status(), StatusCode::NOT_FOUND);
+    let revoked_by_owner = send(&db, revoke(&owner_cookie)).await;
+    assert_eq!(revoked_by_owner.status(), StatusCode::OK);
+
+    let revoked = sign_up(&db, "toolate", "RVKED-CODE3").await;
+    assert!(revoked.contains("invalid, used up or expired"));
+}
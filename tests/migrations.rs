@@ -0,0 +1,130 @@
+//! Integration tests for migrations that backfill rows written before them.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+use basic_site::db::MIGRATOR;
+use basic_site::models::known_device::KnownDevice;
+use basic_site::models::session::Session;
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqlitePoolOptions;
+use uuid::Uuid;
+
+/// The last migration of the schema the site started from.
+const BASELINE_VERSION: i64 = 20_250_807_034_208;
+
+/// Applies the up migrations `filter` selects, in order.
+async fn migrate(db: &SqlitePool, filter: fn(i64) -> bool) {
+    for migration in MIGRATOR.iter().filter(|migration| {
+        !migration.migration_type.is_down_migration()
+            && filter(migration.version)
+    }) {
+        sqlx::raw_sql(&migration.sql)
+            .execute(db)
+            .await
+            .expect("Failed to run migration");
+    }
+}
+
+/// Stores a session the way the baseline did, with the port in its address.
+async fn insert_baseline_session(
+    db: &SqlitePool,
+    user_id: Uuid,
+    ip_address: &str,
+) -> Uuid {
+    let id = Uuid::new_v4();
+    let now = current_time_micros();
+    sqlx::query(
+        "INSERT INTO session (id, user_id, ip_address, user_agent, created_at, expires_at)
+        VALUES (?, ?, ?, 'TestAgent/1.0', ?, ?)",
+    )
+    .bind(id)
+    .bind(user_id)
+    .bind(ip_address)
+    .bind(now)
+    .bind(now.saturating_add(1_000_000))
+    .execute(db)
+    .await
+    .expect("session insert failed");
+    id
+}
+
+#[tokio::test]
+async fn baseline_sessions_seed_known_devices_without_ports() {
+    let db = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create in-memory database");
+    migrate(&db, |version| version <= BASELINE_VERSION).await;
+
+    let user_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO user (id, username, password_hash, created_at)
+        VALUES (?, 'baseline', '', 0)",
+    )
+    .bind(user_id)
+    .execute(&db)
+    .await
+    .expect("user insert failed");
+    let v4 = insert_baseline_session(&db, user_id, "203.0.113.7:51234").await;
+    let v6 = insert_baseline_session(&db, user_id, "[2001:db8::1]:443").await;
+
+    migrate(&db, |version| version > BASELINE_VERSION).await;
+
+    for (id, ip) in [(v4, "203.0.113.7"), (v6, "2001:db8::1")] {
+        let session = Session::get_by_id(&db, id)
+            .await
+            .expect("get failed")
+            .expect("session missing");
+        assert_eq!(session.last_ip, ip);
+        let new_device = KnownDevice::record(
+            &db,
+            user_id,
+            ip,
+            "TestAgent/1.0",
+            current_time_micros(),
+        )
+        .await
+        .expect("record failed");
+        assert!(!new_device, "{ip} was seeded as known");
+    }
+}
+
+#[tokio::test]
+async fn only_ascii_usernames_are_lowercased_in_sql() {
+    let db = SqlitePoolOptions::new()
+        .connect("sqlite::memory:")
+        .await
+        .expect("Failed to create in-memory database");
+    migrate(&db, |version| version <= BASELINE_VERSION).await;
+    for username in ["Bob", "\u{c9}MILE"] {
+        sqlx::query(
+            "INSERT INTO user (id, username, password_hash, created_at)
+            VALUES (?, ?, '', 0)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(username)
+        .execute(&db)
+        .await
+        .expect("user insert failed");
+    }
+
+    migrate(&db, |version| version > BASELINE_VERSION).await;
+
+    let canonical: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT username, username_canonical FROM user ORDER BY username",
+    )
+    .fetch_all(&db)
+    .await
+    .expect("select failed");
+    assert_eq!(
+        canonical,
+        [
+            ("Bob".to_owned(), Some("bob".to_owned())),
+            ("\u{c9}MILE".to_owned(), None),
+        ]
+    );
+}
@@ -1,33 +1,37 @@
 //! Integration tests for database models.
-
-use basic_site::models::{session::Session, user::User};
-use basic_site::password::generate_hash;
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    clippy::shadow_unrelated,
+    clippy::shadow_reuse,
+    clippy::default_numeric_fallback,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use argon2::Params;
+use basic_site::models::known_device::KnownDevice;
+use basic_site::models::session::{
+    BROWSER_TTL_MICROS, LAST_SEEN_INTERVAL_MICROS, PERSISTENT_TTL_MICROS,
+    RENEWAL_INTERVAL_MICROS, Session,
+};
+use basic_site::models::user::User;
+use basic_site::password::{self, PasswordConfig, Verification, generate_hash};
 use basic_site::util::current_time_micros;
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
 use uuid::Uuid;
 
-/// Creates an in-memory SQLite database with migrations applied.
-async fn setup_test_db() -> SqlitePool {
-    let pool = SqlitePoolOptions::new()
-        .connect("sqlite::memory:")
-        .await
-        .expect("Failed to create in-memory database");
-
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .expect("Failed to run migrations");
-
-    pool
-}
+use common::setup_test_db;
 
 fn create_test_user(username: &str, password: &str) -> User {
     User {
         id: Uuid::new_v4(),
         username: username.to_owned(),
-        password_hash: generate_hash(password),
+        password_hash: Some(generate_hash(password)),
         email: None,
         created_at: current_time_micros(),
+        is_admin: false,
     }
 }
 
@@ -68,6 +72,79 @@ async fn user_get_by_username_not_found() {
     assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
 }
 
+#[tokio::test]
+async fn user_get_by_username_ignores_case() {
+    let db = setup_test_db().await;
+    let user = create_test_user("JaneDoe", "password123");
+    User::insert(&db, &user).await.expect("insert failed");
+
+    let fetched = User::get_by_username(&db, "janedoe")
+        .await
+        .expect("get failed");
+    assert_eq!(fetched.id, user.id);
+    assert_eq!(fetched.username, "JaneDoe");
+}
+
+#[tokio::test]
+async fn user_insert_rejects_case_variants_and_lookalikes() {
+    let db = setup_test_db().await;
+    User::insert(&db, &create_test_user("paypal", "password123"))
+        .await
+        .expect("insert failed");
+
+    // "PayPal", then "paypaI" with a capital I, then a Cyrillic "а"
+    for lookalike in ["PayPal", "paypaI", "p\u{430}ypal"] {
+        let err =
+            User::insert(&db, &create_test_user(lookalike, "password123"))
+                .await
+                .expect_err(lookalike);
+        let sqlx::Error::Database(db_err) = err else {
+            panic!("expected a database error for {lookalike}");
+        };
+        assert!(db_err.is_unique_violation());
+    }
+}
+
+#[tokio::test]
+async fn user_update_username_keeps_keys_in_sync() {
+    let db = setup_test_db().await;
+    let user = create_test_user("oldname", "password123");
+    User::insert(&db, &user).await.expect("insert failed");
+    User::insert(&db, &create_test_user("takenname", "password123"))
+        .await
+        .expect("insert failed");
+
+    User::update_username(&db, user.id, "NewName")
+        .await
+        .expect("rename failed");
+    let fetched = User::get_by_username(&db, "newname")
+        .await
+        .expect("get failed");
+    assert_eq!(fetched.id, user.id);
+
+    User::update_username(&db, user.id, "TAKENNAME")
+        .await
+        .expect_err("case variant of another user");
+}
+
+#[tokio::test]
+async fn user_backfill_username_keys() {
+    let db = setup_test_db().await;
+    let user = create_test_user("legacyuser", "password123");
+    User::insert(&db, &user).await.expect("insert failed");
+    sqlx::query("UPDATE user SET username_skeleton = NULL")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    assert_eq!(User::backfill_username_keys(&db).await.unwrap(), 1);
+    assert_eq!(User::backfill_username_keys(&db).await.unwrap(), 0);
+    // Only the skeleton catches a capital I standing in for an l
+    User::insert(&db, &create_test_user("Iegacyuser", "password123"))
+        .await
+        .expect_err("skeleton restored");
+}
+
 #[tokio::test]
 async fn user_check_login_success() {
     let db = setup_test_db().await;
@@ -99,6 +176,59 @@ async fn user_check_login_nonexistent_user() {
     assert!(result.is_none());
 }
 
+#[tokio::test]
+async fn user_check_login_malformed_hash() {
+    let db = setup_test_db().await;
+    let mut user = create_test_user("brokenhash", "password123");
+    user.password_hash = Some("not-a-phc-string".to_owned());
+
+    User::insert(&db, &user).await.expect("insert failed");
+
+    let result = User::check_login(&db, "brokenhash", "password123").await;
+    assert!(result.is_none());
+}
+
+#[tokio::test]
+async fn user_check_login_without_password() {
+    let db = setup_test_db().await;
+    let mut user = create_test_user("nopassword", "password123");
+    user.password_hash = None;
+
+    User::insert(&db, &user).await.expect("insert failed");
+
+    let result = User::check_login(&db, "nopassword", "").await;
+    assert!(result.is_none());
+}
+
+#[tokio::test]
+async fn user_check_login_upgrades_weak_hash() {
+    let db = setup_test_db().await;
+    let weak = PasswordConfig {
+        params: Params::new(Params::MIN_M_COST, 1, 1, None)
+            .expect("valid params"),
+        ..PasswordConfig::default()
+    };
+    let mut user = create_test_user("weakhash", "password123");
+    user.password_hash = Some(weak.hash("password123"));
+
+    User::insert(&db, &user).await.expect("insert failed");
+
+    let result = User::check_login(&db, "weakhash", "password123")
+        .await
+        .expect("login failed");
+    assert_ne!(result.password_hash, user.password_hash);
+
+    let stored = User::get_by_id(&db, user.id).await.expect("get failed");
+    assert_eq!(stored.password_hash, result.password_hash);
+    assert_eq!(
+        password::verify(
+            "password123",
+            stored.password_hash.as_deref().expect("has a password")
+        ),
+        Verification::Valid
+    );
+}
+
 #[tokio::test]
 async fn user_update_email() {
     let db = setup_test_db().await;
@@ -142,6 +272,9 @@ fn create_test_session(user_id: Uuid) -> Session {
         user_agent: "TestAgent/1.0".to_owned(),
         created_at: now,
         expires_at: now + 604_800_000_000, // 1 week in microseconds
+        persistent: true,
+        last_seen_at: now,
+        last_ip: "127.0.0.1".to_owned(),
     }
 }
 
@@ -232,6 +365,9 @@ async fn session_get_by_user_id_excludes_expired() {
         user_agent: "TestAgent/1.0".to_owned(),
         created_at: now - 1_000_000_000, // 1000 seconds ago
         expires_at: now - 1,             // expired
+        persistent: true,
+        last_seen_at: now - 1_000_000_000,
+        last_ip: "127.0.0.1".to_owned(),
     };
     Session::insert(&db, &expired_session)
         .await
@@ -273,3 +409,143 @@ async fn session_cascade_delete_on_user_delete() {
         .expect("get failed");
     assert!(fetched.is_none());
 }
+
+#[tokio::test]
+async fn session_delete_by_user_id_except() {
+    let db = setup_test_db().await;
+    let user = create_test_user("revokeothers", "password123");
+    User::insert(&db, &user).await.expect("user insert failed");
+
+    let keep = create_test_session(user.id);
+    Session::insert(&db, &keep)
+        .await
+        .expect("session insert failed");
+    for _ in 0..2 {
+        Session::insert(&db, &create_test_session(user.id))
+            .await
+            .expect("session insert failed");
+    }
+
+    let deleted = Session::delete_by_user_id_except(&db, user.id, keep.id)
+        .await
+        .expect("delete failed");
+    assert_eq!(deleted.len(), 2);
+    assert!(!deleted.contains(&keep.id));
+
+    let sessions = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, keep.id);
+}
+
+#[tokio::test]
+async fn session_renew_extends_expiry() {
+    let db = setup_test_db().await;
+    let user = create_test_user("renewuser", "password123");
+    User::insert(&db, &user).await.expect("user insert failed");
+
+    let session = create_test_session(user.id);
+    Session::insert(&db, &session)
+        .await
+        .expect("session insert failed");
+
+    let new_expiry = session.expires_at + 1_000_000;
+    let renewed = Session::renew(&db, session.id, new_expiry)
+        .await
+        .expect("renew failed");
+    assert_eq!(renewed, 1);
+
+    let fetched = Session::get_by_id(&db, session.id)
+        .await
+        .expect("get failed")
+        .expect("session missing");
+    assert_eq!(fetched.expires_at, new_expiry);
+}
+
+#[test]
+fn session_needs_renewal_is_throttled() {
+    let now = current_time_micros();
+    let mut session = create_test_session(Uuid::new_v4());
+
+    session.expires_at = now + PERSISTENT_TTL_MICROS;
+    assert!(!session.needs_renewal(now));
+
+    session.expires_at = now + PERSISTENT_TTL_MICROS - RENEWAL_INTERVAL_MICROS;
+    assert!(!session.needs_renewal(now));
+
+    session.expires_at =
+        now + PERSISTENT_TTL_MICROS - RENEWAL_INTERVAL_MICROS - 1;
+    assert!(session.needs_renewal(now));
+}
+
+#[test]
+fn session_ttl_depends_on_remember_me() {
+    let mut session = create_test_session(Uuid::new_v4());
+    assert_eq!(session.ttl_micros(), PERSISTENT_TTL_MICROS);
+
+    session.persistent = false;
+    assert_eq!(session.ttl_micros(), BROWSER_TTL_MICROS);
+}
+
+#[tokio::test]
+async fn session_touch_records_activity() {
+    let db = setup_test_db().await;
+    let user = create_test_user("touchuser", "password123");
+    User::insert(&db, &user).await.expect("user insert failed");
+
+    let session = create_test_session(user.id);
+    Session::insert(&db, &session)
+        .await
+        .expect("session insert failed");
+
+    let seen_at = session.last_seen_at + 1_000_000;
+    Session::touch(&db, session.id, seen_at, "10.0.0.2")
+        .await
+        .expect("touch failed");
+
+    let fetched = Session::get_by_id(&db, session.id)
+        .await
+        .expect("get failed")
+        .expect("session missing");
+    assert_eq!(fetched.last_seen_at, seen_at);
+    assert_eq!(fetched.last_ip, "10.0.0.2");
+}
+
+#[test]
+fn session_needs_touch_is_throttled() {
+    let session = create_test_session(Uuid::new_v4());
+    let now = session.last_seen_at;
+
+    assert!(
+        !session.needs_touch(now + LAST_SEEN_INTERVAL_MICROS - 1, "127.0.0.1")
+    );
+    assert!(session.needs_touch(now + LAST_SEEN_INTERVAL_MICROS, "127.0.0.1"));
+    assert!(session.needs_touch(now, "10.0.0.2"));
+}
+
+// ============================================================================
+// Known device tests
+// ============================================================================
+
+#[tokio::test]
+async fn known_device_record_reports_new_combinations() {
+    let db = setup_test_db().await;
+    let user = create_test_user("deviceuser", "password123");
+    User::insert(&db, &user).await.expect("user insert failed");
+    let now = current_time_micros();
+
+    let first = KnownDevice::record(&db, user.id, "127.0.0.1", "Agent/1", now)
+        .await
+        .expect("record failed");
+    let repeat = KnownDevice::record(&db, user.id, "127.0.0.1", "Agent/1", now)
+        .await
+        .expect("record failed");
+    let new_ip = KnownDevice::record(&db, user.id, "10.0.0.2", "Agent/1", now)
+        .await
+        .expect("record failed");
+
+    assert!(first);
+    assert!(!repeat);
+    assert!(new_ip);
+}
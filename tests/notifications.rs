@@ -0,0 +1,237 @@
+//! Integration tests for notifications, the navbar bell and delivery
+//! preferences.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::http::StatusCode;
+use basic_site::models::membership::Membership;
+use basic_site::models::notification::{Notification, NotificationKind};
+use basic_site::models::notification_preference::NotificationPreference;
+use basic_site::models::organization::{Organization, Role};
+use basic_site::models::user::User;
+use basic_site::services::events::Broker;
+use basic_site::services::job::{self, Job};
+use basic_site::services::notification::{self, Notice};
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{
+    app_with_jobs, body_text, form_request, get, new_user, send, setup_test_db,
+    sign_in,
+};
+
+/// A signed-in user with an email address; returns the user and their
+/// session cookie.
+async fn signed_in(db: &SqlitePool, username: &str) -> (User, String) {
+    let user = User {
+        email: Some(format!("{username}@example.com")),
+        ..new_user(username)
+    };
+    User::insert(db, &user).await.expect("insert failed");
+    let cookie = sign_in(db, &user).await;
+    (user, cookie)
+}
+
+fn notice(title: &str) -> Notice {
+    Notice {
+        kind: NotificationKind::Account,
+        title: title.to_owned(),
+        body: "Something happened.".to_owned(),
+        link: Some("/settings/security".to_owned()),
+    }
+}
+
+/// Notifies through the service, returning the emails it queued.
+async fn notify(db: &SqlitePool, user: &User, title: &str) -> Vec<Job> {
+    let (job_tx, mut job_rx) = job::channel();
+    notification::notify(
+        db,
+        &job_tx,
+        &Broker::default(),
+        user.id,
+        &notice(title),
+    )
+    .await;
+    let mut jobs = Vec::new();
+    while let Ok(envelope) = job_rx.try_recv() {
+        jobs.push(envelope.job);
+    }
+    jobs
+}
+
+#[tokio::test]
+async fn notices_are_stored_and_emailed() {
+    let db = setup_test_db().await;
+    let (user, _cookie) = signed_in(&db, "alice").await;
+
+    let mut jobs = notify(&db, &user, "Heads up").await;
+    assert_eq!(jobs.len(), 1);
+    let Some(Job::SendEmail { to, subject, body }) = jobs.pop() else {
+        panic!("expected an email");
+    };
+    assert_eq!(to, "alice@example.com");
+    assert_eq!(subject, "Heads up");
+    assert!(body.starts_with("Hi alice,"));
+    assert!(body.contains("http://localhost:3000/settings/security"));
+
+    let stored = Notification::list_by_user(&db, user.id, 10).await.unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].title, "Heads up");
+    assert_eq!(stored[0].read_at, None);
+}
+
+#[tokio::test]
+async fn preferences_choose_the_channels() {
+    let db = setup_test_db().await;
+    let (user, cookie) = signed_in(&db, "alice").await;
+
+    let page = body_text(send(&db, get("/notifications", &cookie)).await).await;
+    assert!(page.contains(r#"name="account.email" checked"#));
+
+    // Only in-app for account notices; sign-in notices are turned off
+    let saved = send(
+        &db,
+        form_request(
+            "/notifications/preferences",
+            &cookie,
+            "account.in_app=on&organization.in_app=on&organization.email=on",
+        ),
+    )
+    .await;
+    assert!(body_text(saved).await.contains("Preferences saved."));
+    let preference =
+        NotificationPreference::get(&db, user.id, NotificationKind::SignIn)
+            .await
+            .unwrap();
+    assert!(!preference.email && !preference.in_app);
+
+    assert!(notify(&db, &user, "Quietly").await.is_empty());
+    assert_eq!(Notification::count_unread(&db, user.id).await.unwrap(), 1);
+
+    send(&db, form_request("/notifications/preferences", &cookie, "")).await;
+    assert!(notify(&db, &user, "Silently").await.is_empty());
+    assert_eq!(Notification::count_unread(&db, user.id).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn the_bell_counts_unread_until_marked_read() {
+    let db = setup_test_db().await;
+    let (user, cookie) = signed_in(&db, "alice").await;
+
+    let empty =
+        body_text(send(&db, get("/notifications/bell", &cookie)).await).await;
+    assert!(empty.contains(r#"aria-label="Notifications""#));
+    assert!(!empty.contains("<sup>"));
+
+    notify(&db, &user, "First").await;
+    notify(&db, &user, "Second").await;
+    let bell =
+        body_text(send(&db, get("/notifications/bell", &cookie)).await).await;
+    assert!(bell.contains("Notifications, 2 unread"));
+    assert!(bell.contains("<sup>2</sup>"));
+    assert!(bell.contains("every 60s"));
+
+    let page = body_text(send(&db, get("/notifications", &cookie)).await).await;
+    assert!(page.contains("<strong>First</strong>"));
+    assert!(page.contains("Mark all read"));
+
+    let first = Notification::list_by_user(&db, user.id, 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|notification| notification.title == "First")
+        .expect("notified")
+        .id;
+    let marked = send(
+        &db,
+        form_request(&format!("/notifications/{first}/read"), &cookie, ""),
+    )
+    .await;
+    assert_eq!(marked.headers()["hx-trigger"], "notifications-changed");
+    let item = body_text(marked).await;
+    assert!(!item.contains("Mark read"));
+    assert_eq!(Notification::count_unread(&db, user.id).await.unwrap(), 1);
+
+    let all = send(&db, form_request("/notifications/read", &cookie, "")).await;
+    assert_eq!(all.headers()["hx-trigger"], "notifications-changed");
+    assert!(!body_text(all).await.contains("Mark all read"));
+    assert_eq!(Notification::count_unread(&db, user.id).await.unwrap(), 0);
+
+    let signed_out = send(&db, get("/notifications/bell", "")).await;
+    assert_eq!(signed_out.status(), StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn users_only_mark_their_own() {
+    let db = setup_test_db().await;
+    let (alice, _alice_cookie) = signed_in(&db, "alice").await;
+    let (_bob, bob_cookie) = signed_in(&db, "bob").await;
+    notify(&db, &alice, "Private").await;
+    let id = Notification::list_by_user(&db, alice.id, 10).await.unwrap()[0].id;
+
+    let response = send(
+        &db,
+        form_request(&format!("/notifications/{id}/read"), &bob_cookie, ""),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(Notification::count_unread(&db, alice.id).await.unwrap(), 1);
+
+    let page =
+        body_text(send(&db, get("/notifications", &bob_cookie)).await).await;
+    assert!(page.contains("You have no notifications."));
+}
+
+#[tokio::test]
+async fn role_changes_notify_the_member() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (regular, regular_cookie) = signed_in(&db, "regular").await;
+    let now = current_time_micros();
+    let acme = Organization {
+        id: Uuid::new_v4(),
+        name: "Acme Co".to_owned(),
+        slug: "acme-co".to_owned(),
+        created_at: now,
+    };
+    Organization::insert(&db, &acme).await.unwrap();
+    for (user, role) in [(&owner, Role::Owner), (&regular, Role::Member)] {
+        let membership = Membership {
+            organization_id: acme.id,
+            user_id: user.id,
+            role,
+            created_at: now,
+        };
+        Membership::insert(&db, &membership).await.unwrap();
+    }
+
+    let (router, mut job_rx) = app_with_jobs(db.clone());
+    router
+        .oneshot(form_request(
+            &format!("/orgs/acme-co/members/{}", regular.id),
+            &owner_cookie,
+            "role=admin",
+        ))
+        .await
+        .unwrap();
+    let envelope = job_rx.try_recv().expect("an email was queued");
+    let Job::SendEmail { to, .. } = envelope.job else {
+        panic!("expected an email");
+    };
+    assert_eq!(to, "regular@example.com");
+
+    let page =
+        body_text(send(&db, get("/notifications", &regular_cookie)).await)
+            .await;
+    assert!(page.contains("Your role in Acme Co changed"));
+    assert!(page.contains("founder made you admin in Acme Co."));
+    assert!(page.contains(r#"href="/orgs/acme-co""#));
+}
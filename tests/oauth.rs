@@ -0,0 +1,462 @@
+//! Integration tests for this site acting as an OIDC provider: client
+//! registration, the authorization-code flow and token verification.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    clippy::indexing_slicing,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode, header};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use basic_site::issuer::{self, IdTokenClaims, IssuerConfig};
+use basic_site::jwt::{self, JwkSet, SigningKey};
+use basic_site::models::audit_event::{AuditEvent, AuditKind};
+use basic_site::models::oauth_client::OAuthClient;
+use basic_site::models::user::User;
+use basic_site::oidc;
+use basic_site::util::current_time_micros;
+use reqwest::Url;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+use common::{body_text, form_request, get, send};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+const VERIFIER: &str = "a-code-verifier-long-enough-to-be-realistic-123";
+
+async fn setup_test_db() -> SqlitePool {
+    issuer::init(IssuerConfig::new(
+        SigningKey::from_pem(include_str!("fixtures/oidc-signing-key.pem"))
+            .expect("valid test key"),
+    ));
+    common::setup_test_db().await
+}
+
+async fn body_json(response: Response<Body>) -> Value {
+    serde_json::from_str(&body_text(response).await).expect("JSON body")
+}
+
+/// A signed-in user; returns the user and their session cookie.
+async fn sign_in(db: &SqlitePool, username: &str) -> (User, String) {
+    let user = User {
+        email: Some(format!("{username}@example.com")),
+        ..common::new_user(username)
+    };
+    User::insert(db, &user).await.expect("insert failed");
+    let cookie = common::sign_in(db, &user).await;
+    (user, cookie)
+}
+
+/// Registers "Test App"; returns its client ID and secret.
+async fn register_client(db: &SqlitePool) -> (String, String) {
+    let (id, secret) = (oidc::random_token(), oidc::random_token());
+    let client = OAuthClient {
+        id: id.clone(),
+        name: "Test App".to_owned(),
+        secret_hash: issuer::hash_secret(&secret),
+        redirect_uris: format!("{REDIRECT_URI}\nhttp://localhost:8080/cb"),
+        created_by: None,
+        created_at: current_time_micros(),
+    };
+    OAuthClient::insert(db, &client)
+        .await
+        .expect("insert client");
+    (id, secret)
+}
+
+/// The parameters of a valid authorization request, with `overrides`
+/// replacing any of the same name.
+fn authorize_params(
+    client_id: &str,
+    overrides: &[(&str, &str)],
+) -> Vec<(String, String)> {
+    let challenge = oidc::pkce_challenge(VERIFIER);
+    let defaults = [
+        ("client_id", client_id),
+        ("redirect_uri", REDIRECT_URI),
+        ("response_type", "code"),
+        ("scope", "openid profile email"),
+        ("state", "app-state"),
+        ("nonce", "app-nonce"),
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+    defaults
+        .iter()
+        .map(|&(name, value)| {
+            let chosen = overrides
+                .iter()
+                .find(|pair| pair.0 == name)
+                .map_or(value, |pair| pair.1);
+            (name.to_owned(), chosen.to_owned())
+        })
+        .collect()
+}
+
+fn encode(params: &[(String, String)]) -> String {
+    let mut url = Url::parse("http://localhost/").unwrap();
+    url.query_pairs_mut().extend_pairs(params);
+    url.query().unwrap_or_default().to_owned()
+}
+
+async fn authorize(
+    db: &SqlitePool,
+    cookie: &str,
+    params: &[(String, String)],
+) -> Response<Body> {
+    let uri = format!("/oauth/authorize?{}", encode(params));
+    send(db, get(&uri, cookie)).await
+}
+
+/// Answers the consent page for `params`.
+async fn decide(
+    db: &SqlitePool,
+    cookie: &str,
+    params: &[(String, String)],
+    decision: &str,
+) -> Response<Body> {
+    let mut fields = params.to_vec();
+    fields.push(("decision".to_owned(), decision.to_owned()));
+    send(
+        db,
+        form_request("/oauth/authorize", cookie, &encode(&fields)),
+    )
+    .await
+}
+
+/// Where a redirect to the client went.
+fn client_redirect(response: &Response<Body>) -> Url {
+    assert_eq!(response.status(), StatusCode::SEE_OTHER, "not redirected");
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    assert!(
+        location.starts_with(REDIRECT_URI),
+        "redirected to {location}"
+    );
+    Url::parse(location).unwrap()
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|pair| pair.0 == name)
+        .map(|pair| pair.1.into_owned())
+}
+
+/// Exchanges `code` with HTTP Basic client credentials.
+async fn exchange(
+    db: &SqlitePool,
+    client: &(String, String),
+    code: &str,
+    verifier: &str,
+) -> Response<Body> {
+    let credentials = STANDARD.encode(format!("{}:{}", client.0, client.1));
+    let body = encode(&[
+        ("grant_type".to_owned(), "authorization_code".to_owned()),
+        ("code".to_owned(), code.to_owned()),
+        ("redirect_uri".to_owned(), REDIRECT_URI.to_owned()),
+        ("code_verifier".to_owned(), verifier.to_owned()),
+    ]);
+    let request = Request::post("/oauth/token")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::AUTHORIZATION, format!("Basic {credentials}"))
+        .body(Body::from(body))
+        .unwrap();
+    send(db, request).await
+}
+
+async fn published_keys(db: &SqlitePool) -> JwkSet {
+    let request = Request::get("/oauth/jwks").body(Body::empty()).unwrap();
+    serde_json::from_str(&body_text(send(db, request).await).await)
+        .expect("a JWK set")
+}
+
+#[tokio::test]
+async fn discovery_describes_the_endpoints() {
+    let db = setup_test_db().await;
+    let request = Request::get("/.well-known/openid-configuration")
+        .body(Body::empty())
+        .unwrap();
+    let discovery = body_json(send(&db, request).await).await;
+    assert_eq!(discovery["issuer"], issuer::issuer());
+    assert_eq!(discovery["userinfo_endpoint"], issuer::userinfo_url());
+    assert!(
+        discovery["jwks_uri"]
+            .as_str()
+            .is_some_and(|uri| uri.ends_with("/oauth/jwks"))
+    );
+    assert_eq!(discovery["code_challenge_methods_supported"][0], "S256");
+
+    let keys = published_keys(&db).await;
+    let kid = issuer::config().signing_key().kid();
+    assert!(keys.find(Some(kid)).is_some());
+}
+
+#[tokio::test]
+async fn consented_code_exchanges_for_verifiable_tokens() {
+    let db = setup_test_db().await;
+    let (user, cookie) = sign_in(&db, "consenter").await;
+    let client = register_client(&db).await;
+    let params = authorize_params(&client.0, &[]);
+
+    let consent = authorize(&db, &cookie, &params).await;
+    assert_eq!(consent.status(), StatusCode::OK);
+    let page = body_text(consent).await;
+    assert!(page.contains("Sign in to Test App?"));
+    assert!(page.contains("Your email address"));
+
+    let redirect =
+        client_redirect(&decide(&db, &cookie, &params, "allow").await);
+    assert_eq!(
+        query_param(&redirect, "state").as_deref(),
+        Some("app-state")
+    );
+    let code = query_param(&redirect, "code").expect("a code");
+
+    let response = exchange(&db, &client, &code, VERIFIER).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    let tokens = body_json(response).await;
+    assert_eq!(tokens["token_type"], "Bearer");
+
+    let keys = published_keys(&db).await;
+    let id_token: IdTokenClaims =
+        jwt::verify(tokens["id_token"].as_str().unwrap(), &keys).unwrap();
+    assert_eq!(id_token.iss, issuer::issuer());
+    assert_eq!(id_token.aud, client.0);
+    assert_eq!(id_token.nonce.as_deref(), Some("app-nonce"));
+    assert_eq!(id_token.user.sub, user.id.to_string());
+    assert_eq!(
+        id_token.user.preferred_username.as_deref(),
+        Some("consenter")
+    );
+
+    let now_secs = current_time_micros().div_euclid(1_000_000);
+    let access_token = tokens["access_token"].as_str().unwrap();
+    let access = issuer::verify_access_token(access_token, now_secs)
+        .expect("access token verifies");
+    let userinfo = issuer::userinfo(&db, &access).await.unwrap().unwrap();
+    assert_eq!(userinfo.email.as_deref(), Some("consenter@example.com"));
+    assert_eq!(userinfo.email_verified, Some(false));
+    assert!(
+        issuer::verify_access_token(
+            tokens["id_token"].as_str().unwrap(),
+            now_secs
+        )
+        .is_none(),
+        "ID tokens aren't access tokens"
+    );
+
+    // Codes are single-use
+    let replay = exchange(&db, &client, &code, VERIFIER).await;
+    assert_eq!(replay.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(replay).await["error"], "invalid_grant");
+
+    // Consent is remembered for the same scopes
+    let again = authorize(&db, &cookie, &params).await;
+    assert!(query_param(&client_redirect(&again), "code").is_some());
+
+    let events = AuditEvent::get_by_user_id(&db, user.id, 10).await.unwrap();
+    assert_eq!(events[0].kind, AuditKind::AppAuthorized);
+    assert!(events[0].detail.starts_with("Test App"));
+}
+
+#[tokio::test]
+async fn scope_limits_the_claims_shared() {
+    let db = setup_test_db().await;
+    let (_user, cookie) = sign_in(&db, "private").await;
+    let client = register_client(&db).await;
+    let params = authorize_params(&client.0, &[("scope", "openid")]);
+
+    let redirect =
+        client_redirect(&decide(&db, &cookie, &params, "allow").await);
+    let code = query_param(&redirect, "code").unwrap();
+    let tokens = body_json(exchange(&db, &client, &code, VERIFIER).await).await;
+    assert_eq!(tokens["scope"], "openid");
+    let now_secs = current_time_micros().div_euclid(1_000_000);
+    let access = issuer::verify_access_token(
+        tokens["access_token"].as_str().unwrap(),
+        now_secs,
+    )
+    .unwrap();
+    let userinfo = issuer::userinfo(&db, &access).await.unwrap().unwrap();
+    assert_eq!(userinfo.preferred_username, None);
+    assert_eq!(userinfo.email, None);
+
+    // Asking for more than was granted shows the consent page again
+    let wider = authorize_params(&client.0, &[("scope", "openid email")]);
+    let response = authorize(&db, &cookie, &wider).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn token_endpoint_checks_client_secret_and_verifier() {
+    let db = setup_test_db().await;
+    let (_user, cookie) = sign_in(&db, "verifier").await;
+    let client = register_client(&db).await;
+    let params = authorize_params(&client.0, &[]);
+
+    let redirect =
+        client_redirect(&decide(&db, &cookie, &params, "allow").await);
+    let code = query_param(&redirect, "code").unwrap();
+
+    let wrong_secret = (client.0.clone(), "not-the-secret".to_owned());
+    let unauthorized = exchange(&db, &wrong_secret, &code, VERIFIER).await;
+    assert_eq!(unauthorized.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body_json(unauthorized).await["error"], "invalid_client");
+
+    let mismatched = exchange(&db, &client, &code, "some-other-verifier").await;
+    assert_eq!(mismatched.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(mismatched).await["error"], "invalid_grant");
+}
+
+#[tokio::test]
+async fn untrusted_requests_are_shown_and_others_returned_to_the_app() {
+    let db = setup_test_db().await;
+    let (_user, cookie) = sign_in(&db, "careful").await;
+    let client = register_client(&db).await;
+
+    let unregistered = authorize_params(
+        &client.0,
+        &[("redirect_uri", "https://evil.example.com/callback")],
+    );
+    let shown = authorize(&db, &cookie, &unregistered).await;
+    assert_eq!(shown.status(), StatusCode::BAD_REQUEST);
+    assert!(body_text(shown).await.contains("didn't register"));
+
+    let unknown = authorize_params("no-such-client", &[]);
+    let unknown_shown = authorize(&db, &cookie, &unknown).await;
+    assert_eq!(unknown_shown.status(), StatusCode::BAD_REQUEST);
+
+    let no_pkce = authorize_params(&client.0, &[("code_challenge", "")]);
+    let redirect = client_redirect(&authorize(&db, &cookie, &no_pkce).await);
+    assert_eq!(
+        query_param(&redirect, "error").as_deref(),
+        Some("invalid_request")
+    );
+    assert_eq!(
+        query_param(&redirect, "state").as_deref(),
+        Some("app-state")
+    );
+
+    let params = authorize_params(&client.0, &[]);
+    let denied = client_redirect(&decide(&db, &cookie, &params, "deny").await);
+    assert_eq!(
+        query_param(&denied, "error").as_deref(),
+        Some("access_denied")
+    );
+    assert_eq!(query_param(&denied, "code"), None);
+}
+
+#[tokio::test]
+async fn signed_out_users_log_in_and_come_back() {
+    let db = setup_test_db().await;
+    let (_user, cookie) = sign_in(&db, "returning").await;
+    let client = register_client(&db).await;
+    let params = authorize_params(&client.0, &[]);
+
+    // Without the (SameSite=Strict) cookie, go on via a page of our own
+    let continued = authorize(&db, "", &params).await;
+    assert_eq!(continued.status(), StatusCode::OK);
+    let page = body_text(continued).await;
+    let authorize_path = format!("/oauth/authorize?{}", encode(&params));
+    let login_path = format!(
+        "/login?next={}",
+        percent_encoding::utf8_percent_encode(
+            &authorize_path,
+            percent_encoding::NON_ALPHANUMERIC
+        )
+    );
+    assert!(page.contains(&format!("url={login_path}")), "{page}");
+
+    // With a session, the login page sends the browser straight back
+    let request = Request::get(&login_path)
+        .header(header::COOKIE, &cookie)
+        .body(Body::empty())
+        .unwrap();
+    let returned = send(&db, request).await;
+    assert_eq!(returned.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        returned.headers()[header::LOCATION],
+        authorize_path.as_str()
+    );
+
+    // Without one, logging in carries on to `next`
+    let credentials = encode(&[
+        ("username".to_owned(), "returning".to_owned()),
+        ("password".to_owned(), "password123".to_owned()),
+        ("next".to_owned(), authorize_path.clone()),
+    ]);
+    let login = send(&db, form_request("/session", "", &credentials)).await;
+    assert_eq!(login.headers()["HX-Redirect"], authorize_path.as_str());
+
+    // Other sites aren't valid destinations
+    let offsite = send(
+        &db,
+        form_request(
+            "/session",
+            "",
+            "username=returning&password=password123&next=%2F%2Fevil.example.com",
+        ),
+    )
+    .await;
+    assert_eq!(offsite.headers()["HX-Redirect"], "/");
+}
+
+#[tokio::test]
+async fn admins_register_and_remove_clients() {
+    let db = setup_test_db().await;
+    let (_member, member_cookie) = sign_in(&db, "member").await;
+    let (admin, admin_cookie) = sign_in(&db, "operator").await;
+    sqlx::query("UPDATE user SET is_admin = TRUE WHERE id = ?")
+        .bind(admin.id)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let forbidden = Request::get("/admin/clients")
+        .header(header::COOKIE, &member_cookie)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&db, forbidden).await.status(), StatusCode::FORBIDDEN);
+
+    let rejected = send(
+        &db,
+        form_request(
+            "/admin/clients",
+            &admin_cookie,
+            "name=Wiki&redirect_uris=http%3A%2F%2Fwiki.example.com%2Fcb",
+        ),
+    )
+    .await;
+    let section = body_text(rejected).await;
+    assert!(section.contains("can't be a redirect URI"), "{section}");
+    assert!(section.contains("http://wiki.example.com/cb"));
+    assert!(OAuthClient::list(&db).await.unwrap().is_empty());
+
+    let created = send(
+        &db,
+        form_request(
+            "/admin/clients",
+            &admin_cookie,
+            "name=Wiki&redirect_uris=https%3A%2F%2Fwiki.example.com%2Fcb%0D%0A",
+        ),
+    )
+    .await;
+    assert!(body_text(created).await.contains("Client secret"));
+    let clients = OAuthClient::list(&db).await.unwrap();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].redirect_uris, "https://wiki.example.com/cb");
+    assert_eq!(clients[0].created_by, Some(admin.id));
+
+    let request = Request::delete(format!("/admin/clients/{}", clients[0].id))
+        .header(header::COOKIE, &admin_cookie)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send(&db, request).await.status(), StatusCode::OK);
+    assert!(OAuthClient::list(&db).await.unwrap().is_empty());
+}
@@ -0,0 +1,514 @@
+//! Integration tests for OIDC sign-in, signup and account linking against
+//! a mock identity provider.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{
+    HeaderMap, HeaderValue, Request, Response, StatusCode, header,
+};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use basic_site::app_state::AppState;
+use basic_site::models::identity::Identity;
+use basic_site::models::user::User;
+use basic_site::oidc::{self, ProviderConfig, Providers};
+use basic_site::util::current_time_micros;
+use basic_site::web;
+use reqwest::Url;
+use rsa::pkcs8::DecodePrivateKey as _;
+use rsa::traits::PublicKeyParts as _;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+use serde_json::{Value, json};
+use sha2::{Digest as _, Sha256};
+use sqlx::SqlitePool;
+use tokio::net::TcpListener;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{body_text, insert_user, setup_test_db, sign_in};
+
+const CLIENT_ID: &str = "basic-site";
+const CLIENT_SECRET: &str = "client-secret";
+const KEY_ID: &str = "test-key";
+
+/// A sign-in the mock provider has approved, redeemable once.
+struct Grant {
+    claims: Value,
+    code_challenge: String,
+}
+
+/// Plays an identity provider: discovery, keys and a token endpoint that
+/// checks client credentials and PKCE. Tests approve sign-ins directly
+/// with [`MockProvider::approve`] instead of going through a login page.
+#[derive(Clone)]
+struct MockProvider {
+    issuer: String,
+    key: Arc<RsaPrivateKey>,
+    grants: Arc<Mutex<HashMap<String, Grant>>>,
+}
+
+impl MockProvider {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let key = RsaPrivateKey::from_pkcs8_pem(include_str!(
+            "fixtures/oidc-signing-key.pem"
+        ))
+        .expect("valid test key");
+        let mock = Self {
+            issuer: format!("http://{addr}"),
+            key: Arc::new(key),
+            grants: Arc::default(),
+        };
+        let server = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(mock.clone());
+        tokio::spawn(async move { axum::serve(listener, server).await });
+        mock
+    }
+
+    fn providers(&self) -> Arc<Providers> {
+        Arc::new(Providers::new(vec![ProviderConfig {
+            name: "mock".to_owned(),
+            label: "Mock ID".to_owned(),
+            issuer: self.issuer.clone(),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: CLIENT_SECRET.to_owned(),
+        }]))
+    }
+
+    /// Approves the sign-in described by `authorize_url` for `subject`,
+    /// returning the code to hand back. `extra` is merged into the
+    /// standard claims.
+    fn approve(
+        &self,
+        authorize_url: &Url,
+        subject: &str,
+        extra: &Value,
+    ) -> String {
+        let param = |name: &str| {
+            query_param(authorize_url, name)
+                .unwrap_or_else(|| panic!("no {name} in authorize URL"))
+        };
+        assert_eq!(param("client_id"), CLIENT_ID, "wrong client");
+        assert_eq!(param("code_challenge_method"), "S256", "PKCE not used");
+        let mut claims = json!({
+            "iss": self.issuer,
+            "sub": subject,
+            "aud": CLIENT_ID,
+            "exp": current_time_micros().div_euclid(1_000_000) + 300,
+            "nonce": param("nonce"),
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().cloned().unwrap_or_default());
+        let code = Uuid::new_v4().to_string();
+        self.grants.lock().unwrap().insert(
+            code.clone(),
+            Grant {
+                claims,
+                code_challenge: param("code_challenge"),
+            },
+        );
+        code
+    }
+
+    fn sign(&self, claims: &Value) -> String {
+        let header = json!({ "alg": "RS256", "typ": "JWT", "kid": KEY_ID });
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let digest = Sha256::digest(signed.as_bytes());
+        let signature = self
+            .key
+            .sign(Pkcs1v15Sign::new::<Sha256>(), &digest)
+            .unwrap();
+        format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature))
+    }
+}
+
+async fn discovery(State(mock): State<MockProvider>) -> Json<Value> {
+    Json(json!({
+        "issuer": mock.issuer,
+        "authorization_endpoint": format!("{}/authorize", mock.issuer),
+        "token_endpoint": format!("{}/token", mock.issuer),
+        "jwks_uri": format!("{}/jwks", mock.issuer),
+    }))
+}
+
+async fn jwks(State(mock): State<MockProvider>) -> Json<Value> {
+    let public = mock.key.to_public_key();
+    Json(json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": KEY_ID,
+            "use": "sig",
+            "alg": "RS256",
+            "n": URL_SAFE_NO_PAD.encode(public.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(public.e().to_bytes_be()),
+        }]
+    }))
+}
+
+async fn token(
+    State(mock): State<MockProvider>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    // No auth methods are advertised, so the client must use HTTP Basic
+    let expected = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{CLIENT_ID}:{CLIENT_SECRET}"))
+    );
+    if headers
+        .get(header::AUTHORIZATION)
+        .map(HeaderValue::as_bytes)
+        != Some(expected.as_bytes())
+    {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let grant = mock
+        .grants
+        .lock()
+        .unwrap()
+        .remove(&params["code"])
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if oidc::pkce_challenge(&params["code_verifier"]) != grant.code_challenge {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(Json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "id_token": mock.sign(&grant.claims),
+    })))
+}
+
+fn app(db: SqlitePool, mock: &MockProvider) -> Router {
+    web::router().with_state(AppState {
+        oidc: mock.providers(),
+        ..common::state(db).0
+    })
+}
+
+async fn send(
+    db: &SqlitePool,
+    mock: &MockProvider,
+    request: Request<Body>,
+) -> Response<Body> {
+    app(db.clone(), mock).oneshot(request).await.unwrap()
+}
+
+/// The `name=value` part of the response cookie called `name`.
+fn set_cookie(response: &Response<Body>, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .find(|pair| {
+            pair.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+                .is_some_and(|value| !value.is_empty())
+        })
+        .map(str::to_owned)
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|pair| pair.0 == name)
+        .map(|pair| pair.1.into_owned())
+}
+
+/// A sign-in sent to the provider: where the browser went, and the state
+/// cookie it was given.
+struct Started {
+    authorize_url: Url,
+    state_cookie: String,
+}
+
+impl Started {
+    fn state(&self) -> String {
+        query_param(&self.authorize_url, "state").expect("no state")
+    }
+}
+
+async fn start(
+    db: &SqlitePool,
+    mock: &MockProvider,
+    request: Request<Body>,
+) -> Started {
+    let response = send(db, mock, request).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER, "not redirected");
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    let authorize_url = Url::parse(location).expect("absolute URL");
+    assert!(
+        location.starts_with(&format!("{}/authorize?", mock.issuer)),
+        "redirected to {location}"
+    );
+    Started {
+        state_cookie: set_cookie(&response, "oidc_state")
+            .expect("no state cookie"),
+        authorize_url,
+    }
+}
+
+async fn start_sign_in(db: &SqlitePool, mock: &MockProvider) -> Started {
+    start(
+        db,
+        mock,
+        Request::get("/auth/mock").body(Body::empty()).unwrap(),
+    )
+    .await
+}
+
+/// Returns from the provider with `code`, as the browser holding
+/// `state_cookie` would.
+async fn callback(
+    db: &SqlitePool,
+    mock: &MockProvider,
+    state_cookie: &str,
+    state: &str,
+    code: &str,
+) -> Response<Body> {
+    let request =
+        Request::get(format!("/auth/mock/callback?code={code}&state={state}"))
+            .header(header::COOKIE, state_cookie)
+            .header(header::USER_AGENT, "TestAgent/1.0")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+            .body(Body::empty())
+            .unwrap();
+    send(db, mock, request).await
+}
+
+#[tokio::test]
+async fn sign_in_creates_a_passwordless_account_once() {
+    let db = setup_test_db().await;
+    let mock = MockProvider::start().await;
+
+    let started = start_sign_in(&db, &mock).await;
+    let code = mock.approve(
+        &started.authorize_url,
+        "subject-1",
+        &json!({
+            "preferred_username": "jane.doe",
+            "email": "jane@example.com",
+            "email_verified": true,
+        }),
+    );
+    let response =
+        callback(&db, &mock, &started.state_cookie, &started.state(), &code)
+            .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(set_cookie(&response, "session_id").is_some());
+    assert!(body_text(response).await.contains("url=/"));
+
+    let user = User::get_by_username(&db, "janedoe")
+        .await
+        .expect("account created");
+    assert_eq!(user.password_hash, None);
+    assert_eq!(user.email.as_deref(), Some("jane@example.com"));
+
+    // Signing in again finds the same account
+    let again = start_sign_in(&db, &mock).await;
+    let second_code = mock.approve(
+        &again.authorize_url,
+        "subject-1",
+        &json!({ "preferred_username": "someone-else" }),
+    );
+    let second = callback(
+        &db,
+        &mock,
+        &again.state_cookie,
+        &again.state(),
+        &second_code,
+    )
+    .await;
+    assert!(set_cookie(&second, "session_id").is_some());
+    let count: i64 = sqlx::query_scalar("SELECT count(*) FROM user")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn callback_requires_the_browser_that_started() {
+    let db = setup_test_db().await;
+    let mock = MockProvider::start().await;
+
+    let started = start_sign_in(&db, &mock).await;
+    let code = mock.approve(&started.authorize_url, "subject-1", &json!({}));
+    let other_browser = start_sign_in(&db, &mock).await;
+    let response = callback(
+        &db,
+        &mock,
+        &other_browser.state_cookie,
+        &started.state(),
+        &code,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(set_cookie(&response, "session_id").is_none());
+
+    let identity = Identity::get(&db, "mock", "subject-1").await.unwrap();
+    assert!(identity.is_none());
+}
+
+#[tokio::test]
+async fn callback_rejects_replays_and_wrong_nonces() {
+    let db = setup_test_db().await;
+    let mock = MockProvider::start().await;
+
+    let started = start_sign_in(&db, &mock).await;
+    let code = mock.approve(
+        &started.authorize_url,
+        "subject-1",
+        &json!({ "nonce": "not-the-nonce-we-sent" }),
+    );
+    let response =
+        callback(&db, &mock, &started.state_cookie, &started.state(), &code)
+            .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(body_text(response).await.contains("verify your Mock ID"));
+
+    // The state was used up by the failed attempt
+    let retry =
+        callback(&db, &mock, &started.state_cookie, &started.state(), &code)
+            .await;
+    assert_eq!(retry.status(), StatusCode::BAD_REQUEST);
+    assert!(body_text(retry).await.contains("already used"));
+}
+
+#[tokio::test]
+async fn password_user_links_and_unlinks_a_provider() {
+    let db = setup_test_db().await;
+    let mock = MockProvider::start().await;
+    let user = insert_user(&db, "linkuser").await;
+    let cookie = sign_in(&db, &user).await;
+
+    let started = start(
+        &db,
+        &mock,
+        Request::post("/settings/identities/mock")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    let code = mock.approve(
+        &started.authorize_url,
+        "subject-9",
+        &json!({ "email": "link@example.com", "email_verified": "true" }),
+    );
+    // The strict session cookie doesn't come back from the provider
+    let response =
+        callback(&db, &mock, &started.state_cookie, &started.state(), &code)
+            .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(set_cookie(&response, "session_id").is_none());
+
+    let identity = Identity::get(&db, "mock", "subject-9")
+        .await
+        .unwrap()
+        .expect("linked");
+    assert_eq!(identity.user_id, user.id);
+
+    let settings = send(
+        &db,
+        &mock,
+        Request::get("/settings")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(
+        body_text(settings)
+            .await
+            .contains("Connected as link@example.com")
+    );
+
+    let unlinked = send(
+        &db,
+        &mock,
+        Request::delete("/settings/identities/mock")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(body_text(unlinked).await.contains("Not connected"));
+    assert!(
+        Identity::get(&db, "mock", "subject-9")
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn only_sign_in_method_stays_until_a_password_is_set() {
+    let db = setup_test_db().await;
+    let mock = MockProvider::start().await;
+
+    let started = start_sign_in(&db, &mock).await;
+    let code = mock.approve(
+        &started.authorize_url,
+        "subject-2",
+        &json!({ "name": "Sam Smith" }),
+    );
+    callback(&db, &mock, &started.state_cookie, &started.state(), &code).await;
+    let user = User::get_by_username(&db, "SamSmith")
+        .await
+        .expect("account created");
+    let cookie = sign_in(&db, &user).await;
+
+    let unlink = || {
+        Request::delete("/settings/identities/mock")
+            .header(header::COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let refused = send(&db, &mock, unlink()).await;
+    assert!(body_text(refused).await.contains("Set a password"));
+
+    let set_password = send(
+        &db,
+        &mock,
+        Request::post("/settings/password")
+            .header(header::COOKIE, &cookie)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("new_password=correct-horse-battery-staple"))
+            .unwrap(),
+    )
+    .await;
+    assert!(body_text(set_password).await.contains("Password updated"));
+
+    let unlinked = send(&db, &mock, unlink()).await;
+    assert!(body_text(unlinked).await.contains("Not connected"));
+    assert!(
+        User::check_login(&db, "SamSmith", "correct-horse-battery-staple")
+            .await
+            .is_some()
+    );
+}
@@ -0,0 +1,353 @@
+//! Integration tests for organizations, memberships and invitations.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use basic_site::models::membership::Membership;
+use basic_site::models::notification::Notification;
+use basic_site::models::organization::{Organization, Role};
+use basic_site::models::organization_invite::OrganizationInvite;
+use basic_site::models::user::User;
+use basic_site::organization::{generate_token, hash_token};
+use basic_site::services::job::Job;
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{
+    app_with_jobs, body_text, delete, form_request, get, send, setup_test_db,
+    signed_in,
+};
+
+/// Creates "Acme Co" through the form, owned by the cookie's user.
+async fn create_acme(db: &SqlitePool, cookie: &str) -> Organization {
+    let response =
+        send(db, form_request("/orgs", cookie, "name=Acme+Co&slug=")).await;
+    assert_eq!(
+        response.headers()["hx-redirect"],
+        "/orgs/acme-co",
+        "creating the organization redirects to it"
+    );
+    Organization::get_by_slug(db, "acme-co")
+        .await
+        .unwrap()
+        .expect("organization created")
+}
+
+async fn add_signed_in(
+    db: &SqlitePool,
+    organization: &Organization,
+    user: &User,
+    role: Role,
+) {
+    let membership = Membership {
+        organization_id: organization.id,
+        user_id: user.id,
+        role,
+        created_at: current_time_micros(),
+    };
+    Membership::insert(db, &membership).await.expect("insert");
+}
+
+#[tokio::test]
+async fn only_members_see_an_organization() {
+    let db = setup_test_db().await;
+    let (_owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (_outsider, outsider_cookie) = signed_in(&db, "outsider").await;
+    create_acme(&db, &owner_cookie).await;
+
+    let page =
+        body_text(send(&db, get("/orgs/acme-co", &owner_cookie)).await).await;
+    assert!(page.contains("<h1>Acme Co</h1>"));
+    assert!(page.contains("owner here"));
+    assert!(page.contains("Send invitation"));
+
+    let hidden = send(&db, get("/orgs/acme-co", &outsider_cookie)).await;
+    assert_eq!(hidden.status(), StatusCode::NOT_FOUND);
+    let missing = send(&db, get("/orgs/nowhere", &owner_cookie)).await;
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    let signed_out = send(&db, get("/orgs/acme-co", "")).await;
+    assert_eq!(signed_out.headers()["location"], "/login");
+}
+
+#[tokio::test]
+async fn slugs_are_checked_and_unique() {
+    let db = setup_test_db().await;
+    let (_owner, cookie) = signed_in(&db, "founder").await;
+    create_acme(&db, &cookie).await;
+
+    let taken = send(&db, form_request("/orgs", &cookie, "name=ACME+co")).await;
+    assert!(
+        body_text(taken)
+            .await
+            .contains("Another organization already uses that URL.")
+    );
+    let reserved =
+        send(&db, form_request("/orgs", &cookie, "name=Join&slug=join")).await;
+    assert!(body_text(reserved).await.contains("That URL is reserved."));
+    let blank = send(&db, form_request("/orgs", &cookie, "name=+++")).await;
+    assert!(body_text(blank).await.contains("Use 1 to 60 characters."));
+}
+
+#[tokio::test]
+async fn invitations_are_emailed_and_accepted_once() {
+    let db = setup_test_db().await;
+    let (_owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (joiner, joiner_cookie) = signed_in(&db, "joiner").await;
+    User::update_email(&db, joiner.id, Some("Joiner@example.com"))
+        .await
+        .unwrap();
+    let acme = create_acme(&db, &owner_cookie).await;
+
+    let (router, mut job_rx) = app_with_jobs(db.clone());
+    let response = router
+        .oneshot(form_request(
+            "/orgs/acme-co/invites",
+            &owner_cookie,
+            "email=joiner%40example.com&role=admin",
+        ))
+        .await
+        .unwrap();
+    let html = body_text(response).await;
+    assert!(html.contains("Invitation sent to joiner@example.com."));
+    assert!(html.contains("joiner@example.com</td>"));
+
+    let envelope = job_rx.try_recv().expect("an email was queued");
+    let Job::SendEmail { to, subject, body } = envelope.job else {
+        panic!("expected an email");
+    };
+    assert_eq!(to, "joiner@example.com");
+    assert!(subject.contains("Acme Co"));
+    let path = body
+        .split_whitespace()
+        .find_map(|word| word.split_once("/orgs/join/"))
+        .map(|(_, token)| format!("/orgs/join/{token}"))
+        .expect("a join link");
+
+    // Opened from the email without the SameSite=Strict cookie
+    let from_email = body_text(send(&db, get(&path, "")).await).await;
+    assert!(from_email.contains("/login?next=%2Forgs%2Fjoin%2F"));
+
+    let confirm = body_text(send(&db, get(&path, &joiner_cookie)).await).await;
+    assert!(confirm.contains("Join Acme Co?"));
+    assert!(confirm.contains("as admin"));
+
+    let accepted = send(&db, form_request(&path, &joiner_cookie, "")).await;
+    assert_eq!(accepted.headers()["location"], "/orgs/acme-co");
+    assert_eq!(
+        Membership::role_of(&db, acme.id, joiner.id).await.unwrap(),
+        Some(Role::Admin)
+    );
+
+    let again = send(&db, form_request(&path, &joiner_cookie, "")).await;
+    assert_eq!(again.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn roles_limit_who_can_invite_and_manage() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (admin, admin_cookie) = signed_in(&db, "manager").await;
+    let (regular, regular_cookie) = signed_in(&db, "regular").await;
+    let acme = create_acme(&db, &owner_cookie).await;
+    add_signed_in(&db, &acme, &admin, Role::Admin).await;
+    add_signed_in(&db, &acme, &regular, Role::Member).await;
+
+    let refused = send(
+        &db,
+        form_request(
+            "/orgs/acme-co/invites",
+            &regular_cookie,
+            "email=x%40example.com&role=member",
+        ),
+    )
+    .await;
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    let page =
+        body_text(send(&db, get("/orgs/acme-co", &regular_cookie)).await).await;
+    assert!(!page.contains("Send invitation"));
+
+    let too_high = send(
+        &db,
+        form_request(
+            "/orgs/acme-co/invites",
+            &admin_cookie,
+            "email=x%40example.com&role=owner",
+        ),
+    )
+    .await;
+    assert!(
+        body_text(too_high)
+            .await
+            .contains("You can't invite people with that role.")
+    );
+
+    let owner_url = format!("/orgs/acme-co/members/{}", owner.id);
+    let remove_owner = send(&db, delete(&owner_url, &admin_cookie)).await;
+    assert_eq!(remove_owner.status(), StatusCode::FORBIDDEN);
+
+    let regular_url = format!("/orgs/acme-co/members/{}", regular.id);
+    let promoted =
+        send(&db, form_request(&regular_url, &admin_cookie, "role=admin"))
+            .await;
+    assert_eq!(promoted.status(), StatusCode::OK);
+    assert_eq!(
+        Membership::role_of(&db, acme.id, regular.id).await.unwrap(),
+        Some(Role::Admin)
+    );
+
+    let removed = send(&db, delete(&regular_url, &owner_cookie)).await;
+    assert!(!body_text(removed).await.contains("regular"));
+    let gone = send(&db, get("/orgs/acme-co", &regular_cookie)).await;
+    assert_eq!(gone.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_last_owner_cannot_leave() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (partner, _partner_cookie) = signed_in(&db, "partner").await;
+    let acme = create_acme(&db, &owner_cookie).await;
+    add_signed_in(&db, &acme, &partner, Role::Member).await;
+
+    let own_url = format!("/orgs/acme-co/members/{}", owner.id);
+    let refused = send(&db, delete(&own_url, &owner_cookie)).await;
+    assert!(
+        body_text(refused)
+            .await
+            .contains("Make someone else an owner before leaving")
+    );
+
+    let partner_url = format!("/orgs/acme-co/members/{}", partner.id);
+    send(&db, form_request(&partner_url, &owner_cookie, "role=owner")).await;
+    let left = send(&db, delete(&own_url, &owner_cookie)).await;
+    assert_eq!(left.headers()["hx-redirect"], "/orgs");
+    assert_eq!(
+        Membership::role_of(&db, acme.id, owner.id).await.unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn one_of_two_owners_can_leave() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (partner, _partner_cookie) = signed_in(&db, "partner").await;
+    let acme = create_acme(&db, &owner_cookie).await;
+    add_signed_in(&db, &acme, &partner, Role::Owner).await;
+
+    assert!(Membership::delete(&db, acme.id, owner.id).await.unwrap());
+    assert!(!Membership::delete(&db, acme.id, partner.id).await.unwrap());
+    assert_eq!(
+        Membership::role_of(&db, acme.id, partner.id).await.unwrap(),
+        Some(Role::Owner)
+    );
+}
+
+#[tokio::test]
+async fn members_accepting_again_keep_their_role_quietly() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (admin, admin_cookie) = signed_in(&db, "manager").await;
+    let acme = create_acme(&db, &owner_cookie).await;
+    add_signed_in(&db, &acme, &admin, Role::Admin).await;
+    User::update_email(&db, admin.id, Some("manager@example.com"))
+        .await
+        .unwrap();
+
+    let token = generate_token();
+    let now = current_time_micros();
+    let invite = OrganizationInvite {
+        id: Uuid::new_v4(),
+        organization_id: acme.id,
+        email: "manager@example.com".to_owned(),
+        role: Role::Member,
+        token_hash: hash_token(&token),
+        invited_by: owner.id,
+        expires_at: now.saturating_add(60_000_000),
+        created_at: now,
+    };
+    OrganizationInvite::upsert(&db, &invite).await.unwrap();
+
+    let path = format!("/orgs/join/{token}");
+    let accepted = send(&db, form_request(&path, &admin_cookie, "")).await;
+    assert_eq!(accepted.headers()["location"], "/orgs/acme-co");
+    assert_eq!(
+        Membership::role_of(&db, acme.id, admin.id).await.unwrap(),
+        Some(Role::Admin)
+    );
+    assert_eq!(Notification::count_unread(&db, owner.id).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn invitations_only_work_for_the_invited_address() {
+    let db = setup_test_db().await;
+    let (owner, owner_cookie) = signed_in(&db, "founder").await;
+    let (holder, holder_cookie) = signed_in(&db, "holder").await;
+    User::update_email(&db, holder.id, Some("holder@example.com"))
+        .await
+        .unwrap();
+    let acme = create_acme(&db, &owner_cookie).await;
+
+    let token = generate_token();
+    let now = current_time_micros();
+    let invite = OrganizationInvite {
+        id: Uuid::new_v4(),
+        organization_id: acme.id,
+        email: "partner@example.com".to_owned(),
+        role: Role::Owner,
+        token_hash: hash_token(&token),
+        invited_by: owner.id,
+        expires_at: now.saturating_add(60_000_000),
+        created_at: now,
+    };
+    OrganizationInvite::upsert(&db, &invite).await.unwrap();
+
+    let path = format!("/orgs/join/{token}");
+    let page = send(&db, get(&path, &holder_cookie)).await;
+    assert_eq!(page.status(), StatusCode::FORBIDDEN);
+    assert!(body_text(page).await.contains("isn't for you"));
+    let refused = send(&db, form_request(&path, &holder_cookie, "")).await;
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        Membership::role_of(&db, acme.id, holder.id).await.unwrap(),
+        None
+    );
+
+    let (partner, partner_cookie) = signed_in(&db, "partner").await;
+    User::update_email(&db, partner.id, Some("partner@example.com"))
+        .await
+        .unwrap();
+    let accepted = send(&db, form_request(&path, &partner_cookie, "")).await;
+    assert_eq!(accepted.headers()["location"], "/orgs/acme-co");
+}
+
+#[tokio::test]
+async fn switcher_lists_organizations_and_names_the_current_one() {
+    let db = setup_test_db().await;
+    let (_owner, cookie) = signed_in(&db, "founder").await;
+
+    let none = body_text(send(&db, get("/orgs/switcher", &cookie)).await).await;
+    assert!(none.contains(r#"<a href="/orgs">Organizations</a>"#));
+
+    create_acme(&db, &cookie).await;
+    let request = Request::get("/orgs/switcher")
+        .header(header::COOKIE, &cookie)
+        .header("HX-Current-URL", "http://localhost:3000/orgs/acme-co")
+        .body(Body::empty())
+        .unwrap();
+    let menu = body_text(send(&db, request).await).await;
+    assert!(menu.contains("<summary>Acme Co</summary>"), "{menu}");
+    assert!(menu.contains(r#"aria-current="page""#));
+
+    let signed_out = send(&db, get("/orgs/switcher", "")).await;
+    assert_eq!(signed_out.status(), StatusCode::NO_CONTENT);
+}
@@ -0,0 +1,66 @@
+//! Integration test for exporting spans over OTLP. It sets
+//! `OTEL_EXPORTER_OTLP_ENDPOINT`, which is process-wide, so it has a test
+//! binary to itself.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+use std::env;
+use std::time::Duration;
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::routing::post;
+use basic_site::telemetry::tracer_provider;
+use opentelemetry::trace::{Tracer as _, TracerProvider as _};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::task;
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn spans_reach_the_configured_collector() {
+    // Stands in for a collector, passing on each export request's body
+    let (exports_tx, mut exports_rx) = mpsc::unbounded_channel::<Bytes>();
+    let collector = Router::new().route(
+        "/v1/traces",
+        post(move |body: Bytes| async move {
+            exports_tx.send(body).expect("test is listening");
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, collector).await });
+
+    let endpoint = format!("http://{addr}/");
+    #[allow(
+        clippy::allow_attributes,
+        clippy::semicolon_outside_block,
+        reason = "Conflicts with semicolon_inside_block, which is also denied"
+    )]
+    // SAFETY: this binary has no other tests, so nothing else reads the
+    // environment while it's written.
+    unsafe {
+        env::set_var("OTEL_EXPORTER_OTLP_ENDPOINT", endpoint);
+    }
+    let provider = tracer_provider().expect("export is configured");
+    provider.tracer("test").in_span("exported-work", |_cx| {});
+    // Shutting down flushes the batch with a blocking HTTP request, which
+    // the collector task answers meanwhile
+    task::spawn_blocking(move || provider.shutdown())
+        .await
+        .unwrap()
+        .expect("spans flushed");
+
+    let body = timeout(Duration::from_secs(5), exports_rx.recv())
+        .await
+        .expect("an export within the timeout")
+        .expect("an export");
+    let name = b"exported-work";
+    assert!(
+        body.windows(name.len()).any(|window| window == name),
+        "the span is in the export"
+    );
+}
@@ -0,0 +1,69 @@
+//! Integration tests for page compression and conditional GET.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode};
+use basic_site::security_headers::{
+    SecurityHeadersConfig, with_security_headers,
+};
+use basic_site::web;
+use tower::ServiceExt as _;
+
+/// Sends `request` through the pages wrapped in security headers, as in
+/// production, so each response carries its own CSP nonce.
+async fn send(request: Request<Body>) -> Response<Body> {
+    let (state, _job_rx) = common::state(common::setup_test_db().await);
+    let config = SecurityHeadersConfig {
+        csp_report_only: false,
+        csp_report_uri: None,
+        hsts_max_age: None,
+    };
+    with_security_headers(web::router(), config)
+        .with_state(state)
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn page_is_compressed_when_accepted() {
+    let response = send(
+        Request::get("/")
+            .header("accept-encoding", "zstd")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "zstd");
+}
+
+#[tokio::test]
+async fn page_is_uncompressed_without_accept_encoding() {
+    let response = send(Request::get("/").body(Body::empty()).unwrap()).await;
+
+    assert!(response.headers().get("content-encoding").is_none());
+}
+
+#[tokio::test]
+async fn pages_with_a_nonce_are_always_sent_in_full() {
+    let first = send(Request::get("/about").body(Body::empty()).unwrap()).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("etag").is_none());
+
+    let second = send(
+        Request::get("/about")
+            .header("if-none-match", "*")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(second.status(), StatusCode::OK);
+}
@@ -0,0 +1,135 @@
+//! Integration tests for signup validation, the password policy and live
+//! strength feedback.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Response, StatusCode};
+use basic_site::models::user::User;
+use sqlx::SqlitePool;
+
+use common::{body_text, form_request, send, setup_test_db};
+
+async fn post_form(
+    db: SqlitePool,
+    uri: &str,
+    form: impl AsRef<str>,
+) -> Response<Body> {
+    send(&db, form_request(uri, "", form.as_ref())).await
+}
+
+#[tokio::test]
+async fn signup_rejects_breached_password() {
+    let db = setup_test_db().await;
+    let response = post_form(
+        db.clone(),
+        "/signup",
+        "username=breachuser&password=password123",
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("data breach"));
+    User::get_by_username(&db, "breachuser")
+        .await
+        .expect_err("user should not be created");
+}
+
+#[tokio::test]
+async fn signup_rejects_password_containing_username() {
+    let db = setup_test_db().await;
+    let response = post_form(
+        db.clone(),
+        "/signup",
+        "username=zebrafinch&password=zebrafinch2024",
+    )
+    .await;
+
+    assert!(body_text(response).await.contains("too easy to guess"));
+    User::get_by_username(&db, "zebrafinch")
+        .await
+        .expect_err("user should not be created");
+}
+
+#[tokio::test]
+async fn signup_accepts_unicode_passphrase() {
+    let db = setup_test_db().await;
+    let response = post_form(
+        db.clone(),
+        "/signup",
+        "username=unicodeuser&password=caf%C3%A9+m%C3%B6we+kitesurf",
+    )
+    .await;
+
+    assert_eq!(response.headers()["hx-redirect"], "/");
+    User::check_login(&db, "unicodeuser", "caf\u{e9} m\u{f6}we kitesurf")
+        .await
+        .expect("passphrase should log in");
+}
+
+#[tokio::test]
+async fn strength_endpoint_returns_feedback() {
+    let weak = post_form(
+        setup_test_db().await,
+        "/password/strength",
+        "username=someone&password=qwerty",
+    )
+    .await;
+    assert_eq!(weak.status(), StatusCode::OK);
+    let weak_html = body_text(weak).await;
+    assert!(weak_html.contains(r#"id="password-feedback""#));
+    assert!(weak_html.contains(r#"value="0""#));
+
+    let strong = post_form(
+        setup_test_db().await,
+        "/password/strength",
+        "new_password=correct+horse+battery+staple",
+    )
+    .await;
+    let strong_html = body_text(strong).await;
+    assert!(strong_html.contains(r#"value="4""#));
+    assert!(strong_html.contains("Very strong"));
+}
+
+#[tokio::test]
+async fn strength_endpoint_rejects_overlong_passwords() {
+    let response = post_form(
+        setup_test_db().await,
+        "/password/strength",
+        format!("password={}", "a".repeat(100_000)),
+    )
+    .await;
+    let html = body_text(response).await;
+    assert!(html.contains(r#"value="0""#));
+    assert!(html.contains("Password must be between 8 and 128 characters."));
+}
+
+#[tokio::test]
+async fn strength_endpoint_clears_for_empty_password() {
+    let response =
+        post_form(setup_test_db().await, "/password/strength", "password=")
+            .await;
+    let html = body_text(response).await;
+    assert!(html.contains(r#"id="password-feedback""#));
+    assert!(!html.contains("progress"));
+}
+
+#[tokio::test]
+async fn signup_reports_errors_per_field() {
+    let response = post_form(
+        setup_test_db().await,
+        "/signup",
+        "username=ab&password=short",
+    )
+    .await;
+    let html = body_text(response).await;
+
+    assert_eq!(html.matches(r#"aria-invalid="true""#).count(), 2);
+    assert!(html.contains("Username must be between 5 and 20 characters"));
+    assert!(html.contains("Password must be between 8 and 128 characters"));
+}
@@ -0,0 +1,247 @@
+//! Integration tests for public profiles, privacy settings and avatars.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use std::io::Cursor;
+
+use axum::body::{self, Body};
+use axum::http::{Request, Response, StatusCode, header};
+use basic_site::avatar;
+use basic_site::models::profile::{Profile, Visibility};
+use basic_site::models::user::User;
+use basic_site::util::current_time_micros;
+use image::{DynamicImage, ImageFormat, RgbImage};
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+
+use common::{
+    app, body_text, form_request, insert_user, send, setup_test_db, sign_in,
+};
+
+const BOUNDARY: &str = "profile-test-boundary";
+
+async fn get(
+    db: &SqlitePool,
+    path: &str,
+    cookie: Option<&str>,
+) -> Response<Body> {
+    let mut request = Request::get(path);
+    if let Some(value) = cookie {
+        request = request.header(header::COOKIE, value);
+    }
+    app(db.clone())
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+async fn body_bytes(response: Response<Body>) -> Vec<u8> {
+    body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+async fn insert_profile(
+    db: &SqlitePool,
+    user: &User,
+    bio_visibility: Visibility,
+) {
+    let profile = Profile {
+        display_name: "Jane Doe".to_owned(),
+        bio: "Secret hobbies".to_owned(),
+        links: "https://jane.example.com".to_owned(),
+        bio_visibility,
+        ..Profile::default()
+    };
+    Profile::upsert(db, user.id, &profile, current_time_micros())
+        .await
+        .expect("upsert failed");
+}
+
+async fn upload_avatar(
+    db: &SqlitePool,
+    cookie: &str,
+    content_type: &str,
+    data: &[u8],
+) -> Response<Body> {
+    let mut payload = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"me\"\r\nContent-Type: {content_type}\r\n\r\n"
+    )
+    .into_bytes();
+    payload.extend_from_slice(data);
+    payload.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    let request = Request::post("/settings/avatar")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(header::COOKIE, cookie)
+        .body(Body::from(payload))
+        .unwrap();
+    app(db.clone()).oneshot(request).await.unwrap()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .expect("encoding failed");
+    bytes
+}
+
+#[tokio::test]
+async fn visitors_see_only_visible_fields() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    insert_profile(&db, &owner, Visibility::Members).await;
+
+    let anonymous = body_text(get(&db, "/users/janedoe", None).await).await;
+    assert!(anonymous.contains("Jane Doe"));
+    assert!(anonymous.contains(
+        r#"href="https://jane.example.com" rel="nofollow noopener ugc""#
+    ));
+    assert!(!anonymous.contains("Secret hobbies"));
+    assert!(!anonymous.contains("Active Sessions"));
+
+    let member = insert_user(&db, "memberuser").await;
+    let cookie = sign_in(&db, &member).await;
+    let signed_in =
+        body_text(get(&db, "/users/janedoe", Some(&cookie)).await).await;
+    assert!(signed_in.contains("Secret hobbies"));
+    assert!(!signed_in.contains("Active Sessions"));
+}
+
+#[tokio::test]
+async fn owner_sees_everything_and_sessions() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    insert_profile(&db, &owner, Visibility::OnlyMe).await;
+    let cookie = sign_in(&db, &owner).await;
+
+    let page = body_text(get(&db, "/users/janedoe", Some(&cookie)).await).await;
+    assert!(page.contains("Secret hobbies"));
+    assert!(page.contains("Active Sessions"));
+    assert!(page.contains("This device"));
+}
+
+#[tokio::test]
+async fn unknown_user_is_not_found() {
+    let db = setup_test_db().await;
+
+    let response = get(&db, "/users/nobodyhere", None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn profile_rejects_non_web_links() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    let cookie = sign_in(&db, &owner).await;
+
+    let request = form_request(
+        "/settings/profile",
+        &cookie,
+        "display_name=Jane&bio=&links=javascript%3Aalert(1)\
+         &bio_visibility=everyone&links_visibility=everyone&avatar_visibility=everyone",
+    );
+    let page = body_text(send(&db, request).await).await;
+
+    assert!(page.contains("links start with https://"));
+    let profile = Profile::get(&db, owner.id).await.expect("get failed");
+    assert!(profile.display_name.is_empty());
+}
+
+#[tokio::test]
+async fn avatar_is_resized_and_respects_visibility() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    let cookie = sign_in(&db, &owner).await;
+
+    let form = body_text(
+        upload_avatar(&db, &cookie, "image/png", &png(400, 200)).await,
+    )
+    .await;
+    assert!(form.contains("Avatar updated!"));
+
+    let response = get(&db, "/users/janedoe/avatar", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        avatar::CONTENT_TYPE
+    );
+    let stored = image::load_from_memory(&body_bytes(response).await)
+        .expect("avatar is an image");
+    assert_eq!(
+        (stored.width(), stored.height()),
+        (avatar::SIZE, avatar::SIZE)
+    );
+
+    let profile = Profile {
+        avatar_visibility: Visibility::OnlyMe,
+        ..Profile::default()
+    };
+    Profile::upsert(&db, owner.id, &profile, current_time_micros())
+        .await
+        .expect("upsert failed");
+    let hidden = get(&db, "/users/janedoe/avatar", None).await;
+    assert_eq!(hidden.status(), StatusCode::NOT_FOUND);
+    let own = get(&db, "/users/janedoe/avatar", Some(&cookie)).await;
+    assert_eq!(own.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn avatar_revalidates_with_its_etag() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    let cookie = sign_in(&db, &owner).await;
+    upload_avatar(&db, &cookie, "image/png", &png(64, 64)).await;
+
+    let first = get(&db, "/users/janedoe/avatar", None).await;
+    assert_eq!(first.headers()[header::CACHE_CONTROL], "private, no-cache");
+    let etag = first.headers()[header::ETAG].clone();
+
+    let revalidate = Request::get("/users/janedoe/avatar")
+        .header(header::IF_NONE_MATCH, etag)
+        .body(Body::empty())
+        .unwrap();
+    let second = app(db.clone()).oneshot(revalidate).await.unwrap();
+    assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+
+    let from_htmx = Request::get("/users/janedoe/avatar")
+        .header("hx-request", "true")
+        .body(Body::empty())
+        .unwrap();
+    let swapped = app(db).oneshot(from_htmx).await.unwrap();
+    assert_eq!(swapped.status(), StatusCode::OK);
+    assert!(swapped.headers().get(header::ETAG).is_none());
+}
+
+#[tokio::test]
+async fn avatar_upload_rejects_non_images() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "janedoe").await;
+    let cookie = sign_in(&db, &owner).await;
+
+    let wrong_type = body_text(
+        upload_avatar(&db, &cookie, "image/svg+xml", b"<svg/>").await,
+    )
+    .await;
+    assert!(wrong_type.contains("isn't a PNG, JPEG, GIF or WebP image"));
+
+    let mislabelled = body_text(
+        upload_avatar(&db, &cookie, "image/png", b"not an image").await,
+    )
+    .await;
+    assert!(mislabelled.contains("isn't a PNG, JPEG, GIF or WebP image"));
+
+    let response = get(&db, "/users/janedoe/avatar", Some(&cookie)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,112 @@
+//! Integration tests for the security headers middleware.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+mod common;
+
+use axum::Router;
+use axum::body::{self, Body};
+use axum::http::{Request, Response};
+use axum::routing::get;
+use basic_site::security_headers::{
+    SecurityHeadersConfig, with_security_headers,
+};
+use basic_site::web::pages;
+use tower::ServiceExt as _;
+
+const ENFORCING: SecurityHeadersConfig = SecurityHeadersConfig {
+    csp_report_only: false,
+    csp_report_uri: None,
+    hsts_max_age: Some(600),
+};
+
+async fn test_app(config: SecurityHeadersConfig) -> Router {
+    let (state, _job_rx) = common::state(common::setup_test_db().await);
+    let routes = Router::new().route("/", get(|| async { pages::about("") }));
+    with_security_headers(routes, config).with_state(state)
+}
+
+async fn get_root(app: Router) -> Response<Body> {
+    app.oneshot(Request::get("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn header<'a>(response: &'a Response<Body>, name: &str) -> Option<&'a str> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn sets_security_headers() {
+    let response = get_root(test_app(ENFORCING).await).await;
+
+    assert_eq!(header(&response, "x-frame-options"), Some("DENY"));
+    assert_eq!(header(&response, "x-content-type-options"), Some("nosniff"));
+    assert_eq!(
+        header(&response, "referrer-policy"),
+        Some("strict-origin-when-cross-origin")
+    );
+    assert!(header(&response, "permissions-policy").is_some());
+    assert_eq!(
+        header(&response, "strict-transport-security"),
+        Some("max-age=600; includeSubDomains")
+    );
+    let csp = header(&response, "content-security-policy").unwrap();
+    assert!(csp.contains("frame-ancestors 'none'"));
+    assert!(header(&response, "content-security-policy-report-only").is_none());
+}
+
+#[tokio::test]
+async fn nonce_matches_rendered_page() {
+    let response = get_root(test_app(ENFORCING).await).await;
+
+    let csp = header(&response, "content-security-policy")
+        .unwrap()
+        .to_owned();
+    let nonce = csp
+        .split("'nonce-")
+        .nth(1)
+        .and_then(|rest| rest.split('\'').next())
+        .expect("CSP should contain a nonce");
+
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let page = String::from_utf8(bytes.to_vec()).unwrap();
+    assert!(page.contains(nonce));
+}
+
+#[tokio::test]
+async fn nonce_changes_per_request() {
+    let app = test_app(ENFORCING).await;
+
+    let first = get_root(app.clone()).await;
+    let second = get_root(app).await;
+
+    assert_ne!(
+        header(&first, "content-security-policy"),
+        header(&second, "content-security-policy")
+    );
+}
+
+#[tokio::test]
+async fn report_only_mode() {
+    let config = SecurityHeadersConfig {
+        csp_report_only: true,
+        csp_report_uri: Some("/csp-reports".to_owned()),
+        hsts_max_age: None,
+    };
+    let response = get_root(test_app(config).await).await;
+
+    assert!(header(&response, "content-security-policy").is_none());
+    assert!(header(&response, "strict-transport-security").is_none());
+    let csp = header(&response, "content-security-policy-report-only")
+        .expect("missing report-only CSP");
+    assert!(csp.ends_with("; report-uri /csp-reports"));
+}
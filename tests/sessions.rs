@@ -0,0 +1,368 @@
+//! Integration tests for login cookies and sliding session renewal.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    clippy::indexing_slicing,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use std::net::SocketAddr;
+
+use axum::body::{self, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, Response, StatusCode, header};
+use basic_site::models::session::{
+    PERSISTENT_TTL_MICROS, RENEWAL_INTERVAL_MICROS, Session,
+};
+use basic_site::models::user::User;
+use basic_site::services::job;
+use basic_site::util::current_time_micros;
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{
+    app, app_with_jobs, body_text, form_request, insert_user, send,
+    setup_test_db,
+};
+
+async fn login(db: SqlitePool, form: &'static str) -> Response<Body> {
+    send(&db, form_request("/session", "", form)).await
+}
+
+fn session_cookie(response: &Response<Body>) -> Option<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find(|cookie| cookie.starts_with("session_id="))
+        .map(str::to_owned)
+}
+
+#[tokio::test]
+async fn login_without_remember_me_sets_browser_cookie() {
+    let db = setup_test_db().await;
+    insert_user(&db, "browseruser").await;
+
+    let response = login(db, "username=browseruser&password=password123").await;
+
+    let cookie = session_cookie(&response).expect("no session cookie");
+    assert!(!cookie.contains("Max-Age"));
+}
+
+#[tokio::test]
+async fn login_with_remember_me_sets_persistent_cookie() {
+    let db = setup_test_db().await;
+    insert_user(&db, "rememberuser").await;
+
+    let response = login(
+        db,
+        "username=rememberuser&password=password123&remember_me=true",
+    )
+    .await;
+
+    let cookie = session_cookie(&response).expect("no session cookie");
+    assert!(cookie.contains("Max-Age=2592000"));
+}
+
+async fn insert_session(
+    db: &SqlitePool,
+    user_id: Uuid,
+    expires_in: i64,
+) -> Session {
+    let now = current_time_micros();
+    let session = Session {
+        id: Uuid::new_v4(),
+        user_id,
+        ip_address: "127.0.0.1:12345".to_owned(),
+        user_agent: "TestAgent/1.0".to_owned(),
+        created_at: now,
+        expires_at: now + expires_in,
+        persistent: true,
+        last_seen_at: now,
+        last_ip: "127.0.0.1".to_owned(),
+    };
+    Session::insert(db, &session)
+        .await
+        .expect("session insert failed");
+    session
+}
+
+async fn request_with_session(
+    db: SqlitePool,
+    expires_in: i64,
+) -> (Response<Body>, Session, SqlitePool) {
+    let user = insert_user(&db, "slidinguser").await;
+    let session = insert_session(&db, user.id, expires_in).await;
+
+    let request = Request::get("/about")
+        .header(header::COOKIE, format!("session_id={}", session.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db.clone()).oneshot(request).await.unwrap();
+    (response, session, db)
+}
+
+#[tokio::test]
+async fn stale_session_is_renewed() {
+    let expires_in = PERSISTENT_TTL_MICROS - 2 * RENEWAL_INTERVAL_MICROS;
+    let (response, session, db) =
+        request_with_session(setup_test_db().await, expires_in).await;
+
+    let cookie = session_cookie(&response).expect("cookie not re-issued");
+    assert!(cookie.contains("Max-Age"));
+    let renewed = Session::get_by_id(&db, session.id)
+        .await
+        .expect("get failed")
+        .expect("session missing");
+    assert!(renewed.expires_at > session.expires_at);
+}
+
+#[tokio::test]
+async fn fresh_session_is_not_renewed() {
+    let (response, session, db) =
+        request_with_session(setup_test_db().await, PERSISTENT_TTL_MICROS)
+            .await;
+
+    assert!(session_cookie(&response).is_none());
+    let fetched = Session::get_by_id(&db, session.id)
+        .await
+        .expect("get failed")
+        .expect("session missing");
+    assert_eq!(fetched.expires_at, session.expires_at);
+}
+
+#[tokio::test]
+async fn profile_marks_current_session() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "profileuser").await;
+    let current = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+    let other = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+
+    let request = Request::get("/users/profileuser")
+        .header(header::COOKIE, format!("session_id={}", current.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db).oneshot(request).await.unwrap();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let page = String::from_utf8(bytes.to_vec()).unwrap();
+
+    assert!(page.contains("This device"));
+    assert!(page.contains(
+        "Unknown browser on unknown OS \u{2014} last active just now"
+    ));
+    assert!(!page.contains(&format!("/sessions/{}", current.id)));
+    assert!(page.contains(&format!("/sessions/{}", other.id)));
+}
+
+#[tokio::test]
+async fn current_session_cannot_be_revoked_by_id() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "revokeself").await;
+    let current = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+
+    let request = Request::delete(format!("/sessions/{}", current.id))
+        .header(header::COOKIE, format!("session_id={}", current.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db.clone()).oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(
+        Session::get_by_id(&db, current.id)
+            .await
+            .expect("get failed")
+            .is_some()
+    );
+}
+
+#[tokio::test]
+async fn sign_out_everywhere_else_keeps_current() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "signoutothers").await;
+    let current = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+    insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+    insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+
+    let request = Request::delete("/sessions")
+        .header(header::COOKIE, format!("session_id={}", current.id))
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let remaining = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].id, current.id);
+}
+
+#[tokio::test]
+async fn login_leaves_no_session_if_the_device_is_not_recorded() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "untracked").await;
+    sqlx::query(
+        "CREATE TRIGGER refuse_devices BEFORE INSERT ON known_device
+        BEGIN SELECT RAISE(ABORT, 'devices are refused'); END",
+    )
+    .execute(&db)
+    .await
+    .expect("create trigger");
+
+    let response =
+        login(db.clone(), "username=untracked&password=password123").await;
+    assert!(response.headers().get(header::SET_COOKIE).is_none());
+
+    let sessions = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert!(sessions.is_empty());
+}
+
+#[tokio::test]
+async fn password_change_warns_when_other_sessions_stay() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "stuckdevices").await;
+    let current = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+    insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+    sqlx::query(
+        "CREATE TRIGGER keep_sessions BEFORE DELETE ON session
+        BEGIN SELECT RAISE(ABORT, 'sessions are kept'); END",
+    )
+    .execute(&db)
+    .await
+    .expect("create trigger");
+
+    let request = form_request(
+        "/settings/password",
+        &format!("session_id={}", current.id),
+        "current_password=password123&new_password=correct+horse+battery+staple",
+    );
+    let page = body_text(send(&db, request).await).await;
+    assert!(page.contains("Password updated successfully!"));
+    assert!(page.contains("Your other devices are still signed in."));
+    assert!(!page.contains("Other devices have been signed out"));
+
+    let remaining = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert_eq!(remaining.len(), 2);
+}
+
+#[tokio::test]
+async fn request_from_new_ip_updates_last_seen() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "roaminguser").await;
+    let session = insert_session(&db, user.id, PERSISTENT_TTL_MICROS).await;
+
+    let request = Request::get("/about")
+        .header(header::COOKIE, format!("session_id={}", session.id))
+        .extension(ConnectInfo(SocketAddr::from(([10, 0, 0, 2], 5000))))
+        .body(Body::empty())
+        .unwrap();
+    app(db.clone()).oneshot(request).await.unwrap();
+
+    let fetched = Session::get_by_id(&db, session.id)
+        .await
+        .expect("get failed")
+        .expect("session missing");
+    assert_eq!(fetched.last_ip, "10.0.0.2");
+    assert!(fetched.last_seen_at >= session.last_seen_at);
+}
+
+async fn login_with_jobs(
+    db: SqlitePool,
+    user_agent: &'static str,
+) -> Vec<job::Envelope> {
+    let (router, mut job_rx) = app_with_jobs(db);
+    let request = Request::post("/session")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(header::USER_AGENT, user_agent)
+        .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+        .body(Body::from("username=alerteduser&password=password123"))
+        .unwrap();
+    router.oneshot(request).await.unwrap();
+
+    let mut jobs = Vec::new();
+    while let Ok(envelope) = job_rx.try_recv() {
+        jobs.push(envelope);
+    }
+    jobs
+}
+
+#[tokio::test]
+async fn login_from_new_device_sends_alert() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "alerteduser").await;
+    User::update_email(&db, user.id, Some("alerted@example.com"))
+        .await
+        .expect("update failed");
+
+    let mut first = login_with_jobs(db.clone(), "TestAgent/1.0").await;
+    assert_eq!(first.len(), 1);
+    let job::Job::SendEmail { to, body, .. } = first.remove(0).job else {
+        panic!("expected an email job");
+    };
+    assert_eq!(to, "alerted@example.com");
+    assert!(body.contains("127.0.0.1"));
+
+    let again = login_with_jobs(db.clone(), "TestAgent/1.0").await;
+    assert!(again.is_empty());
+
+    let other_browser = login_with_jobs(db, "OtherAgent/2.0").await;
+    assert_eq!(other_browser.len(), 1);
+}
+
+#[tokio::test]
+async fn alert_link_revokes_session() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "alerteduser").await;
+    User::update_email(&db, user.id, Some("alerted@example.com"))
+        .await
+        .expect("update failed");
+
+    let mut jobs = login_with_jobs(db.clone(), "TestAgent/1.0").await;
+    let job::Job::SendEmail { body, .. } = jobs.remove(0).job else {
+        panic!("expected an email job");
+    };
+    let path = body
+        .split_whitespace()
+        .find_map(|word| word.strip_prefix("http://localhost:3000"))
+        .expect("no revoke link");
+
+    let sessions = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert_eq!(sessions.len(), 1);
+
+    // Following the link, as mail scanners do, only asks
+    let follow = Request::get(path).body(Body::empty()).unwrap();
+    let confirm = app(db.clone()).oneshot(follow).await.unwrap();
+    assert_eq!(confirm.status(), StatusCode::OK);
+    let page = body_text(confirm).await;
+    assert!(page.contains(&format!(r#"method="post" action="{path}""#)));
+    let unchanged = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert_eq!(unchanged.len(), 1);
+
+    let sign_out = Request::post(path).body(Body::empty()).unwrap();
+    let signed_out = app(db.clone()).oneshot(sign_out).await.unwrap();
+    assert!(body_text(signed_out).await.contains("Device signed out"));
+
+    let remaining = Session::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert!(remaining.is_empty());
+
+    let follow_again = Request::get(path).body(Body::empty()).unwrap();
+    let spent = app(db).oneshot(follow_again).await.unwrap();
+    assert!(body_text(spent).await.contains("Nothing to sign out"));
+}
@@ -0,0 +1,128 @@
+//! Integration tests for fingerprinted static asset serving.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+mod common;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode};
+use basic_site::web::{self, assets};
+use tower::ServiceExt as _;
+
+async fn test_app() -> Router {
+    let (state, _job_rx) = common::state(common::setup_test_db().await);
+    web::static_router().with_state(state)
+}
+
+async fn send(request: Request<Body>) -> Response<Body> {
+    test_app().await.oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn hashed_url_is_immutable_and_compressed() {
+    let response = send(
+        Request::get(assets::url("pico.min.css"))
+            .header("accept-encoding", "gzip, br")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "br");
+    assert_eq!(response.headers()["content-type"], "text/css");
+    assert_eq!(
+        response.headers()["cache-control"],
+        "public, max-age=31536000, immutable"
+    );
+}
+
+#[tokio::test]
+async fn plain_url_revalidates_with_etag() {
+    let first = send(
+        Request::get("/static/htmx.min.js")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert!(first.headers().get("content-encoding").is_none());
+    assert_eq!(first.headers()["cache-control"], "public, no-cache");
+    let etag = first.headers()["etag"].clone();
+
+    let second = send(
+        Request::get("/static/htmx.min.js")
+            .header("if-none-match", etag)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn encodings_revalidate_separately() {
+    let brotli = send(
+        Request::get("/static/htmx.min.js")
+            .header("accept-encoding", "br")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    let brotli_etag = brotli.headers()["etag"].clone();
+    assert_ne!(
+        brotli_etag,
+        send(
+            Request::get("/static/htmx.min.js")
+                .body(Body::empty())
+                .unwrap()
+        )
+        .await
+        .headers()["etag"]
+    );
+
+    let identity = send(
+        Request::get("/static/htmx.min.js")
+            .header("if-none-match", brotli_etag.clone())
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(identity.status(), StatusCode::OK);
+
+    let revalidated = send(
+        Request::get("/static/htmx.min.js")
+            .header("accept-encoding", "br")
+            .header("if-none-match", brotli_etag)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(revalidated.headers()["vary"], "Accept-Encoding");
+}
+
+#[tokio::test]
+async fn old_root_urls_still_serve() {
+    for path in ["/pico.min.css", "/pico.colors.min.css", "/htmx.min.js"] {
+        let response =
+            send(Request::get(path).body(Body::empty()).unwrap()).await;
+        assert_eq!(response.status(), StatusCode::OK, "{path}");
+        assert_eq!(response.headers()["cache-control"], "public, no-cache");
+    }
+}
+
+#[tokio::test]
+async fn unknown_asset_is_not_found() {
+    let response = send(
+        Request::get("/static/missing.css")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,146 @@
+//! Integration tests for request tracing and job trace propagation.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    reason = "Integration tests live outside a test module"
+)]
+
+mod common;
+
+use axum::Router;
+use axum::body::Body;
+use axum::http::Request;
+use axum::routing::get;
+use basic_site::services::{Job, job};
+use basic_site::telemetry::with_request_tracing;
+use opentelemetry::global;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+use tower::ServiceExt as _;
+use tracing::subscriber::{self, DefaultGuard};
+use tracing_subscriber::layer::SubscriberExt as _;
+use uuid::Uuid;
+
+use common::setup_test_db;
+
+async fn test_app() -> Router {
+    let (state, _job_rx) = common::state(setup_test_db().await);
+    with_request_tracing(Router::new().route("/", get(|| async { "ok" })))
+        .with_state(state)
+}
+
+/// Routes spans for the current thread into an in-memory exporter, standing
+/// in for an OTLP collector.
+fn capture_spans() -> (SdkTracerProvider, InMemorySpanExporter, DefaultGuard) {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("test")),
+    );
+    let guard = subscriber::set_default(subscriber);
+    (provider, exporter, guard)
+}
+
+#[tokio::test]
+async fn request_id_generated_and_echoed() {
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(Request::get("/").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let request_id = response
+        .headers()
+        .get("x-request-id")
+        .expect("missing x-request-id")
+        .to_str()
+        .unwrap();
+    Uuid::parse_str(request_id).expect("request ID should be a UUID");
+}
+
+#[tokio::test]
+async fn incoming_request_id_preserved() {
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::get("/")
+                .header("x-request-id", "upstream-123")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.headers()["x-request-id"], "upstream-123");
+}
+
+#[tokio::test]
+async fn traceparent_continues_remote_trace() {
+    let (provider, exporter, _guard) = capture_spans();
+    let app = test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::get("/")
+                .header(
+                    "traceparent",
+                    "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                )
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    drop(response);
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let request = spans
+        .iter()
+        .find(|span| span.name == "request")
+        .expect("request span not exported");
+    assert_eq!(
+        request.span_context.trace_id().to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_eq!(request.parent_span_id.to_string(), "00f067aa0ba902b7");
+    assert!(request.parent_span_is_remote);
+}
+
+#[tokio::test]
+async fn job_span_links_to_enqueuing_span() {
+    let (provider, exporter, _guard) = capture_spans();
+    let (job_tx, job_rx) = job::channel();
+
+    tracing::info_span!("request").in_scope(|| {
+        job_tx
+            .send(Job::SendEmail {
+                to: "user@example.com".to_owned(),
+                subject: "Hello".to_owned(),
+                body: "World".to_owned(),
+            })
+            .unwrap();
+    });
+    drop(job_tx);
+    job::run(setup_test_db().await, job_rx).await;
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let request = spans
+        .iter()
+        .find(|span| span.name == "request")
+        .expect("request span not exported");
+    let job_span = spans
+        .iter()
+        .find(|span| span.name == "job")
+        .expect("job span not exported");
+    assert!(job_span.links.iter().any(|link| {
+        link.span_context.span_id() == request.span_context.span_id()
+    }));
+}
@@ -0,0 +1,306 @@
+//! Integration tests for file uploads, signed downloads and storage
+//! backends.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::{env, fs};
+
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Method, Request, StatusCode, header};
+use axum::routing::put;
+use basic_site::app_state::AppState;
+use basic_site::models::upload::Upload;
+use basic_site::storage::local::LocalStorage;
+use basic_site::storage::memory::MemoryStorage;
+use basic_site::storage::s3::{S3Config, S3Storage};
+use basic_site::storage::{SharedStorage, Storage as _};
+use basic_site::upload::DEFAULT_MAX_BYTES;
+use basic_site::web;
+use sqlx::SqlitePool;
+use tokio::net::TcpListener;
+use tower::ServiceExt as _;
+use uuid::Uuid;
+
+use common::{body_text, insert_user, setup_test_db, sign_in};
+
+const BOUNDARY: &str = "upload-test-boundary";
+
+fn app(db: SqlitePool, storage: &SharedStorage) -> Router {
+    web::router().with_state(AppState {
+        storage: Arc::clone(storage),
+        ..common::state(db).0
+    })
+}
+
+fn upload_request(
+    cookie: &str,
+    filename: &str,
+    content_type: &str,
+    data: &[u8],
+) -> Request<Body> {
+    let mut payload = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+    )
+    .into_bytes();
+    payload.extend_from_slice(data);
+    payload.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    Request::post("/uploads")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .header(header::COOKIE, cookie)
+        .body(Body::from(payload))
+        .unwrap()
+}
+
+/// The first signed download link in a rendered uploads section.
+fn download_link(page: &str) -> String {
+    page.split("href=\"")
+        .find(|rest| rest.starts_with("/uploads/"))
+        .and_then(|rest| rest.split('"').next())
+        .expect("no download link")
+        .replace("&amp;", "&")
+}
+
+#[tokio::test]
+async fn uploaded_file_downloads_through_signed_link() {
+    let db = setup_test_db().await;
+    let storage = MemoryStorage::shared();
+    let user = insert_user(&db, "uploader").await;
+    let cookie = sign_in(&db, &user).await;
+
+    let response = app(db.clone(), &storage)
+        .oneshot(upload_request(&cookie, "notes.txt", "text/plain", b"hello"))
+        .await
+        .unwrap();
+    let section = body_text(response).await;
+    assert!(section.contains("Uploaded notes.txt."));
+
+    let link = download_link(&section);
+    let download = app(db.clone(), &storage)
+        .oneshot(Request::get(&link).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(download.status(), StatusCode::OK);
+    assert_eq!(download.headers()[header::CONTENT_TYPE], "text/plain");
+    assert!(
+        download.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment; filename=\"notes.txt\"")
+    );
+    assert_eq!(body_text(download).await, "hello");
+
+    let tampered = link.replace("signature=", "signature=00");
+    let forbidden = app(db, &storage)
+        .oneshot(Request::get(&tampered).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(forbidden.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn disallowed_and_oversized_files_are_rejected() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "uploader").await;
+    let cookie = sign_in(&db, &user).await;
+
+    let html = app(db.clone(), &MemoryStorage::shared())
+        .oneshot(upload_request(
+            &cookie,
+            "page.html",
+            "text/html",
+            b"<script>",
+        ))
+        .await
+        .unwrap();
+    assert!(body_text(html).await.contains("text/html aren't accepted"));
+
+    let oversized = vec![b'a'; DEFAULT_MAX_BYTES + 1];
+    let large = app(db.clone(), &MemoryStorage::shared())
+        .oneshot(upload_request(&cookie, "big.txt", "text/plain", &oversized))
+        .await
+        .unwrap();
+    assert!(
+        body_text(large)
+            .await
+            .contains("Files can be at most 10.0 MB.")
+    );
+
+    let uploads = Upload::get_by_user_id(&db, user.id)
+        .await
+        .expect("get failed");
+    assert!(uploads.is_empty());
+}
+
+#[tokio::test]
+async fn only_the_owner_can_delete() {
+    let db = setup_test_db().await;
+    let storage = MemoryStorage::shared();
+    let owner = insert_user(&db, "uploader").await;
+    let owner_cookie = sign_in(&db, &owner).await;
+    let other = insert_user(&db, "otheruser").await;
+    let other_cookie = sign_in(&db, &other).await;
+
+    app(db.clone(), &storage)
+        .oneshot(upload_request(
+            &owner_cookie,
+            "a.txt",
+            "text/plain",
+            b"data",
+        ))
+        .await
+        .unwrap();
+    let upload = Upload::get_by_user_id(&db, owner.id)
+        .await
+        .expect("get failed")
+        .remove(0);
+
+    let delete = |cookie: &str| {
+        Request::delete(format!("/uploads/{}", upload.id))
+            .header(header::COOKIE, cookie)
+            .body(Body::empty())
+            .unwrap()
+    };
+    let refused = app(db.clone(), &storage)
+        .oneshot(delete(&other_cookie))
+        .await
+        .unwrap();
+    assert_eq!(refused.status(), StatusCode::NOT_FOUND);
+    assert!(storage.get(&upload.storage_key).await.unwrap().is_some());
+
+    let deleted = app(db.clone(), &storage)
+        .oneshot(delete(&owner_cookie))
+        .await
+        .unwrap();
+    assert_eq!(deleted.status(), StatusCode::OK);
+    assert!(storage.get(&upload.storage_key).await.unwrap().is_none());
+    assert!(
+        Upload::get_by_id(&db, upload.id)
+            .await
+            .expect("get failed")
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn local_storage_round_trips() {
+    let root = env::temp_dir().join(format!("uploads-{}", Uuid::new_v4()));
+    let storage = LocalStorage::new(&root);
+
+    storage
+        .put("uploads/a/b", "text/plain", Bytes::from_static(b"local"))
+        .await
+        .expect("put failed");
+    let stored = storage.get("uploads/a/b").await.expect("get failed");
+    assert_eq!(stored.as_deref(), Some(&b"local"[..]));
+    storage.get("../escape").await.expect_err("unsafe key");
+
+    storage.delete("uploads/a/b").await.expect("delete failed");
+    storage
+        .delete("uploads/a/b")
+        .await
+        .expect("missing is fine");
+    assert!(storage.get("uploads/a/b").await.unwrap().is_none());
+
+    fs::remove_dir_all(root).expect("cleanup failed");
+}
+
+/// Objects held by the stand-in S3 server, keyed by path.
+type Bucket = Arc<Mutex<HashMap<String, Bytes>>>;
+
+/// Answers like an S3 server for path-style object requests, refusing any
+/// that aren't signed for the test credentials.
+async fn object(
+    State(bucket): State<Bucket>,
+    Path(path): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    data: Bytes,
+) -> StatusCode {
+    let signed = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.starts_with("AWS4-HMAC-SHA256 Credential=test-access-key/")
+                && value.contains(
+                    "SignedHeaders=host;x-amz-content-sha256;x-amz-date",
+                )
+        });
+    if !signed || !headers.contains_key("x-amz-date") {
+        return StatusCode::FORBIDDEN;
+    }
+    let mut objects = bucket.lock().unwrap();
+    match method.as_str() {
+        "PUT" => {
+            objects.insert(path, data);
+            StatusCode::OK
+        }
+        "DELETE" => {
+            objects.remove(&path);
+            StatusCode::NO_CONTENT
+        }
+        _ => StatusCode::METHOD_NOT_ALLOWED,
+    }
+}
+
+async fn get_object(
+    State(bucket): State<Bucket>,
+    Path(path): Path<String>,
+) -> Result<Bytes, StatusCode> {
+    bucket
+        .lock()
+        .unwrap()
+        .get(&path)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[tokio::test]
+async fn s3_storage_talks_to_a_compatible_server() {
+    let bucket = Bucket::default();
+    let server = Router::new()
+        .route("/{*path}", put(object).delete(object).get(get_object))
+        .with_state(Arc::clone(&bucket));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, server).await });
+
+    let storage = S3Storage::new(S3Config {
+        endpoint: format!("http://{addr}"),
+        bucket: "test-bucket".to_owned(),
+        region: "us-east-1".to_owned(),
+        access_key_id: "test-access-key".to_owned(),
+        secret_access_key: "test-secret".to_owned(),
+    })
+    .expect("valid config");
+
+    storage
+        .put("uploads/u/1", "text/plain", Bytes::from_static(b"remote"))
+        .await
+        .expect("put failed");
+    assert!(
+        bucket
+            .lock()
+            .unwrap()
+            .contains_key("test-bucket/uploads/u/1")
+    );
+    let stored = storage.get("uploads/u/1").await.expect("get failed");
+    assert_eq!(stored.as_deref(), Some(&b"remote"[..]));
+    assert!(storage.get("uploads/u/2").await.unwrap().is_none());
+
+    storage.delete("uploads/u/1").await.expect("delete failed");
+    assert!(bucket.lock().unwrap().is_empty());
+}
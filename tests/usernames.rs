@@ -0,0 +1,122 @@
+//! Integration tests for username changes, history redirects and holds.
+#![allow(
+    clippy::allow_attributes,
+    clippy::tests_outside_test_module,
+    clippy::arithmetic_side_effects,
+    reason = "Integration tests live outside a test module and favour brevity"
+)]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use basic_site::models::user::User;
+use basic_site::models::username_history::UsernameHistory;
+use basic_site::username::{DEFAULT_CHANGE_COOLDOWN_DAYS, DEFAULT_HOLD_DAYS};
+use basic_site::util::{MICROS_PER_DAY, current_time_micros};
+use sqlx::SqlitePool;
+use tower::ServiceExt as _;
+
+use common::{
+    app, body_text, form_request, insert_user, send, setup_test_db, sign_in,
+};
+
+async fn rename(db: &SqlitePool, cookie: &str, new_username: &str) -> String {
+    let form = format!("new_username={new_username}");
+    body_text(send(db, form_request("/settings/username", cookie, &form)).await)
+        .await
+}
+
+#[tokio::test]
+async fn old_profile_url_redirects_permanently() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "oldhandle").await;
+    let cookie = sign_in(&db, &user).await;
+
+    let html = rename(&db, &cookie, "newhandle").await;
+    assert!(html.contains("Username updated successfully!"));
+
+    let request = Request::get("/users/OldHandle")
+        .body(Body::empty())
+        .unwrap();
+    let response = app(db.clone()).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(response.headers()[header::LOCATION], "/users/newhandle");
+}
+
+#[tokio::test]
+async fn username_changes_have_a_cooldown() {
+    let db = setup_test_db().await;
+    let user = insert_user(&db, "firstname").await;
+    let cookie = sign_in(&db, &user).await;
+
+    assert!(
+        rename(&db, &cookie, "secondname")
+            .await
+            .contains("Username updated successfully!")
+    );
+    assert!(
+        rename(&db, &cookie, "thirdname")
+            .await
+            .contains("You can change your username again after")
+    );
+    User::get_by_username(&db, "secondname")
+        .await
+        .expect("still the second name");
+
+    // Once the cooldown has passed, the change goes through
+    let long_ago = current_time_micros()
+        - (DEFAULT_CHANGE_COOLDOWN_DAYS + 1) * MICROS_PER_DAY;
+    sqlx::query("UPDATE username_history SET changed_at = ?")
+        .bind(long_ago)
+        .execute(&db)
+        .await
+        .unwrap();
+    assert!(
+        rename(&db, &cookie, "thirdname")
+            .await
+            .contains("Username updated successfully!")
+    );
+}
+
+#[tokio::test]
+async fn released_username_is_held_for_others() {
+    let db = setup_test_db().await;
+    let owner = insert_user(&db, "coveted").await;
+    let other = insert_user(&db, "latecomer").await;
+    User::rename(&db, owner.id, "coveted", "moved", current_time_micros())
+        .await
+        .expect("rename failed");
+
+    // Someone else can't take it, or a lookalike of it, during the hold
+    let other_cookie = sign_in(&db, &other).await;
+    for wanted in ["coveted", "C0veted"] {
+        assert!(
+            rename(&db, &other_cookie, wanted)
+                .await
+                .contains("Username is taken or too similar")
+        );
+    }
+
+    // The previous owner may always take it back
+    let hold_start = current_time_micros() - DEFAULT_HOLD_DAYS * MICROS_PER_DAY;
+    assert!(
+        !UsernameHistory::is_held(&db, "coveted", hold_start, Some(owner.id))
+            .await
+            .unwrap()
+    );
+
+    // Once the hold has expired, anyone can have it
+    let expired =
+        current_time_micros() - (DEFAULT_HOLD_DAYS + 1) * MICROS_PER_DAY;
+    sqlx::query("UPDATE username_history SET changed_at = ?")
+        .bind(expired)
+        .execute(&db)
+        .await
+        .unwrap();
+    assert!(
+        rename(&db, &other_cookie, "coveted")
+            .await
+            .contains("Username updated successfully!")
+    );
+}